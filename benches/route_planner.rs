@@ -0,0 +1,85 @@
+//! Timing benchmark for the route planner. Run with `cargo bench --bench route_planner`.
+//!
+//! Stops are generated from a fixed seed, so every run plans the same routes.
+
+use std::{hint::black_box, time::Instant};
+
+use medbook_deliveryservice::services::route_planner::{self, Coordinates, PlannerSettings, Stop};
+
+const SIZES: [usize; 4] = [10, 25, 50, 100];
+const ITERATIONS: u32 = 20;
+
+/// Linear congruential generator, enough for spreading stops around a city.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn stops(count: usize) -> Vec<Stop<usize>> {
+    let mut rng = Lcg(42);
+    (0..count)
+        .map(|key| {
+            let location = Coordinates {
+                latitude: 13.65 + rng.next_f64() * 0.2,
+                longitude: 100.45 + rng.next_f64() * 0.2,
+            };
+            // A third of the stops have a booked two hour slot within the working day.
+            let window = (key % 3 == 0).then(|| {
+                let start = (rng.next_f64() * 6.0).floor() * 60.0;
+                (start, start + 120.0)
+            });
+            Stop {
+                key,
+                location,
+                window,
+                priority: (key % 5 == 0) as u8 * 2,
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    let settings = PlannerSettings {
+        start: Coordinates {
+            latitude: 13.75,
+            longitude: 100.55,
+        },
+        speed_kmh: 30.0,
+        service_minutes: 5.0,
+    };
+
+    println!(
+        "{:>6} {:>12} {:>12} {:>14}",
+        "stops", "mean (ms)", "distance km", "lateness min"
+    );
+    for size in SIZES {
+        let stops = stops(size);
+        let route = route_planner::plan(&stops, &settings);
+        assert_eq!(
+            route,
+            route_planner::plan(&stops, &settings),
+            "planning {size} stops is not deterministic"
+        );
+
+        let started = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(route_planner::plan(black_box(&stops), &settings));
+        }
+        let mean = started.elapsed() / ITERATIONS;
+
+        println!(
+            "{:>6} {:>12.3} {:>12.1} {:>14.1}",
+            size,
+            mean.as_secs_f64() * 1000.0,
+            route.total_distance_km,
+            route.total_lateness_minutes
+        );
+    }
+}
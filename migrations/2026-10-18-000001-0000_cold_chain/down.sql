@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE delivery_temperature_excursions cascade;
+DROP TABLE delivery_temperature_readings cascade;
+
+ALTER TABLE deliveries
+    DROP COLUMN min_temperature_c,
+    DROP COLUMN max_temperature_c,
+    DROP COLUMN requires_review;
@@ -0,0 +1,43 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    ADD COLUMN min_temperature_c DOUBLE PRECISION,
+    ADD COLUMN max_temperature_c DOUBLE PRECISION,
+    ADD COLUMN requires_review BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE "delivery_temperature_readings" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    temperature_c DOUBLE PRECISION NOT NULL,
+    source VARCHAR(64) NOT NULL DEFAULT 'COURIER', -- COURIER, DATA_LOGGER
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX delivery_temperature_readings_series_idx
+ON delivery_temperature_readings (delivery_id, recorded_at);
+
+CREATE TRIGGER update_delivery_temperature_reading_timestamp
+BEFORE UPDATE ON delivery_temperature_readings
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "delivery_temperature_excursions" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    reading_id UUID NOT NULL references delivery_temperature_readings(id) on delete cascade,
+    temperature_c DOUBLE PRECISION NOT NULL,
+    min_temperature_c DOUBLE PRECISION,
+    max_temperature_c DOUBLE PRECISION,
+    reviewed_by VARCHAR(100),
+    review_note TEXT,
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_delivery_temperature_excursion_timestamp
+BEFORE UPDATE ON delivery_temperature_excursions
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE delivery_custody_entries cascade;
+DROP FUNCTION reject_custody_entry_change;
+
+ALTER TABLE deliveries
+    DROP COLUMN is_controlled;
@@ -0,0 +1,33 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    ADD COLUMN is_controlled BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE "delivery_custody_entries" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    sequence INTEGER NOT NULL,
+    handoff_type VARCHAR(64) NOT NULL, -- PHARMACY_TO_COURIER, COURIER_TO_COURIER, COURIER_TO_RECIPIENT
+    from_actor VARCHAR(100) NOT NULL,
+    to_actor VARCHAR(100) NOT NULL,
+    location TEXT NOT NULL,
+    seal_number VARCHAR(100) NOT NULL,
+    handed_over_at TIMESTAMPTZ NOT NULL,
+    previous_hash VARCHAR(64),
+    entry_hash VARCHAR(64) NOT NULL,
+    signature VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (delivery_id, sequence)
+);
+
+-- The ledger is append-only: entries can never be modified or removed once written.
+CREATE OR REPLACE FUNCTION reject_custody_entry_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'delivery_custody_entries is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER reject_delivery_custody_entry_change
+BEFORE UPDATE OR DELETE ON delivery_custody_entries
+FOR EACH ROW
+EXECUTE FUNCTION reject_custody_entry_change();
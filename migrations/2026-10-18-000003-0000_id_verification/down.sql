@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE delivery_id_verifications cascade;
+
+ALTER TABLE deliveries
+    DROP COLUMN requires_id_verification;
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    ADD COLUMN requires_id_verification BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE "delivery_id_verifications" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    id_type VARCHAR(64) NOT NULL, -- NATIONAL_ID, PASSPORT, DRIVING_LICENSE
+    masked_id_number VARCHAR(64) NOT NULL,
+    presented_name VARCHAR(100) NOT NULL,
+    recipient_name VARCHAR(100),
+    name_matched BOOLEAN NOT NULL,
+    verified_by VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_delivery_id_verification_timestamp
+BEFORE UPDATE ON delivery_id_verifications
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE package_items cascade;
+DROP TABLE delivery_packages cascade;
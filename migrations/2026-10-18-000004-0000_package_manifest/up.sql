@@ -0,0 +1,36 @@
+-- Your SQL goes here
+
+CREATE TABLE "delivery_packages" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    package_number INTEGER NOT NULL,
+    weight_grams INTEGER NOT NULL,
+    length_mm INTEGER NOT NULL,
+    width_mm INTEGER NOT NULL,
+    height_mm INTEGER NOT NULL,
+    is_fragile BOOLEAN NOT NULL DEFAULT FALSE,
+    is_refrigerated BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (delivery_id, package_number)
+);
+
+CREATE TRIGGER update_delivery_package_timestamp
+BEFORE UPDATE ON delivery_packages
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "package_items" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    package_id UUID NOT NULL references delivery_packages(id) on delete cascade,
+    order_item_id INTEGER NOT NULL, -- Line item in the orders service
+    quantity INTEGER NOT NULL,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_package_item_timestamp
+BEFORE UPDATE ON package_items
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
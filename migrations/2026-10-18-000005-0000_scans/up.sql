@@ -0,0 +1,22 @@
+-- Your SQL goes here
+
+CREATE TABLE "delivery_scans" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    package_id UUID references delivery_packages(id) on delete cascade,
+    scan_type VARCHAR(64) NOT NULL, -- PICKUP, HUB_INBOUND, HUB_OUTBOUND, OUT_FOR_DELIVERY, DELIVERY_ATTEMPT
+    location TEXT NOT NULL,
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    scanned_by VARCHAR(100) NOT NULL,
+    scanned_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX delivery_scans_delivery_id_idx ON delivery_scans (delivery_id, scanned_at);
+
+CREATE TRIGGER update_delivery_scan_timestamp
+BEFORE UPDATE ON delivery_scans
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
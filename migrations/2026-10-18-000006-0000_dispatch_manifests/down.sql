@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE dispatch_manifest_deliveries cascade;
+DROP TABLE dispatch_manifests cascade;
+
+ALTER TABLE deliveries
+    DROP COLUMN courier_id,
+    DROP COLUMN handed_over_at;
+
+DROP TABLE couriers cascade;
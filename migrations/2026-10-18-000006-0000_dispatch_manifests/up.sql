@@ -0,0 +1,49 @@
+-- Your SQL goes here
+
+CREATE TABLE "couriers" (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    phone_number VARCHAR(20),
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_courier_timestamp
+BEFORE UPDATE ON couriers
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+ALTER TABLE deliveries
+    ADD COLUMN courier_id INTEGER references couriers(id) on delete set null,
+    ADD COLUMN handed_over_at TIMESTAMPTZ;
+
+CREATE TABLE "dispatch_manifests" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    courier_id INTEGER NOT NULL references couriers(id),
+    window_start TIMESTAMPTZ NOT NULL,
+    window_end TIMESTAMPTZ NOT NULL,
+    status VARCHAR(64) NOT NULL DEFAULT 'GENERATED', -- GENERATED, CONFIRMED
+    delivery_count INTEGER NOT NULL,
+    package_count INTEGER NOT NULL,
+    csv TEXT NOT NULL,
+    pdf BYTEA NOT NULL,
+    generated_by VARCHAR(100) NOT NULL,
+    handed_over_by VARCHAR(100),
+    received_by VARCHAR(100),
+    confirmed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_dispatch_manifest_timestamp
+BEFORE UPDATE ON dispatch_manifests
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "dispatch_manifest_deliveries" (
+    manifest_id UUID NOT NULL references dispatch_manifests(id) on delete cascade,
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    package_count INTEGER NOT NULL,
+    PRIMARY KEY (manifest_id, delivery_id)
+);
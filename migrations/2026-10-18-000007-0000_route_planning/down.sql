@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE deliveries
+    DROP COLUMN latitude,
+    DROP COLUMN longitude,
+    DROP COLUMN slot_start,
+    DROP COLUMN slot_end,
+    DROP COLUMN route_sequence,
+    DROP COLUMN estimated_arrival_at;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    ADD COLUMN latitude DOUBLE PRECISION,
+    ADD COLUMN longitude DOUBLE PRECISION,
+    ADD COLUMN slot_start TIMESTAMPTZ,
+    ADD COLUMN slot_end TIMESTAMPTZ,
+    ADD COLUMN route_sequence INTEGER,
+    ADD COLUMN estimated_arrival_at TIMESTAMPTZ;
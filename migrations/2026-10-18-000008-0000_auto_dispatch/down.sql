@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE deliveries
+    DROP COLUMN zone;
+
+ALTER TABLE couriers
+    DROP COLUMN vehicle_type,
+    DROP COLUMN zone,
+    DROP COLUMN max_deliveries,
+    DROP COLUMN capacity_grams,
+    DROP COLUMN latitude,
+    DROP COLUMN longitude;
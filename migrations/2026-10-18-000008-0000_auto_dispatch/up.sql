@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+ALTER TABLE couriers
+    ADD COLUMN vehicle_type VARCHAR(32) NOT NULL DEFAULT 'MOTORCYCLE', -- BICYCLE, MOTORCYCLE, CAR, VAN
+    ADD COLUMN zone VARCHAR(64),
+    ADD COLUMN max_deliveries INTEGER,
+    ADD COLUMN capacity_grams INTEGER,
+    ADD COLUMN latitude DOUBLE PRECISION,
+    ADD COLUMN longitude DOUBLE PRECISION;
+
+ALTER TABLE deliveries
+    ADD COLUMN zone VARCHAR(64);
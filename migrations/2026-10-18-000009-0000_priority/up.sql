@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    ADD COLUMN priority VARCHAR(16) NOT NULL DEFAULT 'STANDARD'; -- STANDARD, EXPRESS, URGENT
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+
+CREATE TABLE "delivery_incidents" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    category VARCHAR(64) NOT NULL,
+    severity VARCHAR(16) NOT NULL, -- LOW, MEDIUM, HIGH, CRITICAL
+    status VARCHAR(32) NOT NULL DEFAULT 'OPEN',
+    description TEXT NOT NULL,
+    -- Identifies incidents raised automatically so the same problem is not raised twice
+    dedup_key VARCHAR(200) UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX delivery_incidents_delivery_id_idx ON delivery_incidents (delivery_id);
+
+CREATE TRIGGER update_delivery_incident_timestamp
+BEFORE UPDATE ON delivery_incidents
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
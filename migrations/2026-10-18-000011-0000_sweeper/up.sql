@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+-- Audit trail of what the stuck delivery sweeper did, append-only
+CREATE TABLE "delivery_sweeper_actions" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    action VARCHAR(16) NOT NULL, -- ESCALATE, CANCEL, REQUEUE
+    delivery_status VARCHAR(64) NOT NULL,
+    last_activity_at TIMESTAMPTZ NOT NULL,
+    detail TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX delivery_sweeper_actions_created_at_idx ON delivery_sweeper_actions (created_at);
@@ -0,0 +1,16 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE delivery_incident_attachments cascade;
+DROP TABLE delivery_incident_comments cascade;
+
+ALTER TABLE delivery_incidents
+    DROP COLUMN reporter_type,
+    DROP COLUMN reported_by,
+    DROP COLUMN resolution,
+    DROP COLUMN resolution_note,
+    DROP COLUMN resolved_by,
+    DROP COLUMN resolved_at;
+
+DROP INDEX deliveries_patient_id_idx;
+
+ALTER TABLE deliveries
+    DROP COLUMN patient_id;
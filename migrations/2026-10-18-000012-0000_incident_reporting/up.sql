@@ -0,0 +1,43 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    ADD COLUMN patient_id INTEGER;
+
+CREATE INDEX deliveries_patient_id_idx ON deliveries (patient_id);
+
+ALTER TABLE delivery_incidents
+    ADD COLUMN reporter_type VARCHAR(16) NOT NULL DEFAULT 'SYSTEM', -- SYSTEM, PATIENT, COURIER, STAFF
+    ADD COLUMN reported_by VARCHAR(100),
+    ADD COLUMN resolution VARCHAR(16), -- NONE, RESHIP, REFUND
+    ADD COLUMN resolution_note TEXT,
+    ADD COLUMN resolved_by VARCHAR(100),
+    ADD COLUMN resolved_at TIMESTAMPTZ;
+
+CREATE TABLE "delivery_incident_comments" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    incident_id UUID NOT NULL references delivery_incidents(id) on delete cascade,
+    author_type VARCHAR(16) NOT NULL, -- PATIENT, COURIER, STAFF
+    author VARCHAR(100) NOT NULL,
+    body TEXT NOT NULL,
+    -- Internal comments are only visible to staff
+    is_internal BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_delivery_incident_comment_timestamp
+BEFORE UPDATE ON delivery_incident_comments
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "delivery_incident_attachments" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    incident_id UUID NOT NULL references delivery_incidents(id) on delete cascade,
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    content BYTEA NOT NULL,
+    uploaded_by_type VARCHAR(16) NOT NULL,
+    uploaded_by VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+
+CREATE TABLE "delivery_ratings" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL UNIQUE references deliveries(id) on delete cascade,
+    patient_id INTEGER NOT NULL,
+    -- Courier and zone at the time of delivery, for reporting
+    courier_id INTEGER references couriers(id) on delete set null,
+    zone VARCHAR(64),
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    tags JSONB NOT NULL DEFAULT '[]',
+    feedback TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX delivery_ratings_created_at_idx ON delivery_ratings (created_at);
+
+CREATE TRIGGER update_delivery_rating_timestamp
+BEFORE UPDATE ON delivery_ratings
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
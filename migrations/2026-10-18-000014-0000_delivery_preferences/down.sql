@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE deliveries
+    DROP COLUMN delivery_instructions,
+    DROP COLUMN access_code_encrypted,
+    DROP COLUMN preferred_contact_method,
+    DROP COLUMN contactless_consent;
+
+ALTER TABLE delivery_addresses
+    DROP COLUMN delivery_instructions,
+    DROP COLUMN access_code_encrypted,
+    DROP COLUMN preferred_contact_method,
+    DROP COLUMN contactless_consent;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+ALTER TABLE delivery_addresses
+    ADD COLUMN delivery_instructions TEXT,
+    -- AES-256-GCM, nonce followed by ciphertext
+    ADD COLUMN access_code_encrypted BYTEA,
+    ADD COLUMN preferred_contact_method VARCHAR(16),
+    ADD COLUMN contactless_consent BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Snapshot of the address preferences when the delivery was requested
+ALTER TABLE deliveries
+    ADD COLUMN delivery_instructions TEXT,
+    ADD COLUMN access_code_encrypted BYTEA,
+    ADD COLUMN preferred_contact_method VARCHAR(16),
+    ADD COLUMN contactless_consent BOOLEAN NOT NULL DEFAULT FALSE;
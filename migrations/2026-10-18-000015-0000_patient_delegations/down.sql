@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE patient_proxy_audit_logs cascade;
+DROP TABLE patient_delegations cascade;
@@ -0,0 +1,37 @@
+-- Your SQL goes here
+
+CREATE TABLE "patient_delegations" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- Patient granting access
+    patient_id INTEGER NOT NULL,
+    -- User acting on the patient's behalf
+    delegate_id INTEGER NOT NULL,
+    access_level VARCHAR(16) NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX patient_delegations_active_idx
+    ON patient_delegations (patient_id, delegate_id)
+    WHERE revoked_at IS NULL;
+CREATE INDEX patient_delegations_delegate_id_idx ON patient_delegations (delegate_id);
+
+CREATE TRIGGER update_patient_delegation_timestamp
+BEFORE UPDATE ON patient_delegations
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+-- Append-only record of everything delegates do on a patient's behalf
+CREATE TABLE "patient_proxy_audit_logs" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delegation_id UUID NOT NULL references patient_delegations(id) on delete cascade,
+    patient_id INTEGER NOT NULL,
+    delegate_id INTEGER NOT NULL,
+    action VARCHAR(64) NOT NULL,
+    resource_type VARCHAR(64) NOT NULL,
+    resource_id VARCHAR(64),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX patient_proxy_audit_logs_patient_id_idx ON patient_proxy_audit_logs (patient_id, created_at);
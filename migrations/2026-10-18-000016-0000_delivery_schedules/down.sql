@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE deliveries DROP COLUMN schedule_id;
+
+DROP TABLE delivery_schedule_runs cascade;
+DROP TABLE delivery_schedules cascade;
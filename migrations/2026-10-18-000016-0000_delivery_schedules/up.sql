@@ -0,0 +1,41 @@
+-- Your SQL goes here
+
+CREATE TABLE "delivery_schedules" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    patient_id INTEGER NOT NULL,
+    delivery_address_id INTEGER references delivery_addresses(id) on delete set null,
+    -- Order repeated on every run
+    source_order_id INTEGER NOT NULL,
+    -- RRULE subset, e.g. FREQ=MONTHLY;INTERVAL=1;BYMONTHDAY=5
+    recurrence VARCHAR(255) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'ACTIVE',
+    starts_on DATE NOT NULL,
+    ends_on DATE,
+    -- NULL once the schedule has ended
+    next_run_on DATE,
+    -- Run the last reminder was sent for
+    reminded_for DATE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX delivery_schedules_patient_id_idx ON delivery_schedules (patient_id);
+CREATE INDEX delivery_schedules_next_run_on_idx ON delivery_schedules (next_run_on) WHERE status = 'ACTIVE';
+
+CREATE TRIGGER update_delivery_schedule_timestamp
+BEFORE UPDATE ON delivery_schedules
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "delivery_schedule_runs" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    schedule_id UUID NOT NULL references delivery_schedules(id) on delete cascade,
+    run_on DATE NOT NULL,
+    -- REQUESTED or SKIPPED
+    status VARCHAR(16) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (schedule_id, run_on)
+);
+
+ALTER TABLE deliveries
+    ADD COLUMN schedule_id UUID references delivery_schedules(id) on delete set null;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+-- Orders carried by each delivery. `deliveries.order_id` stays the order the delivery
+-- was created for; orders consolidated into it later are only linked here.
+CREATE TABLE "delivery_orders" (
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    order_id INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (delivery_id, order_id)
+);
+
+CREATE INDEX delivery_orders_order_id_idx ON delivery_orders (order_id);
+
+INSERT INTO delivery_orders (delivery_id, order_id, created_at)
+SELECT id, order_id, created_at FROM deliveries;
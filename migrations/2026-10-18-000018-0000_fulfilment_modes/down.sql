@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX deliveries_pickup_expires_at_idx;
+
+ALTER TABLE deliveries
+    DROP COLUMN fulfilment_mode,
+    DROP COLUMN pickup_point,
+    DROP COLUMN locker_compartment,
+    DROP COLUMN pickup_code_hash,
+    DROP COLUMN pickup_expires_at,
+    DROP COLUMN collected_at;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    ADD COLUMN fulfilment_mode VARCHAR(32) NOT NULL DEFAULT 'HOME',
+    -- Pharmacy or locker the parcel is collected from
+    ADD COLUMN pickup_point VARCHAR(128),
+    ADD COLUMN locker_compartment VARCHAR(64),
+    -- SHA-256 of the pickup code, which is only ever sent to the patient
+    ADD COLUMN pickup_code_hash VARCHAR(64),
+    ADD COLUMN pickup_expires_at TIMESTAMPTZ,
+    ADD COLUMN collected_at TIMESTAMPTZ;
+
+CREATE INDEX deliveries_pickup_expires_at_idx ON deliveries (pickup_expires_at)
+    WHERE status = 'READY_FOR_PICKUP';
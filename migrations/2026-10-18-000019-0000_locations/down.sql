@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE dispatch_manifests DROP COLUMN origin_location_id;
+ALTER TABLE deliveries DROP COLUMN origin_location_id;
+
+DROP TABLE location_opening_hours cascade;
+DROP TABLE locations cascade;
@@ -0,0 +1,40 @@
+-- Your SQL goes here
+
+CREATE TABLE "locations" (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    location_type VARCHAR(16) NOT NULL, -- PHARMACY, HUB, LAB
+    address TEXT NOT NULL,
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    -- IANA time zone the opening hours and cutoff are in
+    timezone VARCHAR(64) NOT NULL DEFAULT 'Asia/Bangkok',
+    -- Orders placed at or after this local time ship on the next opening day
+    cutoff_time TIME,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_location_timestamp
+BEFORE UPDATE ON locations
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "location_opening_hours" (
+    id SERIAL PRIMARY KEY,
+    location_id INTEGER NOT NULL references locations(id) on delete cascade,
+    -- ISO weekday, 1 = Monday
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    opens_at TIME NOT NULL,
+    closes_at TIME NOT NULL,
+    CHECK (opens_at < closes_at)
+);
+
+CREATE INDEX location_opening_hours_location_id_idx ON location_opening_hours (location_id);
+
+ALTER TABLE deliveries
+    ADD COLUMN origin_location_id INTEGER references locations(id) on delete set null;
+
+ALTER TABLE dispatch_manifests
+    ADD COLUMN origin_location_id INTEGER references locations(id) on delete set null;
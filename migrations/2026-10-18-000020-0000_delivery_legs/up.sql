@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+CREATE TABLE "delivery_legs" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    -- 1 for the leg leaving the pharmacy
+    sequence INTEGER NOT NULL,
+    courier_id INTEGER references couriers(id) on delete set null,
+    origin_location_id INTEGER references locations(id) on delete set null,
+    -- NULL for the last leg, which ends at the recipient
+    destination_location_id INTEGER references locations(id) on delete set null,
+    status VARCHAR(16) NOT NULL DEFAULT 'PENDING', -- PENDING, IN_TRANSIT, COMPLETED, CANCELLED
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (delivery_id, sequence)
+);
+
+CREATE INDEX delivery_legs_courier_id_idx ON delivery_legs (courier_id) WHERE status = 'IN_TRANSIT';
+
+CREATE TRIGGER update_delivery_leg_timestamp
+BEFORE UPDATE ON delivery_legs
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
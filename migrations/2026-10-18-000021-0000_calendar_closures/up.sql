@@ -0,0 +1,23 @@
+-- Your SQL goes here
+
+CREATE TABLE "calendar_closures" (
+    id SERIAL PRIMARY KEY,
+    -- A closure covers one zone or one location, or everywhere when both are NULL
+    zone VARCHAR(64),
+    location_id INTEGER references locations(id) on delete cascade,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    reason TEXT NOT NULL,
+    created_by VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (starts_on <= ends_on),
+    CHECK (zone IS NULL OR location_id IS NULL)
+);
+
+CREATE INDEX calendar_closures_ends_on_idx ON calendar_closures (ends_on);
+
+CREATE TRIGGER update_calendar_closure_timestamp
+BEFORE UPDATE ON calendar_closures
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
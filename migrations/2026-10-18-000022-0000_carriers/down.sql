@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX deliveries_carrier_tracking_number_idx;
+
+ALTER TABLE deliveries
+    DROP COLUMN carrier,
+    DROP COLUMN tracking_number;
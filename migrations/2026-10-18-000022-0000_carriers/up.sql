@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    -- External carrier the delivery is outsourced to, NULL when our couriers carry it
+    ADD COLUMN carrier VARCHAR(64),
+    ADD COLUMN tracking_number VARCHAR(128);
+
+CREATE UNIQUE INDEX deliveries_carrier_tracking_number_idx
+    ON deliveries (carrier, tracking_number)
+    WHERE tracking_number IS NOT NULL;
@@ -0,0 +1,30 @@
+-- Your SQL goes here
+
+CREATE TABLE "carrier_events" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    carrier VARCHAR(64) NOT NULL,
+    -- The carrier's ID of the event, used to drop redelivered webhooks
+    event_id VARCHAR(128) NOT NULL,
+    tracking_number VARCHAR(128) NOT NULL,
+    code VARCHAR(64) NOT NULL,
+    description TEXT,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    -- NULL when no delivery has the tracking number
+    delivery_id UUID references deliveries(id) on delete cascade,
+    outcome VARCHAR(16) NOT NULL, -- APPLIED, LOGGED, UNRECOGNIZED, UNMATCHED, REJECTED
+    -- Why the event was not applied
+    note TEXT,
+    reviewed_by VARCHAR(100),
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (carrier, event_id)
+);
+
+CREATE INDEX carrier_events_review_idx ON carrier_events (created_at)
+    WHERE outcome IN ('UNRECOGNIZED', 'UNMATCHED', 'REJECTED') AND reviewed_at IS NULL;
+
+CREATE TRIGGER update_carrier_event_timestamp
+BEFORE UPDATE ON carrier_events
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE webhook_outbox_cursor cascade;
+DROP TABLE webhook_delivery_attempts cascade;
+DROP TABLE webhook_deliveries cascade;
+DROP TABLE webhook_subscriptions cascade;
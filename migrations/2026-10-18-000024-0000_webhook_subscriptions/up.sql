@@ -0,0 +1,73 @@
+-- Your SQL goes here
+
+CREATE TABLE "webhook_subscriptions" (
+    id SERIAL PRIMARY KEY,
+    -- Partner the webhooks go to
+    name VARCHAR(100) NOT NULL,
+    url TEXT NOT NULL,
+    -- Outbox event types sent to the partner, as a JSON array
+    event_types JSONB NOT NULL DEFAULT '[]',
+    secret VARCHAR(128) NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_webhook_subscription_timestamp
+BEFORE UPDATE ON webhook_subscriptions
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "webhook_deliveries" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscription_id INTEGER NOT NULL references webhook_subscriptions(id) on delete cascade,
+    -- NULL for test events
+    outbox_id INTEGER,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'PENDING', -- PENDING, SUCCEEDED, FAILED
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- NULL once no further attempt is planned
+    next_attempt_at TIMESTAMPTZ,
+    last_response_status INTEGER,
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (subscription_id, outbox_id)
+);
+
+CREATE INDEX webhook_deliveries_due_idx ON webhook_deliveries (next_attempt_at) WHERE status = 'PENDING';
+
+CREATE TRIGGER update_webhook_delivery_timestamp
+BEFORE UPDATE ON webhook_deliveries
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
+
+CREATE TABLE "webhook_delivery_attempts" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    webhook_delivery_id UUID NOT NULL references webhook_deliveries(id) on delete cascade,
+    attempt INTEGER NOT NULL,
+    -- NULL when the partner could not be reached
+    response_status INTEGER,
+    error TEXT,
+    duration_ms INTEGER NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX webhook_delivery_attempts_webhook_delivery_id_idx ON webhook_delivery_attempts (webhook_delivery_id);
+
+-- Last outbox event fanned out to subscriptions. Starts at the current end of the outbox,
+-- so partners only get events from now on.
+CREATE TABLE "webhook_outbox_cursor" (
+    id INTEGER PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    last_outbox_id INTEGER NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO webhook_outbox_cursor (last_outbox_id) SELECT COALESCE(MAX(id), 0) FROM outbox;
+
+CREATE TRIGGER update_webhook_outbox_cursor_timestamp
+BEFORE UPDATE ON webhook_outbox_cursor
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
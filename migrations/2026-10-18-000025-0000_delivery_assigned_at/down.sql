@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX deliveries_courier_id_assigned_at_idx;
+
+ALTER TABLE deliveries
+    DROP COLUMN assigned_at;
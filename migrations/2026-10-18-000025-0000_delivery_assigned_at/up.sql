@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    -- When the current courier was assigned, NULL while no courier carries the delivery
+    ADD COLUMN assigned_at TIMESTAMPTZ;
+
+-- The assignment time of existing deliveries is not known, their last change is the best guess
+UPDATE deliveries SET assigned_at = updated_at WHERE courier_id IS NOT NULL;
+
+CREATE INDEX deliveries_courier_id_assigned_at_idx ON deliveries (courier_id, assigned_at);
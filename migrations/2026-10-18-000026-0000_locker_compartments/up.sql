@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+-- Compartments of the lockers served by the database locker provider, seeded from LOCKER_SITES
+CREATE TABLE "locker_compartments" (
+    locker_id VARCHAR(128) NOT NULL,
+    compartment VARCHAR(64) NOT NULL,
+    -- Delivery occupying the compartment, NULL while it is free
+    delivery_id UUID references deliveries(id) on delete set null,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (locker_id, compartment)
+);
+
+CREATE UNIQUE INDEX locker_compartments_delivery_id_idx ON locker_compartments (delivery_id);
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE deliveries
+    -- Wrong pickup codes entered since the current code was issued
+    ADD COLUMN pickup_code_attempts INT NOT NULL DEFAULT 0;
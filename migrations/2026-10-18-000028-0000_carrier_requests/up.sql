@@ -0,0 +1,30 @@
+-- Your SQL goes here
+
+-- Bookings and cancellations owed to carriers. They are committed before the carrier is
+-- called, so a call is never made for a transaction that rolled back, and are retried
+-- until the carrier answers.
+CREATE TABLE "carrier_requests" (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id UUID NOT NULL references deliveries(id) on delete cascade,
+    carrier VARCHAR(64) NOT NULL,
+    action VARCHAR(16) NOT NULL, -- CREATE, CANCEL
+    -- The shipment to cancel, or the one booked once a CREATE succeeded
+    tracking_number VARCHAR(128),
+    -- Added to the delivery log once the carrier answered
+    reason TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'PENDING', -- PENDING, SUCCEEDED, FAILED
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- NULL once no further attempt is planned
+    next_attempt_at TIMESTAMPTZ DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX carrier_requests_delivery_id_idx ON carrier_requests (delivery_id);
+CREATE INDEX carrier_requests_due_idx ON carrier_requests (next_attempt_at) WHERE status = 'PENDING';
+
+CREATE TRIGGER update_carrier_request_timestamp
+BEFORE UPDATE ON carrier_requests
+FOR EACH ROW
+EXECUTE FUNCTION diesel_set_updated_at();
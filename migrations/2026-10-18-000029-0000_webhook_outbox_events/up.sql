@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+-- Outbox events above the cursor that were fanned out to subscriptions. Transactions
+-- commit out of ID order, so events are marked one by one, and the cursor only moves past
+-- events old enough that nothing can commit below them anymore.
+CREATE TABLE "webhook_outbox_events" (
+    outbox_id INTEGER PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE delivery_addresses
+    DROP COLUMN recipient_name_latin,
+    DROP COLUMN street_address_latin,
+    DROP COLUMN city_latin,
+    DROP COLUMN state_latin;
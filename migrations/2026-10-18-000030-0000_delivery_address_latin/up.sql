@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+ALTER TABLE delivery_addresses
+    -- Latin-script spellings printed on labels and manifests, whose fonts cannot show Thai
+    ADD COLUMN recipient_name_latin VARCHAR(100),
+    ADD COLUMN street_address_latin VARCHAR(255),
+    ADD COLUMN city_latin VARCHAR(100),
+    ADD COLUMN state_latin VARCHAR(100);
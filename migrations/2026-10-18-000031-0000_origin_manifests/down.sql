@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM dispatch_manifests WHERE courier_id IS NULL;
+
+ALTER TABLE dispatch_manifests
+    DROP CONSTRAINT dispatch_manifests_courier_or_origin,
+    ALTER COLUMN courier_id SET NOT NULL;
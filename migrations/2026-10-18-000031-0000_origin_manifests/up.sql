@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Manifests of every courier collecting from an origin have no single courier
+ALTER TABLE dispatch_manifests
+    ALTER COLUMN courier_id DROP NOT NULL,
+    ADD CONSTRAINT dispatch_manifests_courier_or_origin
+        CHECK (courier_id IS NOT NULL OR origin_location_id IS NOT NULL);
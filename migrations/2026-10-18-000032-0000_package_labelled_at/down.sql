@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE delivery_packages
+    DROP COLUMN labelled_at;
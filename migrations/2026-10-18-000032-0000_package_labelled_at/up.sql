@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE delivery_packages
+    -- When a label was first rendered for the package
+    ADD COLUMN labelled_at TIMESTAMPTZ;
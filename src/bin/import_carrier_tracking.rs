@@ -0,0 +1,50 @@
+//! Imports a carrier's tracking CSV file, such as the nightly files of carriers without
+//! webhooks, and prints the import report as JSON.
+//!
+//! Usage: `import_carrier_tracking <carrier> <file> [columns]`, where `columns` overrides
+//! the carrier's `CARRIER_CSV_COLUMNS_<CARRIER>` mapping.
+
+use anyhow::{Context, Result, bail};
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+use medbook_core::{bootstrap, config};
+use medbook_deliveryservice::services::{carrier_imports, carriers};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    bootstrap::init_tracing();
+    bootstrap::init_env();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (carrier, path, columns) = match args.as_slice() {
+        [carrier, path] => (carrier, path, None),
+        [carrier, path, columns] => (carrier, path, Some(columns)),
+        _ => bail!("Usage: import_carrier_tracking <carrier> <file> [columns]"),
+    };
+
+    let adapter = carriers::adapter(carrier)?;
+    let mapping = match columns {
+        Some(columns) => carrier_imports::ColumnMapping::parse(columns)?,
+        None => carrier_imports::ColumnMapping::of(adapter.name())?,
+    };
+    let file = std::fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?;
+
+    let config = config::load()?;
+    let conn = &mut AsyncPgConnection::establish(&config.database.url)
+        .await
+        .context("Failed to connect to the DB")?;
+
+    let report = carrier_imports::import(conn, adapter, &mapping, &file).await?;
+    tracing::info!(
+        "Imported {} rows from {}: {} applied, {} skipped, {} failed",
+        report.rows,
+        path,
+        report.applied.len(),
+        report.skipped.len(),
+        report.failed.len()
+    );
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).context("Failed to serialize import report")?
+    );
+    Ok(())
+}
@@ -0,0 +1,314 @@
+//! Settings specific to the delivery service, read from the environment on top of
+//! the shared `medbook_core::config`.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+/// Secret used to sign custody ledger entries.
+pub fn custody_signing_key() -> Result<String> {
+    std::env::var("CUSTODY_SIGNING_KEY").context("CUSTODY_SIGNING_KEY is not set")
+}
+
+/// 256-bit key, hex encoded, used to encrypt delivery access codes.
+pub fn access_code_key() -> Result<[u8; 32]> {
+    let key = std::env::var("ACCESS_CODE_ENCRYPTION_KEY")
+        .context("ACCESS_CODE_ENCRYPTION_KEY is not set")?;
+    hex::decode(key)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .context("ACCESS_CODE_ENCRYPTION_KEY must be 64 hex characters")
+}
+
+/// How long a delivery of some priority may take to be picked up and to be delivered,
+/// counted from its creation.
+#[derive(Debug, Clone, Copy)]
+pub struct SlaTarget {
+    pub pickup: chrono::Duration,
+    pub delivery: chrono::Duration,
+}
+
+/// SLA targets per priority.
+#[derive(Debug, Clone, Copy)]
+pub struct SlaTargets {
+    pub standard: SlaTarget,
+    pub express: SlaTarget,
+    pub urgent: SlaTarget,
+}
+
+impl SlaTargets {
+    /// Targets of `priority`. Unknown priorities get the standard targets.
+    pub fn of(&self, priority: &str) -> SlaTarget {
+        match priority {
+            "URGENT" => self.urgent,
+            "EXPRESS" => self.express,
+            _ => self.standard,
+        }
+    }
+
+    /// The longest target of any priority and stage.
+    pub fn longest(&self) -> chrono::Duration {
+        [self.standard, self.express, self.urgent]
+            .iter()
+            .flat_map(|target| [target.pickup, target.delivery])
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Reads `SLA_<PRIORITY>_PICKUP_MINUTES` and `SLA_<PRIORITY>_DELIVERY_MINUTES`, falling
+/// back to defaults for unset variables.
+pub fn sla_targets() -> Result<SlaTargets> {
+    fn target(priority: &str, pickup_minutes: i64, delivery_minutes: i64) -> Result<SlaTarget> {
+        let minutes = |stage: &str, default: i64| -> Result<chrono::Duration> {
+            let name = format!("SLA_{priority}_{stage}_MINUTES");
+            let minutes = match std::env::var(&name) {
+                Ok(minutes) => minutes
+                    .parse()
+                    .with_context(|| format!("{name} must be a whole number of minutes"))?,
+                Err(_) => default,
+            };
+            Ok(chrono::Duration::minutes(minutes))
+        };
+        Ok(SlaTarget {
+            pickup: minutes("PICKUP", pickup_minutes)?,
+            delivery: minutes("DELIVERY", delivery_minutes)?,
+        })
+    }
+
+    Ok(SlaTargets {
+        standard: target("STANDARD", 240, 1440)?,
+        express: target("EXPRESS", 60, 240)?,
+        urgent: target("URGENT", 15, 60)?,
+    })
+}
+
+/// Minutes an EN_ROUTE delivery may run past its estimated arrival before it counts
+/// as an SLA breach.
+pub fn sla_eta_grace() -> Result<chrono::Duration> {
+    let minutes = match std::env::var("SLA_ETA_GRACE_MINUTES") {
+        Ok(minutes) => minutes
+            .parse()
+            .context("SLA_ETA_GRACE_MINUTES must be a whole number of minutes")?,
+        Err(_) => 15,
+    };
+    Ok(chrono::Duration::minutes(minutes))
+}
+
+/// Reads a job interval in seconds from `name`. Unset or zero disables the job.
+fn job_interval(name: &str) -> Result<Option<Duration>> {
+    match std::env::var(name) {
+        Ok(secs) => {
+            let secs: u64 = secs
+                .parse()
+                .with_context(|| format!("{name} must be a whole number of seconds"))?;
+            Ok((secs > 0).then(|| Duration::from_secs(secs)))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// How often the auto-dispatch worker runs.
+pub fn auto_dispatch_interval() -> Result<Option<Duration>> {
+    job_interval("AUTO_DISPATCH_INTERVAL_SECS")
+}
+
+/// How often deliveries are checked for SLA breaches.
+pub fn sla_check_interval() -> Result<Option<Duration>> {
+    job_interval("SLA_CHECK_INTERVAL_SECS")
+}
+
+/// Scoring strategy the auto-dispatch worker uses, `balanced` by default.
+pub fn dispatch_strategy() -> String {
+    std::env::var("DISPATCH_STRATEGY").unwrap_or_else(|_| "balanced".into())
+}
+
+/// How long a delivery may go without a delivery log before the sweeper acts on it.
+pub fn stuck_delivery_after() -> Result<chrono::Duration> {
+    let minutes = match std::env::var("STUCK_DELIVERY_MINUTES") {
+        Ok(minutes) => minutes
+            .parse()
+            .context("STUCK_DELIVERY_MINUTES must be a whole number of minutes")?,
+        Err(_) => 720,
+    };
+    Ok(chrono::Duration::minutes(minutes))
+}
+
+/// What the sweeper does with stuck deliveries: `escalate` (default), `cancel` or `requeue`.
+pub fn stuck_delivery_policy() -> String {
+    std::env::var("STUCK_DELIVERY_POLICY").unwrap_or_else(|_| "escalate".into())
+}
+
+/// How often the stuck delivery sweeper runs.
+pub fn stuck_sweep_interval() -> Result<Option<Duration>> {
+    job_interval("STUCK_SWEEP_INTERVAL_SECS")
+}
+
+/// How long a parcel waits for collection before it is returned, 72 hours by default.
+pub fn pickup_hold_time() -> Result<chrono::Duration> {
+    let hours = match std::env::var("PICKUP_HOLD_HOURS") {
+        Ok(hours) => hours
+            .parse()
+            .context("PICKUP_HOLD_HOURS must be a whole number of hours")?,
+        Err(_) => 72,
+    };
+    Ok(chrono::Duration::hours(hours))
+}
+
+/// How often parcels awaiting collection are checked for expiry.
+pub fn pickup_expiry_interval() -> Result<Option<Duration>> {
+    job_interval("PICKUP_EXPIRY_INTERVAL_SECS")
+}
+
+/// Compartments of the lockers served by the local locker providers, as
+/// `LOCKER_ID:COMPARTMENT,COMPARTMENT;LOCKER_ID:...`.
+pub fn locker_sites() -> String {
+    std::env::var("LOCKER_SITES").unwrap_or_default()
+}
+
+/// Locker provider compartments are allocated through, `database` by default.
+pub fn locker_provider() -> String {
+    std::env::var("LOCKER_PROVIDER").unwrap_or_else(|_| "database".into())
+}
+
+/// How often locker compartments are allocated for parcels ready for pickup and released
+/// once collected or taken back.
+pub fn locker_sync_interval() -> Result<Option<Duration>> {
+    job_interval("LOCKER_SYNC_INTERVAL_SECS")
+}
+
+/// How often delivery schedules are checked for due runs.
+pub fn schedule_check_interval() -> Result<Option<Duration>> {
+    job_interval("SCHEDULE_CHECK_INTERVAL_SECS")
+}
+
+/// Reads a number of days from `name`, falling back to `default` when unset.
+fn days(name: &str, default: i64) -> Result<chrono::Duration> {
+    let days = match std::env::var(name) {
+        Ok(days) => days
+            .parse()
+            .with_context(|| format!("{name} must be a whole number of days"))?,
+        Err(_) => default,
+    };
+    Ok(chrono::Duration::days(days))
+}
+
+/// How long before a scheduled run its order is requested, 3 days by default.
+pub fn schedule_lead_time() -> Result<chrono::Duration> {
+    days("SCHEDULE_LEAD_DAYS", 3)
+}
+
+/// How long before a scheduled run the patient is reminded, 2 days by default. Zero
+/// disables reminders.
+pub fn schedule_reminder_time() -> Result<chrono::Duration> {
+    days("SCHEDULE_REMINDER_DAYS", 2)
+}
+
+/// How long after a delivery is created new orders of the same patient to the same address
+/// are consolidated into it, 10 minutes by default. Zero disables consolidation.
+pub fn consolidation_window() -> Result<Option<chrono::Duration>> {
+    let minutes: i64 = match std::env::var("CONSOLIDATION_WINDOW_MINUTES") {
+        Ok(minutes) => minutes
+            .parse()
+            .context("CONSOLIDATION_WINDOW_MINUTES must be a whole number of minutes")?,
+        Err(_) => 10,
+    };
+    Ok((minutes > 0).then(|| chrono::Duration::minutes(minutes)))
+}
+
+/// Length of the delivery slots offered from an origin's opening hours.
+pub fn slot_length() -> Result<chrono::Duration> {
+    let minutes: i64 = match std::env::var("SLOT_LENGTH_MINUTES") {
+        Ok(minutes) => minutes
+            .parse()
+            .context("SLOT_LENGTH_MINUTES must be a whole number of minutes")?,
+        Err(_) => 120,
+    };
+    if minutes <= 0 {
+        anyhow::bail!("SLOT_LENGTH_MINUTES must be positive");
+    }
+    Ok(chrono::Duration::minutes(minutes))
+}
+
+/// Time zone working days are counted in, `Asia/Bangkok` by default.
+pub fn calendar_timezone() -> Result<chrono_tz::Tz> {
+    let timezone = std::env::var("CALENDAR_TIMEZONE").unwrap_or_else(|_| "Asia/Bangkok".into());
+    timezone
+        .parse()
+        .map_err(|_| anyhow::anyhow!("CALENDAR_TIMEZONE {timezone} is not a known time zone"))
+}
+
+/// Weekdays nothing ships on, as comma separated names such as `Sat,Sun`. None by default.
+pub fn non_working_weekdays() -> Result<Vec<chrono::Weekday>> {
+    std::env::var("NON_WORKING_WEEKDAYS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|weekday| !weekday.is_empty())
+        .map(|weekday| {
+            weekday.parse().map_err(|_| {
+                anyhow::anyhow!("NON_WORKING_WEEKDAYS has an unknown weekday {weekday}")
+            })
+        })
+        .collect()
+}
+
+/// Which deliveries auto-dispatch hands to an external carrier, as `zone:ZONE=CARRIER` or
+/// `priority:PRIORITY=CARRIER` rules separated by `;`. The first matching rule wins.
+pub fn carrier_rules() -> String {
+    std::env::var("CARRIER_RULES").unwrap_or_default()
+}
+
+/// How long the mock carrier takes between tracking statuses, 30 minutes by default.
+pub fn mock_carrier_step() -> Result<chrono::Duration> {
+    let minutes: i64 = match std::env::var("MOCK_CARRIER_STEP_MINUTES") {
+        Ok(minutes) => minutes
+            .parse()
+            .context("MOCK_CARRIER_STEP_MINUTES must be a whole number of minutes")?,
+        Err(_) => 30,
+    };
+    Ok(chrono::Duration::minutes(minutes))
+}
+
+/// How often carrier bookings and cancellations left pending are retried.
+pub fn carrier_request_interval() -> Result<Option<Duration>> {
+    job_interval("CARRIER_REQUEST_INTERVAL_SECS")
+}
+
+/// Secrets webhooks of `carrier` may be signed with, comma separated in
+/// `CARRIER_WEBHOOK_SECRETS_<CARRIER>`. Listing the old and the new secret while a carrier
+/// rotates keeps its webhooks flowing.
+pub fn carrier_webhook_secrets(carrier: &str) -> Vec<String> {
+    std::env::var(format!(
+        "CARRIER_WEBHOOK_SECRETS_{}",
+        carrier.to_uppercase()
+    ))
+    .unwrap_or_default()
+    .split(',')
+    .map(str::trim)
+    .filter(|secret| !secret.is_empty())
+    .map(String::from)
+    .collect()
+}
+
+/// Columns of the tracking CSV files `carrier` sends, as `field=Header` pairs separated by
+/// `;` in `CARRIER_CSV_COLUMNS_<CARRIER>`, such as `tracking_number=AWB;code=Status`.
+/// Fields not listed are read from columns named after them.
+pub fn carrier_csv_columns(carrier: &str) -> String {
+    std::env::var(format!("CARRIER_CSV_COLUMNS_{}", carrier.to_uppercase())).unwrap_or_default()
+}
+
+/// How often outbox events are fanned out to webhook subscriptions and due webhooks sent.
+pub fn webhook_dispatch_interval() -> Result<Option<Duration>> {
+    job_interval("WEBHOOK_DISPATCH_INTERVAL_SECS")
+}
+
+/// How many times a webhook is attempted before it is given up on, 8 by default.
+pub fn webhook_max_attempts() -> Result<i32> {
+    match std::env::var("WEBHOOK_MAX_ATTEMPTS") {
+        Ok(attempts) => attempts
+            .parse()
+            .context("WEBHOOK_MAX_ATTEMPTS must be a whole number"),
+        Err(_) => Ok(8),
+    }
+}
@@ -1,44 +1,203 @@
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
-use diesel::SelectableHelper;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
 use diesel_async::{AsyncConnection, RunQueryDsl};
 use futures::future::BoxFuture;
 use lapin::{message::Delivery, options::BasicAckOptions};
 use medbook_core::{app_error::AppError, app_state::AppState, outbox};
-use medbook_events::{DeliveryCreatedEvent, DeliveryOrderRequestEvent};
-use tracing::info;
+use medbook_events::DeliveryCreatedEvent;
+use tracing::{error, info, warn};
 
 use crate::{
-    models::{CreateDeliveryEntity, DeliveryEntity},
-    schema::deliveries,
+    config,
+    events::DeliveryOrderRequest,
+    models::{
+        CreateDeliveryEntity, CreateDeliveryOrderEntity, DeliveryAddressEntity, DeliveryEntity,
+    },
+    schema::{deliveries, delivery_addresses, delivery_orders, locations},
+    services,
 };
 
 pub fn order_request(delivery: Delivery, state: Arc<AppState>) -> BoxFuture<'static, Result<()>> {
     Box::pin(async move {
         let conn = &mut state.db_pool.get().await?;
-        let payload: DeliveryOrderRequestEvent =
-            serde_json::from_str(str::from_utf8(&delivery.data)?)?;
+        let payload: DeliveryOrderRequest = serde_json::from_str(str::from_utf8(&delivery.data)?)?;
+
+        let priority = match payload.priority.as_deref() {
+            None => "STANDARD",
+            Some(priority) if services::deliveries::priority_rank(priority).is_some() => priority,
+            Some(priority) => {
+                warn!(
+                    "Order {} has unknown priority {}, treating it as STANDARD",
+                    payload.event.order_id, priority
+                );
+                "STANDARD"
+            }
+        }
+        .to_string();
+
+        let fulfilment_mode = match payload.fulfilment_mode.as_deref() {
+            None => "HOME",
+            Some(mode) if services::deliveries::FULFILMENT_MODES.contains(&mode) => mode,
+            Some(mode) => {
+                warn!(
+                    "Order {} has unknown fulfilment mode {}, delivering it HOME",
+                    payload.event.order_id, mode
+                );
+                "HOME"
+            }
+        };
+        let fulfilment_mode = if fulfilment_mode == "LOCKER" && payload.pickup_point.is_none() {
+            warn!(
+                "Order {} is for a LOCKER but names no locker, delivering it HOME",
+                payload.event.order_id
+            );
+            "HOME"
+        } else {
+            fulfilment_mode
+        }
+        .to_string();
+
+        let patient_id = payload.patient_id.or_else(|| {
+            let patient_id = payload.event.delivery_address.as_ref()?.get("patient_id")?;
+            i32::try_from(patient_id.as_i64()?).ok()
+        });
+
+        let address_id = payload
+            .event
+            .delivery_address
+            .as_ref()
+            .and_then(|address| address.get("id")?.as_i64())
+            .and_then(|id| i32::try_from(id).ok());
+
+        // Rejecting the order for an invalid package would fail the message on every
+        // redelivery, so the delivery is created without it instead.
+        let packages: Vec<_> = payload
+            .packages
+            .into_iter()
+            .enumerate()
+            .filter_map(
+                |(i, manifest)| match services::packages::validate_manifest(&manifest) {
+                    Ok(()) => Some(manifest),
+                    Err(reason) => {
+                        error!(
+                            "Order {} package {} is invalid, {}; leaving it out",
+                            payload.event.order_id,
+                            i + 1,
+                            reason
+                        );
+                        None
+                    }
+                },
+            )
+            .collect();
+
+        let consolidation_window = config::consolidation_window()?;
 
         let deliv = conn
             .transaction(move |conn| {
                 Box::pin(async move {
-                    let deliv: DeliveryEntity = diesel::insert_into(deliveries::table)
-                        .values(CreateDeliveryEntity {
-                            delivery_address: payload.delivery_address,
-                            order_id: payload.order_id,
-                            status: "PREPARING".into(),
-                        })
-                        .returning(DeliveryEntity::as_returning())
-                        .get_result(conn)
-                        .await
-                        .context("Failed to create delivery")?;
+                    // The snapshot from the order service has no preferences, so they are
+                    // copied from the patient's saved address.
+                    let address: Option<DeliveryAddressEntity> = match (address_id, patient_id) {
+                        (Some(address_id), Some(patient_id)) => delivery_addresses::table
+                            .find(address_id)
+                            .filter(delivery_addresses::patient_id.eq(patient_id))
+                            .get_result(conn)
+                            .await
+                            .optional()
+                            .context("Failed to get delivery address")?,
+                        _ => None,
+                    };
+
+                    let origin_location_id = match payload.origin_location_id {
+                        Some(origin_id) => {
+                            let known: Option<i32> = locations::table
+                                .find(origin_id)
+                                .select(locations::id)
+                                .get_result(conn)
+                                .await
+                                .optional()
+                                .context("Failed to get origin location")?;
+                            if known.is_none() {
+                                warn!(
+                                    "Order {} ships from unknown location {}, leaving its origin empty",
+                                    payload.event.order_id, origin_id
+                                );
+                            }
+                            known
+                        }
+                        None => None,
+                    };
+
+                    let new_delivery = CreateDeliveryEntity {
+                        delivery_address: payload.event.delivery_address,
+                        order_id: payload.event.order_id,
+                        status: "PREPARING".into(),
+                        min_temperature_c: payload.min_temperature_c,
+                        max_temperature_c: payload.max_temperature_c,
+                        is_controlled: payload.is_controlled,
+                        requires_id_verification: payload.requires_id_verification,
+                        latitude: payload.latitude,
+                        longitude: payload.longitude,
+                        slot_start: payload.slot_start,
+                        slot_end: payload.slot_end,
+                        zone: payload.zone,
+                        priority,
+                        patient_id,
+                        delivery_instructions: address
+                            .as_ref()
+                            .and_then(|address| address.delivery_instructions.clone()),
+                        access_code_encrypted: address
+                            .as_ref()
+                            .and_then(|address| address.access_code_encrypted.clone()),
+                        preferred_contact_method: address
+                            .as_ref()
+                            .and_then(|address| address.preferred_contact_method.clone()),
+                        contactless_consent: address
+                            .is_some_and(|address| address.contactless_consent),
+                        schedule_id: payload.schedule_id,
+                        fulfilment_mode,
+                        pickup_point: payload.pickup_point,
+                        origin_location_id,
+                    };
+
+                    let consolidated = match consolidation_window {
+                        Some(window) => {
+                            services::consolidation::attach(conn, &new_delivery, window).await?
+                        }
+                        None => None,
+                    };
+                    let deliv = match consolidated {
+                        Some(deliv) => deliv,
+                        None => {
+                            let deliv: DeliveryEntity = diesel::insert_into(deliveries::table)
+                                .values(new_delivery)
+                                .returning(DeliveryEntity::as_returning())
+                                .get_result(conn)
+                                .await
+                                .context("Failed to create delivery")?;
+
+                            diesel::insert_into(delivery_orders::table)
+                                .values(CreateDeliveryOrderEntity {
+                                    delivery_id: deliv.id,
+                                    order_id: deliv.order_id,
+                                })
+                                .execute(conn)
+                                .await
+                                .context("Failed to link order to delivery")?;
+                            deliv
+                        }
+                    };
+
+                    services::packages::create_packages(conn, deliv.id, packages).await?;
 
                     outbox::publish(
                         conn,
                         "orders.delivery_created".into(),
                         DeliveryCreatedEvent {
-                            order_id: payload.order_id,
+                            order_id: payload.event.order_id,
                             delivery_id: deliv.id,
                         },
                     )
@@ -0,0 +1,120 @@
+//! Minimal CSV reading and writing used by the imports and export endpoints.
+
+use anyhow::{Result, bail};
+
+/// Appends one CSV record to `out`, quoting fields where RFC 4180 requires it.
+pub fn write_record<S: AsRef<str>>(out: &mut String, fields: &[S]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let field = field.as_ref();
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Splits RFC 4180 CSV into records, skipping blank lines. Accepts both `\r\n` and `\n`
+/// line endings and a leading byte order mark.
+pub fn read_records(input: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' | '\n' if !quoted => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|field| !field.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("CSV ends inside a quoted field");
+    }
+    record.push(field);
+    if record.iter().any(|field| !field.is_empty()) {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_records_unquotes_fields() {
+        let records = read_records("a,\"b,c\",\"say \"\"hi\"\"\"\n").unwrap();
+        assert_eq!(records, vec![vec!["a", "b,c", "say \"hi\""]]);
+    }
+
+    #[test]
+    fn read_records_keeps_line_breaks_inside_quotes() {
+        let records = read_records("\"line 1\r\nline 2\",x\r\n").unwrap();
+        assert_eq!(records, vec![vec!["line 1\r\nline 2", "x"]]);
+    }
+
+    #[test]
+    fn read_records_accepts_crlf_and_lf_and_skips_blank_lines() {
+        let records = read_records("a,b\r\n\r\nc,d\ne,f").unwrap();
+        assert_eq!(
+            records,
+            vec![vec!["a", "b"], vec!["c", "d"], vec!["e", "f"]]
+        );
+    }
+
+    #[test]
+    fn read_records_strips_a_byte_order_mark() {
+        let records = read_records("\u{feff}Tracking Number,Status\r\n").unwrap();
+        assert_eq!(records, vec![vec!["Tracking Number", "Status"]]);
+    }
+
+    #[test]
+    fn read_records_keeps_empty_fields() {
+        let records = read_records("a,,\n").unwrap();
+        assert_eq!(records, vec![vec!["a", "", ""]]);
+    }
+
+    #[test]
+    fn read_records_rejects_unterminated_quotes() {
+        assert!(read_records("a,\"b\n").is_err());
+    }
+
+    #[test]
+    fn read_records_reads_what_write_record_writes() {
+        let fields = ["plain", "with, comma", "with \"quotes\"", "two\nlines"];
+        let mut out = String::new();
+        write_record(&mut out, &fields);
+        write_record(&mut out, &fields);
+        assert_eq!(
+            read_records(&out).unwrap(),
+            vec![fields.to_vec(), fields.to_vec()]
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Code 128 (code set B) barcode encoding.
+
+/// Bar and space widths, in modules, of each symbol value. 103-105 are the start symbols.
+const PATTERNS: [&str; 106] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232",
+];
+const START_B: usize = 104;
+const STOP: &str = "2331112";
+
+/// Encodes `data` as alternating bar/space widths in modules, starting with a bar.
+/// Returns `None` if `data` contains characters outside printable ASCII.
+pub fn encode(data: &str) -> Option<Vec<u8>> {
+    let values = data
+        .bytes()
+        .map(|byte| (32..=127).contains(&byte).then(|| usize::from(byte - 32)))
+        .collect::<Option<Vec<_>>>()?;
+
+    let checksum = values
+        .iter()
+        .enumerate()
+        .fold(START_B, |sum, (i, value)| sum + (i + 1) * value)
+        % 103;
+
+    let symbols = std::iter::once(PATTERNS[START_B])
+        .chain(values.iter().map(|value| PATTERNS[*value]))
+        .chain([PATTERNS[checksum], STOP]);
+
+    Some(
+        symbols
+            .flat_map(|pattern| pattern.bytes().map(|width| width - b'0'))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_frames_data_with_start_b_checksum_and_stop() {
+        // P J J 1 2 3 C: 104 + 48 + 2*42 + 3*42 + 4*17 + 5*18 + 6*19 + 7*35 = 879, and
+        // 879 mod 103 = 55.
+        let widths = encode("PJJ123C").unwrap();
+        assert_eq!(widths.len(), 9 * 6 + 7);
+        assert_eq!(widths[..6], [2, 1, 1, 2, 1, 4]);
+        assert_eq!(widths[6..12], [3, 1, 3, 1, 2, 1]);
+        assert_eq!(widths[48..54], [3, 1, 1, 3, 2, 1]);
+        assert_eq!(widths[54..], [2, 3, 3, 1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn encode_gives_every_symbol_eleven_modules() {
+        let widths = encode("0123456789abcdef").unwrap();
+        let (symbols, stop) = widths.split_at(widths.len() - 7);
+        assert!(
+            symbols.chunks(6).all(|symbol| symbol
+                .iter()
+                .map(|width| u32::from(*width))
+                .sum::<u32>()
+                == 11)
+        );
+        assert_eq!(stop.iter().map(|width| u32::from(*width)).sum::<u32>(), 13);
+    }
+
+    #[test]
+    fn encode_rejects_characters_outside_code_set_b() {
+        assert_eq!(encode("ABC\n"), None);
+        assert_eq!(encode("กรุงเทพ"), None);
+    }
+}
@@ -0,0 +1,295 @@
+//! Package labels, as PDF for office printers and ZPL for thermal printers.
+
+use qrcodegen::{QrCode, QrCodeEcc};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::{
+    code128,
+    pdf::{self, Document, Font, Page},
+};
+use crate::models::{DeliveryEntity, DeliveryPackageEntity};
+
+/// Label size: 4 x 6 inches.
+const WIDTH_IN: f32 = 4.0;
+const HEIGHT_IN: f32 = 6.0;
+/// Thermal printer resolution in dots per inch.
+const ZPL_DPI: f32 = 203.0;
+/// Side of the QR code on the PDF label, in points.
+const QR_SIZE_PT: f32 = 80.0;
+
+pub struct PackageLabel {
+    pub delivery_id: Uuid,
+    pub package_id: Uuid,
+    pub package_number: i32,
+    pub package_count: usize,
+    pub recipient_name: String,
+    pub address_lines: Vec<String>,
+    pub handling: Vec<&'static str>,
+}
+
+impl PackageLabel {
+    pub fn new(
+        delivery: &DeliveryEntity,
+        package: &DeliveryPackageEntity,
+        package_count: usize,
+    ) -> Self {
+        let address = delivery.delivery_address.as_ref();
+        let value = |name: &str| {
+            address
+                .and_then(|address| address.get(name))
+                .and_then(Value::as_str)
+        };
+        // Thermal printer fonts cannot show Thai script either.
+        let field = |name: &str| {
+            super::printable(
+                value(name).unwrap_or_default(),
+                value(&format!("{name}_latin")),
+            )
+            .to_string()
+        };
+
+        let mut handling = Vec::new();
+        if package.is_fragile {
+            handling.push("FRAGILE");
+        }
+        if package.is_refrigerated {
+            handling.push("KEEP COLD");
+        }
+        if delivery.is_controlled {
+            handling.push("CONTROLLED");
+        }
+        if delivery.requires_id_verification {
+            handling.push("ID CHECK");
+        }
+
+        let locality = [field("city"), field("state"), field("postal_code")]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Self {
+            delivery_id: delivery.id,
+            package_id: package.id,
+            package_number: package.package_number,
+            package_count,
+            recipient_name: field("recipient_name"),
+            address_lines: vec![mask_street_address(&field("street_address")), locality],
+            handling,
+        }
+    }
+
+    /// Contents of the 2D code: the delivery and package IDs, as parsed by `parse_code`.
+    pub fn code(&self) -> String {
+        format!("{}:{}", self.delivery_id, self.package_id)
+    }
+
+    /// Contents of the linear barcode: the package ID without hyphens, to keep it short
+    /// enough for a 4-inch label.
+    pub fn barcode(&self) -> String {
+        self.package_id.simple().to_string()
+    }
+
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let width = WIDTH_IN * 72.0;
+        let height = HEIGHT_IN * 72.0;
+        let margin = 6.0 * pdf::MM;
+        let mut page = Page::new();
+
+        page.stroke_rect(margin / 2.0, margin / 2.0, width - margin, height - margin);
+
+        let mut y = height - margin - 14.0;
+        page.text(margin, y, 10.0, Font::Regular, "DELIVER TO");
+        y -= 22.0;
+        page.text(margin, y, 18.0, Font::Bold, &self.recipient_name);
+        for line in &self.address_lines {
+            y -= 18.0;
+            page.text(margin, y, 12.0, Font::Regular, line);
+        }
+
+        y -= 30.0;
+        page.text(
+            margin,
+            y,
+            14.0,
+            Font::Bold,
+            &format!("PACKAGE {} OF {}", self.package_number, self.package_count),
+        );
+
+        let mut x = margin;
+        for handling in &self.handling {
+            let box_width = handling.len() as f32 * 7.5 + 12.0;
+            page.stroke_rect(x, y - 36.0, box_width, 22.0);
+            page.text(x + 6.0, y - 29.0, 11.0, Font::Bold, handling);
+            x += box_width + 6.0;
+        }
+
+        // Between the handling boxes and the barcode, clear of the border by a quiet zone.
+        if let Ok(qr) = QrCode::encode_text(&self.code(), QrCodeEcc::Medium) {
+            page.matrix(
+                width - 2.0 * margin - QR_SIZE_PT,
+                182.0 + QR_SIZE_PT,
+                QR_SIZE_PT / qr.size() as f32,
+                qr.size(),
+                |column, row| qr.get_module(column, row),
+            );
+        }
+
+        if let Some(bars) = code128::encode(&self.barcode()) {
+            let modules: u32 = bars.iter().map(|width| u32::from(*width)).sum();
+            let module_width = (width - 2.0 * margin) / modules as f32;
+            page.barcode(margin, 70.0, module_width, 90.0, &bars);
+        }
+        page.text(
+            margin,
+            56.0,
+            9.0,
+            Font::Regular,
+            &self.package_id.to_string(),
+        );
+        page.text(
+            margin,
+            40.0,
+            9.0,
+            Font::Regular,
+            &format!("Delivery {}", self.delivery_id),
+        );
+
+        let mut document = Document::new(width, height);
+        document.push(page);
+        document.render()
+    }
+
+    pub fn to_zpl(&self) -> String {
+        let dots = |inches: f32| (inches * ZPL_DPI) as i32;
+        let mut zpl = format!(
+            "^XA^CI28^PW{}^LL{}\n^FO20,20^GB{},{},3^FS\n",
+            dots(WIDTH_IN),
+            dots(HEIGHT_IN),
+            dots(WIDTH_IN) - 40,
+            dots(HEIGHT_IN) - 40,
+        );
+
+        zpl.push_str("^FO50,50^A0N,28,28^FDDELIVER TO^FS\n");
+        zpl.push_str(&format!(
+            "^FO50,90^A0N,48,48^FD{}^FS\n",
+            zpl_field(&self.recipient_name)
+        ));
+        let mut y = 150;
+        for line in &self.address_lines {
+            zpl.push_str(&format!("^FO50,{y}^A0N,34,34^FD{}^FS\n", zpl_field(line)));
+            y += 45;
+        }
+
+        y += 25;
+        zpl.push_str(&format!(
+            "^FO50,{y}^A0N,40,40^FDPACKAGE {} OF {}^FS\n",
+            self.package_number, self.package_count
+        ));
+
+        y += 60;
+        let mut x = 50;
+        for handling in &self.handling {
+            let box_width = handling.len() as i32 * 22 + 30;
+            zpl.push_str(&format!("^FO{x},{y}^GB{box_width},60,3^FS\n"));
+            zpl.push_str(&format!(
+                "^FO{},{}^A0N,32,32^FD{handling}^FS\n",
+                x + 15,
+                y + 15
+            ));
+            x += box_width + 15;
+        }
+
+        zpl.push_str(&format!(
+            "^FO50,{}^BQN,2,6^FDMA,{}^FS\n",
+            y + 90,
+            self.code()
+        ));
+        zpl.push_str(&format!(
+            "^FO19,{}^BY2^BCN,160,Y,N,N^FD{}^FS\n",
+            dots(HEIGHT_IN) - 280,
+            self.barcode()
+        ));
+        zpl.push_str("^XZ\n");
+        zpl
+    }
+}
+
+/// Resolves scanned label contents to `(delivery_id, package_id)`. The 2D code holds
+/// both IDs; the linear barcode holds a bare ID, which may be either.
+pub fn parse_code(code: &str) -> Option<(Option<Uuid>, Uuid)> {
+    match code.trim().split_once(':') {
+        Some((delivery_id, package_id)) => {
+            Some((Some(delivery_id.parse().ok()?), package_id.parse().ok()?))
+        }
+        None => Some((None, code.trim().parse().ok()?)),
+    }
+}
+
+/// Keeps the house number and the first letter of each other word of a street address.
+pub fn mask_street_address(street_address: &str) -> String {
+    let mut words = street_address.split_whitespace();
+    let Some(house_number) = words.next() else {
+        return String::new();
+    };
+    std::iter::once(house_number.to_string())
+        .chain(words.map(|word| {
+            word.chars()
+                .enumerate()
+                .map(|(i, c)| if i == 0 { c } else { '*' })
+                .collect()
+        }))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Strips the ZPL control characters from a field value.
+fn zpl_field(value: &str) -> String {
+    value.replace(['^', '~'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELIVERY_ID: &str = "6f1c2a9e-8d3b-4c57-9a1e-2b7d4f0c8e13";
+    const PACKAGE_ID: &str = "0d9e7b52-3a4f-4e1b-8c6d-5f2a1b3c4d5e";
+
+    #[test]
+    fn parse_code_reads_both_ids_from_the_2d_code() {
+        assert_eq!(
+            parse_code(&format!(" {DELIVERY_ID}:{PACKAGE_ID}\n")),
+            Some((
+                Some(DELIVERY_ID.parse().unwrap()),
+                PACKAGE_ID.parse().unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_code_reads_the_bare_id_of_the_barcode() {
+        let package_id: Uuid = PACKAGE_ID.parse().unwrap();
+        assert_eq!(
+            parse_code(&package_id.simple().to_string()),
+            Some((None, package_id))
+        );
+    }
+
+    #[test]
+    fn parse_code_rejects_anything_else() {
+        assert_eq!(parse_code(""), None);
+        assert_eq!(parse_code("PKG-1"), None);
+        assert_eq!(parse_code(&format!("{DELIVERY_ID}:PKG-1")), None);
+        assert_eq!(parse_code(&format!("order:{PACKAGE_ID}")), None);
+    }
+
+    #[test]
+    fn mask_street_address_keeps_the_house_number_and_initials() {
+        assert_eq!(
+            mask_street_address("99/1 Sukhumvit Road"),
+            "99/1 S******** R***"
+        );
+        assert_eq!(mask_street_address("  "), "");
+    }
+}
@@ -0,0 +1,192 @@
+//! Dispatch manifests handed over with a batch of parcels, as CSV and PDF.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use uuid::Uuid;
+
+use super::{
+    pdf::{Document, Font, Page},
+    printable,
+};
+use crate::csv;
+
+/// A4 portrait, in points.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+const ROW_HEIGHT: f32 = 16.0;
+
+pub struct ManifestRow {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub recipient_name: String,
+    pub city: String,
+    /// Latin-script spellings printed on the PDF when it cannot show the fields above.
+    pub recipient_name_latin: Option<String>,
+    pub city_latin: Option<String>,
+    pub package_count: i32,
+}
+
+pub struct Manifest<'a> {
+    pub id: Uuid,
+    /// Couriers collecting the parcels, usually just one.
+    pub courier_names: &'a [String],
+    /// Pharmacy, hub or lab the parcels leave from, when the manifest covers only one.
+    pub origin_name: Option<&'a str>,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub generated_by: &'a str,
+    pub generated_at: DateTime<Utc>,
+    pub rows: &'a [ManifestRow],
+}
+
+impl Manifest<'_> {
+    pub fn package_count(&self) -> i32 {
+        self.rows.iter().map(|row| row.package_count).sum()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        csv::write_record(
+            &mut out,
+            &[
+                "manifest_id",
+                "delivery_id",
+                "order_id",
+                "recipient_name",
+                "city",
+                "package_count",
+            ],
+        );
+        for row in self.rows {
+            csv::write_record(
+                &mut out,
+                &[
+                    self.id.to_string(),
+                    row.delivery_id.to_string(),
+                    row.order_id.to_string(),
+                    row.recipient_name.clone(),
+                    row.city.clone(),
+                    row.package_count.to_string(),
+                ],
+            );
+        }
+        out
+    }
+
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let mut document = Document::new(PAGE_WIDTH, PAGE_HEIGHT);
+        let mut page = Page::new();
+        let mut y = PAGE_HEIGHT - MARGIN;
+
+        page.text(MARGIN, y, 18.0, Font::Bold, "Dispatch manifest");
+        y -= 24.0;
+        let mut lines = vec![
+            format!("Manifest: {}", self.id),
+            format!(
+                "{}: {}",
+                if self.courier_names.len() == 1 {
+                    "Courier"
+                } else {
+                    "Couriers"
+                },
+                self.courier_names.join(", ")
+            ),
+        ];
+        if let Some(origin_name) = self.origin_name {
+            lines.push(format!("Origin: {origin_name}"));
+        }
+        lines.extend([
+            format!(
+                "Window: {} to {}",
+                self.window_start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                self.window_end.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+            format!(
+                "Generated by {} at {}",
+                self.generated_by,
+                self.generated_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+        ]);
+        for line in lines {
+            page.text(MARGIN, y, 10.0, Font::Regular, &line);
+            y -= 14.0;
+        }
+
+        y -= 10.0;
+        header_row(&mut page, y);
+        y -= ROW_HEIGHT;
+
+        for (i, row) in self.rows.iter().enumerate() {
+            if y < MARGIN + ROW_HEIGHT {
+                document.push(page);
+                page = Page::new();
+                y = PAGE_HEIGHT - MARGIN;
+                header_row(&mut page, y);
+                y -= ROW_HEIGHT;
+            }
+            let cells = [
+                (i + 1).to_string(),
+                row.delivery_id.to_string(),
+                row.order_id.to_string(),
+                printable(&row.recipient_name, row.recipient_name_latin.as_deref()).into(),
+                printable(&row.city, row.city_latin.as_deref()).into(),
+                row.package_count.to_string(),
+            ];
+            for (x, cell) in COLUMNS.iter().map(|(x, _)| x).zip(&cells) {
+                page.text(MARGIN + x, y, 8.0, Font::Regular, cell);
+            }
+            y -= ROW_HEIGHT;
+        }
+
+        // Totals and the signature block stay together on one page.
+        if y < MARGIN + 150.0 {
+            document.push(page);
+            page = Page::new();
+            y = PAGE_HEIGHT - MARGIN;
+        }
+        y -= 10.0;
+        page.line(MARGIN, y + 12.0, PAGE_WIDTH - MARGIN, y + 12.0);
+        page.text(
+            MARGIN,
+            y,
+            11.0,
+            Font::Bold,
+            &format!(
+                "Total: {} deliveries, {} packages",
+                self.rows.len(),
+                self.package_count()
+            ),
+        );
+
+        y -= 50.0;
+        for party in ["Handed over by (pharmacy)", "Received by (courier)"] {
+            page.text(MARGIN, y, 10.0, Font::Bold, party);
+            y -= 30.0;
+            for (x, label) in [(0.0, "Name"), (190.0, "Signature"), (380.0, "Date / time")] {
+                page.line(MARGIN + x, y, MARGIN + x + 150.0, y);
+                page.text(MARGIN + x, y - 12.0, 8.0, Font::Regular, label);
+            }
+            y -= 40.0;
+        }
+
+        document.push(page);
+        document.render()
+    }
+}
+
+/// Column offsets from the margin, and headings.
+const COLUMNS: [(f32, &str); 6] = [
+    (0.0, "#"),
+    (25.0, "Delivery"),
+    (200.0, "Order"),
+    (250.0, "Recipient"),
+    (400.0, "City"),
+    (480.0, "Packages"),
+];
+
+fn header_row(page: &mut Page, y: f32) {
+    for (x, heading) in COLUMNS {
+        page.text(MARGIN + x, y, 9.0, Font::Bold, heading);
+    }
+    page.line(MARGIN, y - 4.0, PAGE_WIDTH - MARGIN, y - 4.0);
+}
@@ -0,0 +1,26 @@
+//! Printable documents generated by the service.
+
+pub mod code128;
+pub mod labels;
+pub mod manifests;
+pub mod pdf;
+
+/// `text`, or its Latin-script spelling when the PDF fonts cannot show `text`.
+pub fn printable<'a>(text: &'a str, latin: Option<&'a str>) -> &'a str {
+    match latin {
+        Some(latin) if !pdf::can_show(text) => latin,
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printable_prefers_the_latin_spelling_of_thai_text() {
+        assert_eq!(printable("สมชาย", Some("Somchai")), "Somchai");
+        assert_eq!(printable("สมชาย", None), "สมชาย");
+        assert_eq!(printable("Somchai", Some("Somchai J.")), "Somchai");
+    }
+}
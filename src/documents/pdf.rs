@@ -0,0 +1,217 @@
+//! A small PDF writer covering what labels and manifests need: text in the standard
+//! Helvetica fonts, filled and stroked rectangles, lines, and bar and matrix codes.
+//!
+//! Fonts are not embedded, so text is limited to printable ASCII; other characters are
+//! printed as `?`. Documents print the Latin-script spelling of Thai address fields
+//! instead, see [`super::printable`].
+
+use std::fmt::Write;
+
+/// Points per millimetre.
+pub const MM: f32 = 72.0 / 25.4;
+
+#[derive(Clone, Copy)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// A page under construction. Coordinates are in points from the bottom-left corner.
+#[derive(Default)]
+pub struct Page {
+    content: String,
+}
+
+impl Page {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+        let _ = writeln!(
+            self.content,
+            "BT /{} {size:.1} Tf {x:.2} {y:.2} Td ({}) Tj ET",
+            font.resource(),
+            escape(text)
+        );
+    }
+
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let _ = writeln!(self.content, "{x:.2} {y:.2} {width:.2} {height:.2} re f");
+    }
+
+    pub fn stroke_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let _ = writeln!(self.content, "{x:.2} {y:.2} {width:.2} {height:.2} re S");
+    }
+
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        let _ = writeln!(self.content, "{x1:.2} {y1:.2} m {x2:.2} {y2:.2} l S");
+    }
+
+    /// Draws bars from alternating bar/space widths, such as those from `code128::encode`.
+    pub fn barcode(&mut self, x: f32, y: f32, module_width: f32, height: f32, widths: &[u8]) {
+        let mut cursor = x;
+        for (i, width) in widths.iter().enumerate() {
+            let width = f32::from(*width) * module_width;
+            if i % 2 == 0 {
+                self.fill_rect(cursor, y, width, height);
+            }
+            cursor += width;
+        }
+    }
+
+    /// Draws a square matrix code of `size` modules, such as a QR code, with its top-left
+    /// corner at `(x, y)`. `is_dark` tells whether the module at a column and row is dark.
+    pub fn matrix(
+        &mut self,
+        x: f32,
+        y: f32,
+        module_size: f32,
+        size: i32,
+        is_dark: impl Fn(i32, i32) -> bool,
+    ) {
+        for row in 0..size {
+            for column in 0..size {
+                if is_dark(column, row) {
+                    self.fill_rect(
+                        x + column as f32 * module_size,
+                        y - (row + 1) as f32 * module_size,
+                        module_size,
+                        module_size,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// A document whose pages all share one size, given in points.
+pub struct Document {
+    width: f32,
+    height: f32,
+    pages: Vec<Page>,
+}
+
+impl Document {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            pages: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, page: Page) {
+        self.pages.push(page);
+    }
+
+    pub fn render(&self) -> Vec<u8> {
+        // Objects 1-4 are the catalog, page tree and fonts; each page adds a page and a
+        // content object.
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..self.pages.len())
+                    .map(|i| format!("{} 0 R", 5 + i * 2))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                self.pages.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+        ];
+        for (i, page) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                self.width,
+                self.height,
+                6 + i * 2
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                page.content.len(),
+                page.content
+            ));
+        }
+
+        let mut out: Vec<u8> = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{object}\nendobj\n", i + 1).as_bytes());
+        }
+
+        let xref = out.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        );
+        out.extend_from_slice(trailer.as_bytes());
+        out
+    }
+}
+
+/// Whether the fonts can show every character of `text`.
+pub fn can_show(text: &str) -> bool {
+    text.chars().all(is_shown)
+}
+
+fn is_shown(c: char) -> bool {
+    matches!(c, ' '..='~')
+}
+
+/// Escapes a string for a PDF literal, replacing characters the fonts cannot show.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if is_shown(c) => escaped.push(c),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_escapes_string_delimiters() {
+        assert_eq!(escape(r"Ward (B) \ 2"), r"Ward \(B\) \\ 2");
+    }
+
+    #[test]
+    fn escape_replaces_characters_the_fonts_cannot_show() {
+        assert_eq!(escape("สมชาย ใจดี"), "????? ????");
+        assert_eq!(escape("Café\t1"), "Caf??1");
+    }
+
+    #[test]
+    fn can_show_only_printable_ascii() {
+        assert!(can_show("Somchai Jaidee, 10110"));
+        assert!(!can_show("สมชาย"));
+    }
+}
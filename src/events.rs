@@ -0,0 +1,204 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use medbook_events::{DeliveryOrderRequestEvent, DeliverySuccessEvent};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Payload of `delivery.order_request`, extended with fields this service understands
+/// on top of the shared `DeliveryOrderRequestEvent`. Extra fields are optional so that
+/// producers sending only the shared event keep working.
+#[derive(Deserialize)]
+pub struct DeliveryOrderRequest {
+    #[serde(flatten)]
+    pub event: DeliveryOrderRequestEvent,
+    #[serde(default)]
+    pub min_temperature_c: Option<f64>,
+    #[serde(default)]
+    pub max_temperature_c: Option<f64>,
+    /// Whether the order contains controlled substances requiring a custody chain.
+    #[serde(default)]
+    pub is_controlled: bool,
+    /// Whether the order contains flagged items the recipient must show ID for.
+    #[serde(default)]
+    pub requires_id_verification: bool,
+    /// Geocoded delivery address, used for route planning.
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    /// Time window the recipient booked for the delivery.
+    #[serde(default)]
+    pub slot_start: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub slot_end: Option<DateTime<Utc>>,
+    /// Dispatch zone the delivery address falls in.
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// `STANDARD`, `EXPRESS` or `URGENT`. Defaults to `STANDARD`.
+    #[serde(default)]
+    pub priority: Option<String>,
+    /// Patient the order belongs to. Falls back to `patient_id` in the address snapshot.
+    #[serde(default)]
+    pub patient_id: Option<i32>,
+    /// Delivery schedule the order was requested for, if any.
+    #[serde(default)]
+    pub schedule_id: Option<Uuid>,
+    /// `HOME`, `PHARMACY_PICKUP` or `LOCKER`. Defaults to `HOME`.
+    #[serde(default)]
+    pub fulfilment_mode: Option<String>,
+    /// Pharmacy or locker to collect the parcel from. Required for `LOCKER`.
+    #[serde(default)]
+    pub pickup_point: Option<String>,
+    /// Pharmacy, hub or lab the order is dispatched from.
+    #[serde(default)]
+    pub origin_location_id: Option<i32>,
+    #[serde(default)]
+    pub packages: Vec<PackageManifest>,
+}
+
+/// A parcel of an order as packed by the pharmacy.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PackageManifest {
+    pub weight_grams: i32,
+    pub length_mm: i32,
+    pub width_mm: i32,
+    pub height_mm: i32,
+    #[serde(default)]
+    pub is_fragile: bool,
+    #[serde(default)]
+    pub is_refrigerated: bool,
+    #[serde(default)]
+    pub items: Vec<PackageItemManifest>,
+}
+
+/// An order line item packed into a parcel.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PackageItemManifest {
+    pub order_item_id: i32,
+    pub quantity: i32,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Published as `delivery.temperature_excursion` when a reading falls outside the
+/// delivery's required temperature range.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryTemperatureExcursionEvent {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub excursion_id: Uuid,
+    pub temperature_c: f64,
+    pub min_temperature_c: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Published as `delivery.sla_breached` the first time a delivery misses one of its
+/// SLA deadlines.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliverySlaBreachedEvent {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub incident_id: Uuid,
+    /// `PICKUP_OVERDUE`, `DELIVERY_OVERDUE` or `ETA_PASSED`.
+    pub breach: String,
+    pub priority: String,
+    pub deadline: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Published as `delivery.cancelled` when a delivery is cancelled, once for every order it
+/// carries.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryCancelledEvent {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub reason: String,
+}
+
+/// Published as `delivery.stuck` when a delivery without recent activity is escalated.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryStuckEvent {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub incident_id: Uuid,
+    pub status: String,
+    pub last_activity_at: DateTime<Utc>,
+}
+
+/// Published as `orders.delivery_incident_resolved` when an incident is resolved, once for
+/// every order the delivery carries, so the orders service can reship or refund.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryIncidentResolvedEvent {
+    pub incident_id: Uuid,
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub category: String,
+    /// `NONE`, `RESHIP` or `REFUND`.
+    pub resolution: String,
+    pub resolution_note: Option<String>,
+    pub resolved_at: DateTime<Utc>,
+}
+
+/// Published as `orders.recurring_order_requested` ahead of a delivery schedule's run, so
+/// the orders service can repeat `source_order_id`. The resulting `delivery.order_request`
+/// should carry `schedule_id`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecurringOrderRequestedEvent {
+    pub schedule_id: Uuid,
+    pub patient_id: i32,
+    pub source_order_id: i32,
+    pub run_on: NaiveDate,
+    pub delivery_address: Value,
+}
+
+/// Published as `delivery.schedule_reminder` a few days before a delivery schedule's run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryScheduleReminderEvent {
+    pub schedule_id: Uuid,
+    pub patient_id: i32,
+    pub source_order_id: i32,
+    pub run_on: NaiveDate,
+}
+
+/// Published as `delivery.ready_for_pickup` when a parcel can be collected. Carries the
+/// pickup code, which is not stored in readable form.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryReadyForPickupEvent {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub patient_id: Option<i32>,
+    pub fulfilment_mode: String,
+    pub pickup_point: Option<String>,
+    pub locker_compartment: Option<String>,
+    pub pickup_code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Published as `delivery.pickup_expired` when an uncollected parcel is sent back.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryPickupExpiredEvent {
+    pub delivery_id: Uuid,
+    pub order_ids: Vec<i32>,
+    pub pickup_point: Option<String>,
+    pub expired_at: DateTime<Utc>,
+}
+
+/// Payload of `orders.delivery_success`, carrying the recipient ID check for deliveries
+/// that required one.
+#[derive(Serialize)]
+pub struct DeliverySuccess {
+    #[serde(flatten)]
+    pub event: DeliverySuccessEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_verification: Option<IdVerificationOutcome>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdVerificationOutcome {
+    pub id_type: String,
+    pub masked_id_number: String,
+    pub name_matched: bool,
+    pub verified_by: String,
+    pub verified_at: DateTime<Utc>,
+}
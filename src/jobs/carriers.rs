@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use diesel_async::{AsyncPgConnection, pooled_connection::bb8::Pool};
+use tracing::{error, info, warn};
+
+use crate::services::carriers;
+
+/// Retries carrier bookings and cancellations left pending every `interval`. Requests are
+/// claimed one batch at a time, so replicas running this at the same time do not conflict.
+pub async fn run(pool: Pool<AsyncPgConnection>, interval: Duration) {
+    info!("Carrier requests retried every {:?}", interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tick(&pool).await {
+            error!("Carrier request retry failed: {:?}", err);
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>) -> Result<()> {
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let processed = carriers::process_due(conn)
+        .await
+        .context("Failed to process carrier requests")?;
+    for request in processed
+        .iter()
+        .filter(|request| request.status != "SUCCEEDED")
+    {
+        warn!(
+            carrier_request_id = %request.id,
+            delivery_id = %request.delivery_id,
+            "Carrier {} {} attempt {} failed, now {}: {}",
+            request.carrier,
+            request.action,
+            request.attempts,
+            request.status,
+            request.last_error.as_deref().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use diesel_async::{AsyncConnection, AsyncPgConnection, pooled_connection::bb8::Pool};
+use medbook_core::app_error::AppError;
+use tracing::{error, info};
+
+use crate::services::{
+    carriers,
+    dispatch::{self, ScoringStrategy},
+};
+
+/// Assigns waiting deliveries to couriers every `interval`.
+pub async fn run(
+    pool: Pool<AsyncPgConnection>,
+    strategy: Box<dyn ScoringStrategy>,
+    interval: Duration,
+) {
+    info!(
+        "Auto-dispatch running every {:?} with the {} strategy",
+        interval,
+        strategy.name()
+    );
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tick(&pool, strategy.as_ref()).await {
+            error!("Auto-dispatch failed: {:?}", err);
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>, strategy: &dyn ScoringStrategy) -> Result<()> {
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let decisions = conn
+        .transaction::<_, AppError, _>(|conn| Box::pin(dispatch::run(conn, strategy, false)))
+        .await
+        .context("Auto-dispatch transaction failed")?;
+
+    for decision in decisions
+        .iter()
+        .filter(|decision| decision.carrier.is_some())
+    {
+        if let Err(err) = carriers::process(conn, decision.delivery_id).await {
+            error!(delivery_id = %decision.delivery_id, "Failed to book carrier: {:?}", err);
+        }
+    }
+
+    let assigned = decisions
+        .iter()
+        .filter(|decision| decision.courier_id.is_some() || decision.carrier.is_some())
+        .count();
+    if !decisions.is_empty() {
+        info!(
+            "Auto-dispatch assigned {} of {} waiting deliveries",
+            assigned,
+            decisions.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use diesel_async::{AsyncPgConnection, pooled_connection::bb8::Pool};
+use tracing::{error, info};
+
+use crate::services::{lockers::LockerProvider, pickups};
+
+/// Allocates and releases locker compartments every `interval`. Allocations are kept per
+/// delivery, so replicas running this at the same time do not conflict.
+pub async fn run(
+    pool: Pool<AsyncPgConnection>,
+    provider: Box<dyn LockerProvider>,
+    interval: Duration,
+) {
+    info!(
+        "Locker sync running every {:?} with the {} provider",
+        interval,
+        provider.name()
+    );
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tick(&pool, provider.as_ref()).await {
+            error!("Locker sync failed: {:?}", err);
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>, provider: &dyn LockerProvider) -> Result<()> {
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let sync = pickups::sync_lockers(conn, provider)
+        .await
+        .context("Locker sync failed")?;
+    if sync.allocated + sync.waiting + sync.released > 0 {
+        info!(
+            "Allocated {} locker compartments, {} parcels waiting, released {}",
+            sync.allocated, sync.waiting, sync.released
+        );
+    }
+    Ok(())
+}
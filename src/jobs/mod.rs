@@ -0,0 +1,89 @@
+//! Background workers running alongside the HTTP server and consumers.
+
+use anyhow::{Context, Result};
+use diesel_async::{
+    AsyncPgConnection, RunQueryDsl,
+    pooled_connection::{AsyncDieselConnectionManager, bb8::Pool},
+};
+
+use crate::{config, services::sweeper::SweepPolicy};
+
+pub mod carriers;
+pub mod dispatch;
+pub mod lockers;
+pub mod pickups;
+pub mod schedules;
+pub mod sla;
+pub mod sweeper;
+pub mod webhooks;
+
+diesel::define_sql_function! {
+    fn pg_try_advisory_xact_lock(key: diesel::sql_types::BigInt) -> diesel::sql_types::Bool;
+}
+
+/// Takes the advisory lock `key` until the end of the current transaction, so that only
+/// one replica runs a job at a time. Returns `false` when another replica holds it.
+pub(crate) async fn try_lock(conn: &mut AsyncPgConnection, key: i64) -> Result<bool> {
+    diesel::select(pg_try_advisory_xact_lock(key))
+        .get_result(conn)
+        .await
+        .context("Failed to take advisory lock")
+}
+
+/// Starts the configured workers on a connection pool of their own.
+pub async fn spawn(database_url: &str) -> Result<()> {
+    let pool = Pool::builder()
+        .build(AsyncDieselConnectionManager::<AsyncPgConnection>::new(
+            database_url,
+        ))
+        .await
+        .context("Failed to build the jobs DB connection pool")?;
+
+    if let Some(interval) = config::auto_dispatch_interval()? {
+        let strategy = config::dispatch_strategy();
+        let strategy = crate::services::dispatch::strategy_by_name(&strategy)
+            .with_context(|| format!("Unknown dispatch strategy {strategy}"))?;
+        tokio::spawn(dispatch::run(pool.clone(), strategy, interval));
+    }
+
+    if let Some(interval) = config::carrier_request_interval()? {
+        tokio::spawn(carriers::run(pool.clone(), interval));
+    }
+
+    if let Some(interval) = config::sla_check_interval()? {
+        tokio::spawn(sla::run(pool.clone(), interval));
+    }
+
+    if let Some(interval) = config::pickup_expiry_interval()? {
+        tokio::spawn(pickups::run(pool.clone(), interval));
+    }
+
+    if let Some(interval) = config::locker_sync_interval()? {
+        let name = config::locker_provider();
+        let provider = crate::services::lockers::provider_by_name(
+            &name,
+            pool.clone(),
+            &config::locker_sites(),
+        )
+        .await?
+        .with_context(|| format!("Unknown locker provider {name}"))?;
+        tokio::spawn(lockers::run(pool.clone(), provider, interval));
+    }
+
+    if let Some(interval) = config::schedule_check_interval()? {
+        tokio::spawn(schedules::run(pool.clone(), interval));
+    }
+
+    if let Some(interval) = config::stuck_sweep_interval()? {
+        let policy = config::stuck_delivery_policy();
+        let policy = SweepPolicy::from_name(&policy)
+            .with_context(|| format!("Unknown stuck delivery policy {policy}"))?;
+        tokio::spawn(sweeper::run(pool.clone(), policy, interval));
+    }
+
+    if let Some(interval) = config::webhook_dispatch_interval()? {
+        tokio::spawn(webhooks::run(pool.clone(), interval));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use diesel_async::{AsyncConnection, AsyncPgConnection, pooled_connection::bb8::Pool};
+use medbook_core::app_error::AppError;
+use tracing::{error, info};
+
+use crate::services::pickups;
+
+const LOCK_KEY: i64 = 0x6d62_0004;
+
+/// Returns parcels that were not collected in time every `interval`.
+pub async fn run(pool: Pool<AsyncPgConnection>, interval: Duration) {
+    info!("Pickup expiry running every {:?}", interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tick(&pool).await {
+            error!("Pickup expiry failed: {:?}", err);
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>) -> Result<()> {
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let expired = conn
+        .transaction::<_, AppError, _>(|conn| {
+            Box::pin(async move {
+                if !super::try_lock(conn, LOCK_KEY).await? {
+                    return Ok(Vec::new());
+                }
+                pickups::expire(conn, Utc::now()).await
+            })
+        })
+        .await
+        .context("Pickup expiry transaction failed")?;
+
+    for delivery in expired {
+        info!(
+            delivery_id = %delivery.id,
+            "Pickup at {} expired, returning delivery",
+            delivery.pickup_point.as_deref().unwrap_or("pharmacy")
+        );
+    }
+    Ok(())
+}
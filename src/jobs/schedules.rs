@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use diesel_async::{AsyncConnection, AsyncPgConnection, pooled_connection::bb8::Pool};
+use medbook_core::app_error::AppError;
+use tracing::{error, info};
+
+use crate::{config, services::schedules};
+
+const LOCK_KEY: i64 = 0x6d62_0003;
+
+/// Sends reminders and requests orders for delivery schedules every `interval`.
+pub async fn run(pool: Pool<AsyncPgConnection>, interval: Duration) {
+    info!("Delivery scheduler running every {:?}", interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tick(&pool).await {
+            error!("Delivery schedule run failed: {:?}", err);
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>) -> Result<()> {
+    let lead_time = config::schedule_lead_time()?;
+    let reminder_time = config::schedule_reminder_time()?;
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let actions = conn
+        .transaction::<_, AppError, _>(|conn| {
+            Box::pin(async move {
+                if !super::try_lock(conn, LOCK_KEY).await? {
+                    return Ok(Vec::new());
+                }
+                schedules::run_due(conn, Utc::now().date_naive(), lead_time, reminder_time).await
+            })
+        })
+        .await
+        .context("Delivery schedule transaction failed")?;
+
+    for action in actions {
+        info!(
+            schedule_id = %action.schedule_id,
+            "Delivery schedule {} for run on {}",
+            action.action,
+            action.run_on
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, pooled_connection::bb8::Pool};
+use medbook_core::{app_error::AppError, outbox};
+use tracing::{error, info};
+
+use crate::{
+    config,
+    events::DeliverySlaBreachedEvent,
+    models::{CreateDeliveryIncidentEntity, DeliveryEntity},
+    schema::deliveries,
+    services::{
+        calendar::Calendar,
+        deliveries::CLOSED_STATUSES,
+        incidents,
+        sla::{self, SlaDeadlines},
+    },
+};
+
+const LOCK_KEY: i64 = 0x6d62_0001;
+
+/// Raises an incident for every SLA breach, checking every `interval`.
+pub async fn run(pool: Pool<AsyncPgConnection>, interval: Duration) {
+    info!("SLA breach detection running every {:?}", interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        match tick(&pool).await {
+            Ok(raised) if raised > 0 => info!("Raised {} SLA breaches", raised),
+            Ok(_) => {}
+            Err(err) => error!("SLA breach detection failed: {:?}", err),
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>) -> Result<usize> {
+    let targets = config::sla_targets()?;
+    let eta_grace = config::sla_eta_grace()?;
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    conn.transaction::<_, AppError, _>(|conn| {
+        Box::pin(async move {
+            if !super::try_lock(conn, LOCK_KEY).await? {
+                return Ok(0);
+            }
+
+            let now = Utc::now();
+            let shortest_target = [targets.standard, targets.express, targets.urgent]
+                .into_iter()
+                .flat_map(|target| [target.pickup, target.delivery])
+                .min()
+                .unwrap_or_default();
+            let candidates: Vec<DeliveryEntity> = deliveries::table
+                .filter(deliveries::status.ne_all(CLOSED_STATUSES))
+                .filter(
+                    deliveries::created_at
+                        .lt(now - shortest_target)
+                        .or(deliveries::estimated_arrival_at.lt(now - eta_grace)),
+                )
+                .order_by(deliveries::created_at.asc())
+                .get_results(conn)
+                .await
+                .context("Failed to get deliveries")?;
+
+            let calendar = Calendar::load(conn).await?;
+            let mut raised = 0;
+            for delivery in candidates {
+                let deadlines = SlaDeadlines::of(&delivery, &targets, &calendar);
+                for breach in sla::breaches(&delivery, &deadlines, now, eta_grace) {
+                    let Some(incident) = incidents::raise(
+                        conn,
+                        CreateDeliveryIncidentEntity {
+                            delivery_id: delivery.id,
+                            category: "SLA_BREACH".into(),
+                            severity: breach.severity.into(),
+                            description: format!(
+                                "{} delivery breached {} while {}, deadline was {}",
+                                delivery.priority,
+                                breach.kind,
+                                delivery.status,
+                                breach.deadline.to_rfc3339()
+                            ),
+                            dedup_key: Some(format!("sla:{}:{}", delivery.id, breach.kind)),
+                            reporter_type: "SYSTEM".into(),
+                            reported_by: None,
+                        },
+                    )
+                    .await?
+                    else {
+                        continue;
+                    };
+
+                    outbox::publish(
+                        conn,
+                        "delivery.sla_breached".into(),
+                        DeliverySlaBreachedEvent {
+                            delivery_id: delivery.id,
+                            order_id: delivery.order_id,
+                            incident_id: incident.id,
+                            breach: breach.kind.into(),
+                            priority: delivery.priority.clone(),
+                            deadline: breach.deadline,
+                            detected_at: now,
+                        },
+                    )
+                    .await
+                    .context("Failed to create outbox")?;
+                    raised += 1;
+                }
+            }
+            Ok(raised)
+        })
+    })
+    .await
+    .context("SLA breach transaction failed")
+}
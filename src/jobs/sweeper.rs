@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use diesel_async::{AsyncConnection, AsyncPgConnection, pooled_connection::bb8::Pool};
+use medbook_core::app_error::AppError;
+use tracing::{error, info};
+
+use crate::{
+    config,
+    services::{
+        carriers,
+        sweeper::{self, SweepPolicy},
+    },
+};
+
+const LOCK_KEY: i64 = 0x6d62_0002;
+
+/// Acts on stuck deliveries according to `policy`, sweeping every `interval`.
+pub async fn run(pool: Pool<AsyncPgConnection>, policy: SweepPolicy, interval: Duration) {
+    info!(
+        "Stuck delivery sweeper running every {:?} with the {:?} policy",
+        interval, policy
+    );
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tick(&pool, policy).await {
+            error!("Stuck delivery sweep failed: {:?}", err);
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>, policy: SweepPolicy) -> Result<()> {
+    let stuck_after = config::stuck_delivery_after()?;
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let actions = conn
+        .transaction::<_, AppError, _>(|conn| {
+            Box::pin(async move {
+                if !super::try_lock(conn, LOCK_KEY).await? {
+                    return Ok(Vec::new());
+                }
+                sweeper::sweep(conn, policy, stuck_after, false).await
+            })
+        })
+        .await
+        .context("Stuck delivery sweep transaction failed")?;
+
+    for action in actions.iter().filter(|action| action.action != "NONE") {
+        info!(
+            delivery_id = %action.delivery_id,
+            "Sweeper action {}: {}",
+            action.action,
+            action.detail
+        );
+        if action.action == "CANCEL"
+            && let Err(err) = carriers::process(conn, action.delivery_id).await
+        {
+            error!(delivery_id = %action.delivery_id, "Failed to cancel carrier shipment: {:?}", err);
+        }
+    }
+    Ok(())
+}
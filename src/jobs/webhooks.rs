@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use diesel_async::{AsyncConnection, AsyncPgConnection, pooled_connection::bb8::Pool};
+use medbook_core::app_error::AppError;
+use tracing::{error, info, warn};
+
+use crate::services::webhooks;
+
+const LOCK_KEY: i64 = 0x6d62_0005;
+
+/// Queues new outbox events for their subscriptions and sends due webhooks every
+/// `interval`.
+pub async fn run(pool: Pool<AsyncPgConnection>, interval: Duration) {
+    info!("Webhook dispatch running every {:?}", interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = tick(&pool).await {
+            error!("Webhook dispatch failed: {:?}", err);
+        }
+    }
+}
+
+async fn tick(pool: &Pool<AsyncPgConnection>) -> Result<()> {
+    let conn = &mut pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let (queued, due) = conn
+        .transaction::<_, AppError, _>(|conn| {
+            Box::pin(async move {
+                if !super::try_lock(conn, LOCK_KEY).await? {
+                    return Ok((0, Vec::new()));
+                }
+                let now = Utc::now();
+                let queued = webhooks::fan_out(conn, now).await?;
+                let due = webhooks::claim_due(conn, now).await?;
+                Ok((queued, due))
+            })
+        })
+        .await
+        .context("Webhook dispatch transaction failed")?;
+
+    if queued > 0 {
+        info!("Queued {} webhooks", queued);
+    }
+    // Sent outside the transaction, the claim keeps other replicas off them meanwhile.
+    for (delivery, subscription) in due {
+        match webhooks::send(conn, &subscription, &delivery).await {
+            Ok(sent) if sent.status != "SUCCEEDED" => warn!(
+                webhook_id = %sent.id,
+                subscription_id = sent.subscription_id,
+                "Webhook attempt {} failed, now {}: {}",
+                sent.attempts,
+                sent.status,
+                sent.last_error.as_deref().unwrap_or_default()
+            ),
+            Ok(_) => {}
+            Err(err) => error!(webhook_id = %delivery.id, "Failed to send webhook: {:?}", err),
+        }
+    }
+    Ok(())
+}
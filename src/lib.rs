@@ -1,4 +1,10 @@
+pub mod config;
 pub mod consumers;
+pub mod csv;
+pub mod documents;
+pub mod events;
+pub mod jobs;
 pub mod models;
 pub mod routes;
 pub mod schema;
+pub mod services;
@@ -5,7 +5,7 @@ use medbook_core::{
     bootstrap::{self, bootstrap},
     config, db, swagger,
 };
-use medbook_deliveryservice::{consumers, routes};
+use medbook_deliveryservice::{consumers, jobs, routes, services};
 use utoipa::openapi::InfoBuilder;
 
 /// Migrations embedded into the binary which helps with streamlining image building process
@@ -17,8 +17,32 @@ async fn main() -> Result<()> {
     bootstrap::init_env();
 
     let routes = routes::deliveries::routes_with_openapi()
+        .merge(routes::calendar::routes_with_openapi())
+        .merge(routes::carrier_imports::routes_with_openapi())
+        .merge(routes::carrier_webhooks::routes_with_openapi())
+        .merge(routes::carriers::routes_with_openapi())
+        .merge(routes::cold_chain::routes_with_openapi())
+        .merge(routes::couriers::routes_with_openapi())
+        .merge(routes::custody::routes_with_openapi())
+        .merge(routes::dispatch::routes_with_openapi())
         .merge(routes::delivery_addresses::routes_with_openapi())
-        .merge(routes::patients::delivery_addresses::routes_with_openapi());
+        .merge(routes::id_verifications::routes_with_openapi())
+        .merge(routes::incidents::routes_with_openapi())
+        .merge(routes::labels::routes_with_openapi())
+        .merge(routes::legs::routes_with_openapi())
+        .merge(routes::locations::routes_with_openapi())
+        .merge(routes::manifests::routes_with_openapi())
+        .merge(routes::pickups::routes_with_openapi())
+        .merge(routes::ratings::routes_with_openapi())
+        .merge(routes::scans::routes_with_openapi())
+        .merge(routes::sweeper::routes_with_openapi())
+        .merge(routes::webhooks::routes_with_openapi())
+        .merge(routes::patients::delegations::routes_with_openapi())
+        .merge(routes::patients::deliveries::routes_with_openapi())
+        .merge(routes::patients::delivery_addresses::routes_with_openapi())
+        .merge(routes::patients::incidents::routes_with_openapi())
+        .merge(routes::patients::ratings::routes_with_openapi())
+        .merge(routes::patients::schedules::routes_with_openapi());
 
     let mut openapi = routes.get_openapi().clone();
     openapi.info = InfoBuilder::new()
@@ -35,6 +59,11 @@ async fn main() -> Result<()> {
     let migrations_count = db::run_migrations_blocking(MIGRATIONS, &config.database.url).await?;
     tracing::info!("Run {} new migrations successfully", migrations_count);
 
+    services::calendar::check_bundled_holidays()?;
+
+    tracing::info!("Starting background jobs...");
+    jobs::spawn(&config.database.url).await?;
+
     tracing::info!("Bootstrapping...");
     bootstrap(
         "DeliveryService",
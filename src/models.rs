@@ -1,7 +1,7 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use diesel::{
     Selectable,
-    prelude::{AsChangeset, Identifiable, Insertable, Queryable},
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -24,6 +24,16 @@ pub struct DeliveryAddressEntity {
     pub is_default: Option<bool>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub delivery_instructions: Option<String>,
+    #[serde(skip)]
+    pub access_code_encrypted: Option<Vec<u8>>,
+    pub preferred_contact_method: Option<String>,
+    pub contactless_consent: bool,
+    /// Latin-script spellings printed on labels and manifests in place of Thai script.
+    pub recipient_name_latin: Option<String>,
+    pub street_address_latin: Option<String>,
+    pub city_latin: Option<String>,
+    pub state_latin: Option<String>,
 }
 
 #[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -36,6 +46,68 @@ pub struct DeliveryEntity {
     pub status: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub min_temperature_c: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+    pub requires_review: bool,
+    pub is_controlled: bool,
+    pub requires_id_verification: bool,
+    pub courier_id: Option<i32>,
+    pub handed_over_at: Option<DateTime<Utc>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub slot_start: Option<DateTime<Utc>>,
+    pub slot_end: Option<DateTime<Utc>>,
+    /// Position in the courier's optimized route, starting at 1.
+    pub route_sequence: Option<i32>,
+    pub estimated_arrival_at: Option<DateTime<Utc>>,
+    pub zone: Option<String>,
+    pub priority: String,
+    pub patient_id: Option<i32>,
+    /// Preferences below are only shown with the assigned courier's delivery instructions,
+    /// which tell whether there is an access code but never the code itself.
+    #[serde(skip)]
+    pub delivery_instructions: Option<String>,
+    #[serde(skip)]
+    pub access_code_encrypted: Option<Vec<u8>>,
+    #[serde(skip)]
+    pub preferred_contact_method: Option<String>,
+    #[serde(skip)]
+    pub contactless_consent: bool,
+    pub schedule_id: Option<Uuid>,
+    pub fulfilment_mode: String,
+    pub pickup_point: Option<String>,
+    pub locker_compartment: Option<String>,
+    #[serde(skip)]
+    pub pickup_code_hash: Option<String>,
+    pub pickup_expires_at: Option<DateTime<Utc>>,
+    pub collected_at: Option<DateTime<Utc>>,
+    /// Pharmacy, hub or lab the delivery leaves from.
+    pub origin_location_id: Option<i32>,
+    /// External carrier the delivery is outsourced to.
+    pub carrier: Option<String>,
+    /// The carrier's tracking number.
+    pub tracking_number: Option<String>,
+    /// When the current courier was assigned.
+    pub assigned_at: Option<DateTime<Utc>>,
+    /// Wrong pickup codes entered since the current code was issued.
+    pub pickup_code_attempts: i32,
+}
+
+impl DeliveryEntity {
+    /// A string field of the delivery address snapshot, such as `recipient_name`.
+    pub fn address_field(&self, name: &str) -> Option<&str> {
+        self.delivery_address.as_ref()?.get(name)?.as_str()
+    }
+
+    /// Whether `temperature_c` lies within the delivery's required range.
+    /// Deliveries without a range accept any temperature.
+    pub fn is_temperature_in_range(&self, temperature_c: f64) -> bool {
+        self.min_temperature_c
+            .is_none_or(|min| temperature_c >= min)
+            && self
+                .max_temperature_c
+                .is_none_or(|max| temperature_c <= max)
+    }
 }
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
@@ -45,11 +117,31 @@ pub struct CreateDeliveryEntity {
     pub delivery_address: Option<Value>,
     pub order_id: i32,
     pub status: String,
+    pub min_temperature_c: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+    pub is_controlled: bool,
+    pub requires_id_verification: bool,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub slot_start: Option<DateTime<Utc>>,
+    pub slot_end: Option<DateTime<Utc>>,
+    pub zone: Option<String>,
+    pub priority: String,
+    pub patient_id: Option<i32>,
+    pub delivery_instructions: Option<String>,
+    pub access_code_encrypted: Option<Vec<u8>>,
+    pub preferred_contact_method: Option<String>,
+    pub contactless_consent: bool,
+    pub schedule_id: Option<Uuid>,
+    pub fulfilment_mode: String,
+    pub pickup_point: Option<String>,
+    pub origin_location_id: Option<i32>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug, Clone, AsChangeset)]
 #[diesel(table_name = crate::schema::delivery_addresses)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(treat_none_as_null = true)]
 pub struct CreateDeliveryAddressEntity {
     pub patient_id: i32,
     pub recipient_name: String,
@@ -60,6 +152,43 @@ pub struct CreateDeliveryAddressEntity {
     pub postal_code: String,
     pub country: String,
     pub is_default: bool,
+    pub delivery_instructions: Option<String>,
+    pub access_code_encrypted: Option<Vec<u8>>,
+    pub preferred_contact_method: Option<String>,
+    pub contactless_consent: bool,
+    pub recipient_name_latin: Option<String>,
+    pub street_address_latin: Option<String>,
+    pub city_latin: Option<String>,
+    pub state_latin: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_legs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryLegEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub sequence: i32,
+    pub courier_id: Option<i32>,
+    pub origin_location_id: Option<i32>,
+    /// `None` for the last leg, which ends at the recipient.
+    pub destination_location_id: Option<i32>,
+    pub status: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::delivery_legs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryLegEntity {
+    pub delivery_id: Uuid,
+    pub sequence: i32,
+    pub courier_id: Option<i32>,
+    pub origin_location_id: Option<i32>,
+    pub destination_location_id: Option<i32>,
 }
 
 #[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, ToSchema)]
@@ -82,3 +211,807 @@ pub struct CreateDeliveryLogEntity {
     pub description: String,
     pub status: String,
 }
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_temperature_readings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryTemperatureReadingEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub temperature_c: f64,
+    pub source: String,
+    pub recorded_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_temperature_readings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryTemperatureReadingEntity {
+    pub delivery_id: Uuid,
+    pub temperature_c: f64,
+    pub source: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_temperature_excursions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryTemperatureExcursionEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub reading_id: Uuid,
+    pub temperature_c: f64,
+    pub min_temperature_c: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+    pub reviewed_by: Option<String>,
+    pub review_note: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_temperature_excursions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryTemperatureExcursionEntity {
+    pub delivery_id: Uuid,
+    pub reading_id: Uuid,
+    pub temperature_c: f64,
+    pub min_temperature_c: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_custody_entries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryCustodyEntryEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub sequence: i32,
+    pub handoff_type: String,
+    pub from_actor: String,
+    pub to_actor: String,
+    pub location: String,
+    pub seal_number: String,
+    pub handed_over_at: DateTime<Utc>,
+    pub previous_hash: Option<String>,
+    pub entry_hash: String,
+    pub signature: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_custody_entries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryCustodyEntryEntity {
+    pub delivery_id: Uuid,
+    pub sequence: i32,
+    pub handoff_type: String,
+    pub from_actor: String,
+    pub to_actor: String,
+    pub location: String,
+    pub seal_number: String,
+    pub handed_over_at: DateTime<Utc>,
+    pub previous_hash: Option<String>,
+    pub entry_hash: String,
+    pub signature: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_id_verifications)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryIdVerificationEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub id_type: String,
+    pub masked_id_number: String,
+    pub presented_name: String,
+    pub recipient_name: Option<String>,
+    pub name_matched: bool,
+    pub verified_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_id_verifications)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryIdVerificationEntity {
+    pub delivery_id: Uuid,
+    pub id_type: String,
+    pub masked_id_number: String,
+    pub presented_name: String,
+    pub recipient_name: Option<String>,
+    pub name_matched: bool,
+    pub verified_by: String,
+}
+
+#[derive(
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    ToSchema,
+)]
+#[diesel(table_name = crate::schema::delivery_packages)]
+#[diesel(belongs_to(DeliveryEntity, foreign_key = delivery_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryPackageEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub package_number: i32,
+    pub weight_grams: i32,
+    pub length_mm: i32,
+    pub width_mm: i32,
+    pub height_mm: i32,
+    pub is_fragile: bool,
+    pub is_refrigerated: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When a label was first rendered for the package.
+    pub labelled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_packages)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryPackageEntity {
+    pub delivery_id: Uuid,
+    pub package_number: i32,
+    pub weight_grams: i32,
+    pub length_mm: i32,
+    pub width_mm: i32,
+    pub height_mm: i32,
+    pub is_fragile: bool,
+    pub is_refrigerated: bool,
+}
+
+#[derive(
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    ToSchema,
+)]
+#[diesel(table_name = crate::schema::package_items)]
+#[diesel(belongs_to(DeliveryPackageEntity, foreign_key = package_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PackageItemEntity {
+    pub id: Uuid,
+    pub package_id: Uuid,
+    pub order_item_id: i32,
+    pub quantity: i32,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::package_items)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreatePackageItemEntity {
+    pub package_id: Uuid,
+    pub order_item_id: i32,
+    pub quantity: i32,
+    pub description: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_scans)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryScanEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub package_id: Option<Uuid>,
+    pub scan_type: String,
+    pub location: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub scanned_by: String,
+    pub scanned_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_scans)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryScanEntity {
+    pub delivery_id: Uuid,
+    pub package_id: Option<Uuid>,
+    pub scan_type: String,
+    pub location: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub scanned_by: String,
+    pub scanned_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_incidents)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryIncidentEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub category: String,
+    pub severity: String,
+    pub status: String,
+    pub description: String,
+    pub dedup_key: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub reporter_type: String,
+    pub reported_by: Option<String>,
+    pub resolution: Option<String>,
+    pub resolution_note: Option<String>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_incidents)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryIncidentEntity {
+    pub delivery_id: Uuid,
+    pub category: String,
+    pub severity: String,
+    pub description: String,
+    pub dedup_key: Option<String>,
+    pub reporter_type: String,
+    pub reported_by: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_incident_comments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryIncidentCommentEntity {
+    pub id: Uuid,
+    pub incident_id: Uuid,
+    pub author_type: String,
+    pub author: String,
+    pub body: String,
+    pub is_internal: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_incident_comments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryIncidentCommentEntity {
+    pub incident_id: Uuid,
+    pub author_type: String,
+    pub author: String,
+    pub body: String,
+    pub is_internal: bool,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_incident_attachments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryIncidentAttachmentEntity {
+    pub id: Uuid,
+    pub incident_id: Uuid,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i32,
+    #[serde(skip)]
+    pub content: Vec<u8>,
+    pub uploaded_by_type: String,
+    pub uploaded_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::delivery_incident_attachments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryIncidentAttachmentEntity {
+    pub incident_id: Uuid,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i32,
+    pub content: Vec<u8>,
+    pub uploaded_by_type: String,
+    pub uploaded_by: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_sweeper_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliverySweeperActionEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub action: String,
+    pub delivery_status: String,
+    pub last_activity_at: DateTime<Utc>,
+    pub detail: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_sweeper_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliverySweeperActionEntity {
+    pub delivery_id: Uuid,
+    pub action: String,
+    pub delivery_status: String,
+    pub last_activity_at: DateTime<Utc>,
+    pub detail: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_ratings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryRatingEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub patient_id: i32,
+    pub courier_id: Option<i32>,
+    pub zone: Option<String>,
+    pub rating: i32,
+    pub tags: Value,
+    pub feedback: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_ratings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryRatingEntity {
+    pub delivery_id: Uuid,
+    pub patient_id: i32,
+    pub courier_id: Option<i32>,
+    pub zone: Option<String>,
+    pub rating: i32,
+    pub tags: Value,
+    pub feedback: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::couriers)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CourierEntity {
+    pub id: i32,
+    pub name: String,
+    pub phone_number: Option<String>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub vehicle_type: String,
+    pub zone: Option<String>,
+    pub max_deliveries: Option<i32>,
+    pub capacity_grams: Option<i32>,
+    /// Last known position of the courier.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug, Clone, AsChangeset)]
+#[diesel(table_name = crate::schema::couriers)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateCourierEntity {
+    pub name: String,
+    pub phone_number: Option<String>,
+    pub is_active: bool,
+    pub vehicle_type: String,
+    pub zone: Option<String>,
+    pub max_deliveries: Option<i32>,
+    pub capacity_grams: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::dispatch_manifests)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DispatchManifestEntity {
+    pub id: Uuid,
+    /// `None` on manifests of every courier collecting from the origin.
+    pub courier_id: Option<i32>,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub status: String,
+    pub delivery_count: i32,
+    pub package_count: i32,
+    /// Served by the document endpoint rather than inline.
+    #[serde(skip)]
+    pub csv: String,
+    #[serde(skip)]
+    pub pdf: Vec<u8>,
+    pub generated_by: String,
+    pub handed_over_by: Option<String>,
+    pub received_by: Option<String>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub origin_location_id: Option<i32>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::dispatch_manifests)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDispatchManifestEntity {
+    pub courier_id: Option<i32>,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub status: String,
+    pub delivery_count: i32,
+    pub package_count: i32,
+    pub csv: String,
+    pub pdf: Vec<u8>,
+    pub generated_by: String,
+    pub origin_location_id: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::dispatch_manifest_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DispatchManifestDeliveryEntity {
+    pub manifest_id: Uuid,
+    pub delivery_id: Uuid,
+    pub package_count: i32,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::locations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LocationEntity {
+    pub id: i32,
+    pub name: String,
+    pub location_type: String,
+    pub address: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: String,
+    pub cutoff_time: Option<NaiveTime>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug, Clone, AsChangeset)]
+#[diesel(table_name = crate::schema::locations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(treat_none_as_null = true)]
+pub struct CreateLocationEntity {
+    pub name: String,
+    pub location_type: String,
+    pub address: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: String,
+    pub cutoff_time: Option<NaiveTime>,
+    pub is_active: bool,
+}
+
+#[derive(
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    ToSchema,
+)]
+#[diesel(table_name = crate::schema::location_opening_hours)]
+#[diesel(belongs_to(LocationEntity, foreign_key = location_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LocationOpeningHoursEntity {
+    pub id: i32,
+    pub location_id: i32,
+    /// ISO weekday, 1 being Monday.
+    pub weekday: i16,
+    pub opens_at: NaiveTime,
+    pub closes_at: NaiveTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::location_opening_hours)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateLocationOpeningHoursEntity {
+    pub location_id: i32,
+    pub weekday: i16,
+    pub opens_at: NaiveTime,
+    pub closes_at: NaiveTime,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::calendar_closures)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CalendarClosureEntity {
+    pub id: i32,
+    pub zone: Option<String>,
+    pub location_id: Option<i32>,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reason: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::calendar_closures)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateCalendarClosureEntity {
+    pub zone: Option<String>,
+    pub location_id: Option<i32>,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reason: String,
+    pub created_by: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::patient_delegations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PatientDelegationEntity {
+    pub id: Uuid,
+    pub patient_id: i32,
+    pub delegate_id: i32,
+    pub access_level: String,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::patient_delegations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreatePatientDelegationEntity {
+    pub patient_id: i32,
+    pub delegate_id: i32,
+    pub access_level: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::patient_proxy_audit_logs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PatientProxyAuditLogEntity {
+    pub id: Uuid,
+    pub delegation_id: Uuid,
+    pub patient_id: i32,
+    pub delegate_id: i32,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::patient_proxy_audit_logs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreatePatientProxyAuditLogEntity {
+    pub delegation_id: Uuid,
+    pub patient_id: i32,
+    pub delegate_id: i32,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_schedules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryScheduleEntity {
+    pub id: Uuid,
+    pub patient_id: i32,
+    pub delivery_address_id: Option<i32>,
+    pub source_order_id: i32,
+    pub recurrence: String,
+    pub status: String,
+    pub starts_on: NaiveDate,
+    pub ends_on: Option<NaiveDate>,
+    pub next_run_on: Option<NaiveDate>,
+    pub reminded_for: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_schedules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryScheduleEntity {
+    pub patient_id: i32,
+    pub delivery_address_id: Option<i32>,
+    pub source_order_id: i32,
+    pub recurrence: String,
+    pub starts_on: NaiveDate,
+    pub ends_on: Option<NaiveDate>,
+    pub next_run_on: Option<NaiveDate>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_schedule_runs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryScheduleRunEntity {
+    pub id: Uuid,
+    pub schedule_id: Uuid,
+    pub run_on: NaiveDate,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_schedule_runs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryScheduleRunEntity {
+    pub schedule_id: Uuid,
+    pub run_on: NaiveDate,
+    pub status: String,
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::delivery_orders)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeliveryOrderEntity {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::delivery_orders)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateDeliveryOrderEntity {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::carrier_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CarrierEventEntity {
+    pub id: Uuid,
+    pub carrier: String,
+    pub event_id: String,
+    pub tracking_number: String,
+    pub code: String,
+    pub description: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub delivery_id: Option<Uuid>,
+    pub outcome: String,
+    pub note: Option<String>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::carrier_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateCarrierEventEntity {
+    pub carrier: String,
+    pub event_id: String,
+    pub tracking_number: String,
+    pub code: String,
+    pub description: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub delivery_id: Option<Uuid>,
+    pub outcome: String,
+    pub note: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::carrier_requests)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CarrierRequestEntity {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub carrier: String,
+    pub action: String,
+    pub tracking_number: Option<String>,
+    pub reason: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::carrier_requests)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateCarrierRequestEntity {
+    pub delivery_id: Uuid,
+    pub carrier: String,
+    pub action: String,
+    pub tracking_number: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::webhook_subscriptions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookSubscriptionEntity {
+    pub id: i32,
+    pub name: String,
+    pub url: String,
+    /// Outbox event types sent to the partner.
+    pub event_types: Value,
+    #[serde(skip)]
+    pub secret: String,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug, AsChangeset)]
+#[diesel(table_name = crate::schema::webhook_subscriptions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateWebhookSubscriptionEntity {
+    pub name: String,
+    pub url: String,
+    pub event_types: Value,
+    pub secret: String,
+    pub is_active: bool,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::webhook_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDeliveryEntity {
+    pub id: Uuid,
+    pub subscription_id: i32,
+    pub outbox_id: Option<i32>,
+    pub event_type: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::webhook_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateWebhookDeliveryEntity {
+    pub subscription_id: i32,
+    pub outbox_id: Option<i32>,
+    pub event_type: String,
+    pub payload: String,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::webhook_delivery_attempts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDeliveryAttemptEntity {
+    pub id: Uuid,
+    pub webhook_delivery_id: Uuid,
+    pub attempt: i32,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub attempted_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::webhook_delivery_attempts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreateWebhookDeliveryAttemptEntity {
+    pub webhook_delivery_id: Uuid,
+    pub attempt: i32,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
@@ -0,0 +1,224 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::{
+    models::{CalendarClosureEntity, CreateCalendarClosureEntity},
+    schema::{calendar_closures, locations},
+    services::calendar::{self, Holiday},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/calendar",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_holidays))
+            .routes(utoipa_axum::routes!(get_closures))
+            .routes(utoipa_axum::routes!(create_closure))
+            .routes(utoipa_axum::routes!(delete_closure)),
+    )
+}
+
+#[derive(Deserialize, IntoParams)]
+struct CalendarQuery {
+    /// Only include days on or after this date.
+    from: Option<NaiveDate>,
+    /// Only include days on or before this date.
+    to: Option<NaiveDate>,
+}
+
+/// Fetch the bundled public holidays.
+#[utoipa::path(
+    get,
+    path = "/holidays",
+    tags = ["Calendar"],
+    params(CalendarQuery),
+    responses(
+        (status = 200, description = "List public holidays", body = StdResponse<Vec<Holiday>, String>)
+    )
+)]
+async fn get_holidays(Query(query): Query<CalendarQuery>) -> Result<impl IntoResponse, AppError> {
+    let holidays: Vec<Holiday> = calendar::holidays()
+        .iter()
+        .filter(|holiday| query.from.is_none_or(|from| holiday.date >= from))
+        .filter(|holiday| query.to.is_none_or(|to| holiday.date <= to))
+        .cloned()
+        .collect();
+
+    Ok(StdResponse {
+        data: Some(holidays),
+        message: Some("Get holidays successfully"),
+    })
+}
+
+/// Fetch the closures overlapping a date range.
+#[utoipa::path(
+    get,
+    path = "/closures",
+    tags = ["Calendar"],
+    params(CalendarQuery),
+    responses(
+        (status = 200, description = "List calendar closures", body = StdResponse<Vec<CalendarClosureEntity>, String>)
+    )
+)]
+async fn get_closures(
+    Query(query): Query<CalendarQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let mut closures = calendar_closures::table
+        .order_by(calendar_closures::starts_on.asc())
+        .into_boxed();
+    if let Some(from) = query.from {
+        closures = closures.filter(calendar_closures::ends_on.ge(from));
+    }
+    if let Some(to) = query.to {
+        closures = closures.filter(calendar_closures::starts_on.le(to));
+    }
+    let closures: Vec<CalendarClosureEntity> = closures
+        .get_results(conn)
+        .await
+        .context("Failed to get calendar closures")?;
+
+    Ok(StdResponse {
+        data: Some(closures),
+        message: Some("Get calendar closures successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateClosureReq {
+    /// Zone that is closed. Leave both `zone` and `location_id` empty to close everywhere.
+    zone: Option<String>,
+    /// Origin location that is closed.
+    location_id: Option<i32>,
+    starts_on: NaiveDate,
+    /// Last closed day, inclusive.
+    ends_on: NaiveDate,
+    reason: String,
+    created_by: String,
+}
+
+impl CreateClosureReq {
+    fn into_entity(self) -> Result<CreateCalendarClosureEntity, AppError> {
+        if self.zone.is_some() && self.location_id.is_some() {
+            return Err(AppError::BadRequest(
+                "A closure applies to either a zone or a location".into(),
+            ));
+        }
+        if self.starts_on > self.ends_on {
+            return Err(AppError::BadRequest(
+                "starts_on must not be after ends_on".into(),
+            ));
+        }
+        Ok(CreateCalendarClosureEntity {
+            zone: self.zone,
+            location_id: self.location_id,
+            starts_on: self.starts_on,
+            ends_on: self.ends_on,
+            reason: self.reason,
+            created_by: self.created_by,
+        })
+    }
+}
+
+/// Close a zone, a location or everywhere for a range of days. Deadlines, slots and
+/// arrival estimates skip closed days.
+#[utoipa::path(
+    post,
+    path = "/closures",
+    tags = ["Calendar"],
+    request_body = CreateClosureReq,
+    responses(
+        (status = 200, description = "Created calendar closure successfully", body = StdResponse<CalendarClosureEntity, String>)
+    )
+)]
+async fn create_closure(
+    State(state): State<AppState>,
+    Json(body): Json<CreateClosureReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let new_closure = body.into_entity()?;
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    if let Some(location_id) = new_closure.location_id {
+        locations::table
+            .find(location_id)
+            .select(locations::id)
+            .get_result::<i32>(conn)
+            .await
+            .optional()
+            .context("Failed to get location")?
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown location {location_id}")))?;
+    }
+
+    let closure: CalendarClosureEntity = diesel::insert_into(calendar_closures::table)
+        .values(new_closure)
+        .returning(CalendarClosureEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to create calendar closure")?;
+
+    Ok(StdResponse {
+        data: Some(closure),
+        message: Some("Created calendar closure successfully"),
+    })
+}
+
+/// Reopen by removing a closure.
+#[utoipa::path(
+    delete,
+    path = "/closures/{id}",
+    tags = ["Calendar"],
+    params(
+        ("id" = i32, Path, description = "Calendar closure ID to delete")
+    ),
+    responses(
+        (status = 200, description = "Deleted calendar closure successfully", body = StdResponse<CalendarClosureEntity, String>)
+    )
+)]
+async fn delete_closure(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let closure: CalendarClosureEntity = diesel::delete(calendar_closures::table.find(id))
+        .returning(CalendarClosureEntity::as_returning())
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to delete calendar closure")?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(closure),
+        message: Some("Deleted calendar closure successfully"),
+    })
+}
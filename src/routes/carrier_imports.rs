@@ -0,0 +1,74 @@
+use anyhow::Context;
+use axum::{
+    extract::{DefaultBodyLimit, Path, Query, State},
+    response::IntoResponse,
+};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::services::{
+    self,
+    carrier_imports::{self, ColumnMapping, ImportReport},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/carriers",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(import_carrier_tracking))
+            .layer(DefaultBodyLimit::max(carrier_imports::MAX_FILE_BYTES)),
+    )
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ImportTrackingQuery {
+    /// Column mapping as `field=Header` pairs separated by `;`. Defaults to the carrier's
+    /// configured mapping.
+    columns: Option<String>,
+}
+
+/// Import a tracking CSV file of up to 10 MB from a carrier, applying its status updates to
+/// the deliveries they match by tracking number.
+#[utoipa::path(
+    post,
+    path = "/{carrier}/tracking-imports",
+    tags = ["Carrier Imports"],
+    params(
+        ("carrier" = String, Path, description = "Carrier the file comes from"),
+        ImportTrackingQuery
+    ),
+    request_body(content = String, description = "CSV file with a header row", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Imported carrier tracking", body = StdResponse<ImportReport, String>)
+    )
+)]
+async fn import_carrier_tracking(
+    Path(carrier): Path<String>,
+    Query(query): Query<ImportTrackingQuery>,
+    State(state): State<AppState>,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let adapter = services::carriers::adapter(&carrier)?;
+    let mapping = match query.columns {
+        Some(columns) => ColumnMapping::parse(&columns)?,
+        None => ColumnMapping::of(adapter.name())?,
+    };
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let report = services::carrier_imports::import(conn, adapter, &mapping, &body).await?;
+
+    Ok(StdResponse {
+        data: Some(report),
+        message: Some("Imported carrier tracking"),
+    })
+}
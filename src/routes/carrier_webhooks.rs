@@ -0,0 +1,202 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    config,
+    models::CarrierEventEntity,
+    schema::carrier_events,
+    services::{self, carrier_webhooks::REVIEW_OUTCOMES},
+};
+
+/// Header carrying the hex encoded HMAC-SHA256 of the webhook body.
+const SIGNATURE_HEADER: &str = "x-carrier-signature";
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/webhooks/carriers",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(receive_carrier_webhook))
+            .routes(utoipa_axum::routes!(get_carrier_events))
+            .routes(utoipa_axum::routes!(review_carrier_event)),
+    )
+}
+
+#[derive(Serialize, ToSchema)]
+struct WebhookEventRes {
+    event_id: String,
+    /// `APPLIED`, `LOGGED`, `UNRECOGNIZED`, `UNMATCHED`, `REJECTED` or `DUPLICATE`.
+    outcome: &'static str,
+}
+
+/// Receive tracking updates pushed by a carrier. The body is signed with one of the
+/// carrier's secrets in the `X-Carrier-Signature` header.
+#[utoipa::path(
+    post,
+    path = "/{carrier}",
+    tags = ["Carrier Webhooks"],
+    params(
+        ("carrier" = String, Path, description = "Carrier pushing the updates"),
+        ("X-Carrier-Signature" = String, Header, description = "Hex encoded HMAC-SHA256 of the body")
+    ),
+    request_body(content = String, description = "Webhook in the carrier's own format", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Received carrier webhook successfully", body = StdResponse<Vec<WebhookEventRes>, String>)
+    )
+)]
+async fn receive_carrier_webhook(
+    Path(carrier): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let adapter = services::carriers::adapter(&carrier)?;
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|signature| signature.to_str().ok())
+        .unwrap_or_default();
+    if !services::carrier_webhooks::verify_signature(
+        &config::carrier_webhook_secrets(adapter.name()),
+        &body,
+        signature,
+    ) {
+        return Err(AppError::BadRequest("Invalid webhook signature".into()));
+    }
+    let events = adapter
+        .parse_webhook(&body)
+        .map_err(|err| AppError::BadRequest(format!("{err:#}")))?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let mut results = Vec::with_capacity(events.len());
+    for event in &events {
+        let outcome = services::carrier_webhooks::receive(conn, adapter, event).await?;
+        results.push(WebhookEventRes {
+            event_id: event.event_id.clone(),
+            outcome,
+        });
+    }
+
+    Ok(StdResponse {
+        data: Some(results),
+        message: Some("Received carrier webhook successfully"),
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+struct GetCarrierEventsQuery {
+    carrier: Option<String>,
+    /// Only events with this outcome. Defaults to the unreviewed events needing review.
+    outcome: Option<String>,
+}
+
+/// Fetch carrier events, by default those not applied and not reviewed yet, oldest first.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tags = ["Carrier Webhooks"],
+    params(GetCarrierEventsQuery),
+    responses(
+        (status = 200, description = "List carrier events", body = StdResponse<Vec<CarrierEventEntity>, String>)
+    )
+)]
+async fn get_carrier_events(
+    Query(query): Query<GetCarrierEventsQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let mut events = carrier_events::table
+        .order_by(carrier_events::created_at.asc())
+        .into_boxed();
+    if let Some(carrier) = query.carrier {
+        events = events.filter(carrier_events::carrier.eq(carrier));
+    }
+    events = match query.outcome {
+        Some(outcome) => events.filter(carrier_events::outcome.eq(outcome)),
+        None => events
+            .filter(carrier_events::outcome.eq_any(REVIEW_OUTCOMES))
+            .filter(carrier_events::reviewed_at.is_null()),
+    };
+    let events: Vec<CarrierEventEntity> = events
+        .get_results(conn)
+        .await
+        .context("Failed to get carrier events")?;
+
+    Ok(StdResponse {
+        data: Some(events),
+        message: Some("Get carrier events successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ReviewCarrierEventReq {
+    reviewed_by: String,
+}
+
+/// Mark a carrier event as reviewed once it has been dealt with.
+#[utoipa::path(
+    patch,
+    path = "/events/{id}/review",
+    tags = ["Carrier Webhooks"],
+    params(
+        ("id" = Uuid, Path, description = "Carrier event ID to mark as reviewed")
+    ),
+    request_body = ReviewCarrierEventReq,
+    responses(
+        (status = 200, description = "Reviewed carrier event successfully", body = StdResponse<CarrierEventEntity, String>)
+    )
+)]
+async fn review_carrier_event(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<ReviewCarrierEventReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let event: CarrierEventEntity = diesel::update(carrier_events::table.find(id))
+        .set((
+            carrier_events::reviewed_by.eq(body.reviewed_by),
+            carrier_events::reviewed_at.eq(Utc::now()),
+        ))
+        .returning(CarrierEventEntity::as_returning())
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to review carrier event")?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(event),
+        message: Some("Reviewed carrier event successfully"),
+    })
+}
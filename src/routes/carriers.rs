@@ -0,0 +1,306 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use diesel::{OptionalExtension, QueryDsl};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::DeliveryEntity,
+    schema::deliveries,
+    services::{self, carriers::CarrierEvent},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/deliveries",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(
+                hand_over_to_carrier,
+                take_back_from_carrier
+            ))
+            .routes(utoipa_axum::routes!(get_carrier_tracking)),
+    )
+}
+
+#[derive(Deserialize, ToSchema)]
+struct HandOverReq {
+    /// Carrier to book with. Defaults to the carrier the carrier rules pick.
+    carrier: Option<String>,
+}
+
+/// Outsource a PREPARING delivery to an external carrier. Controlled deliveries stay with
+/// our couriers.
+#[utoipa::path(
+    post,
+    path = "/{id}/carrier",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to outsource")
+    ),
+    request_body = HandOverReq,
+    responses(
+        (status = 200, description = "Handed delivery over to carrier successfully", body = StdResponse<DeliveryEntity, String>)
+    )
+)]
+async fn hand_over_to_carrier(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<HandOverReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let request = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                if delivery.status != "PREPARING" {
+                    return Err(AppError::BadRequest(format!(
+                        "Delivery is {}, only PREPARING deliveries can be outsourced",
+                        delivery.status
+                    )));
+                }
+                if delivery.fulfilment_mode == "PHARMACY_PICKUP" {
+                    return Err(AppError::BadRequest(
+                        "Pharmacy pickups are not delivered by carriers".into(),
+                    ));
+                }
+                if let Some(carrier) = &delivery.carrier {
+                    return Err(AppError::BadRequest(format!(
+                        "Delivery is already with carrier {carrier}"
+                    )));
+                }
+                if delivery.is_controlled {
+                    return Err(AppError::BadRequest(
+                        "Controlled deliveries must stay in the custody of our couriers".into(),
+                    ));
+                }
+                if !services::legs::get_legs(conn, id).await?.is_empty() {
+                    return Err(AppError::BadRequest(
+                        "Delivery is routed through hubs by our couriers".into(),
+                    ));
+                }
+
+                let (carrier, reason) = match &body.carrier {
+                    Some(name) => (
+                        services::carriers::adapter(name)?,
+                        "Outsourced manually".to_string(),
+                    ),
+                    None => {
+                        let (carrier, rule) = services::carriers::registry()?
+                            .carrier_for(&delivery)
+                            .ok_or_else(|| {
+                                AppError::BadRequest(
+                                    "No carrier rule matches the delivery, name a carrier".into(),
+                                )
+                            })?;
+                        (carrier, format!("Outsourced by the {rule} rule"))
+                    }
+                };
+
+                let (_, request) =
+                    services::carriers::request_shipment(conn, &delivery, carrier, &reason).await?;
+                Ok::<_, AppError>(request)
+            })
+        })
+        .await?;
+
+    // The carrier is booked once the delivery is reserved for it.
+    let requests = services::carriers::process(conn, id).await?;
+    if let Some(failed) = requests
+        .iter()
+        .find(|processed| processed.id == request.id && processed.status == "FAILED")
+    {
+        return Err(AppError::BadRequest(format!(
+            "Carrier {} could not take the delivery: {}",
+            failed.carrier,
+            failed.last_error.as_deref().unwrap_or_default()
+        )));
+    }
+    let delivery: DeliveryEntity = deliveries::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .context("Failed to get delivery")?;
+
+    Ok(StdResponse {
+        data: Some(delivery),
+        message: Some("Handed delivery over to carrier successfully"),
+    })
+}
+
+/// Cancel the carrier shipment of a PREPARING delivery so our couriers can take it again.
+#[utoipa::path(
+    delete,
+    path = "/{id}/carrier",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to take back")
+    ),
+    responses(
+        (status = 200, description = "Took delivery back from carrier successfully", body = StdResponse<DeliveryEntity, String>)
+    )
+)]
+async fn take_back_from_carrier(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let request = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let current: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                let Some(carrier) = &current.carrier else {
+                    return Err(AppError::BadRequest(
+                        "Delivery is not with a carrier".into(),
+                    ));
+                };
+                if current.status != "PREPARING" {
+                    return Err(AppError::BadRequest(format!(
+                        "Delivery is {}, the carrier already has it",
+                        current.status
+                    )));
+                }
+                services::carriers::request_cancel(conn, &current, "Taken back by hand")
+                    .await?
+                    .ok_or_else(|| {
+                        AppError::BadRequest(format!(
+                            "Shipment with carrier {carrier} is still being booked"
+                        ))
+                    })
+            })
+        })
+        .await?;
+
+    // The delivery is only taken back once the carrier has cancelled the shipment.
+    let requests = services::carriers::process(conn, id).await?;
+    if let Some(failed) = requests
+        .iter()
+        .find(|processed| processed.id == request.id && processed.status == "FAILED")
+    {
+        return Err(AppError::BadRequest(format!(
+            "Carrier {} could not cancel the shipment: {}",
+            failed.carrier,
+            failed.last_error.as_deref().unwrap_or_default()
+        )));
+    }
+    let delivery: DeliveryEntity = deliveries::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .context("Failed to get delivery")?;
+
+    Ok(StdResponse {
+        data: Some(delivery),
+        message: Some("Took delivery back from carrier successfully"),
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct TrackedEvent {
+    #[serde(flatten)]
+    event: CarrierEvent,
+    /// Our delivery status for the carrier's code, `null` when the code is not known.
+    status: Option<&'static str>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CarrierTrackingRes {
+    carrier: String,
+    tracking_number: String,
+    events: Vec<TrackedEvent>,
+}
+
+/// Fetch the tracking history of an outsourced delivery from its carrier.
+#[utoipa::path(
+    get,
+    path = "/{id}/carrier/tracking",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to track")
+    ),
+    responses(
+        (status = 200, description = "Fetched carrier tracking successfully", body = StdResponse<CarrierTrackingRes, String>)
+    )
+)]
+async fn get_carrier_tracking(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery: DeliveryEntity = deliveries::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery")?
+        .ok_or(AppError::NotFound)?;
+
+    let (Some(carrier), Some(tracking_number)) = (delivery.carrier, delivery.tracking_number)
+    else {
+        return Err(AppError::BadRequest(
+            "Delivery is not with a carrier".into(),
+        ));
+    };
+    let adapter = services::carriers::adapter(&carrier)?;
+    let events = adapter
+        .fetch_tracking(&tracking_number)
+        .await
+        .with_context(|| format!("Failed to fetch tracking from carrier {carrier}"))?
+        .into_iter()
+        .map(|event| TrackedEvent {
+            status: adapter.map_status(&event.code),
+            event,
+        })
+        .collect();
+
+    Ok(StdResponse {
+        data: Some(CarrierTrackingRes {
+            carrier,
+            tracking_number,
+            events,
+        }),
+        message: Some("Get carrier tracking successfully"),
+    })
+}
@@ -0,0 +1,374 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+    outbox,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    events::DeliveryTemperatureExcursionEvent,
+    models::{
+        CreateDeliveryLogEntity, CreateDeliveryTemperatureExcursionEntity,
+        CreateDeliveryTemperatureReadingEntity, DeliveryEntity, DeliveryLogEntity,
+        DeliveryTemperatureExcursionEntity, DeliveryTemperatureReadingEntity,
+    },
+    schema::{
+        deliveries, delivery_logs, delivery_temperature_excursions, delivery_temperature_readings,
+    },
+};
+
+/// Length limit of `reviewed_by`, the size of its column.
+const MAX_REVIEWER_CHARS: usize = 100;
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/deliveries",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_temperature_readings))
+            .routes(utoipa_axum::routes!(record_temperature_readings))
+            .routes(utoipa_axum::routes!(review_temperature_excursions)),
+    )
+}
+
+#[derive(Serialize, ToSchema)]
+struct GetTemperatureReadingsRes {
+    readings: Vec<DeliveryTemperatureReadingEntity>,
+    excursions: Vec<DeliveryTemperatureExcursionEntity>,
+}
+
+/// Fetch the temperature time series and excursions of a delivery.
+#[utoipa::path(
+    get,
+    path = "/{id}/temperature-readings",
+    tags = ["Cold Chain"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch readings for")
+    ),
+    responses(
+        (status = 200, description = "Fetched temperature readings successfully", body = StdResponse<GetTemperatureReadingsRes, String>)
+    )
+)]
+async fn get_temperature_readings(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let readings: Vec<DeliveryTemperatureReadingEntity> = delivery_temperature_readings::table
+        .filter(delivery_temperature_readings::delivery_id.eq(id))
+        .order_by(delivery_temperature_readings::recorded_at.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get temperature readings")?;
+
+    let excursions: Vec<DeliveryTemperatureExcursionEntity> =
+        delivery_temperature_excursions::table
+            .filter(delivery_temperature_excursions::delivery_id.eq(id))
+            .order_by(delivery_temperature_excursions::created_at.asc())
+            .get_results(conn)
+            .await
+            .context("Failed to get temperature excursions")?;
+
+    Ok(StdResponse {
+        data: Some(GetTemperatureReadingsRes {
+            readings,
+            excursions,
+        }),
+        message: Some("Get temperature readings successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct TemperatureReadingReq {
+    temperature_c: f64,
+    /// Defaults to the time the reading is received.
+    recorded_at: Option<DateTime<Utc>>,
+    /// COURIER or DATA_LOGGER.
+    source: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RecordTemperatureReadingsReq {
+    readings: Vec<TemperatureReadingReq>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RecordTemperatureReadingsRes {
+    readings: Vec<DeliveryTemperatureReadingEntity>,
+    excursions: Vec<DeliveryTemperatureExcursionEntity>,
+}
+
+/// Submit temperature readings for a delivery, as sent by couriers or data loggers.
+/// Out-of-range readings open an excursion and flag the delivery for pharmacist review.
+#[utoipa::path(
+    post,
+    path = "/{id}/temperature-readings",
+    tags = ["Cold Chain"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID the readings belong to")
+    ),
+    request_body = RecordTemperatureReadingsReq,
+    responses(
+        (status = 200, description = "Recorded temperature readings successfully", body = StdResponse<RecordTemperatureReadingsRes, String>)
+    )
+)]
+async fn record_temperature_readings(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<RecordTemperatureReadingsReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    if body.readings.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one reading is required".into(),
+        ));
+    }
+
+    let allowed_sources = ["COURIER", "DATA_LOGGER"];
+    if body
+        .readings
+        .iter()
+        .any(|reading| !allowed_sources.contains(&reading.source.as_str()))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Allowed sources are: {}",
+            allowed_sources.join(", ")
+        )));
+    }
+
+    let (readings, excursions) = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                let now = Utc::now();
+                let readings: Vec<DeliveryTemperatureReadingEntity> =
+                    diesel::insert_into(delivery_temperature_readings::table)
+                        .values(
+                            body.readings
+                                .into_iter()
+                                .map(|reading| CreateDeliveryTemperatureReadingEntity {
+                                    delivery_id: delivery.id,
+                                    temperature_c: reading.temperature_c,
+                                    source: reading.source,
+                                    recorded_at: reading.recorded_at.unwrap_or(now),
+                                })
+                                .collect::<Vec<_>>(),
+                        )
+                        .returning(DeliveryTemperatureReadingEntity::as_returning())
+                        .get_results(conn)
+                        .await
+                        .context("Failed to create temperature readings")?;
+
+                let mut excursions = Vec::new();
+                for reading in readings
+                    .iter()
+                    .filter(|reading| !delivery.is_temperature_in_range(reading.temperature_c))
+                {
+                    let excursion: DeliveryTemperatureExcursionEntity =
+                        diesel::insert_into(delivery_temperature_excursions::table)
+                            .values(CreateDeliveryTemperatureExcursionEntity {
+                                delivery_id: delivery.id,
+                                reading_id: reading.id,
+                                temperature_c: reading.temperature_c,
+                                min_temperature_c: delivery.min_temperature_c,
+                                max_temperature_c: delivery.max_temperature_c,
+                            })
+                            .returning(DeliveryTemperatureExcursionEntity::as_returning())
+                            .get_result(conn)
+                            .await
+                            .context("Failed to create temperature excursion")?;
+
+                    diesel::insert_into(delivery_logs::table)
+                        .values(CreateDeliveryLogEntity {
+                            delivery_id: delivery.id,
+                            description: format!(
+                                "Temperature excursion: {:.1}°C recorded at {}",
+                                reading.temperature_c, reading.recorded_at
+                            ),
+                            status: delivery.status.clone(),
+                        })
+                        .execute(conn)
+                        .await
+                        .context("Failed to create delivery log")?;
+
+                    outbox::publish(
+                        conn,
+                        "delivery.temperature_excursion".into(),
+                        DeliveryTemperatureExcursionEvent {
+                            delivery_id: delivery.id,
+                            order_id: delivery.order_id,
+                            excursion_id: excursion.id,
+                            temperature_c: reading.temperature_c,
+                            min_temperature_c: delivery.min_temperature_c,
+                            max_temperature_c: delivery.max_temperature_c,
+                            recorded_at: reading.recorded_at,
+                        },
+                    )
+                    .await
+                    .context("Failed to send outbox")?;
+
+                    excursions.push(excursion);
+                }
+
+                if !excursions.is_empty() && !delivery.requires_review {
+                    diesel::update(deliveries::table.find(delivery.id))
+                        .set(deliveries::requires_review.eq(true))
+                        .execute(conn)
+                        .await
+                        .context("Failed to flag delivery for review")?;
+                }
+
+                Ok::<_, AppError>((readings, excursions))
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(RecordTemperatureReadingsRes {
+            readings,
+            excursions,
+        }),
+        message: Some("Recorded temperature readings successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ReviewTemperatureExcursionsReq {
+    /// Pharmacist reviewing the excursions, at most 100 characters.
+    reviewed_by: String,
+    note: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReviewTemperatureExcursionsRes {
+    excursions: Vec<DeliveryTemperatureExcursionEntity>,
+    delivery_log: DeliveryLogEntity,
+}
+
+/// Record a pharmacist's review of a delivery's open excursions, clearing it for confirmation.
+#[utoipa::path(
+    post,
+    path = "/{id}/temperature-review",
+    tags = ["Cold Chain"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to review")
+    ),
+    request_body = ReviewTemperatureExcursionsReq,
+    responses(
+        (status = 200, description = "Reviewed temperature excursions successfully", body = StdResponse<ReviewTemperatureExcursionsRes, String>)
+    )
+)]
+async fn review_temperature_excursions(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<ReviewTemperatureExcursionsReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let reviewed_by = body.reviewed_by.trim().to_string();
+    if reviewed_by.is_empty() || reviewed_by.chars().count() > MAX_REVIEWER_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "reviewed_by must be between 1 and {MAX_REVIEWER_CHARS} characters"
+        )));
+    }
+    let note = body.note.trim().to_string();
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let (excursions, delivery_log) = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                if !delivery.requires_review {
+                    return Err(AppError::BadRequest(
+                        "Delivery has no excursions awaiting review".into(),
+                    ));
+                }
+
+                let excursions: Vec<DeliveryTemperatureExcursionEntity> = diesel::update(
+                    delivery_temperature_excursions::table
+                        .filter(delivery_temperature_excursions::delivery_id.eq(delivery.id))
+                        .filter(delivery_temperature_excursions::reviewed_at.is_null()),
+                )
+                .set((
+                    delivery_temperature_excursions::reviewed_by.eq(&reviewed_by),
+                    delivery_temperature_excursions::review_note.eq(&note),
+                    delivery_temperature_excursions::reviewed_at.eq(Utc::now()),
+                ))
+                .returning(DeliveryTemperatureExcursionEntity::as_returning())
+                .get_results(conn)
+                .await
+                .context("Failed to review temperature excursions")?;
+
+                diesel::update(deliveries::table.find(delivery.id))
+                    .set(deliveries::requires_review.eq(false))
+                    .execute(conn)
+                    .await
+                    .context("Failed to clear delivery review flag")?;
+
+                let delivery_log = diesel::insert_into(delivery_logs::table)
+                    .values(CreateDeliveryLogEntity {
+                        delivery_id: delivery.id,
+                        description: format!(
+                            "Temperature excursions reviewed by {reviewed_by}: {note}"
+                        ),
+                        status: delivery.status,
+                    })
+                    .returning(DeliveryLogEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .context("Failed to create delivery log")?;
+
+                Ok::<_, AppError>((excursions, delivery_log))
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(ReviewTemperatureExcursionsRes {
+            excursions,
+            delivery_log,
+        }),
+        message: Some("Reviewed temperature excursions successfully"),
+    })
+}
@@ -0,0 +1,533 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        CourierEntity, CreateCourierEntity, CreateDeliveryLogEntity, DeliveryEntity,
+        DeliveryLogEntity,
+    },
+    schema::{couriers, deliveries, delivery_logs},
+    services::{
+        self,
+        calendar::{Calendar, Scope},
+        dispatch,
+        route_planner::{self, Coordinates, PlannerSettings, Stop},
+    },
+};
+
+/// Average courier speed assumed when the request does not give one.
+const DEFAULT_SPEED_KMH: f64 = 30.0;
+/// Time spent handing over a delivery at each stop when the request does not give one.
+const DEFAULT_SERVICE_MINUTES: f64 = 5.0;
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new()
+        .nest(
+            "/couriers",
+            OpenApiRouter::new()
+                .routes(utoipa_axum::routes!(get_couriers))
+                .routes(utoipa_axum::routes!(create_courier))
+                .routes(utoipa_axum::routes!(update_courier))
+                .routes(utoipa_axum::routes!(optimize_route))
+                .routes(utoipa_axum::routes!(get_delivery_instructions)),
+        )
+        .nest(
+            "/deliveries",
+            OpenApiRouter::new().routes(utoipa_axum::routes!(assign_courier)),
+        )
+}
+
+/// Fetch all couriers.
+#[utoipa::path(
+    get,
+    path = "/",
+    tags = ["Couriers"],
+    responses(
+        (status = 200, description = "List all couriers", body = StdResponse<Vec<CourierEntity>, String>)
+    )
+)]
+async fn get_couriers(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let couriers: Vec<CourierEntity> = couriers::table
+        .order_by(couriers::id.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get couriers")?;
+
+    Ok(StdResponse {
+        data: Some(couriers),
+        message: Some("Get couriers successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateCourierReq {
+    name: String,
+    phone_number: Option<String>,
+    is_active: bool,
+    /// One of `BICYCLE`, `MOTORCYCLE` (default), `CAR`, `VAN`.
+    #[serde(default = "default_vehicle_type")]
+    vehicle_type: String,
+    zone: Option<String>,
+    max_deliveries: Option<i32>,
+    capacity_grams: Option<i32>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+fn default_vehicle_type() -> String {
+    "MOTORCYCLE".into()
+}
+
+impl CreateCourierReq {
+    fn into_entity(self) -> Result<CreateCourierEntity, AppError> {
+        if !dispatch::VEHICLE_TYPES.contains(&self.vehicle_type.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "Allowed vehicle types are: {}",
+                dispatch::VEHICLE_TYPES.join(", ")
+            )));
+        }
+        Ok(CreateCourierEntity {
+            name: self.name,
+            phone_number: self.phone_number,
+            is_active: self.is_active,
+            vehicle_type: self.vehicle_type,
+            zone: self.zone,
+            max_deliveries: self.max_deliveries,
+            capacity_grams: self.capacity_grams,
+            latitude: self.latitude,
+            longitude: self.longitude,
+        })
+    }
+}
+
+/// Register a courier.
+#[utoipa::path(
+    post,
+    path = "/",
+    tags = ["Couriers"],
+    request_body = CreateCourierReq,
+    responses(
+        (status = 200, description = "Created courier successfully", body = StdResponse<CourierEntity, String>)
+    )
+)]
+async fn create_courier(
+    State(state): State<AppState>,
+    Json(body): Json<CreateCourierReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let courier: CourierEntity = diesel::insert_into(couriers::table)
+        .values(body.into_entity()?)
+        .returning(CourierEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to create courier")?;
+
+    Ok(StdResponse {
+        data: Some(courier),
+        message: Some("Created courier successfully"),
+    })
+}
+
+/// Update a courier's details.
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    tags = ["Couriers"],
+    params(
+        ("id" = i32, Path, description = "Courier ID to update")
+    ),
+    request_body = CreateCourierReq,
+    responses(
+        (status = 200, description = "Updated courier successfully", body = StdResponse<CourierEntity, String>)
+    )
+)]
+async fn update_courier(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+    Json(body): Json<CreateCourierReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let courier: CourierEntity = diesel::update(couriers::table.find(id))
+        .set(body.into_entity()?)
+        .returning(CourierEntity::as_returning())
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to update courier")?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(courier),
+        message: Some("Updated courier successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AssignCourierReq {
+    /// `null` unassigns the delivery.
+    courier_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct AssignCourierRes {
+    updated_delivery: DeliveryEntity,
+    delivery_log: DeliveryLogEntity,
+}
+
+/// Assign a delivery to a courier, or unassign it.
+#[utoipa::path(
+    patch,
+    path = "/{id}/courier",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to assign")
+    ),
+    request_body = AssignCourierReq,
+    responses(
+        (status = 200, description = "Assigned courier successfully", body = StdResponse<AssignCourierRes, String>)
+    )
+)]
+async fn assign_courier(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<AssignCourierReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let (updated_delivery, delivery_log) = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let courier: Option<CourierEntity> = match body.courier_id {
+                    Some(courier_id) => Some(
+                        couriers::table
+                            .find(courier_id)
+                            .filter(couriers::is_active.eq(true))
+                            .get_result(conn)
+                            .await
+                            .optional()
+                            .context("Failed to get courier")?
+                            .ok_or_else(|| {
+                                AppError::BadRequest("Courier does not exist or is inactive".into())
+                            })?,
+                    ),
+                    None => None,
+                };
+
+                let delivery: DeliveryEntity = diesel::update(deliveries::table.find(id))
+                    .set((
+                        deliveries::courier_id.eq(body.courier_id),
+                        deliveries::assigned_at.eq(body.courier_id.map(|_| Utc::now())),
+                    ))
+                    .returning(DeliveryEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to assign courier")?
+                    .ok_or(AppError::NotFound)?;
+
+                let delivery_log = diesel::insert_into(delivery_logs::table)
+                    .values(CreateDeliveryLogEntity {
+                        delivery_id: delivery.id,
+                        description: match &courier {
+                            Some(courier) => format!("Assigned to courier {}", courier.name),
+                            None => "Unassigned from courier".into(),
+                        },
+                        status: delivery.status.clone(),
+                    })
+                    .returning(DeliveryLogEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .context("Failed to create delivery log")?;
+
+                Ok::<_, AppError>((delivery, delivery_log))
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(AssignCourierRes {
+            updated_delivery,
+            delivery_log,
+        }),
+        message: Some("Assigned courier successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct OptimizeRouteReq {
+    start_latitude: f64,
+    start_longitude: f64,
+    /// When the courier sets off. Defaults to now.
+    start_at: Option<DateTime<Utc>>,
+    speed_kmh: Option<f64>,
+    service_minutes: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RouteStopRes {
+    delivery_id: Uuid,
+    route_sequence: i32,
+    estimated_arrival_at: DateTime<Utc>,
+    distance_km: f64,
+    lateness_minutes: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct OptimizeRouteRes {
+    courier_id: i32,
+    stops: Vec<RouteStopRes>,
+    total_distance_km: f64,
+    total_lateness_minutes: f64,
+    /// Assigned deliveries left out of the route because they have no coordinates.
+    unrouted_delivery_ids: Vec<Uuid>,
+}
+
+/// Plan the stop order of a courier's outstanding deliveries and save it on the deliveries.
+#[utoipa::path(
+    post,
+    path = "/{id}/route/optimize",
+    tags = ["Couriers"],
+    params(
+        ("id" = i32, Path, description = "Courier ID to plan the route of")
+    ),
+    request_body = OptimizeRouteReq,
+    responses(
+        (status = 200, description = "Optimized route successfully", body = StdResponse<OptimizeRouteRes, String>)
+    )
+)]
+async fn optimize_route(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+    Json(body): Json<OptimizeRouteReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let settings = PlannerSettings {
+        start: Coordinates {
+            latitude: body.start_latitude,
+            longitude: body.start_longitude,
+        },
+        speed_kmh: body.speed_kmh.unwrap_or(DEFAULT_SPEED_KMH),
+        service_minutes: body.service_minutes.unwrap_or(DEFAULT_SERVICE_MINUTES),
+    };
+    if settings.speed_kmh <= 0.0 || settings.service_minutes < 0.0 {
+        return Err(AppError::BadRequest(
+            "speed_kmh must be positive and service_minutes must not be negative".into(),
+        ));
+    }
+    let start_at = body.start_at.unwrap_or_else(Utc::now);
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let res = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let zone: Option<String> = couriers::table
+                    .find(id)
+                    .select(couriers::zone)
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get courier")?
+                    .ok_or(AppError::NotFound)?;
+
+                // The route starts on the courier's next working day and pauses over days
+                // not worked.
+                let calendar = Calendar::load(conn).await?;
+                let scope = Scope {
+                    zone: zone.as_deref(),
+                    location_id: None,
+                };
+                let start_at = calendar.next_working_time(start_at, scope);
+
+                let assigned: Vec<DeliveryEntity> = deliveries::table
+                    .filter(deliveries::courier_id.eq(id))
+                    .filter(deliveries::status.ne_all(services::deliveries::CLOSED_STATUSES))
+                    .order_by(deliveries::id.asc())
+                    .for_update()
+                    .get_results(conn)
+                    .await
+                    .context("Failed to get assigned deliveries")?;
+
+                let minutes_from_start =
+                    |at: DateTime<Utc>| (at - start_at).num_seconds() as f64 / 60.0;
+                let mut stops = Vec::new();
+                let mut unrouted_delivery_ids = Vec::new();
+                for delivery in &assigned {
+                    let (Some(latitude), Some(longitude)) = (delivery.latitude, delivery.longitude)
+                    else {
+                        unrouted_delivery_ids.push(delivery.id);
+                        continue;
+                    };
+                    stops.push(Stop {
+                        key: delivery.id,
+                        location: Coordinates {
+                            latitude,
+                            longitude,
+                        },
+                        window: match (delivery.slot_start, delivery.slot_end) {
+                            (Some(slot_start), Some(slot_end)) => {
+                                Some((minutes_from_start(slot_start), minutes_from_start(slot_end)))
+                            }
+                            (Some(slot_start), None) => {
+                                Some((minutes_from_start(slot_start), f64::INFINITY))
+                            }
+                            (None, Some(slot_end)) => {
+                                Some((f64::NEG_INFINITY, minutes_from_start(slot_end)))
+                            }
+                            (None, None) => None,
+                        },
+                        priority: services::deliveries::priority_rank(&delivery.priority)
+                            .unwrap_or_default() as u8,
+                    });
+                }
+
+                let route = route_planner::plan(&stops, &settings);
+
+                let mut route_stops = Vec::with_capacity(route.stops.len());
+                for (i, stop) in route.stops.iter().enumerate() {
+                    let route_sequence = i as i32 + 1;
+                    let estimated_arrival_at = calendar.add_working_time(
+                        start_at,
+                        Duration::seconds((stop.service_start_minute * 60.0).round() as i64),
+                        scope,
+                    );
+                    diesel::update(deliveries::table.find(stop.key))
+                        .set((
+                            deliveries::route_sequence.eq(route_sequence),
+                            deliveries::estimated_arrival_at.eq(estimated_arrival_at),
+                        ))
+                        .execute(conn)
+                        .await
+                        .context("Failed to save route sequence")?;
+                    route_stops.push(RouteStopRes {
+                        delivery_id: stop.key,
+                        route_sequence,
+                        estimated_arrival_at,
+                        distance_km: stop.distance_km,
+                        lateness_minutes: stop.lateness_minutes,
+                    });
+                }
+
+                if !unrouted_delivery_ids.is_empty() {
+                    diesel::update(
+                        deliveries::table.filter(deliveries::id.eq_any(&unrouted_delivery_ids)),
+                    )
+                    .set((
+                        deliveries::route_sequence.eq(None::<i32>),
+                        deliveries::estimated_arrival_at.eq(None::<DateTime<Utc>>),
+                    ))
+                    .execute(conn)
+                    .await
+                    .context("Failed to clear route sequence")?;
+                }
+
+                Ok::<_, AppError>(OptimizeRouteRes {
+                    courier_id: id,
+                    stops: route_stops,
+                    total_distance_km: route.total_distance_km,
+                    total_lateness_minutes: route.total_lateness_minutes,
+                    unrouted_delivery_ids,
+                })
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(res),
+        message: Some("Optimized route successfully"),
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct DeliveryInstructionsRes {
+    delivery_id: Uuid,
+    delivery_instructions: Option<String>,
+    /// Whether the patient left an access code. The code itself is not returned, as
+    /// couriers do not authenticate with this service and any caller could ask for it.
+    has_access_code: bool,
+    preferred_contact_method: Option<String>,
+    contactless_consent: bool,
+}
+
+/// Fetch the patient's delivery instructions for a delivery assigned to the courier.
+#[utoipa::path(
+    get,
+    path = "/{id}/deliveries/{delivery_id}/instructions",
+    tags = ["Couriers"],
+    params(
+        ("id" = i32, Path, description = "Courier ID"),
+        ("delivery_id" = Uuid, Path, description = "Delivery ID assigned to the courier")
+    ),
+    responses(
+        (status = 200, description = "Fetched delivery instructions successfully", body = StdResponse<DeliveryInstructionsRes, String>)
+    )
+)]
+async fn get_delivery_instructions(
+    Path((id, delivery_id)): Path<(i32, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery: DeliveryEntity = deliveries::table
+        .find(delivery_id)
+        .filter(deliveries::courier_id.eq(id))
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery")?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(DeliveryInstructionsRes {
+            delivery_id: delivery.id,
+            delivery_instructions: delivery.delivery_instructions,
+            has_access_code: delivery.access_code_encrypted.is_some(),
+            preferred_contact_method: delivery.preferred_contact_method,
+            contactless_consent: delivery.contactless_consent,
+        }),
+        message: Some("Get delivery instructions successfully"),
+    })
+}
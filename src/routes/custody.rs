@@ -0,0 +1,316 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    config, csv,
+    models::{
+        CreateDeliveryCustodyEntryEntity, CreateDeliveryLogEntity, DeliveryCustodyEntryEntity,
+        DeliveryEntity,
+    },
+    schema::{deliveries, delivery_custody_entries, delivery_logs},
+    services::custody::{self, CustodyHandoff, CustodyVerification},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/deliveries",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_custody_chain))
+            .routes(utoipa_axum::routes!(record_custody_handoff))
+            .routes(utoipa_axum::routes!(export_custody_chain)),
+    )
+}
+
+#[derive(Serialize, ToSchema)]
+struct GetCustodyChainRes {
+    entries: Vec<DeliveryCustodyEntryEntity>,
+    verification: CustodyVerification,
+}
+
+/// Fetch the custody ledger of a delivery along with its verification result.
+#[utoipa::path(
+    get,
+    path = "/{id}/custody",
+    tags = ["Custody"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch the custody chain for")
+    ),
+    responses(
+        (status = 200, description = "Fetched custody chain successfully", body = StdResponse<GetCustodyChainRes, String>)
+    )
+)]
+async fn get_custody_chain(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let entries: Vec<DeliveryCustodyEntryEntity> = delivery_custody_entries::table
+        .filter(delivery_custody_entries::delivery_id.eq(id))
+        .order_by(delivery_custody_entries::sequence.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get custody entries")?;
+
+    let key = config::custody_signing_key()?;
+    let verification = custody::verify(&entries, key.as_bytes());
+
+    Ok(StdResponse {
+        data: Some(GetCustodyChainRes {
+            entries,
+            verification,
+        }),
+        message: Some("Get custody chain successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RecordCustodyHandoffReq {
+    /// PHARMACY_TO_COURIER, COURIER_TO_COURIER, COURIER_TO_RECIPIENT or, for parcels collected
+    /// at the pharmacy, PHARMACY_TO_RECIPIENT.
+    handoff_type: String,
+    from_actor: String,
+    to_actor: String,
+    location: String,
+    seal_number: String,
+    /// Defaults to the time the handoff is received.
+    handed_over_at: Option<DateTime<Utc>>,
+}
+
+/// Append a handoff to the custody ledger of a delivery.
+#[utoipa::path(
+    post,
+    path = "/{id}/custody",
+    tags = ["Custody"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID the handoff belongs to")
+    ),
+    request_body = RecordCustodyHandoffReq,
+    responses(
+        (status = 200, description = "Recorded custody handoff successfully", body = StdResponse<DeliveryCustodyEntryEntity, String>)
+    )
+)]
+async fn record_custody_handoff(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<RecordCustodyHandoffReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    if !custody::HANDOFF_TYPES.contains(&body.handoff_type.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Allowed handoff types are: {}",
+            custody::HANDOFF_TYPES.join(", ")
+        )));
+    }
+
+    let key = config::custody_signing_key()?;
+
+    let entry = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                // Locking the delivery serialises appends so the chain cannot fork.
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+                if !delivery.is_controlled {
+                    return Err(AppError::BadRequest(
+                        "Only controlled deliveries keep a custody ledger".into(),
+                    ));
+                }
+
+                let entries: Vec<DeliveryCustodyEntryEntity> = delivery_custody_entries::table
+                    .filter(delivery_custody_entries::delivery_id.eq(delivery.id))
+                    .order_by(delivery_custody_entries::sequence.asc())
+                    .get_results(conn)
+                    .await
+                    .context("Failed to get custody entries")?;
+
+                custody::check_next_handoff(&entries, &body.handoff_type, &body.from_actor)
+                    .map_err(AppError::BadRequest)?;
+
+                let previous_hash = entries.last().map(|last| last.entry_hash.clone());
+                let handoff = CustodyHandoff {
+                    delivery_id: delivery.id,
+                    sequence: entries.len() as i32 + 1,
+                    handoff_type: &body.handoff_type,
+                    from_actor: &body.from_actor,
+                    to_actor: &body.to_actor,
+                    location: &body.location,
+                    seal_number: &body.seal_number,
+                    handed_over_at: body
+                        .handed_over_at
+                        .unwrap_or_else(Utc::now)
+                        .trunc_subsecs(6),
+                };
+                let entry_hash = custody::entry_hash(previous_hash.as_deref(), &handoff);
+                let signature = custody::sign(key.as_bytes(), &entry_hash);
+
+                let entry: DeliveryCustodyEntryEntity =
+                    diesel::insert_into(delivery_custody_entries::table)
+                        .values(CreateDeliveryCustodyEntryEntity {
+                            delivery_id: delivery.id,
+                            sequence: handoff.sequence,
+                            handoff_type: body.handoff_type.clone(),
+                            from_actor: body.from_actor.clone(),
+                            to_actor: body.to_actor.clone(),
+                            location: body.location.clone(),
+                            seal_number: body.seal_number.clone(),
+                            handed_over_at: handoff.handed_over_at,
+                            previous_hash,
+                            entry_hash,
+                            signature,
+                        })
+                        .returning(DeliveryCustodyEntryEntity::as_returning())
+                        .get_result(conn)
+                        .await
+                        .context("Failed to create custody entry")?;
+
+                diesel::insert_into(delivery_logs::table)
+                    .values(CreateDeliveryLogEntity {
+                        delivery_id: delivery.id,
+                        description: format!(
+                            "Custody handed from {} to {} at {} (seal {})",
+                            entry.from_actor, entry.to_actor, entry.location, entry.seal_number
+                        ),
+                        status: delivery.status,
+                    })
+                    .execute(conn)
+                    .await
+                    .context("Failed to create delivery log")?;
+
+                Ok::<_, AppError>(entry)
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(entry),
+        message: Some("Recorded custody handoff successfully"),
+    })
+}
+
+/// Export the custody ledger of a delivery as CSV for regulatory audit.
+/// The `x-custody-intact` and `x-custody-complete` headers carry the verification result.
+#[utoipa::path(
+    get,
+    path = "/{id}/custody/export",
+    tags = ["Custody"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to export the custody chain for")
+    ),
+    responses(
+        (status = 200, description = "Exported custody chain successfully", content_type = "text/csv", body = String)
+    )
+)]
+async fn export_custody_chain(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let entries: Vec<DeliveryCustodyEntryEntity> = delivery_custody_entries::table
+        .filter(delivery_custody_entries::delivery_id.eq(id))
+        .order_by(delivery_custody_entries::sequence.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get custody entries")?;
+
+    if entries.is_empty() {
+        return Err(AppError::NotFound);
+    }
+
+    let key = config::custody_signing_key()?;
+    let verification = custody::verify(&entries, key.as_bytes());
+
+    let mut body = String::new();
+    csv::write_record(
+        &mut body,
+        &[
+            "delivery_id",
+            "sequence",
+            "handoff_type",
+            "from_actor",
+            "to_actor",
+            "location",
+            "seal_number",
+            "handed_over_at",
+            "previous_hash",
+            "entry_hash",
+            "signature",
+        ],
+    );
+    for entry in &entries {
+        csv::write_record(
+            &mut body,
+            &[
+                entry.delivery_id.to_string(),
+                entry.sequence.to_string(),
+                entry.handoff_type.clone(),
+                entry.from_actor.clone(),
+                entry.to_actor.clone(),
+                entry.location.clone(),
+                entry.seal_number.clone(),
+                entry
+                    .handed_over_at
+                    .to_rfc3339_opts(SecondsFormat::Micros, true),
+                entry.previous_hash.clone().unwrap_or_default(),
+                entry.entry_hash.clone(),
+                entry.signature.clone(),
+            ],
+        );
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"custody-{id}.csv\""),
+            ),
+            (
+                header::HeaderName::from_static("x-custody-intact"),
+                verification.intact.to_string(),
+            ),
+            (
+                header::HeaderName::from_static("x-custody-complete"),
+                verification.complete.to_string(),
+            ),
+        ],
+        body,
+    ))
+}
@@ -6,23 +6,28 @@ use axum::{
     routing,
 };
 
-use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
 use diesel_async::{AsyncConnection, RunQueryDsl};
 
 use medbook_core::{
     app_error::{AppError, StdResponse},
     app_state::AppState,
-    outbox,
 };
-use medbook_events::DeliverySuccessEvent;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
 use uuid::Uuid;
 
 use crate::{
-    models::{CreateDeliveryLogEntity, DeliveryEntity, DeliveryLogEntity},
+    config,
+    models::{CreateDeliveryLogEntity, DeliveryEntity, DeliveryLegEntity, DeliveryLogEntity},
     schema::{deliveries, delivery_logs},
+    services::{
+        self,
+        calendar::Calendar,
+        packages::{ManifestSummary, PackageWithItems},
+        sla::{self, DeliveryWithSla, SlaDeadlines},
+    },
 };
 
 /// Defines all patient-facing product routes (CRUD operations + authorization).
@@ -44,17 +49,25 @@ pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
         OpenApiRouter::new()
             .routes(utoipa_axum::routes!(get_delivery))
             .routes(utoipa_axum::routes!(get_deliveries))
-            .routes(utoipa_axum::routes!(update_delivery_state)),
+            .routes(utoipa_axum::routes!(update_delivery_state))
+            .routes(utoipa_axum::routes!(update_delivery_priority)),
     )
 }
 
 #[derive(Serialize, ToSchema)]
 struct GetDeliveryRes {
     delivery: DeliveryEntity,
+    sla: SlaDeadlines,
+    /// Orders carried by the delivery, including consolidated ones.
+    order_ids: Vec<i32>,
+    /// Legs through hubs, in order. Empty for deliveries going straight to the recipient.
+    legs: Vec<DeliveryLegEntity>,
     delivery_logs: Vec<DeliveryLogEntity>,
+    packages: Vec<PackageWithItems>,
+    manifest: ManifestSummary,
 }
 
-/// Fetch a specific delivery and its logs.
+/// Fetch a specific delivery with its SLA deadlines, legs, logs and package manifest.
 #[utoipa::path(
     get,
     path = "/{id}",
@@ -89,22 +102,34 @@ async fn get_delivery(
         .await
         .context("Failed to get delivery logs")?;
 
+    let order_ids = services::deliveries::order_ids(conn, delivery.id).await?;
+    let legs = services::legs::get_legs(conn, delivery.id).await?;
+    let packages = services::packages::get_packages(conn, delivery.id).await?;
+    let manifest = ManifestSummary::of(packages.iter().map(|package| &package.package));
+    let calendar = Calendar::load(conn).await?;
+    let sla = SlaDeadlines::of(&delivery, &config::sla_targets()?, &calendar);
+
     Ok(StdResponse {
         data: Some(GetDeliveryRes {
             delivery,
+            sla,
+            order_ids,
+            legs,
             delivery_logs,
+            packages,
+            manifest,
         }),
         message: Some("Get delivery successfully"),
     })
 }
 
-/// Fetch all deliveries in the system.
+/// Fetch all deliveries in the system, most urgent first.
 #[utoipa::path(
     get,
     path = "/",
     tags = ["Deliveries"],
     responses(
-        (status = 200, description = "List all deliveries", body = StdResponse<Vec<DeliveryEntity>, String>)
+        (status = 200, description = "List all deliveries", body = StdResponse<Vec<DeliveryWithSla>, String>)
     )
 )]
 async fn get_deliveries(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
@@ -119,6 +144,14 @@ async fn get_deliveries(State(state): State<AppState>) -> Result<impl IntoRespon
         .await
         .context("Failed to get deliveries")?;
 
+    let targets = config::sla_targets()?;
+    let calendar = Calendar::load(conn).await?;
+    let mut deliveries: Vec<DeliveryWithSla> = deliveries
+        .into_iter()
+        .map(|delivery| DeliveryWithSla::of(delivery, &targets, &calendar))
+        .collect();
+    sla::sort_by_urgency(&mut deliveries);
+
     Ok(StdResponse {
         data: Some(deliveries),
         message: Some("Get deliveries successfully"),
@@ -161,7 +194,19 @@ async fn update_delivery_state(
         .await
         .context("Failed to obtain a DB connection pool")?;
 
-    let allowed_statuses = ["PREPARING", "EN_ROUTE", "DELIVERED"];
+    // Collecting takes the patient's pickup code, so it goes through `/collect` instead.
+    let allowed_statuses: Vec<&str> = services::deliveries::FULFILMENT_MODES
+        .iter()
+        .flat_map(|mode| services::deliveries::status_flow(mode))
+        .chain(&services::deliveries::RETURN_STATUSES)
+        .copied()
+        .filter(|status| *status != "COLLECTED")
+        .fold(Vec::new(), |mut statuses, status| {
+            if !statuses.contains(&status) {
+                statuses.push(status);
+            }
+            statuses
+        });
     if !allowed_statuses.contains(&body.status.as_str()) {
         return Err(AppError::BadRequest(format!(
             "Allowed statuses are: {}",
@@ -172,47 +217,120 @@ async fn update_delivery_state(
     let (updated_delivery, delivery_log) = conn
         .transaction(move |conn| {
             Box::pin(async move {
+                services::deliveries::transition(conn, id, body.status, body.description).await
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(UpdateDeliveryStateRes {
+            updated_delivery,
+            delivery_log,
+        }),
+        message: Some("Get deliveries successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpdateDeliveryPriorityReq {
+    /// `STANDARD`, `EXPRESS` or `URGENT`.
+    priority: String,
+    reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct UpdateDeliveryPriorityRes {
+    updated_delivery: DeliveryWithSla,
+    delivery_log: DeliveryLogEntity,
+}
+
+/// Change a delivery's priority, which moves its SLA deadlines.
+#[utoipa::path(
+    patch,
+    path = "/{id}/priority",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to update")
+    ),
+    request_body = UpdateDeliveryPriorityReq,
+    responses(
+        (status = 200, description = "Updated delivery priority successfully", body = StdResponse<UpdateDeliveryPriorityRes, String>)
+    )
+)]
+async fn update_delivery_priority(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<UpdateDeliveryPriorityReq>,
+) -> Result<impl IntoResponse, AppError> {
+    if services::deliveries::priority_rank(&body.priority).is_none() {
+        return Err(AppError::BadRequest(format!(
+            "Allowed priorities are: {}",
+            services::deliveries::PRIORITIES.join(", ")
+        )));
+    }
+    let targets = config::sla_targets()?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let (updated_delivery, delivery_log) = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let current: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                if current.priority == body.priority {
+                    return Err(AppError::BadRequest(format!(
+                        "Delivery is already {}",
+                        current.priority
+                    )));
+                }
+
                 let delivery: DeliveryEntity = diesel::update(deliveries::table.find(id))
-                    .set(deliveries::status.eq(body.status.clone()))
+                    .set(deliveries::priority.eq(&body.priority))
                     .returning(DeliveryEntity::as_returning())
                     .get_result(conn)
                     .await
-                    .context("Failed to update delivery status")?;
+                    .context("Failed to update delivery priority")?;
 
+                let mut description = format!(
+                    "Priority changed from {} to {}",
+                    current.priority, body.priority
+                );
+                if let Some(reason) = &body.reason {
+                    description.push_str(&format!(": {reason}"));
+                }
                 let delivery_log = diesel::insert_into(delivery_logs::table)
                     .values(CreateDeliveryLogEntity {
-                        delivery_id: delivery.id.clone(),
-                        description: body.description,
-                        status: body.status,
+                        delivery_id: delivery.id,
+                        description,
+                        status: delivery.status.clone(),
                     })
                     .returning(DeliveryLogEntity::as_returning())
                     .get_result(conn)
                     .await
                     .context("Failed to create delivery log")?;
 
-                if delivery.status.as_str() == "DELIVERED" {
-                    outbox::publish(
-                        conn,
-                        "orders.delivery_success".into(),
-                        DeliverySuccessEvent {
-                            order_id: delivery.order_id,
-                        },
-                    )
-                    .await
-                    .context("Failed to send outbox")?;
-                }
-
-                Ok::<(DeliveryEntity, DeliveryLogEntity), AppError>((delivery, delivery_log))
+                Ok::<_, AppError>((delivery, delivery_log))
             })
         })
-        .await
-        .context("Transaction failed")?;
+        .await?;
+    let calendar = Calendar::load(conn).await?;
 
     Ok(StdResponse {
-        data: Some(UpdateDeliveryStateRes {
-            updated_delivery,
+        data: Some(UpdateDeliveryPriorityRes {
+            updated_delivery: DeliveryWithSla::of(updated_delivery, &targets, &calendar),
             delivery_log,
         }),
-        message: Some("Get deliveries successfully"),
+        message: Some("Updated delivery priority successfully"),
     })
 }
@@ -0,0 +1,85 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use diesel_async::AsyncConnection;
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::{
+    config,
+    services::{
+        self,
+        dispatch::{self, DispatchDecision},
+    },
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/dispatch",
+        OpenApiRouter::new().routes(utoipa_axum::routes!(run_dispatch)),
+    )
+}
+
+#[derive(Deserialize, IntoParams)]
+struct RunDispatchQuery {
+    /// Only propose assignments without saving them.
+    #[serde(default)]
+    dry_run: bool,
+    /// `balanced` or `nearest`. Defaults to the strategy the worker uses.
+    strategy: Option<String>,
+}
+
+/// Assign waiting deliveries to couriers now, or preview the assignments with `dry_run`.
+/// Deliveries a carrier turns down are offered to our couriers on the next run.
+#[utoipa::path(
+    post,
+    path = "/run",
+    tags = ["Dispatch"],
+    params(RunDispatchQuery),
+    responses(
+        (status = 200, description = "Ran dispatch successfully", body = StdResponse<Vec<DispatchDecision>, String>)
+    )
+)]
+async fn run_dispatch(
+    Query(query): Query<RunDispatchQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let strategy = query.strategy.unwrap_or_else(config::dispatch_strategy);
+    let strategy = dispatch::strategy_by_name(&strategy)
+        .ok_or_else(|| AppError::BadRequest("Allowed strategies are: balanced, nearest".into()))?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let decisions = conn
+        .transaction(|conn| Box::pin(dispatch::run(conn, strategy.as_ref(), query.dry_run)))
+        .await?;
+    if !query.dry_run {
+        for decision in decisions
+            .iter()
+            .filter(|decision| decision.carrier.is_some())
+        {
+            services::carriers::process(conn, decision.delivery_id).await?;
+        }
+    }
+
+    Ok(StdResponse {
+        data: Some(decisions),
+        message: Some(if query.dry_run {
+            "Proposed dispatch successfully"
+        } else {
+            "Ran dispatch successfully"
+        }),
+    })
+}
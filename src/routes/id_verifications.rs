@@ -0,0 +1,151 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use diesel::{OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        CreateDeliveryIdVerificationEntity, CreateDeliveryLogEntity, DeliveryEntity,
+        DeliveryIdVerificationEntity, DeliveryLogEntity,
+    },
+    schema::{deliveries, delivery_id_verifications, delivery_logs},
+    services::id_verification,
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/deliveries",
+        OpenApiRouter::new().routes(utoipa_axum::routes!(verify_recipient_id)),
+    )
+}
+
+#[derive(Deserialize, ToSchema)]
+struct VerifyRecipientIdReq {
+    /// NATIONAL_ID, PASSPORT or DRIVING_LICENSE.
+    id_type: String,
+    /// Only the last four characters are stored.
+    id_number: String,
+    /// Name as printed on the presented ID.
+    presented_name: String,
+    verified_by: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct VerifyRecipientIdRes {
+    verification: DeliveryIdVerificationEntity,
+    delivery_log: DeliveryLogEntity,
+}
+
+/// Record the courier's check of the recipient's ID against the delivery's recipient name.
+#[utoipa::path(
+    post,
+    path = "/{id}/id-verification",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID being handed over")
+    ),
+    request_body = VerifyRecipientIdReq,
+    responses(
+        (status = 200, description = "Recorded ID verification successfully", body = StdResponse<VerifyRecipientIdRes, String>)
+    )
+)]
+async fn verify_recipient_id(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<VerifyRecipientIdReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    if !id_verification::ID_TYPES.contains(&body.id_type.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Allowed ID types are: {}",
+            id_verification::ID_TYPES.join(", ")
+        )));
+    }
+
+    let masked_id_number = id_verification::mask_id_number(&body.id_number)?;
+
+    let (verification, delivery_log) = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                let recipient_name =
+                    id_verification::recipient_name(delivery.delivery_address.as_ref());
+                let name_matched = recipient_name.as_deref().is_some_and(|recipient_name| {
+                    id_verification::names_match(&body.presented_name, recipient_name)
+                });
+
+                let verification: DeliveryIdVerificationEntity =
+                    diesel::insert_into(delivery_id_verifications::table)
+                        .values(CreateDeliveryIdVerificationEntity {
+                            delivery_id: delivery.id,
+                            id_type: body.id_type,
+                            masked_id_number,
+                            presented_name: body.presented_name,
+                            recipient_name,
+                            name_matched,
+                            verified_by: body.verified_by,
+                        })
+                        .returning(DeliveryIdVerificationEntity::as_returning())
+                        .get_result(conn)
+                        .await
+                        .context("Failed to create ID verification")?;
+
+                let delivery_log = diesel::insert_into(delivery_logs::table)
+                    .values(CreateDeliveryLogEntity {
+                        delivery_id: delivery.id,
+                        description: format!(
+                            "Recipient ID {} {} checked by {}: {}",
+                            verification.id_type,
+                            verification.masked_id_number,
+                            verification.verified_by,
+                            if verification.name_matched {
+                                "name matched"
+                            } else {
+                                "name did not match"
+                            }
+                        ),
+                        status: delivery.status,
+                    })
+                    .returning(DeliveryLogEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .context("Failed to create delivery log")?;
+
+                Ok::<_, AppError>((verification, delivery_log))
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(VerifyRecipientIdRes {
+            verification,
+            delivery_log,
+        }),
+        message: Some("Recorded ID verification successfully"),
+    })
+}
@@ -0,0 +1,483 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        CreateDeliveryIncidentAttachmentEntity, CreateDeliveryIncidentCommentEntity,
+        CreateDeliveryIncidentEntity, DeliveryEntity, DeliveryIncidentAttachmentEntity,
+        DeliveryIncidentCommentEntity, DeliveryIncidentEntity,
+    },
+    schema::{deliveries, delivery_incidents},
+    services::incidents::{self, IncidentDetails},
+};
+
+/// Who staff endpoints accept reports and comments from.
+const STAFF_REPORTER_TYPES: [&str; 2] = ["STAFF", "COURIER"];
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new()
+        .nest(
+            "/incidents",
+            OpenApiRouter::new()
+                .routes(utoipa_axum::routes!(get_incidents))
+                .routes(utoipa_axum::routes!(get_incident))
+                .routes(utoipa_axum::routes!(update_incident_status))
+                .routes(utoipa_axum::routes!(create_incident_comment))
+                .routes(utoipa_axum::routes!(get_incident_attachment))
+                .merge(
+                    OpenApiRouter::new()
+                        .routes(utoipa_axum::routes!(create_incident_attachment))
+                        .layer(DefaultBodyLimit::max(incidents::MAX_ATTACHMENT_BYTES)),
+                ),
+        )
+        .nest(
+            "/deliveries",
+            OpenApiRouter::new()
+                .routes(utoipa_axum::routes!(create_delivery_incident))
+                .routes(utoipa_axum::routes!(get_delivery_incidents)),
+        )
+}
+
+fn check_reporter_type(reporter_type: &str) -> Result<(), AppError> {
+    if STAFF_REPORTER_TYPES.contains(&reporter_type) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Allowed reporter types are: {}",
+            STAFF_REPORTER_TYPES.join(", ")
+        )))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+struct GetIncidentsQuery {
+    status: Option<String>,
+    category: Option<String>,
+}
+
+/// Fetch incidents across all deliveries, newest first.
+#[utoipa::path(
+    get,
+    path = "/",
+    tags = ["Incidents"],
+    params(GetIncidentsQuery),
+    responses(
+        (status = 200, description = "List incidents", body = StdResponse<Vec<DeliveryIncidentEntity>, String>)
+    )
+)]
+async fn get_incidents(
+    Query(query): Query<GetIncidentsQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let mut incidents = delivery_incidents::table
+        .order_by(delivery_incidents::created_at.desc())
+        .into_boxed();
+    if let Some(status) = query.status {
+        incidents = incidents.filter(delivery_incidents::status.eq(status));
+    }
+    if let Some(category) = query.category {
+        incidents = incidents.filter(delivery_incidents::category.eq(category));
+    }
+    let incidents: Vec<DeliveryIncidentEntity> = incidents
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery incidents")?;
+
+    Ok(StdResponse {
+        data: Some(incidents),
+        message: Some("Get incidents successfully"),
+    })
+}
+
+/// Fetch an incident with all of its comments and attachments.
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tags = ["Incidents"],
+    params(
+        ("id" = Uuid, Path, description = "Incident ID to fetch")
+    ),
+    responses(
+        (status = 200, description = "Fetched incident successfully", body = StdResponse<IncidentDetails, String>)
+    )
+)]
+async fn get_incident(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident: DeliveryIncidentEntity = delivery_incidents::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery incident")?
+        .ok_or(AppError::NotFound)?;
+
+    let details = incidents::get_details(conn, incident, true).await?;
+
+    Ok(StdResponse {
+        data: Some(details),
+        message: Some("Get incident successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpdateIncidentStatusReq {
+    /// `INVESTIGATING` or `RESOLVED`.
+    status: String,
+    /// Required when resolving: `NONE`, `RESHIP` or `REFUND`.
+    resolution: Option<String>,
+    resolution_note: Option<String>,
+    changed_by: String,
+}
+
+/// Move an incident forward. Resolutions are published to the orders service.
+#[utoipa::path(
+    patch,
+    path = "/{id}/status",
+    tags = ["Incidents"],
+    params(
+        ("id" = Uuid, Path, description = "Incident ID to update")
+    ),
+    request_body = UpdateIncidentStatusReq,
+    responses(
+        (status = 200, description = "Updated incident successfully", body = StdResponse<DeliveryIncidentEntity, String>)
+    )
+)]
+async fn update_incident_status(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<UpdateIncidentStatusReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                incidents::update_status(
+                    conn,
+                    id,
+                    &body.status,
+                    body.resolution,
+                    body.resolution_note,
+                    &body.changed_by,
+                )
+                .await
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(incident),
+        message: Some("Updated incident successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateIncidentCommentReq {
+    /// `STAFF` or `COURIER`.
+    author_type: String,
+    author: String,
+    body: String,
+    /// Internal comments are hidden from patients.
+    #[serde(default)]
+    is_internal: bool,
+}
+
+/// Comment on an incident.
+#[utoipa::path(
+    post,
+    path = "/{id}/comments",
+    tags = ["Incidents"],
+    params(
+        ("id" = Uuid, Path, description = "Incident ID to comment on")
+    ),
+    request_body = CreateIncidentCommentReq,
+    responses(
+        (status = 200, description = "Created comment successfully", body = StdResponse<DeliveryIncidentCommentEntity, String>)
+    )
+)]
+async fn create_incident_comment(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<CreateIncidentCommentReq>,
+) -> Result<impl IntoResponse, AppError> {
+    check_reporter_type(&body.author_type)?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident: DeliveryIncidentEntity = delivery_incidents::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery incident")?
+        .ok_or(AppError::NotFound)?;
+
+    let comment = incidents::add_comment(
+        conn,
+        CreateDeliveryIncidentCommentEntity {
+            incident_id: incident.id,
+            author_type: body.author_type,
+            author: body.author,
+            body: body.body,
+            is_internal: body.is_internal,
+        },
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(comment),
+        message: Some("Created comment successfully"),
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+struct CreateIncidentAttachmentQuery {
+    file_name: String,
+    /// `STAFF` or `COURIER`.
+    uploaded_by_type: String,
+    uploaded_by: String,
+}
+
+/// Attach a photo or document to an incident. The request body is the file itself.
+#[utoipa::path(
+    post,
+    path = "/{id}/attachments",
+    tags = ["Incidents"],
+    params(
+        ("id" = Uuid, Path, description = "Incident ID to attach to"),
+        CreateIncidentAttachmentQuery
+    ),
+    request_body(content = Vec<u8>, description = "JPEG, PNG or PDF file", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Created attachment successfully", body = StdResponse<DeliveryIncidentAttachmentEntity, String>)
+    )
+)]
+async fn create_incident_attachment(
+    Path(id): Path<Uuid>,
+    Query(query): Query<CreateIncidentAttachmentQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    check_reporter_type(&query.uploaded_by_type)?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident: DeliveryIncidentEntity = delivery_incidents::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery incident")?
+        .ok_or(AppError::NotFound)?;
+
+    let attachment = incidents::add_attachment(
+        conn,
+        CreateDeliveryIncidentAttachmentEntity {
+            incident_id: incident.id,
+            file_name: query.file_name,
+            content_type: content_type(&headers),
+            size_bytes: body.len() as i32,
+            content: body.to_vec(),
+            uploaded_by_type: query.uploaded_by_type,
+            uploaded_by: query.uploaded_by,
+        },
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(attachment),
+        message: Some("Created attachment successfully"),
+    })
+}
+
+/// Content type of an upload, without parameters such as `charset`.
+pub(crate) fn content_type(headers: &HeaderMap) -> String {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Download an incident attachment.
+#[utoipa::path(
+    get,
+    path = "/{id}/attachments/{attachment_id}",
+    tags = ["Incidents"],
+    params(
+        ("id" = Uuid, Path, description = "Incident ID the attachment belongs to"),
+        ("attachment_id" = Uuid, Path, description = "Attachment ID to download")
+    ),
+    responses(
+        (status = 200, description = "Attachment content", body = Vec<u8>)
+    )
+)]
+async fn get_incident_attachment(
+    Path((id, attachment_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    incidents::get_attachment(conn, id, attachment_id).await
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateDeliveryIncidentReq {
+    category: String,
+    /// Defaults by category when omitted.
+    severity: Option<String>,
+    description: String,
+    /// `STAFF` or `COURIER`.
+    reporter_type: String,
+    reported_by: String,
+}
+
+/// Report a problem with a delivery on behalf of staff or a courier.
+#[utoipa::path(
+    post,
+    path = "/{id}/incidents",
+    tags = ["Incidents"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to report on")
+    ),
+    request_body = CreateDeliveryIncidentReq,
+    responses(
+        (status = 200, description = "Reported incident successfully", body = StdResponse<DeliveryIncidentEntity, String>)
+    )
+)]
+async fn create_delivery_incident(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<CreateDeliveryIncidentReq>,
+) -> Result<impl IntoResponse, AppError> {
+    incidents::check_category(&body.category)?;
+    check_reporter_type(&body.reporter_type)?;
+    let severity = body
+        .severity
+        .unwrap_or_else(|| incidents::default_severity(&body.category).into());
+    if !incidents::SEVERITIES.contains(&severity.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Allowed severities are: {}",
+            incidents::SEVERITIES.join(", ")
+        )));
+    }
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery: DeliveryEntity = deliveries::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery")?
+        .ok_or(AppError::NotFound)?;
+
+    let incident = incidents::raise(
+        conn,
+        CreateDeliveryIncidentEntity {
+            delivery_id: delivery.id,
+            category: body.category,
+            severity,
+            description: body.description,
+            dedup_key: None,
+            reporter_type: body.reporter_type,
+            reported_by: Some(body.reported_by),
+        },
+    )
+    .await?
+    .context("Incident without a dedup key was not created")?;
+
+    Ok(StdResponse {
+        data: Some(incident),
+        message: Some("Reported incident successfully"),
+    })
+}
+
+/// Fetch the incidents of a delivery, newest first.
+#[utoipa::path(
+    get,
+    path = "/{id}/incidents",
+    tags = ["Incidents"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch incidents of")
+    ),
+    responses(
+        (status = 200, description = "List delivery incidents", body = StdResponse<Vec<DeliveryIncidentEntity>, String>)
+    )
+)]
+async fn get_delivery_incidents(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incidents: Vec<DeliveryIncidentEntity> = delivery_incidents::table
+        .filter(delivery_incidents::delivery_id.eq(id))
+        .order_by(delivery_incidents::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery incidents")?;
+
+    Ok(StdResponse {
+        data: Some(incidents),
+        message: Some("Get delivery incidents successfully"),
+    })
+}
@@ -0,0 +1,120 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::RunQueryDsl;
+use medbook_core::{app_error::AppError, app_state::AppState};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    documents::labels::PackageLabel,
+    models::{DeliveryEntity, DeliveryPackageEntity},
+    schema::{deliveries, delivery_packages},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/deliveries",
+        OpenApiRouter::new().routes(utoipa_axum::routes!(get_package_label)),
+    )
+}
+
+#[derive(Deserialize, IntoParams)]
+struct LabelQuery {
+    /// `pdf` (default) or `zpl`.
+    format: Option<String>,
+}
+
+/// Render the shipping label of a package. Once labelled, its delivery no longer takes
+/// in consolidated orders.
+#[utoipa::path(
+    get,
+    path = "/{id}/packages/{package_number}/label",
+    tags = ["Labels"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID the package belongs to"),
+        ("package_number" = i32, Path, description = "Package number within the delivery"),
+        LabelQuery
+    ),
+    responses(
+        (status = 200, description = "Rendered label as PDF", content_type = "application/pdf", body = Vec<u8>),
+        (status = 200, description = "Rendered label as ZPL", content_type = "application/zpl", body = String)
+    )
+)]
+async fn get_package_label(
+    Path((id, package_number)): Path<(Uuid, i32)>,
+    Query(query): Query<LabelQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery: DeliveryEntity = deliveries::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery")?
+        .ok_or(AppError::NotFound)?;
+
+    let packages: Vec<DeliveryPackageEntity> = delivery_packages::table
+        .filter(delivery_packages::delivery_id.eq(delivery.id))
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery packages")?;
+
+    let package = packages
+        .iter()
+        .find(|package| package.package_number == package_number)
+        .ok_or(AppError::NotFound)?;
+
+    // Orders consolidated into the delivery from now on would make the printed count wrong.
+    if package.labelled_at.is_none() {
+        diesel::update(delivery_packages::table.find(package.id))
+            .filter(delivery_packages::labelled_at.is_null())
+            .set(delivery_packages::labelled_at.eq(Utc::now()))
+            .execute(conn)
+            .await
+            .context("Failed to record label")?;
+    }
+
+    let label = PackageLabel::new(&delivery, package, packages.len());
+    let file_name = format!("label-{}-{}", delivery.id, package.package_number);
+
+    match query.format.as_deref().unwrap_or("pdf") {
+        "pdf" => Ok((
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"{file_name}.pdf\""),
+                ),
+            ],
+            label.to_pdf(),
+        )
+            .into_response()),
+        "zpl" => Ok((
+            [
+                (header::CONTENT_TYPE, "application/zpl".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{file_name}.zpl\""),
+                ),
+            ],
+            label.to_zpl(),
+        )
+            .into_response()),
+        _ => Err(AppError::BadRequest("Allowed formats are: pdf, zpl".into())),
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        CreateDeliveryLegEntity, CreateDeliveryLogEntity, DeliveryEntity, DeliveryLegEntity,
+        LocationEntity,
+    },
+    schema::{couriers, deliveries, delivery_legs, delivery_logs, locations},
+    services,
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/deliveries",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_delivery_legs))
+            .routes(utoipa_axum::routes!(plan_delivery_legs))
+            .routes(utoipa_axum::routes!(assign_leg_courier)),
+    )
+}
+
+/// Fetch the legs of a delivery, in order.
+#[utoipa::path(
+    get,
+    path = "/{id}/legs",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch legs for")
+    ),
+    responses(
+        (status = 200, description = "Fetched delivery legs successfully", body = StdResponse<Vec<DeliveryLegEntity>, String>)
+    )
+)]
+async fn get_delivery_legs(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let legs = services::legs::get_legs(conn, id).await?;
+
+    Ok(StdResponse {
+        data: Some(legs),
+        message: Some("Get delivery legs successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PlanLegReq {
+    courier_id: Option<i32>,
+    origin_location_id: i32,
+    /// Hub the leg ends at. `null` for the last leg, which ends at the recipient.
+    destination_location_id: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PlanLegsReq {
+    /// Legs in travel order. An empty list sends the delivery straight to the recipient.
+    legs: Vec<PlanLegReq>,
+}
+
+/// Route a delivery through hubs, replacing its legs. Only possible before the first leg
+/// has started.
+#[utoipa::path(
+    put,
+    path = "/{id}/legs",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to route")
+    ),
+    request_body = PlanLegsReq,
+    responses(
+        (status = 200, description = "Planned delivery legs successfully", body = StdResponse<Vec<DeliveryLegEntity>, String>)
+    )
+)]
+async fn plan_delivery_legs(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<PlanLegsReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    for (i, leg) in body.legs.iter().enumerate() {
+        let is_last = i + 1 == body.legs.len();
+        if leg.destination_location_id.is_none() != is_last {
+            return Err(AppError::BadRequest(
+                "Only the last leg ends at the recipient".into(),
+            ));
+        }
+        if let Some(previous) = i.checked_sub(1).map(|previous| &body.legs[previous])
+            && previous.destination_location_id != Some(leg.origin_location_id)
+        {
+            return Err(AppError::BadRequest(format!(
+                "Leg {} must start where leg {} ends",
+                i + 1,
+                i
+            )));
+        }
+    }
+
+    let legs = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                if delivery.status != "PREPARING" {
+                    return Err(AppError::BadRequest(format!(
+                        "Delivery is {}, legs can only be planned while PREPARING",
+                        delivery.status
+                    )));
+                }
+                let current = services::legs::get_legs(conn, id).await?;
+                if current.iter().any(|leg| leg.status != "PENDING") {
+                    return Err(AppError::BadRequest(
+                        "Delivery has already started its first leg".into(),
+                    ));
+                }
+                if let (Some(origin_id), Some(first)) =
+                    (delivery.origin_location_id, body.legs.as_slice().first())
+                    && first.origin_location_id != origin_id
+                {
+                    return Err(AppError::BadRequest(format!(
+                        "The first leg must start at the delivery's origin {origin_id}"
+                    )));
+                }
+
+                let hub_ids: Vec<i32> = body
+                    .legs
+                    .iter()
+                    .filter_map(|leg| leg.destination_location_id)
+                    .collect();
+                let hubs: Vec<LocationEntity> = locations::table
+                    .filter(locations::id.eq_any(&hub_ids))
+                    .get_results(conn)
+                    .await
+                    .context("Failed to get hubs")?;
+                for hub_id in &hub_ids {
+                    match hubs.iter().find(|hub| hub.id == *hub_id) {
+                        Some(hub) if hub.location_type == "HUB" => {}
+                        Some(_) => {
+                            return Err(AppError::BadRequest(format!(
+                                "Location {hub_id} is not a hub"
+                            )));
+                        }
+                        None => return Err(AppError::NotFound),
+                    }
+                }
+                if let Some(first) = body.legs.as_slice().first() {
+                    locations::table
+                        .find(first.origin_location_id)
+                        .select(locations::id)
+                        .get_result::<i32>(conn)
+                        .await
+                        .optional()
+                        .context("Failed to get origin location")?
+                        .ok_or(AppError::NotFound)?;
+                }
+
+                let courier_ids: Vec<i32> =
+                    body.legs.iter().filter_map(|leg| leg.courier_id).collect();
+                let known_couriers: Vec<i32> = couriers::table
+                    .filter(couriers::id.eq_any(&courier_ids))
+                    .select(couriers::id)
+                    .get_results(conn)
+                    .await
+                    .context("Failed to get couriers")?;
+                if let Some(unknown) = courier_ids
+                    .iter()
+                    .find(|courier_id| !known_couriers.contains(courier_id))
+                {
+                    return Err(AppError::BadRequest(format!("Unknown courier {unknown}")));
+                }
+
+                diesel::delete(delivery_legs::table.filter(delivery_legs::delivery_id.eq(id)))
+                    .execute(conn)
+                    .await
+                    .context("Failed to clear delivery legs")?;
+
+                let leg_count = body.legs.len();
+                let legs: Vec<DeliveryLegEntity> = if body.legs.is_empty() {
+                    Vec::new()
+                } else {
+                    diesel::insert_into(delivery_legs::table)
+                        .values(
+                            body.legs
+                                .into_iter()
+                                .zip(1..)
+                                .map(|(leg, sequence)| CreateDeliveryLegEntity {
+                                    delivery_id: id,
+                                    sequence,
+                                    courier_id: leg.courier_id,
+                                    origin_location_id: Some(leg.origin_location_id),
+                                    destination_location_id: leg.destination_location_id,
+                                })
+                                .collect::<Vec<_>>(),
+                        )
+                        .returning(DeliveryLegEntity::as_returning())
+                        .get_results(conn)
+                        .await
+                        .context("Failed to create delivery legs")?
+                };
+
+                diesel::insert_into(delivery_logs::table)
+                    .values(CreateDeliveryLogEntity {
+                        delivery_id: id,
+                        description: match leg_count {
+                            0 => "Routed straight to the recipient".into(),
+                            count => format!("Routed through hubs in {count} legs"),
+                        },
+                        status: delivery.status,
+                    })
+                    .execute(conn)
+                    .await
+                    .context("Failed to create delivery log")?;
+
+                Ok::<_, AppError>(legs)
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(legs),
+        message: Some("Planned delivery legs successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AssignLegCourierReq {
+    /// `null` unassigns the leg.
+    courier_id: Option<i32>,
+}
+
+/// Assign the courier of a leg that has not started yet.
+#[utoipa::path(
+    patch,
+    path = "/{id}/legs/{leg_id}/courier",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery the leg belongs to"),
+        ("leg_id" = Uuid, Path, description = "Leg to assign")
+    ),
+    request_body = AssignLegCourierReq,
+    responses(
+        (status = 200, description = "Assigned leg courier successfully", body = StdResponse<DeliveryLegEntity, String>)
+    )
+)]
+async fn assign_leg_courier(
+    Path((id, leg_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    Json(body): Json<AssignLegCourierReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    if let Some(courier_id) = body.courier_id {
+        couriers::table
+            .find(courier_id)
+            .select(couriers::id)
+            .get_result::<i32>(conn)
+            .await
+            .optional()
+            .context("Failed to get courier")?
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown courier {courier_id}")))?;
+    }
+
+    let leg: DeliveryLegEntity = diesel::update(
+        delivery_legs::table
+            .find(leg_id)
+            .filter(delivery_legs::delivery_id.eq(id)),
+    )
+    .filter(delivery_legs::status.eq("PENDING"))
+    .set(delivery_legs::courier_id.eq(body.courier_id))
+    .returning(DeliveryLegEntity::as_returning())
+    .get_result(conn)
+    .await
+    .optional()
+    .context("Failed to assign leg courier")?
+    .ok_or_else(|| AppError::BadRequest("Leg does not exist or has already started".into()))?;
+
+    Ok(StdResponse {
+        data: Some(leg),
+        message: Some("Assigned leg courier successfully"),
+    })
+}
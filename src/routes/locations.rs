@@ -0,0 +1,449 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::{DateTime, NaiveTime, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    config,
+    models::{
+        CourierEntity, CreateLocationEntity, CreateLocationOpeningHoursEntity, LocationEntity,
+        LocationOpeningHoursEntity,
+    },
+    schema::{couriers, location_opening_hours, locations},
+    services::{
+        self,
+        calendar::Calendar,
+        locations::{LOCATION_TYPES, OpeningSchedule, Slot},
+        packages::ManifestSummary,
+    },
+};
+
+/// Opening days included in a quote when the request does not say.
+const DEFAULT_QUOTE_DAYS: usize = 3;
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/locations",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_locations))
+            .routes(utoipa_axum::routes!(create_location))
+            .routes(utoipa_axum::routes!(get_location))
+            .routes(utoipa_axum::routes!(update_location))
+            .routes(utoipa_axum::routes!(get_quote)),
+    )
+}
+
+/// Fetch all pharmacies, hubs and labs.
+#[utoipa::path(
+    get,
+    path = "/",
+    tags = ["Locations"],
+    responses(
+        (status = 200, description = "List all locations", body = StdResponse<Vec<LocationEntity>, String>)
+    )
+)]
+async fn get_locations(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let locations: Vec<LocationEntity> = locations::table
+        .order_by(locations::id.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get locations")?;
+
+    Ok(StdResponse {
+        data: Some(locations),
+        message: Some("Get locations successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct OpeningHoursReq {
+    /// ISO weekday, 1 being Monday.
+    weekday: i16,
+    opens_at: NaiveTime,
+    closes_at: NaiveTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateLocationReq {
+    name: String,
+    /// One of `PHARMACY`, `HUB`, `LAB`.
+    location_type: String,
+    address: String,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// IANA time zone of the opening hours. Defaults to `Asia/Bangkok`.
+    #[serde(default = "default_timezone")]
+    timezone: String,
+    /// Local time after which orders ship on the next opening day.
+    cutoff_time: Option<NaiveTime>,
+    is_active: bool,
+    /// Replaces the location's opening hours. Closed on days without any.
+    opening_hours: Vec<OpeningHoursReq>,
+}
+
+fn default_timezone() -> String {
+    "Asia/Bangkok".into()
+}
+
+impl CreateLocationReq {
+    fn into_entity(self) -> Result<(CreateLocationEntity, Vec<OpeningHoursReq>), AppError> {
+        if !LOCATION_TYPES.contains(&self.location_type.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "Allowed location types are: {}",
+                LOCATION_TYPES.join(", ")
+            )));
+        }
+        services::locations::parse_timezone(&self.timezone)?;
+        for hours in &self.opening_hours {
+            if !(1..=7).contains(&hours.weekday) {
+                return Err(AppError::BadRequest(
+                    "weekday must be between 1 (Monday) and 7 (Sunday)".into(),
+                ));
+            }
+            if hours.opens_at >= hours.closes_at {
+                return Err(AppError::BadRequest(
+                    "opens_at must be before closes_at".into(),
+                ));
+            }
+        }
+        Ok((
+            CreateLocationEntity {
+                name: self.name,
+                location_type: self.location_type,
+                address: self.address,
+                latitude: self.latitude,
+                longitude: self.longitude,
+                timezone: self.timezone,
+                cutoff_time: self.cutoff_time,
+                is_active: self.is_active,
+            },
+            self.opening_hours,
+        ))
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct LocationWithHours {
+    location: LocationEntity,
+    opening_hours: Vec<LocationOpeningHoursEntity>,
+}
+
+/// Replaces the opening hours of `location_id`. Must be called inside a transaction.
+async fn replace_opening_hours(
+    conn: &mut AsyncPgConnection,
+    location_id: i32,
+    opening_hours: Vec<OpeningHoursReq>,
+) -> Result<Vec<LocationOpeningHoursEntity>, AppError> {
+    diesel::delete(
+        location_opening_hours::table.filter(location_opening_hours::location_id.eq(location_id)),
+    )
+    .execute(conn)
+    .await
+    .context("Failed to clear opening hours")?;
+
+    let opening_hours: Vec<LocationOpeningHoursEntity> =
+        diesel::insert_into(location_opening_hours::table)
+            .values(
+                opening_hours
+                    .into_iter()
+                    .map(|hours| CreateLocationOpeningHoursEntity {
+                        location_id,
+                        weekday: hours.weekday,
+                        opens_at: hours.opens_at,
+                        closes_at: hours.closes_at,
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .returning(LocationOpeningHoursEntity::as_returning())
+            .get_results(conn)
+            .await
+            .context("Failed to create opening hours")?;
+    Ok(opening_hours)
+}
+
+/// Register a pharmacy, hub or lab with its opening hours.
+#[utoipa::path(
+    post,
+    path = "/",
+    tags = ["Locations"],
+    request_body = CreateLocationReq,
+    responses(
+        (status = 200, description = "Created location successfully", body = StdResponse<LocationWithHours, String>)
+    )
+)]
+async fn create_location(
+    State(state): State<AppState>,
+    Json(body): Json<CreateLocationReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let (new_location, opening_hours) = body.into_entity()?;
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let location = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let location: LocationEntity = diesel::insert_into(locations::table)
+                    .values(new_location)
+                    .returning(LocationEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .context("Failed to create location")?;
+                let opening_hours = replace_opening_hours(conn, location.id, opening_hours).await?;
+                Ok::<_, AppError>(LocationWithHours {
+                    location,
+                    opening_hours,
+                })
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(location),
+        message: Some("Created location successfully"),
+    })
+}
+
+/// Fetch a location with its opening hours.
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tags = ["Locations"],
+    params(
+        ("id" = i32, Path, description = "Location ID to fetch")
+    ),
+    responses(
+        (status = 200, description = "Fetched location successfully", body = StdResponse<LocationWithHours, String>)
+    )
+)]
+async fn get_location(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let (location, opening_hours) = services::locations::get_location(conn, id).await?;
+
+    Ok(StdResponse {
+        data: Some(LocationWithHours {
+            location,
+            opening_hours,
+        }),
+        message: Some("Get location successfully"),
+    })
+}
+
+/// Update a location's details and replace its opening hours.
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    tags = ["Locations"],
+    params(
+        ("id" = i32, Path, description = "Location ID to update")
+    ),
+    request_body = CreateLocationReq,
+    responses(
+        (status = 200, description = "Updated location successfully", body = StdResponse<LocationWithHours, String>)
+    )
+)]
+async fn update_location(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+    Json(body): Json<CreateLocationReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let (changes, opening_hours) = body.into_entity()?;
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let location = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let location: LocationEntity = diesel::update(locations::table.find(id))
+                    .set(changes)
+                    .returning(LocationEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to update location")?
+                    .ok_or(AppError::NotFound)?;
+                let opening_hours = replace_opening_hours(conn, location.id, opening_hours).await?;
+                Ok::<_, AppError>(LocationWithHours {
+                    location,
+                    opening_hours,
+                })
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(location),
+        message: Some("Updated location successfully"),
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+struct QuoteQuery {
+    /// `STANDARD` (default), `EXPRESS` or `URGENT`.
+    priority: Option<String>,
+    /// When the order would be placed. Defaults to now.
+    ordered_at: Option<DateTime<Utc>>,
+    /// Opening days to offer slots for. Defaults to 3.
+    days: Option<usize>,
+    /// Quote for the packages of this delivery.
+    delivery_id: Option<Uuid>,
+    /// Billable weight of the packages to quote for when there is no delivery yet.
+    billable_weight_grams: Option<i64>,
+    /// Whether any of the packages to quote for must be kept cold.
+    refrigerated: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct QuoteRes {
+    is_open: bool,
+    /// When the order would leave the location, `null` if it does not open soon.
+    ships_at: Option<DateTime<Utc>>,
+    /// Delivery deadline of the priority, counted in working time from `ships_at`.
+    deliver_by: Option<DateTime<Utc>>,
+    billable_weight_grams: i64,
+    requires_refrigeration: bool,
+    /// Whether the vehicle of any active courier can carry the packages.
+    can_carry: bool,
+    /// Delivery slots within opening hours from `ships_at`, regardless of how many
+    /// deliveries are already booked into them. Empty when no courier can carry the packages.
+    slots: Vec<Slot>,
+}
+
+/// Quote when an order from this location would ship and which delivery slots fall within
+/// its opening hours, respecting its cutoff time, holidays and closures. Packages, given by
+/// a delivery or by their billable weight, must fit the vehicle of some active courier;
+/// slots are not checked against what couriers already carry.
+#[utoipa::path(
+    get,
+    path = "/{id}/quote",
+    tags = ["Locations"],
+    params(
+        ("id" = i32, Path, description = "Location the order ships from"),
+        QuoteQuery
+    ),
+    responses(
+        (status = 200, description = "Quoted successfully", body = StdResponse<QuoteRes, String>)
+    )
+)]
+async fn get_quote(
+    Path(id): Path<i32>,
+    Query(query): Query<QuoteQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let priority = query.priority.as_deref().unwrap_or("STANDARD");
+    if services::deliveries::priority_rank(priority).is_none() {
+        return Err(AppError::BadRequest(format!(
+            "Allowed priorities are: {}",
+            services::deliveries::PRIORITIES.join(", ")
+        )));
+    }
+    if query
+        .billable_weight_grams
+        .is_some_and(|weight_grams| weight_grams < 0)
+    {
+        return Err(AppError::BadRequest(
+            "billable_weight_grams must not be negative".into(),
+        ));
+    }
+    if query.delivery_id.is_some()
+        && (query.billable_weight_grams.is_some() || query.refrigerated.is_some())
+    {
+        return Err(AppError::BadRequest(
+            "Quote either for a delivery_id or for billable_weight_grams and refrigerated".into(),
+        ));
+    }
+    let target = config::sla_targets()?.of(priority);
+    let slot_length = config::slot_length()?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let (location, opening_hours) = services::locations::get_location(conn, id).await?;
+    if !location.is_active {
+        return Err(AppError::BadRequest("Location is not active".into()));
+    }
+    let calendar = Calendar::load(conn).await?;
+    let schedule = OpeningSchedule::of(&location, opening_hours, calendar.clone())?;
+
+    let summary = match query.delivery_id {
+        Some(delivery_id) => {
+            let packages = services::packages::get_packages(conn, delivery_id).await?;
+            ManifestSummary::of(packages.iter().map(|package| &package.package))
+        }
+        None => ManifestSummary {
+            billable_weight_grams: query.billable_weight_grams.unwrap_or_default(),
+            requires_refrigeration: query.refrigerated.unwrap_or_default(),
+            ..Default::default()
+        },
+    };
+    let couriers: Vec<CourierEntity> = couriers::table
+        .filter(couriers::is_active.eq(true))
+        .get_results(conn)
+        .await
+        .context("Failed to get couriers")?;
+    let can_carry = couriers
+        .iter()
+        .any(|courier| services::dispatch::fits_vehicle(courier, &summary));
+
+    let ordered_at = query.ordered_at.unwrap_or_else(Utc::now);
+    let ships_at = schedule.ships_at(ordered_at);
+
+    Ok(StdResponse {
+        data: Some(QuoteRes {
+            is_open: schedule.is_open(ordered_at),
+            ships_at,
+            deliver_by: ships_at.map(|ships_at| {
+                calendar.add_working_time(ships_at, target.delivery, schedule.scope())
+            }),
+            billable_weight_grams: summary.billable_weight_grams,
+            requires_refrigeration: summary.requires_refrigeration,
+            can_carry,
+            slots: if can_carry {
+                schedule.slots(
+                    ordered_at,
+                    query.days.unwrap_or(DEFAULT_QUOTE_DAYS),
+                    slot_length,
+                )
+            } else {
+                Vec::new()
+            },
+        }),
+        message: Some("Quoted successfully"),
+    })
+}
@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper, dsl::count_star};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    documents::manifests::{Manifest, ManifestRow},
+    models::{
+        CourierEntity, CreateDeliveryLogEntity, CreateDispatchManifestEntity, DeliveryEntity,
+        DispatchManifestDeliveryEntity, DispatchManifestEntity, LocationEntity,
+    },
+    schema::{
+        couriers, deliveries, delivery_logs, delivery_packages, dispatch_manifest_deliveries,
+        dispatch_manifests, locations,
+    },
+    services,
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/manifests",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_manifests))
+            .routes(utoipa_axum::routes!(create_manifest))
+            .routes(utoipa_axum::routes!(get_manifest))
+            .routes(utoipa_axum::routes!(get_manifest_document))
+            .routes(utoipa_axum::routes!(confirm_manifest)),
+    )
+}
+
+#[derive(Deserialize, IntoParams)]
+struct GetManifestsQuery {
+    courier_id: Option<i32>,
+    origin_location_id: Option<i32>,
+}
+
+/// Fetch generated manifests, newest first.
+#[utoipa::path(
+    get,
+    path = "/",
+    tags = ["Manifests"],
+    params(GetManifestsQuery),
+    responses(
+        (status = 200, description = "List manifests", body = StdResponse<Vec<DispatchManifestEntity>, String>)
+    )
+)]
+async fn get_manifests(
+    Query(query): Query<GetManifestsQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let mut manifests_query = dispatch_manifests::table
+        .order_by(dispatch_manifests::created_at.desc())
+        .into_boxed();
+    if let Some(courier_id) = query.courier_id {
+        manifests_query = manifests_query.filter(dispatch_manifests::courier_id.eq(courier_id));
+    }
+    if let Some(origin_location_id) = query.origin_location_id {
+        manifests_query =
+            manifests_query.filter(dispatch_manifests::origin_location_id.eq(origin_location_id));
+    }
+
+    let manifests: Vec<DispatchManifestEntity> = manifests_query
+        .get_results(conn)
+        .await
+        .context("Failed to get manifests")?;
+
+    Ok(StdResponse {
+        data: Some(manifests),
+        message: Some("Get manifests successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateManifestReq {
+    /// Only include deliveries assigned to this courier.
+    courier_id: Option<i32>,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    /// Only include deliveries leaving this pharmacy, hub or lab. Required without a
+    /// `courier_id`.
+    origin_location_id: Option<i32>,
+    generated_by: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct GetManifestRes {
+    manifest: DispatchManifestEntity,
+    deliveries: Vec<DispatchManifestDeliveryEntity>,
+}
+
+/// Generate a manifest of every delivery assigned within the window that has not been
+/// handed over yet, either to one courier, or to any courier collecting from one origin,
+/// or both.
+#[utoipa::path(
+    post,
+    path = "/",
+    tags = ["Manifests"],
+    request_body = CreateManifestReq,
+    responses(
+        (status = 200, description = "Generated manifest successfully", body = StdResponse<GetManifestRes, String>)
+    )
+)]
+async fn create_manifest(
+    State(state): State<AppState>,
+    Json(body): Json<CreateManifestReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    if body.courier_id.is_none() && body.origin_location_id.is_none() {
+        return Err(AppError::BadRequest(
+            "Give a courier_id, an origin_location_id or both".into(),
+        ));
+    }
+    if body.window_start >= body.window_end {
+        return Err(AppError::BadRequest(
+            "window_start must be before window_end".into(),
+        ));
+    }
+
+    let (manifest, deliveries) = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let courier: Option<CourierEntity> = match body.courier_id {
+                    Some(courier_id) => Some(
+                        couriers::table
+                            .find(courier_id)
+                            .get_result(conn)
+                            .await
+                            .optional()
+                            .context("Failed to get courier")?
+                            .ok_or(AppError::NotFound)?,
+                    ),
+                    None => None,
+                };
+
+                let origin: Option<LocationEntity> = match body.origin_location_id {
+                    Some(origin_id) => Some(
+                        locations::table
+                            .find(origin_id)
+                            .get_result(conn)
+                            .await
+                            .optional()
+                            .context("Failed to get origin location")?
+                            .ok_or(AppError::NotFound)?,
+                    ),
+                    None => None,
+                };
+
+                let mut deliveries_query = deliveries::table
+                    .filter(deliveries::courier_id.is_not_null())
+                    .filter(deliveries::assigned_at.ge(body.window_start))
+                    .filter(deliveries::assigned_at.lt(body.window_end))
+                    .filter(deliveries::handed_over_at.is_null())
+                    .filter(deliveries::status.ne_all(services::deliveries::CLOSED_STATUSES))
+                    .order_by(deliveries::assigned_at.asc())
+                    .into_boxed();
+                if let Some(courier) = &courier {
+                    deliveries_query =
+                        deliveries_query.filter(deliveries::courier_id.eq(courier.id));
+                }
+                if let Some(origin) = &origin {
+                    deliveries_query =
+                        deliveries_query.filter(deliveries::origin_location_id.eq(origin.id));
+                }
+                let manifest_deliveries: Vec<DeliveryEntity> = deliveries_query
+                    .get_results(conn)
+                    .await
+                    .context("Failed to get deliveries")?;
+
+                if manifest_deliveries.is_empty() {
+                    return Err(AppError::BadRequest(
+                        "No deliveries awaiting handover in this window".into(),
+                    ));
+                }
+
+                let courier_names: Vec<String> = match &courier {
+                    Some(courier) => vec![courier.name.clone()],
+                    None => couriers::table
+                        .filter(
+                            couriers::id.eq_any(
+                                manifest_deliveries
+                                    .iter()
+                                    .filter_map(|delivery| delivery.courier_id),
+                            ),
+                        )
+                        .order_by(couriers::name.asc())
+                        .select(couriers::name)
+                        .get_results(conn)
+                        .await
+                        .context("Failed to get couriers")?,
+                };
+
+                let package_counts: HashMap<Uuid, i64> = delivery_packages::table
+                    .filter(
+                        delivery_packages::delivery_id
+                            .eq_any(manifest_deliveries.iter().map(|delivery| delivery.id)),
+                    )
+                    .group_by(delivery_packages::delivery_id)
+                    .select((delivery_packages::delivery_id, count_star()))
+                    .get_results::<(Uuid, i64)>(conn)
+                    .await
+                    .context("Failed to count packages")?
+                    .into_iter()
+                    .collect();
+
+                let rows: Vec<ManifestRow> = manifest_deliveries
+                    .iter()
+                    .map(|delivery| ManifestRow {
+                        delivery_id: delivery.id,
+                        order_id: delivery.order_id,
+                        recipient_name: delivery
+                            .address_field("recipient_name")
+                            .unwrap_or_default()
+                            .into(),
+                        city: delivery.address_field("city").unwrap_or_default().into(),
+                        recipient_name_latin: delivery
+                            .address_field("recipient_name_latin")
+                            .map(Into::into),
+                        city_latin: delivery.address_field("city_latin").map(Into::into),
+                        package_count: package_counts.get(&delivery.id).copied().unwrap_or(0)
+                            as i32,
+                    })
+                    .collect();
+
+                // Documents quote the manifest ID, so they are rendered once the row exists.
+                let manifest: DispatchManifestEntity =
+                    diesel::insert_into(dispatch_manifests::table)
+                        .values(CreateDispatchManifestEntity {
+                            courier_id: courier.as_ref().map(|courier| courier.id),
+                            window_start: body.window_start,
+                            window_end: body.window_end,
+                            status: "GENERATED".into(),
+                            delivery_count: rows.len() as i32,
+                            package_count: rows.iter().map(|row| row.package_count).sum(),
+                            csv: String::new(),
+                            pdf: Vec::new(),
+                            generated_by: body.generated_by,
+                            origin_location_id: origin.as_ref().map(|origin| origin.id),
+                        })
+                        .returning(DispatchManifestEntity::as_returning())
+                        .get_result(conn)
+                        .await
+                        .context("Failed to create manifest")?;
+
+                let document = Manifest {
+                    id: manifest.id,
+                    courier_names: &courier_names,
+                    origin_name: origin.as_ref().map(|origin| origin.name.as_str()),
+                    window_start: manifest.window_start,
+                    window_end: manifest.window_end,
+                    generated_by: &manifest.generated_by,
+                    generated_at: manifest.created_at,
+                    rows: &rows,
+                };
+                let manifest: DispatchManifestEntity =
+                    diesel::update(dispatch_manifests::table.find(manifest.id))
+                        .set((
+                            dispatch_manifests::csv.eq(document.to_csv()),
+                            dispatch_manifests::pdf.eq(document.to_pdf()),
+                        ))
+                        .returning(DispatchManifestEntity::as_returning())
+                        .get_result(conn)
+                        .await
+                        .context("Failed to store manifest documents")?;
+
+                let deliveries: Vec<DispatchManifestDeliveryEntity> =
+                    diesel::insert_into(dispatch_manifest_deliveries::table)
+                        .values(
+                            rows.iter()
+                                .map(|row| DispatchManifestDeliveryEntity {
+                                    manifest_id: manifest.id,
+                                    delivery_id: row.delivery_id,
+                                    package_count: row.package_count,
+                                })
+                                .collect::<Vec<_>>(),
+                        )
+                        .returning(DispatchManifestDeliveryEntity::as_returning())
+                        .get_results(conn)
+                        .await
+                        .context("Failed to link manifest deliveries")?;
+
+                Ok::<_, AppError>((manifest, deliveries))
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(GetManifestRes {
+            manifest,
+            deliveries,
+        }),
+        message: Some("Generated manifest successfully"),
+    })
+}
+
+/// Fetch a manifest and the deliveries on it.
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tags = ["Manifests"],
+    params(
+        ("id" = Uuid, Path, description = "Manifest ID to fetch")
+    ),
+    responses(
+        (status = 200, description = "Fetched manifest successfully", body = StdResponse<GetManifestRes, String>)
+    )
+)]
+async fn get_manifest(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let manifest: DispatchManifestEntity = dispatch_manifests::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get manifest")?
+        .ok_or(AppError::NotFound)?;
+
+    let deliveries: Vec<DispatchManifestDeliveryEntity> = dispatch_manifest_deliveries::table
+        .filter(dispatch_manifest_deliveries::manifest_id.eq(manifest.id))
+        .get_results(conn)
+        .await
+        .context("Failed to get manifest deliveries")?;
+
+    Ok(StdResponse {
+        data: Some(GetManifestRes {
+            manifest,
+            deliveries,
+        }),
+        message: Some("Get manifest successfully"),
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ManifestDocumentQuery {
+    /// `pdf` (default) or `csv`.
+    format: Option<String>,
+}
+
+/// Download the stored manifest document.
+#[utoipa::path(
+    get,
+    path = "/{id}/document",
+    tags = ["Manifests"],
+    params(
+        ("id" = Uuid, Path, description = "Manifest ID to download"),
+        ManifestDocumentQuery
+    ),
+    responses(
+        (status = 200, description = "Manifest as PDF", content_type = "application/pdf", body = Vec<u8>),
+        (status = 200, description = "Manifest as CSV", content_type = "text/csv", body = String)
+    )
+)]
+async fn get_manifest_document(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ManifestDocumentQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let manifest: DispatchManifestEntity = dispatch_manifests::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get manifest")?
+        .ok_or(AppError::NotFound)?;
+
+    match query.format.as_deref().unwrap_or("pdf") {
+        "pdf" => Ok((
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"manifest-{id}.pdf\""),
+                ),
+            ],
+            manifest.pdf,
+        )
+            .into_response()),
+        "csv" => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"manifest-{id}.csv\""),
+                ),
+            ],
+            manifest.csv,
+        )
+            .into_response()),
+        _ => Err(AppError::BadRequest("Allowed formats are: pdf, csv".into())),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ConfirmManifestReq {
+    handed_over_by: String,
+    received_by: String,
+}
+
+/// Confirm the signed manifest, marking its deliveries as handed over to the courier.
+#[utoipa::path(
+    post,
+    path = "/{id}/confirm",
+    tags = ["Manifests"],
+    params(
+        ("id" = Uuid, Path, description = "Manifest ID to confirm")
+    ),
+    request_body = ConfirmManifestReq,
+    responses(
+        (status = 200, description = "Confirmed manifest successfully", body = StdResponse<DispatchManifestEntity, String>)
+    )
+)]
+async fn confirm_manifest(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<ConfirmManifestReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let manifest = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let manifest: DispatchManifestEntity = dispatch_manifests::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get manifest")?
+                    .ok_or(AppError::NotFound)?;
+
+                if manifest.status != "GENERATED" {
+                    return Err(AppError::BadRequest(
+                        "Manifest has already been confirmed".into(),
+                    ));
+                }
+
+                let now = Utc::now();
+                let manifest: DispatchManifestEntity =
+                    diesel::update(dispatch_manifests::table.find(manifest.id))
+                        .set((
+                            dispatch_manifests::status.eq("CONFIRMED"),
+                            dispatch_manifests::handed_over_by.eq(&body.handed_over_by),
+                            dispatch_manifests::received_by.eq(&body.received_by),
+                            dispatch_manifests::confirmed_at.eq(now),
+                        ))
+                        .returning(DispatchManifestEntity::as_returning())
+                        .get_result(conn)
+                        .await
+                        .context("Failed to confirm manifest")?;
+
+                // Deliveries already handed over on another manifest are left as they are.
+                let handed_over: Vec<DeliveryEntity> = diesel::update(
+                    deliveries::table
+                        .filter(
+                            deliveries::id.eq_any(
+                                dispatch_manifest_deliveries::table
+                                    .filter(
+                                        dispatch_manifest_deliveries::manifest_id.eq(manifest.id),
+                                    )
+                                    .select(dispatch_manifest_deliveries::delivery_id),
+                            ),
+                        )
+                        .filter(deliveries::handed_over_at.is_null()),
+                )
+                .set(deliveries::handed_over_at.eq(now))
+                .returning(DeliveryEntity::as_returning())
+                .get_results(conn)
+                .await
+                .context("Failed to mark deliveries as handed over")?;
+
+                for delivery in handed_over {
+                    let description = format!(
+                        "Handed over by {} to {} on manifest {}",
+                        body.handed_over_by, body.received_by, manifest.id
+                    );
+                    if services::deliveries::moves_forward(
+                        &delivery.fulfilment_mode,
+                        &delivery.status,
+                        "PICKED_UP",
+                    ) {
+                        services::deliveries::transition(
+                            conn,
+                            delivery.id,
+                            "PICKED_UP".into(),
+                            description,
+                        )
+                        .await?;
+                    } else {
+                        diesel::insert_into(delivery_logs::table)
+                            .values(CreateDeliveryLogEntity {
+                                delivery_id: delivery.id,
+                                description,
+                                status: delivery.status,
+                            })
+                            .execute(conn)
+                            .await
+                            .context("Failed to create delivery log")?;
+                    }
+                }
+
+                Ok::<_, AppError>(manifest)
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(manifest),
+        message: Some("Confirmed manifest successfully"),
+    })
+}
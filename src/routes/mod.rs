@@ -1,3 +1,22 @@
+pub mod calendar;
+pub mod carrier_imports;
+pub mod carrier_webhooks;
+pub mod carriers;
+pub mod cold_chain;
+pub mod couriers;
+pub mod custody;
 pub mod deliveries;
 pub mod delivery_addresses;
+pub mod dispatch;
+pub mod id_verifications;
+pub mod incidents;
+pub mod labels;
+pub mod legs;
+pub mod locations;
+pub mod manifests;
 pub mod patients;
+pub mod pickups;
+pub mod ratings;
+pub mod scans;
+pub mod sweeper;
+pub mod webhooks;
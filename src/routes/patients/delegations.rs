@@ -0,0 +1,304 @@
+use anyhow::Context;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper,
+    result::DatabaseErrorKind,
+};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+    middleware,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{CreatePatientDelegationEntity, PatientDelegationEntity, PatientProxyAuditLogEntity},
+    schema::{patient_delegations, patient_proxy_audit_logs},
+    services::delegations,
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/patients/delegations",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_my_delegations))
+            .routes(utoipa_axum::routes!(grant_delegation))
+            .routes(utoipa_axum::routes!(get_received_delegations))
+            .routes(utoipa_axum::routes!(revoke_delegation))
+            .routes(utoipa_axum::routes!(get_proxy_audit_logs))
+            .route_layer(axum::middleware::from_fn(
+                middleware::patients_authorization,
+            )),
+    )
+}
+
+/// Lets a delegate act for the patient who granted them access.
+#[derive(Deserialize, IntoParams)]
+pub(crate) struct OnBehalfOfQuery {
+    /// Patient to act for. Defaults to the authenticated patient.
+    pub on_behalf_of: Option<i32>,
+}
+
+/// Fetch the delegations the authenticated patient has granted, including revoked ones.
+#[utoipa::path(
+    get,
+    path = "/",
+    tags = ["Delegations"],
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Fetched granted delegations successfully", body = StdResponse<Vec<PatientDelegationEntity>, String>)
+    )
+)]
+async fn get_my_delegations(
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delegations: Vec<PatientDelegationEntity> = patient_delegations::table
+        .filter(patient_delegations::patient_id.eq(patient_id))
+        .order_by(patient_delegations::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delegations")?;
+
+    Ok(StdResponse {
+        data: Some(delegations),
+        message: Some("Get my delegations successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct GrantDelegationReq {
+    /// User to grant access to.
+    delegate_id: i32,
+    /// `READ` or `MANAGE`.
+    access_level: String,
+}
+
+/// Grant another user access to the authenticated patient's addresses and deliveries.
+/// Granting a user who already has access changes their access level.
+#[utoipa::path(
+    post,
+    path = "/",
+    tags = ["Delegations"],
+    security(("bearerAuth" = [])),
+    request_body = GrantDelegationReq,
+    responses(
+        (status = 200, description = "Granted delegation successfully", body = StdResponse<PatientDelegationEntity, String>)
+    )
+)]
+async fn grant_delegation(
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+    Json(body): Json<GrantDelegationReq>,
+) -> Result<impl IntoResponse, AppError> {
+    if !delegations::ACCESS_LEVELS.contains(&body.access_level.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown access level {}. Allowed levels are: {}",
+            body.access_level,
+            delegations::ACCESS_LEVELS.join(", ")
+        )));
+    }
+    if body.delegate_id == patient_id {
+        return Err(AppError::BadRequest(
+            "Patients cannot delegate to themselves".into(),
+        ));
+    }
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delegation = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let updated: Option<PatientDelegationEntity> = diesel::update(
+                    patient_delegations::table
+                        .filter(patient_delegations::patient_id.eq(patient_id))
+                        .filter(patient_delegations::delegate_id.eq(body.delegate_id))
+                        .filter(patient_delegations::revoked_at.is_null()),
+                )
+                .set(patient_delegations::access_level.eq(&body.access_level))
+                .returning(PatientDelegationEntity::as_returning())
+                .get_result(conn)
+                .await
+                .optional()
+                .context("Failed to update delegation")?;
+                if let Some(updated) = updated {
+                    delegations::audit_delegation(conn, &updated, "UPDATE").await?;
+                    return Ok::<_, AppError>(updated);
+                }
+
+                let delegation = diesel::insert_into(patient_delegations::table)
+                    .values(CreatePatientDelegationEntity {
+                        patient_id,
+                        delegate_id: body.delegate_id,
+                        access_level: body.access_level,
+                    })
+                    .returning(PatientDelegationEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .map_err(|err| match err {
+                        // A concurrent request granted the same delegate first.
+                        diesel::result::Error::DatabaseError(
+                            DatabaseErrorKind::UniqueViolation,
+                            _,
+                        ) => AppError::BadRequest(
+                            "Access is already being granted to this user".into(),
+                        ),
+                        err => anyhow::Error::new(err)
+                            .context("Failed to create delegation")
+                            .into(),
+                    })?;
+                delegations::audit_delegation(conn, &delegation, "GRANT").await?;
+                Ok(delegation)
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(delegation),
+        message: Some("Granted delegation successfully"),
+    })
+}
+
+/// Fetch the active delegations granted to the authenticated user.
+#[utoipa::path(
+    get,
+    path = "/received",
+    tags = ["Delegations"],
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Fetched received delegations successfully", body = StdResponse<Vec<PatientDelegationEntity>, String>)
+    )
+)]
+async fn get_received_delegations(
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delegations: Vec<PatientDelegationEntity> = patient_delegations::table
+        .filter(patient_delegations::delegate_id.eq(patient_id))
+        .filter(patient_delegations::revoked_at.is_null())
+        .order_by(patient_delegations::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delegations")?;
+
+    Ok(StdResponse {
+        data: Some(delegations),
+        message: Some("Get received delegations successfully"),
+    })
+}
+
+/// Revoke a delegation. Either the granting patient or the delegate may revoke it.
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    tags = ["Delegations"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delegation ID to revoke")
+    ),
+    responses(
+        (status = 200, description = "Revoked delegation successfully", body = StdResponse<PatientDelegationEntity, String>)
+    )
+)]
+async fn revoke_delegation(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delegation = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let delegation: PatientDelegationEntity = diesel::update(
+                    patient_delegations::table
+                        .find(id)
+                        .filter(
+                            patient_delegations::patient_id
+                                .eq(patient_id)
+                                .or(patient_delegations::delegate_id.eq(patient_id)),
+                        )
+                        .filter(patient_delegations::revoked_at.is_null()),
+                )
+                .set(patient_delegations::revoked_at.eq(Utc::now()))
+                .returning(PatientDelegationEntity::as_returning())
+                .get_result(conn)
+                .await
+                .optional()
+                .context("Failed to revoke delegation")?
+                .ok_or(AppError::NotFound)?;
+                delegations::audit_delegation(conn, &delegation, "REVOKE").await?;
+                Ok::<_, AppError>(delegation)
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(delegation),
+        message: Some("Revoked delegation successfully"),
+    })
+}
+
+/// Fetch everything delegates have done on the authenticated patient's behalf, along with
+/// delegations being granted and revoked, newest first.
+#[utoipa::path(
+    get,
+    path = "/audit-logs",
+    tags = ["Delegations"],
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, description = "Fetched proxy audit logs successfully", body = StdResponse<Vec<PatientProxyAuditLogEntity>, String>)
+    )
+)]
+async fn get_proxy_audit_logs(
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let audit_logs: Vec<PatientProxyAuditLogEntity> = patient_proxy_audit_logs::table
+        .filter(patient_proxy_audit_logs::patient_id.eq(patient_id))
+        .order_by(patient_proxy_audit_logs::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get proxy audit logs")?;
+
+    Ok(StdResponse {
+        data: Some(audit_logs),
+        message: Some("Get proxy audit logs successfully"),
+    })
+}
@@ -0,0 +1,128 @@
+use anyhow::Context;
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+    middleware,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{DeliveryEntity, DeliveryLogEntity},
+    routes::patients::delegations::OnBehalfOfQuery,
+    schema::{deliveries, delivery_logs},
+    services::{self, delegations},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/patients/deliveries",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_my_deliveries))
+            .routes(utoipa_axum::routes!(get_my_delivery))
+            .route_layer(axum::middleware::from_fn(
+                middleware::patients_authorization,
+            )),
+    )
+}
+
+/// Fetch all deliveries of the authenticated patient, or of a patient who delegated access
+/// to them, newest first.
+#[utoipa::path(
+    get,
+    path = "/my-deliveries",
+    tags = ["Deliveries"],
+    security(("bearerAuth" = [])),
+    params(OnBehalfOfQuery),
+    responses(
+        (status = 200, description = "Fetched patient's deliveries successfully", body = StdResponse<Vec<DeliveryEntity>, String>)
+    )
+)]
+async fn get_my_deliveries(
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let principal = delegations::act_as(conn, patient_id, query.on_behalf_of, false).await?;
+    let deliveries: Vec<DeliveryEntity> = deliveries::table
+        .filter(deliveries::patient_id.eq(principal.patient_id))
+        .order_by(deliveries::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get my deliveries")?;
+    delegations::audit(conn, &principal, "LIST", "DELIVERY", None).await?;
+
+    Ok(StdResponse {
+        data: Some(deliveries),
+        message: Some("Get my deliveries successfully"),
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct GetMyDeliveryRes {
+    delivery: DeliveryEntity,
+    delivery_logs: Vec<DeliveryLogEntity>,
+}
+
+/// Fetch a delivery of the authenticated patient, or of a patient who delegated access to
+/// them, with its logs.
+#[utoipa::path(
+    get,
+    path = "/my-deliveries/{id}",
+    tags = ["Deliveries"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch"),
+        OnBehalfOfQuery
+    ),
+    responses(
+        (status = 200, description = "Fetched delivery successfully", body = StdResponse<GetMyDeliveryRes, String>)
+    )
+)]
+async fn get_my_delivery(
+    Path(id): Path<Uuid>,
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let principal = delegations::act_as(conn, patient_id, query.on_behalf_of, false).await?;
+    let delivery =
+        services::deliveries::get_patient_delivery(conn, principal.patient_id, id).await?;
+    let delivery_logs: Vec<DeliveryLogEntity> = delivery_logs::table
+        .filter(delivery_logs::delivery_id.eq(delivery.id))
+        .order_by(delivery_logs::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery logs")?;
+    delegations::audit(conn, &principal, "VIEW", "DELIVERY", Some(id.to_string())).await?;
+
+    Ok(StdResponse {
+        data: Some(GetMyDeliveryRes {
+            delivery,
+            delivery_logs,
+        }),
+        message: Some("Get my delivery successfully"),
+    })
+}
@@ -1,13 +1,13 @@
 use anyhow::Context;
 use axum::{
     Extension, Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
     routing,
 };
 
-use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
-use diesel_async::RunQueryDsl;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, RunQueryDsl};
 use medbook_core::{
     app_error::{AppError, StdResponse},
     app_state::AppState,
@@ -18,8 +18,12 @@ use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
 
 use crate::{
+    config,
+    documents::pdf,
     models::{CreateDeliveryAddressEntity, DeliveryAddressEntity},
+    routes::patients::delegations::OnBehalfOfQuery,
     schema::delivery_addresses,
+    services::{delegations, preferences},
 };
 
 /// Defines all patient-facing product routes (CRUD operations + authorization).
@@ -56,17 +60,20 @@ pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
     )
 }
 
-/// Fetch all delivery addresses belonging to the authenticated patient.
+/// Fetch all delivery addresses belonging to the authenticated patient, or to a patient
+/// who delegated access to them.
 #[utoipa::path(
     get,
     path = "/my-delivery-addresses",
     tags = ["Delivery Addresses"],
     security(("bearerAuth" = [])),
+    params(OnBehalfOfQuery),
     responses(
         (status = 200, description = "Fetched patient's delivery addresses successfully", body = StdResponse<Vec<DeliveryAddressEntity>, String>)
     )
 )]
 async fn get_my_delivery_addresses(
+    Query(query): Query<OnBehalfOfQuery>,
     State(state): State<AppState>,
     Extension(patient_id): Extension<i32>,
 ) -> Result<impl IntoResponse, AppError> {
@@ -76,11 +83,13 @@ async fn get_my_delivery_addresses(
         .await
         .context("Failed to obtain a DB connection pool")?;
 
+    let principal = delegations::act_as(conn, patient_id, query.on_behalf_of, false).await?;
     let delivery_addresses: Vec<DeliveryAddressEntity> = delivery_addresses::table
-        .filter(delivery_addresses::patient_id.eq(patient_id))
+        .filter(delivery_addresses::patient_id.eq(principal.patient_id))
         .get_results(conn)
         .await
         .context("Failed to get my delivery addresses")?;
+    delegations::audit(conn, &principal, "LIST", "DELIVERY_ADDRESS", None).await?;
 
     Ok(StdResponse {
         data: Some(delivery_addresses),
@@ -97,20 +106,105 @@ struct CreateDeliveryAddressReq {
     state: String,
     postal_code: String,
     country: String,
+    /// Free-text instructions for the courier, e.g. "leave with the building guard".
+    delivery_instructions: Option<String>,
+    /// Building or gate access code. Stored encrypted and never returned; the assigned
+    /// courier is only told that there is one. When updating, omit it to keep the current
+    /// code or send an empty string to remove it.
+    access_code: Option<String>,
+    /// `CALL`, `SMS` or `NONE`.
+    preferred_contact_method: Option<String>,
+    #[serde(default)]
+    contactless_consent: bool,
+    /// Latin-script spellings printed on labels and manifests, which cannot show Thai
+    /// script. Give them when the fields above are not in Latin script.
+    recipient_name_latin: Option<String>,
+    street_address_latin: Option<String>,
+    city_latin: Option<String>,
+    state_latin: Option<String>,
 }
 
-/// Create a new delivery address for the authenticated patient.
+impl CreateDeliveryAddressReq {
+    /// Validates the request. `access_code_encrypted` is the encrypted access code to
+    /// keep when the request does not give one.
+    fn into_entity(
+        self,
+        patient_id: i32,
+        access_code_encrypted: Option<Vec<u8>>,
+    ) -> Result<CreateDeliveryAddressEntity, AppError> {
+        if let Some(method) = &self.preferred_contact_method
+            && !preferences::CONTACT_METHODS.contains(&method.as_str())
+        {
+            return Err(AppError::BadRequest(format!(
+                "Unknown contact method {method}. Allowed methods are: {}",
+                preferences::CONTACT_METHODS.join(", ")
+            )));
+        }
+
+        let latin = |name: &str, value: Option<String>| match value {
+            Some(value) if !value.trim().is_empty() => {
+                if !pdf::can_show(&value) {
+                    return Err(AppError::BadRequest(format!(
+                        "{name} must be in Latin script"
+                    )));
+                }
+                Ok(Some(value.trim().to_string()))
+            }
+            _ => Ok(None),
+        };
+        let recipient_name_latin = latin("recipient_name_latin", self.recipient_name_latin)?;
+        let street_address_latin = latin("street_address_latin", self.street_address_latin)?;
+        let city_latin = latin("city_latin", self.city_latin)?;
+        let state_latin = latin("state_latin", self.state_latin)?;
+
+        let access_code_encrypted = match self.access_code.as_deref().map(str::trim) {
+            None => access_code_encrypted,
+            Some("") => None,
+            Some(access_code) => Some(preferences::encrypt_access_code(
+                &config::access_code_key()?,
+                access_code,
+            )?),
+        };
+
+        Ok(CreateDeliveryAddressEntity {
+            patient_id,
+            recipient_name: self.recipient_name,
+            phone_number: self.phone_number,
+            street_address: self.street_address,
+            city: self.city,
+            state: self.state,
+            postal_code: self.postal_code,
+            country: self.country,
+            is_default: false,
+            delivery_instructions: self
+                .delivery_instructions
+                .filter(|instructions| !instructions.trim().is_empty()),
+            access_code_encrypted,
+            preferred_contact_method: self.preferred_contact_method,
+            contactless_consent: self.contactless_consent,
+            recipient_name_latin,
+            street_address_latin,
+            city_latin,
+            state_latin,
+        })
+    }
+}
+
+/// Create a new delivery address for the authenticated patient, or for a patient who
+/// delegated manage access to them.
 #[utoipa::path(
     post,
     path = "/",
     tags = ["Delivery Addresses"],
     security(("bearerAuth" = [])),
+    params(OnBehalfOfQuery),
     request_body = CreateDeliveryAddressReq,
     responses(
         (status = 200, description = "Created delivery address successfully", body = StdResponse<DeliveryAddressEntity, String>)
     )
 )]
 async fn create_delivery_address(
+    Query(query): Query<OnBehalfOfQuery>,
     State(state): State<AppState>,
     Extension(patient_id): Extension<i32>,
     Json(body): Json<CreateDeliveryAddressReq>,
@@ -121,22 +215,30 @@ async fn create_delivery_address(
         .await
         .context("Failed to obtain a DB connection pool")?;
 
-    let delivery_address: DeliveryAddressEntity = diesel::insert_into(delivery_addresses::table)
-        .values(CreateDeliveryAddressEntity {
-            patient_id,
-            recipient_name: body.recipient_name,
-            phone_number: body.phone_number,
-            street_address: body.street_address,
-            city: body.city,
-            state: body.state,
-            postal_code: body.postal_code,
-            country: body.country,
-            is_default: false,
+    let delivery_address = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let principal =
+                    delegations::act_as(conn, patient_id, query.on_behalf_of, true).await?;
+                let delivery_address: DeliveryAddressEntity =
+                    diesel::insert_into(delivery_addresses::table)
+                        .values(body.into_entity(principal.patient_id, None)?)
+                        .returning(DeliveryAddressEntity::as_returning())
+                        .get_result(conn)
+                        .await
+                        .context("Failed to create delivery address")?;
+                delegations::audit(
+                    conn,
+                    &principal,
+                    "CREATE",
+                    "DELIVERY_ADDRESS",
+                    Some(delivery_address.id.to_string()),
+                )
+                .await?;
+                Ok::<_, AppError>(delivery_address)
+            })
         })
-        .returning(DeliveryAddressEntity::as_returning())
-        .get_result(conn)
-        .await
-        .context("Failed to create delivery address")?;
+        .await?;
 
     Ok(StdResponse {
         data: Some(delivery_address),
@@ -144,14 +246,16 @@ async fn create_delivery_address(
     })
 }
 
-/// Update an existing delivery address belonging to the authenticated patient.
+/// Update an existing delivery address belonging to the authenticated patient, or to a
+/// patient who delegated manage access to them.
 #[utoipa::path(
     patch,
     path = "/{id}",
     tags = ["Delivery Addresses"],
     security(("bearerAuth" = [])),
     params(
-        ("id" = i32, Path, description = "Delivery address ID to update")
+        ("id" = i32, Path, description = "Delivery address ID to update"),
+        OnBehalfOfQuery
     ),
     request_body = CreateDeliveryAddressReq,
     responses(
@@ -160,6 +264,7 @@ async fn create_delivery_address(
 )]
 async fn update_delivery_address(
     Path(id): Path<i32>,
+    Query(query): Query<OnBehalfOfQuery>,
     State(state): State<AppState>,
     Extension(patient_id): Extension<i32>,
     Json(body): Json<CreateDeliveryAddressReq>,
@@ -170,26 +275,42 @@ async fn update_delivery_address(
         .await
         .context("Failed to obtain a DB connection pool")?;
 
-    let delivery_address: DeliveryAddressEntity = diesel::update(
-        delivery_addresses::table
-            .find(id)
-            .filter(delivery_addresses::patient_id.eq(patient_id)),
-    )
-    .set(CreateDeliveryAddressEntity {
-        patient_id,
-        recipient_name: body.recipient_name,
-        phone_number: body.phone_number,
-        street_address: body.street_address,
-        city: body.city,
-        state: body.state,
-        postal_code: body.postal_code,
-        country: body.country,
-        is_default: false,
-    })
-    .returning(DeliveryAddressEntity::as_returning())
-    .get_result(conn)
-    .await
-    .context("Failed to create delivery address")?;
+    let delivery_address = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let principal =
+                    delegations::act_as(conn, patient_id, query.on_behalf_of, true).await?;
+                let current: DeliveryAddressEntity = delivery_addresses::table
+                    .find(id)
+                    .filter(delivery_addresses::patient_id.eq(principal.patient_id))
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery address")?
+                    .ok_or(AppError::NotFound)?;
+
+                let delivery_address: DeliveryAddressEntity = diesel::update(
+                    delivery_addresses::table
+                        .find(id)
+                        .filter(delivery_addresses::patient_id.eq(principal.patient_id)),
+                )
+                .set(body.into_entity(principal.patient_id, current.access_code_encrypted)?)
+                .returning(DeliveryAddressEntity::as_returning())
+                .get_result(conn)
+                .await
+                .context("Failed to create delivery address")?;
+                delegations::audit(
+                    conn,
+                    &principal,
+                    "UPDATE",
+                    "DELIVERY_ADDRESS",
+                    Some(id.to_string()),
+                )
+                .await?;
+                Ok::<_, AppError>(delivery_address)
+            })
+        })
+        .await?;
 
     Ok(StdResponse {
         data: Some(delivery_address),
@@ -197,14 +318,16 @@ async fn update_delivery_address(
     })
 }
 
-/// Delete a delivery address belonging to the authenticated patient.
+/// Delete a delivery address belonging to the authenticated patient, or to a patient who
+/// delegated manage access to them.
 #[utoipa::path(
     delete,
     path = "/{id}",
     tags = ["Delivery Addresses"],
     security(("bearerAuth" = [])),
     params(
-        ("id" = i32, Path, description = "Delivery address ID to delete")
+        ("id" = i32, Path, description = "Delivery address ID to delete"),
+        OnBehalfOfQuery
     ),
     responses(
         (status = 200, description = "Deleted delivery address successfully", body = StdResponse<DeliveryAddressEntity, String>)
@@ -212,6 +335,7 @@ async fn update_delivery_address(
 )]
 async fn delete_delivery_address(
     Path(id): Path<i32>,
+    Query(query): Query<OnBehalfOfQuery>,
     State(state): State<AppState>,
     Extension(patient_id): Extension<i32>,
 ) -> Result<impl IntoResponse, AppError> {
@@ -221,15 +345,32 @@ async fn delete_delivery_address(
         .await
         .context("Failed to obtain a DB connection pool")?;
 
-    let delivery_address: DeliveryAddressEntity = diesel::delete(
-        delivery_addresses::table
-            .filter(delivery_addresses::id.eq(id))
-            .filter(delivery_addresses::patient_id.eq(patient_id)),
-    )
-    .returning(DeliveryAddressEntity::as_returning())
-    .get_result(conn)
-    .await
-    .context("Failed to delete delivery address")?;
+    let delivery_address = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let principal =
+                    delegations::act_as(conn, patient_id, query.on_behalf_of, true).await?;
+                let delivery_address: DeliveryAddressEntity = diesel::delete(
+                    delivery_addresses::table
+                        .filter(delivery_addresses::id.eq(id))
+                        .filter(delivery_addresses::patient_id.eq(principal.patient_id)),
+                )
+                .returning(DeliveryAddressEntity::as_returning())
+                .get_result(conn)
+                .await
+                .context("Failed to delete delivery address")?;
+                delegations::audit(
+                    conn,
+                    &principal,
+                    "DELETE",
+                    "DELIVERY_ADDRESS",
+                    Some(id.to_string()),
+                )
+                .await?;
+                Ok::<_, AppError>(delivery_address)
+            })
+        })
+        .await?;
 
     Ok(StdResponse {
         data: Some(delivery_address),
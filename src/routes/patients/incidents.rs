@@ -0,0 +1,346 @@
+use anyhow::Context;
+use axum::{
+    Extension, Json,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+    middleware,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        CreateDeliveryIncidentAttachmentEntity, CreateDeliveryIncidentCommentEntity,
+        CreateDeliveryIncidentEntity, DeliveryIncidentAttachmentEntity,
+        DeliveryIncidentCommentEntity, DeliveryIncidentEntity,
+    },
+    routes::incidents::content_type,
+    schema::{deliveries, delivery_incidents},
+    services::{
+        self,
+        incidents::{self, IncidentDetails},
+    },
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new()
+        .nest(
+            "/patients/deliveries",
+            OpenApiRouter::new()
+                .routes(utoipa_axum::routes!(report_my_incident))
+                .routes(utoipa_axum::routes!(get_my_delivery_incidents))
+                .route_layer(axum::middleware::from_fn(
+                    middleware::patients_authorization,
+                )),
+        )
+        .nest(
+            "/patients/incidents",
+            OpenApiRouter::new()
+                .routes(utoipa_axum::routes!(get_my_incident))
+                .routes(utoipa_axum::routes!(comment_on_my_incident))
+                .routes(utoipa_axum::routes!(get_my_incident_attachment))
+                .merge(
+                    OpenApiRouter::new()
+                        .routes(utoipa_axum::routes!(attach_to_my_incident))
+                        .layer(DefaultBodyLimit::max(incidents::MAX_ATTACHMENT_BYTES)),
+                )
+                .route_layer(axum::middleware::from_fn(
+                    middleware::patients_authorization,
+                )),
+        )
+}
+
+/// Fetches an incident on one of `patient_id`'s deliveries.
+async fn get_my_incident_entity(
+    conn: &mut AsyncPgConnection,
+    patient_id: i32,
+    id: Uuid,
+) -> Result<DeliveryIncidentEntity, AppError> {
+    let incident = delivery_incidents::table
+        .inner_join(deliveries::table)
+        .filter(delivery_incidents::id.eq(id))
+        .filter(deliveries::patient_id.eq(patient_id))
+        .select(DeliveryIncidentEntity::as_select())
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery incident")?
+        .ok_or(AppError::NotFound)?;
+    Ok(incident)
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ReportIncidentReq {
+    /// `DAMAGED`, `MISSING_ITEMS`, `WRONG_ITEM`, `LATE` or `OTHER`.
+    category: String,
+    description: String,
+}
+
+/// Report a problem with one of the authenticated patient's deliveries.
+#[utoipa::path(
+    post,
+    path = "/{id}/incidents",
+    tags = ["Incidents"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to report on")
+    ),
+    request_body = ReportIncidentReq,
+    responses(
+        (status = 200, description = "Reported incident successfully", body = StdResponse<DeliveryIncidentEntity, String>)
+    )
+)]
+async fn report_my_incident(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+    Json(body): Json<ReportIncidentReq>,
+) -> Result<impl IntoResponse, AppError> {
+    incidents::check_category(&body.category)?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery = services::deliveries::get_patient_delivery(conn, patient_id, id).await?;
+
+    let incident = incidents::raise(
+        conn,
+        CreateDeliveryIncidentEntity {
+            delivery_id: delivery.id,
+            severity: incidents::default_severity(&body.category).into(),
+            category: body.category,
+            description: body.description,
+            dedup_key: None,
+            reporter_type: "PATIENT".into(),
+            reported_by: Some(patient_id.to_string()),
+        },
+    )
+    .await?
+    .context("Incident without a dedup key was not created")?;
+
+    Ok(StdResponse {
+        data: Some(incident),
+        message: Some("Reported incident successfully"),
+    })
+}
+
+/// Fetch the incidents of one of the authenticated patient's deliveries.
+#[utoipa::path(
+    get,
+    path = "/{id}/incidents",
+    tags = ["Incidents"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch incidents of")
+    ),
+    responses(
+        (status = 200, description = "List delivery incidents", body = StdResponse<Vec<DeliveryIncidentEntity>, String>)
+    )
+)]
+async fn get_my_delivery_incidents(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery = services::deliveries::get_patient_delivery(conn, patient_id, id).await?;
+
+    let incidents: Vec<DeliveryIncidentEntity> = delivery_incidents::table
+        .filter(delivery_incidents::delivery_id.eq(delivery.id))
+        .order_by(delivery_incidents::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery incidents")?;
+
+    Ok(StdResponse {
+        data: Some(incidents),
+        message: Some("Get delivery incidents successfully"),
+    })
+}
+
+/// Fetch one of the authenticated patient's incidents with its comments and attachments.
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tags = ["Incidents"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Incident ID to fetch")
+    ),
+    responses(
+        (status = 200, description = "Fetched incident successfully", body = StdResponse<IncidentDetails, String>)
+    )
+)]
+async fn get_my_incident(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident = get_my_incident_entity(conn, patient_id, id).await?;
+    let details = incidents::get_details(conn, incident, false).await?;
+
+    Ok(StdResponse {
+        data: Some(details),
+        message: Some("Get incident successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CommentReq {
+    body: String,
+}
+
+/// Comment on one of the authenticated patient's incidents.
+#[utoipa::path(
+    post,
+    path = "/{id}/comments",
+    tags = ["Incidents"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Incident ID to comment on")
+    ),
+    request_body = CommentReq,
+    responses(
+        (status = 200, description = "Created comment successfully", body = StdResponse<DeliveryIncidentCommentEntity, String>)
+    )
+)]
+async fn comment_on_my_incident(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+    Json(body): Json<CommentReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident = get_my_incident_entity(conn, patient_id, id).await?;
+    let comment = incidents::add_comment(
+        conn,
+        CreateDeliveryIncidentCommentEntity {
+            incident_id: incident.id,
+            author_type: "PATIENT".into(),
+            author: patient_id.to_string(),
+            body: body.body,
+            is_internal: false,
+        },
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(comment),
+        message: Some("Created comment successfully"),
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+struct AttachmentQuery {
+    file_name: String,
+}
+
+/// Attach a photo or document to one of the authenticated patient's incidents. The
+/// request body is the file itself.
+#[utoipa::path(
+    post,
+    path = "/{id}/attachments",
+    tags = ["Incidents"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Incident ID to attach to"),
+        AttachmentQuery
+    ),
+    request_body(content = Vec<u8>, description = "JPEG, PNG or PDF file", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Created attachment successfully", body = StdResponse<DeliveryIncidentAttachmentEntity, String>)
+    )
+)]
+async fn attach_to_my_incident(
+    Path(id): Path<Uuid>,
+    Query(query): Query<AttachmentQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident = get_my_incident_entity(conn, patient_id, id).await?;
+    let attachment = incidents::add_attachment(
+        conn,
+        CreateDeliveryIncidentAttachmentEntity {
+            incident_id: incident.id,
+            file_name: query.file_name,
+            content_type: content_type(&headers),
+            size_bytes: body.len() as i32,
+            content: body.to_vec(),
+            uploaded_by_type: "PATIENT".into(),
+            uploaded_by: patient_id.to_string(),
+        },
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(attachment),
+        message: Some("Created attachment successfully"),
+    })
+}
+
+/// Download an attachment of one of the authenticated patient's incidents.
+#[utoipa::path(
+    get,
+    path = "/{id}/attachments/{attachment_id}",
+    tags = ["Incidents"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Incident ID the attachment belongs to"),
+        ("attachment_id" = Uuid, Path, description = "Attachment ID to download")
+    ),
+    responses(
+        (status = 200, description = "Attachment content", body = Vec<u8>)
+    )
+)]
+async fn get_my_incident_attachment(
+    Path((id, attachment_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<Response, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let incident = get_my_incident_entity(conn, patient_id, id).await?;
+    incidents::get_attachment(conn, incident.id, attachment_id).await
+}
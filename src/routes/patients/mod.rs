@@ -1 +1,6 @@
+pub mod delegations;
+pub mod deliveries;
 pub mod delivery_addresses;
+pub mod incidents;
+pub mod ratings;
+pub mod schedules;
@@ -0,0 +1,159 @@
+use anyhow::Context;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+    middleware,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{CreateDeliveryRatingEntity, DeliveryRatingEntity},
+    schema::delivery_ratings,
+    services::{self, ratings},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/patients/deliveries",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(rate_my_delivery))
+            .routes(utoipa_axum::routes!(get_my_delivery_rating))
+            .route_layer(axum::middleware::from_fn(
+                middleware::patients_authorization,
+            )),
+    )
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RateDeliveryReq {
+    /// From 1 to 5.
+    rating: i32,
+    /// Any of `late`, `polite`, `damaged`.
+    #[serde(default)]
+    tags: Vec<String>,
+    feedback: Option<String>,
+}
+
+/// Rate one of the authenticated patient's delivered deliveries. Each delivery can be rated once.
+#[utoipa::path(
+    post,
+    path = "/{id}/rating",
+    tags = ["Ratings"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to rate")
+    ),
+    request_body = RateDeliveryReq,
+    responses(
+        (status = 200, description = "Rated delivery successfully", body = StdResponse<DeliveryRatingEntity, String>)
+    )
+)]
+async fn rate_my_delivery(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+    Json(mut body): Json<RateDeliveryReq>,
+) -> Result<impl IntoResponse, AppError> {
+    if !(1..=5).contains(&body.rating) {
+        return Err(AppError::BadRequest("Rating must be from 1 to 5".into()));
+    }
+    if let Some(tag) = body
+        .tags
+        .iter()
+        .find(|tag| !ratings::TAGS.contains(&tag.as_str()))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Unknown tag {tag}. Allowed tags are: {}",
+            ratings::TAGS.join(", ")
+        )));
+    }
+    body.tags.sort();
+    body.tags.dedup();
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery = services::deliveries::get_patient_delivery(conn, patient_id, id).await?;
+    if delivery.status != "DELIVERED" && delivery.status != "COLLECTED" {
+        return Err(AppError::BadRequest(
+            "Only delivered or collected deliveries can be rated".into(),
+        ));
+    }
+
+    let rating: DeliveryRatingEntity = diesel::insert_into(delivery_ratings::table)
+        .values(CreateDeliveryRatingEntity {
+            delivery_id: delivery.id,
+            patient_id,
+            courier_id: delivery.courier_id,
+            zone: delivery.zone,
+            rating: body.rating,
+            tags: body.tags.into(),
+            feedback: body.feedback.filter(|feedback| !feedback.trim().is_empty()),
+        })
+        .on_conflict(delivery_ratings::delivery_id)
+        .do_nothing()
+        .returning(DeliveryRatingEntity::as_returning())
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to create delivery rating")?
+        .ok_or_else(|| AppError::BadRequest("Delivery has already been rated".into()))?;
+
+    Ok(StdResponse {
+        data: Some(rating),
+        message: Some("Rated delivery successfully"),
+    })
+}
+
+/// Fetch the authenticated patient's rating of a delivery.
+#[utoipa::path(
+    get,
+    path = "/{id}/rating",
+    tags = ["Ratings"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch the rating of")
+    ),
+    responses(
+        (status = 200, description = "Fetched rating successfully", body = StdResponse<DeliveryRatingEntity, String>)
+    )
+)]
+async fn get_my_delivery_rating(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let delivery = services::deliveries::get_patient_delivery(conn, patient_id, id).await?;
+    let rating: DeliveryRatingEntity = delivery_ratings::table
+        .filter(delivery_ratings::delivery_id.eq(delivery.id))
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery rating")?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(rating),
+        message: Some("Get delivery rating successfully"),
+    })
+}
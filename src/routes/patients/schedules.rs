@@ -0,0 +1,494 @@
+use anyhow::Context;
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::{NaiveDate, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use futures::future::BoxFuture;
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+    middleware,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{CreateDeliveryScheduleEntity, DeliveryScheduleEntity, DeliveryScheduleRunEntity},
+    routes::patients::delegations::OnBehalfOfQuery,
+    schema::{delivery_addresses, delivery_schedule_runs, delivery_schedules},
+    services::{delegations, schedules},
+};
+
+/// Number of upcoming runs shown with a schedule.
+const UPCOMING_RUNS: usize = 5;
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/patients/delivery-schedules",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_my_schedules))
+            .routes(utoipa_axum::routes!(create_schedule))
+            .routes(utoipa_axum::routes!(get_my_schedule))
+            .routes(utoipa_axum::routes!(end_schedule))
+            .routes(utoipa_axum::routes!(pause_schedule))
+            .routes(utoipa_axum::routes!(resume_schedule))
+            .routes(utoipa_axum::routes!(skip_schedule_run))
+            .route_layer(axum::middleware::from_fn(
+                middleware::patients_authorization,
+            )),
+    )
+}
+
+/// Fetch all delivery schedules of the authenticated patient, or of a patient who
+/// delegated access to them.
+#[utoipa::path(
+    get,
+    path = "/",
+    tags = ["Delivery Schedules"],
+    security(("bearerAuth" = [])),
+    params(OnBehalfOfQuery),
+    responses(
+        (status = 200, description = "Fetched delivery schedules successfully", body = StdResponse<Vec<DeliveryScheduleEntity>, String>)
+    )
+)]
+async fn get_my_schedules(
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let principal = delegations::act_as(conn, patient_id, query.on_behalf_of, false).await?;
+    let schedules: Vec<DeliveryScheduleEntity> = delivery_schedules::table
+        .filter(delivery_schedules::patient_id.eq(principal.patient_id))
+        .order_by(delivery_schedules::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery schedules")?;
+    delegations::audit(conn, &principal, "LIST", "DELIVERY_SCHEDULE", None).await?;
+
+    Ok(StdResponse {
+        data: Some(schedules),
+        message: Some("Get my delivery schedules successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateScheduleReq {
+    delivery_address_id: i32,
+    /// Order to repeat on every run.
+    source_order_id: i32,
+    /// RRULE subset, e.g. `FREQ=MONTHLY;INTERVAL=1;BYMONTHDAY=5`.
+    recurrence: String,
+    /// Defaults to today.
+    starts_on: Option<NaiveDate>,
+    ends_on: Option<NaiveDate>,
+}
+
+/// Create a recurring delivery schedule for the authenticated patient, or for a patient
+/// who delegated manage access to them.
+#[utoipa::path(
+    post,
+    path = "/",
+    tags = ["Delivery Schedules"],
+    security(("bearerAuth" = [])),
+    params(OnBehalfOfQuery),
+    request_body = CreateScheduleReq,
+    responses(
+        (status = 200, description = "Created delivery schedule successfully", body = StdResponse<DeliveryScheduleEntity, String>)
+    )
+)]
+async fn create_schedule(
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+    Json(body): Json<CreateScheduleReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let recurrence = schedules::parse_recurrence(&body.recurrence)?;
+    let today = Utc::now().date_naive();
+    let starts_on = body.starts_on.unwrap_or(today);
+    if body.ends_on.is_some_and(|ends_on| ends_on < starts_on) {
+        return Err(AppError::BadRequest(
+            "Schedule cannot end before it starts".into(),
+        ));
+    }
+    let next_run_on = Some(recurrence.next_on_or_after(starts_on, today))
+        .filter(|next| body.ends_on.is_none_or(|ends_on| *next <= ends_on))
+        .ok_or_else(|| AppError::BadRequest("Schedule has no runs left".into()))?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let schedule = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let principal =
+                    delegations::act_as(conn, patient_id, query.on_behalf_of, true).await?;
+                delivery_addresses::table
+                    .find(body.delivery_address_id)
+                    .filter(delivery_addresses::patient_id.eq(principal.patient_id))
+                    .select(delivery_addresses::id)
+                    .get_result::<i32>(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery address")?
+                    .ok_or_else(|| {
+                        AppError::BadRequest("Delivery address does not exist".into())
+                    })?;
+
+                let schedule = diesel::insert_into(delivery_schedules::table)
+                    .values(CreateDeliveryScheduleEntity {
+                        patient_id: principal.patient_id,
+                        delivery_address_id: Some(body.delivery_address_id),
+                        source_order_id: body.source_order_id,
+                        recurrence: body.recurrence,
+                        starts_on,
+                        ends_on: body.ends_on,
+                        next_run_on: Some(next_run_on),
+                    })
+                    .returning(DeliveryScheduleEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .context("Failed to create delivery schedule")?;
+                delegations::audit(
+                    conn,
+                    &principal,
+                    "CREATE",
+                    "DELIVERY_SCHEDULE",
+                    Some(schedule.id.to_string()),
+                )
+                .await?;
+                Ok::<DeliveryScheduleEntity, AppError>(schedule)
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(schedule),
+        message: Some("Created delivery schedule successfully"),
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct GetScheduleRes {
+    schedule: DeliveryScheduleEntity,
+    /// Past runs, newest first.
+    runs: Vec<DeliveryScheduleRunEntity>,
+    /// Next runs if the schedule stays active.
+    upcoming_runs: Vec<NaiveDate>,
+}
+
+/// Fetch a delivery schedule with its past and upcoming runs.
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tags = ["Delivery Schedules"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery schedule ID to fetch"),
+        OnBehalfOfQuery
+    ),
+    responses(
+        (status = 200, description = "Fetched delivery schedule successfully", body = StdResponse<GetScheduleRes, String>)
+    )
+)]
+async fn get_my_schedule(
+    Path(id): Path<Uuid>,
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let principal = delegations::act_as(conn, patient_id, query.on_behalf_of, false).await?;
+    let schedule = schedules::get_patient_schedule(conn, principal.patient_id, id).await?;
+    let runs: Vec<DeliveryScheduleRunEntity> = delivery_schedule_runs::table
+        .filter(delivery_schedule_runs::schedule_id.eq(schedule.id))
+        .order_by(delivery_schedule_runs::run_on.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery schedule runs")?;
+    delegations::audit(
+        conn,
+        &principal,
+        "VIEW",
+        "DELIVERY_SCHEDULE",
+        Some(id.to_string()),
+    )
+    .await?;
+
+    let upcoming_runs = match schedule.next_run_on {
+        Some(next_run_on) => schedules::parse_recurrence(&schedule.recurrence)?
+            .upcoming(schedule.starts_on, next_run_on, UPCOMING_RUNS)
+            .into_iter()
+            .filter(|run_on| schedule.ends_on.is_none_or(|ends_on| *run_on <= ends_on))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(StdResponse {
+        data: Some(GetScheduleRes {
+            schedule,
+            runs,
+            upcoming_runs,
+        }),
+        message: Some("Get my delivery schedule successfully"),
+    })
+}
+
+/// Applies `action` to one of the principal's schedules inside a transaction, auditing it
+/// when taken through a delegation.
+async fn manage_schedule<F>(
+    state: &AppState,
+    patient_id: i32,
+    on_behalf_of: Option<i32>,
+    id: Uuid,
+    audit_action: &'static str,
+    action: F,
+) -> Result<DeliveryScheduleEntity, AppError>
+where
+    F: for<'c> FnOnce(
+            &'c mut AsyncPgConnection,
+            DeliveryScheduleEntity,
+        ) -> BoxFuture<'c, Result<DeliveryScheduleEntity, AppError>>
+        + Send
+        + 'static,
+{
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    conn.transaction(move |conn| {
+        Box::pin(async move {
+            let principal = delegations::act_as(conn, patient_id, on_behalf_of, true).await?;
+            let schedule = schedules::get_patient_schedule(conn, principal.patient_id, id).await?;
+            let schedule = action(conn, schedule).await?;
+            delegations::audit(
+                conn,
+                &principal,
+                audit_action,
+                "DELIVERY_SCHEDULE",
+                Some(id.to_string()),
+            )
+            .await?;
+            Ok(schedule)
+        })
+    })
+    .await
+}
+
+/// End a delivery schedule. No further runs are requested.
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    tags = ["Delivery Schedules"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery schedule ID to end"),
+        OnBehalfOfQuery
+    ),
+    responses(
+        (status = 200, description = "Ended delivery schedule successfully", body = StdResponse<DeliveryScheduleEntity, String>)
+    )
+)]
+async fn end_schedule(
+    Path(id): Path<Uuid>,
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let schedule = manage_schedule(
+        &state,
+        patient_id,
+        query.on_behalf_of,
+        id,
+        "END",
+        |conn, schedule| {
+            Box::pin(async move {
+                let schedule = diesel::update(delivery_schedules::table.find(schedule.id))
+                    .set((
+                        delivery_schedules::status.eq("ENDED"),
+                        delivery_schedules::next_run_on.eq(None::<NaiveDate>),
+                    ))
+                    .get_result(conn)
+                    .await
+                    .context("Failed to end delivery schedule")?;
+                Ok(schedule)
+            })
+        },
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(schedule),
+        message: Some("Ended delivery schedule successfully"),
+    })
+}
+
+/// Pause a delivery schedule. No runs are requested until it is resumed.
+#[utoipa::path(
+    post,
+    path = "/{id}/pause",
+    tags = ["Delivery Schedules"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery schedule ID to pause"),
+        OnBehalfOfQuery
+    ),
+    responses(
+        (status = 200, description = "Paused delivery schedule successfully", body = StdResponse<DeliveryScheduleEntity, String>)
+    )
+)]
+async fn pause_schedule(
+    Path(id): Path<Uuid>,
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let schedule = manage_schedule(
+        &state,
+        patient_id,
+        query.on_behalf_of,
+        id,
+        "PAUSE",
+        |conn, schedule| {
+            Box::pin(async move {
+                if schedule.status != "ACTIVE" {
+                    return Err(AppError::BadRequest(format!(
+                        "Cannot pause a schedule that is {}",
+                        schedule.status
+                    )));
+                }
+                let schedule = diesel::update(delivery_schedules::table.find(schedule.id))
+                    .set(delivery_schedules::status.eq("PAUSED"))
+                    .get_result(conn)
+                    .await
+                    .context("Failed to pause delivery schedule")?;
+                Ok(schedule)
+            })
+        },
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(schedule),
+        message: Some("Paused delivery schedule successfully"),
+    })
+}
+
+/// Resume a paused delivery schedule. Runs missed while paused are not requested.
+#[utoipa::path(
+    post,
+    path = "/{id}/resume",
+    tags = ["Delivery Schedules"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery schedule ID to resume"),
+        OnBehalfOfQuery
+    ),
+    responses(
+        (status = 200, description = "Resumed delivery schedule successfully", body = StdResponse<DeliveryScheduleEntity, String>)
+    )
+)]
+async fn resume_schedule(
+    Path(id): Path<Uuid>,
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let schedule = manage_schedule(
+        &state,
+        patient_id,
+        query.on_behalf_of,
+        id,
+        "RESUME",
+        |conn, schedule| {
+            Box::pin(async move {
+                if schedule.status != "PAUSED" {
+                    return Err(AppError::BadRequest(format!(
+                        "Cannot resume a schedule that is {}",
+                        schedule.status
+                    )));
+                }
+                if schedule.delivery_address_id.is_none() {
+                    return Err(AppError::BadRequest(
+                        "Schedule has no delivery address".into(),
+                    ));
+                }
+                let schedule = diesel::update(delivery_schedules::table.find(schedule.id))
+                    .set(delivery_schedules::status.eq("ACTIVE"))
+                    .get_result::<DeliveryScheduleEntity>(conn)
+                    .await
+                    .context("Failed to resume delivery schedule")?;
+
+                let from = schedule
+                    .next_run_on
+                    .unwrap_or(schedule.starts_on)
+                    .max(Utc::now().date_naive());
+                schedules::advance(conn, &schedule, from).await
+            })
+        },
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(schedule),
+        message: Some("Resumed delivery schedule successfully"),
+    })
+}
+
+/// Skip the next run of a delivery schedule.
+#[utoipa::path(
+    post,
+    path = "/{id}/skip",
+    tags = ["Delivery Schedules"],
+    security(("bearerAuth" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Delivery schedule ID to skip the next run of"),
+        OnBehalfOfQuery
+    ),
+    responses(
+        (status = 200, description = "Skipped delivery schedule run successfully", body = StdResponse<DeliveryScheduleEntity, String>)
+    )
+)]
+async fn skip_schedule_run(
+    Path(id): Path<Uuid>,
+    Query(query): Query<OnBehalfOfQuery>,
+    State(state): State<AppState>,
+    Extension(patient_id): Extension<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let schedule = manage_schedule(
+        &state,
+        patient_id,
+        query.on_behalf_of,
+        id,
+        "SKIP",
+        |conn, schedule| Box::pin(async move { schedules::skip(conn, &schedule).await }),
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(schedule),
+        message: Some("Skipped delivery schedule run successfully"),
+    })
+}
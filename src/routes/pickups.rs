@@ -0,0 +1,77 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{DeliveryEntity, DeliveryLogEntity},
+    services::pickups,
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/deliveries",
+        OpenApiRouter::new().routes(utoipa_axum::routes!(collect_delivery)),
+    )
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CollectDeliveryReq {
+    /// The code sent to the patient when the parcel became ready for pickup.
+    pickup_code: String,
+    /// Pharmacist or locker terminal handing the parcel over.
+    collected_by: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CollectDeliveryRes {
+    updated_delivery: DeliveryEntity,
+    delivery_log: DeliveryLogEntity,
+}
+
+/// Hand a parcel waiting at a pharmacy or locker over against its pickup code.
+#[utoipa::path(
+    post,
+    path = "/{id}/collect",
+    tags = ["Deliveries"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID being collected")
+    ),
+    request_body = CollectDeliveryReq,
+    responses(
+        (status = 200, description = "Collected delivery successfully", body = StdResponse<CollectDeliveryRes, String>)
+    )
+)]
+async fn collect_delivery(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(body): Json<CollectDeliveryReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let (updated_delivery, delivery_log) =
+        pickups::collect(conn, id, &body.pickup_code, &body.collected_by).await?;
+
+    Ok(StdResponse {
+        data: Some(CollectDeliveryRes {
+            updated_delivery,
+            delivery_log,
+        }),
+        message: Some("Collected delivery successfully"),
+    })
+}
@@ -0,0 +1,67 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::services::ratings::{self, RatingSummary};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/ratings",
+        OpenApiRouter::new().routes(utoipa_axum::routes!(get_rating_summary)),
+    )
+}
+
+#[derive(Deserialize, IntoParams)]
+struct RatingSummaryQuery {
+    /// `courier` (default) or `zone`.
+    group_by: Option<String>,
+    /// Only ratings given at or after this time.
+    from: Option<DateTime<Utc>>,
+    /// Only ratings given before this time.
+    to: Option<DateTime<Utc>>,
+}
+
+/// Fetch delivery ratings aggregated per courier or per zone.
+#[utoipa::path(
+    get,
+    path = "/summary",
+    tags = ["Ratings"],
+    params(RatingSummaryQuery),
+    responses(
+        (status = 200, description = "Fetched rating summary successfully", body = StdResponse<Vec<RatingSummary>, String>)
+    )
+)]
+async fn get_rating_summary(
+    Query(query): Query<RatingSummaryQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let summaries = ratings::summarize(
+        conn,
+        query.group_by.as_deref().unwrap_or("courier"),
+        query.from,
+        query.to,
+    )
+    .await?;
+
+    Ok(StdResponse {
+        data: Some(summaries),
+        message: Some("Get rating summary successfully"),
+    })
+}
@@ -0,0 +1,265 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    documents::labels,
+    models::{
+        CreateDeliveryLogEntity, CreateDeliveryScanEntity, DeliveryEntity, DeliveryLogEntity,
+        DeliveryPackageEntity, DeliveryScanEntity,
+    },
+    schema::{deliveries, delivery_logs, delivery_packages, delivery_scans},
+    services,
+};
+
+pub const SCAN_TYPES: [&str; 5] = [
+    "PICKUP",
+    "HUB_INBOUND",
+    "HUB_OUTBOUND",
+    "OUT_FOR_DELIVERY",
+    "DELIVERY_ATTEMPT",
+];
+
+/// The status a scan moves a delivery to, if it is further along than the delivery.
+/// Delivery itself is never confirmed by a scan, as it needs the recipient checks.
+fn scan_status(scan_type: &str) -> Option<&'static str> {
+    match scan_type {
+        "PICKUP" => Some("PICKED_UP"),
+        "OUT_FOR_DELIVERY" => Some("EN_ROUTE"),
+        _ => None,
+    }
+}
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new()
+        .nest(
+            "/scans",
+            OpenApiRouter::new().routes(utoipa_axum::routes!(create_scan)),
+        )
+        .nest(
+            "/deliveries",
+            OpenApiRouter::new().routes(utoipa_axum::routes!(get_delivery_scans)),
+        )
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateScanReq {
+    /// Contents of the scanned label code.
+    code: String,
+    /// PICKUP, HUB_INBOUND, HUB_OUTBOUND, OUT_FOR_DELIVERY or DELIVERY_ATTEMPT.
+    scan_type: String,
+    location: String,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    scanned_by: String,
+    /// Defaults to the time the scan is received.
+    scanned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CreateScanRes {
+    scan: DeliveryScanEntity,
+    delivery: DeliveryEntity,
+    delivery_log: DeliveryLogEntity,
+}
+
+/// Resolves label contents to the scanned delivery and, for package labels, the package.
+async fn resolve_code(
+    conn: &mut AsyncPgConnection,
+    code: &str,
+) -> Result<(Uuid, Option<Uuid>), AppError> {
+    let (delivery_id, id) = labels::parse_code(code)
+        .ok_or_else(|| AppError::BadRequest("Unrecognised label code".into()))?;
+
+    let package: Option<DeliveryPackageEntity> = delivery_packages::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery package")?;
+
+    match (delivery_id, package) {
+        (Some(delivery_id), Some(package)) if package.delivery_id == delivery_id => {
+            Ok((delivery_id, Some(package.id)))
+        }
+        (Some(_), _) => Err(AppError::NotFound),
+        (None, Some(package)) => Ok((package.delivery_id, Some(package.id))),
+        (None, None) => Ok((id, None)),
+    }
+}
+
+/// Record a scan by a courier or hub staff. Scans move the delivery and its legs forward
+/// where the scan type implies a later status, and are always logged.
+#[utoipa::path(
+    post,
+    path = "/",
+    tags = ["Scans"],
+    request_body = CreateScanReq,
+    responses(
+        (status = 200, description = "Recorded scan successfully", body = StdResponse<CreateScanRes, String>)
+    )
+)]
+async fn create_scan(
+    State(state): State<AppState>,
+    Json(body): Json<CreateScanReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    if !SCAN_TYPES.contains(&body.scan_type.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Allowed scan types are: {}",
+            SCAN_TYPES.join(", ")
+        )));
+    }
+
+    let (scan, delivery, delivery_log) = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let (delivery_id, package_id) = resolve_code(conn, &body.code).await?;
+
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(delivery_id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                let scan: DeliveryScanEntity = diesel::insert_into(delivery_scans::table)
+                    .values(CreateDeliveryScanEntity {
+                        delivery_id: delivery.id,
+                        package_id,
+                        scan_type: body.scan_type,
+                        location: body.location,
+                        latitude: body.latitude,
+                        longitude: body.longitude,
+                        scanned_by: body.scanned_by,
+                        scanned_at: body.scanned_at.unwrap_or_else(Utc::now),
+                    })
+                    .returning(DeliveryScanEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .context("Failed to create scan")?;
+
+                let description = format!(
+                    "{} scan at {} by {}",
+                    scan.scan_type, scan.location, scan.scanned_by
+                );
+
+                // Deliveries routed through hubs follow their legs rather than the scan.
+                let legs =
+                    services::legs::apply_scan(conn, delivery.id, &scan.scan_type, scan.scanned_at)
+                        .await?;
+                let (delivery, target) = if legs.is_empty() {
+                    (delivery, scan_status(&scan.scan_type))
+                } else {
+                    // Starting a leg hands the delivery over to the leg's courier.
+                    let delivery: DeliveryEntity = deliveries::table
+                        .find(delivery.id)
+                        .get_result(conn)
+                        .await
+                        .context("Failed to get delivery")?;
+                    (delivery, services::legs::derived_status(&legs))
+                };
+
+                let moves_forward = target.filter(|status| {
+                    services::deliveries::moves_forward(
+                        &delivery.fulfilment_mode,
+                        &delivery.status,
+                        status,
+                    )
+                });
+                let (delivery, delivery_log) = match moves_forward {
+                    Some(status) => {
+                        services::deliveries::transition(
+                            conn,
+                            delivery.id,
+                            status.into(),
+                            description,
+                        )
+                        .await?
+                    }
+                    None => {
+                        let delivery_log = diesel::insert_into(delivery_logs::table)
+                            .values(CreateDeliveryLogEntity {
+                                delivery_id: delivery.id,
+                                description,
+                                status: delivery.status.clone(),
+                            })
+                            .returning(DeliveryLogEntity::as_returning())
+                            .get_result(conn)
+                            .await
+                            .context("Failed to create delivery log")?;
+                        (delivery, delivery_log)
+                    }
+                };
+
+                Ok::<_, AppError>((scan, delivery, delivery_log))
+            })
+        })
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(CreateScanRes {
+            scan,
+            delivery,
+            delivery_log,
+        }),
+        message: Some("Recorded scan successfully"),
+    })
+}
+
+/// Fetch all scans of a delivery, oldest first.
+#[utoipa::path(
+    get,
+    path = "/{id}/scans",
+    tags = ["Scans"],
+    params(
+        ("id" = Uuid, Path, description = "Delivery ID to fetch scans for")
+    ),
+    responses(
+        (status = 200, description = "Fetched scans successfully", body = StdResponse<Vec<DeliveryScanEntity>, String>)
+    )
+)]
+async fn get_delivery_scans(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let scans: Vec<DeliveryScanEntity> = delivery_scans::table
+        .filter(delivery_scans::delivery_id.eq(id))
+        .order_by(delivery_scans::scanned_at.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get scans")?;
+
+    Ok(StdResponse {
+        data: Some(scans),
+        message: Some("Get scans successfully"),
+    })
+}
@@ -0,0 +1,85 @@
+use anyhow::Context;
+use axum::{extract::State, response::IntoResponse};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::{
+    config,
+    models::DeliverySweeperActionEntity,
+    schema::delivery_sweeper_actions,
+    services::sweeper::{self, SweepAction, SweepPolicy},
+};
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/sweeper",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_sweeper_report))
+            .routes(utoipa_axum::routes!(get_sweeper_actions)),
+    )
+}
+
+/// Report what the stuck delivery sweeper would do now, without doing it.
+#[utoipa::path(
+    get,
+    path = "/report",
+    tags = ["Sweeper"],
+    responses(
+        (status = 200, description = "Get sweeper report successfully", body = StdResponse<Vec<SweepAction>, String>)
+    )
+)]
+async fn get_sweeper_report(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let policy = config::stuck_delivery_policy();
+    let policy = SweepPolicy::from_name(&policy)
+        .with_context(|| format!("Unknown stuck delivery policy {policy}"))?;
+    let stuck_after = config::stuck_delivery_after()?;
+
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let actions = conn
+        .transaction(|conn| Box::pin(sweeper::sweep(conn, policy, stuck_after, true)))
+        .await?;
+
+    Ok(StdResponse {
+        data: Some(actions),
+        message: Some("Get sweeper report successfully"),
+    })
+}
+
+/// Fetch the actions the stuck delivery sweeper has taken, newest first.
+#[utoipa::path(
+    get,
+    path = "/actions",
+    tags = ["Sweeper"],
+    responses(
+        (status = 200, description = "List sweeper actions", body = StdResponse<Vec<DeliverySweeperActionEntity>, String>)
+    )
+)]
+async fn get_sweeper_actions(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let actions: Vec<DeliverySweeperActionEntity> = delivery_sweeper_actions::table
+        .order_by(delivery_sweeper_actions::created_at.desc())
+        .get_results(conn)
+        .await
+        .context("Failed to get sweeper actions")?;
+
+    Ok(StdResponse {
+        data: Some(actions),
+        message: Some("Get sweeper actions successfully"),
+    })
+}
@@ -0,0 +1,476 @@
+use anyhow::Context;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use medbook_core::{
+    app_error::{AppError, StdResponse},
+    app_state::AppState,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        CreateWebhookDeliveryEntity, CreateWebhookSubscriptionEntity, WebhookDeliveryAttemptEntity,
+        WebhookDeliveryEntity, WebhookSubscriptionEntity,
+    },
+    schema::{webhook_deliveries, webhook_delivery_attempts, webhook_subscriptions},
+    services::{
+        self,
+        webhooks::{EVENT_TYPES, TEST_EVENT_TYPE},
+    },
+};
+
+/// Most recent webhooks listed per subscription.
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+/// Defines routes with OpenAPI specs. Should be used over `routes()` where possible.
+pub fn routes_with_openapi() -> OpenApiRouter<AppState> {
+    utoipa_axum::router::OpenApiRouter::new().nest(
+        "/webhooks",
+        OpenApiRouter::new()
+            .routes(utoipa_axum::routes!(get_subscriptions, create_subscription))
+            .routes(utoipa_axum::routes!(
+                get_subscription,
+                update_subscription,
+                delete_subscription
+            ))
+            .routes(utoipa_axum::routes!(get_subscription_deliveries))
+            .routes(utoipa_axum::routes!(send_test_event))
+            .routes(utoipa_axum::routes!(get_webhook_delivery))
+            .routes(utoipa_axum::routes!(resend_webhook_delivery)),
+    )
+}
+
+/// Fetch all webhook subscriptions.
+#[utoipa::path(
+    get,
+    path = "/subscriptions",
+    tags = ["Webhooks"],
+    responses(
+        (status = 200, description = "List all webhook subscriptions", body = StdResponse<Vec<WebhookSubscriptionEntity>, String>)
+    )
+)]
+async fn get_subscriptions(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let subscriptions: Vec<WebhookSubscriptionEntity> = webhook_subscriptions::table
+        .order_by(webhook_subscriptions::id.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get webhook subscriptions")?;
+
+    Ok(StdResponse {
+        data: Some(subscriptions),
+        message: Some("Get webhook subscriptions successfully"),
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateSubscriptionReq {
+    /// Partner the webhooks go to.
+    name: String,
+    /// HTTP(S) endpoint webhooks are posted to.
+    url: String,
+    /// Outbox event types to send, such as `orders.delivery_success`.
+    event_types: Vec<String>,
+    /// Shared secret signing the webhooks, at least 16 characters.
+    secret: String,
+    is_active: bool,
+}
+
+impl CreateSubscriptionReq {
+    fn into_entity(self) -> Result<CreateWebhookSubscriptionEntity, AppError> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|err| AppError::BadRequest(format!("Invalid url: {err}")))?;
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(AppError::BadRequest("url must be HTTP or HTTPS".into()));
+        }
+        if self.event_types.is_empty() {
+            return Err(AppError::BadRequest(
+                "Subscribe to at least one event type".into(),
+            ));
+        }
+        if let Some(unknown) = self
+            .event_types
+            .iter()
+            .find(|event_type| !EVENT_TYPES.contains(&event_type.as_str()))
+        {
+            return Err(AppError::BadRequest(format!(
+                "Unknown event type {unknown}, allowed event types are: {}",
+                EVENT_TYPES.join(", ")
+            )));
+        }
+        if !(16..=128).contains(&self.secret.len()) {
+            return Err(AppError::BadRequest(
+                "secret must be between 16 and 128 characters".into(),
+            ));
+        }
+        Ok(CreateWebhookSubscriptionEntity {
+            name: self.name,
+            url: self.url,
+            event_types: json!(self.event_types),
+            secret: self.secret,
+            is_active: self.is_active,
+        })
+    }
+}
+
+/// Subscribe a partner to outbox events.
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tags = ["Webhooks"],
+    request_body = CreateSubscriptionReq,
+    responses(
+        (status = 200, description = "Created webhook subscription successfully", body = StdResponse<WebhookSubscriptionEntity, String>)
+    )
+)]
+async fn create_subscription(
+    State(state): State<AppState>,
+    Json(body): Json<CreateSubscriptionReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let new_subscription = body.into_entity()?;
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let subscription: WebhookSubscriptionEntity = diesel::insert_into(webhook_subscriptions::table)
+        .values(new_subscription)
+        .returning(WebhookSubscriptionEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to create webhook subscription")?;
+
+    Ok(StdResponse {
+        data: Some(subscription),
+        message: Some("Created webhook subscription successfully"),
+    })
+}
+
+/// Fetch a webhook subscription.
+#[utoipa::path(
+    get,
+    path = "/subscriptions/{id}",
+    tags = ["Webhooks"],
+    params(
+        ("id" = i32, Path, description = "Webhook subscription ID to fetch")
+    ),
+    responses(
+        (status = 200, description = "Fetched webhook subscription successfully", body = StdResponse<WebhookSubscriptionEntity, String>)
+    )
+)]
+async fn get_subscription(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let subscription: WebhookSubscriptionEntity = webhook_subscriptions::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get webhook subscription")?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(subscription),
+        message: Some("Get webhook subscription successfully"),
+    })
+}
+
+/// Update a webhook subscription. Webhooks already queued keep their event.
+#[utoipa::path(
+    patch,
+    path = "/subscriptions/{id}",
+    tags = ["Webhooks"],
+    params(
+        ("id" = i32, Path, description = "Webhook subscription ID to update")
+    ),
+    request_body = CreateSubscriptionReq,
+    responses(
+        (status = 200, description = "Updated webhook subscription successfully", body = StdResponse<WebhookSubscriptionEntity, String>)
+    )
+)]
+async fn update_subscription(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+    Json(body): Json<CreateSubscriptionReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let changes = body.into_entity()?;
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let subscription: WebhookSubscriptionEntity =
+        diesel::update(webhook_subscriptions::table.find(id))
+            .set(changes)
+            .returning(WebhookSubscriptionEntity::as_returning())
+            .get_result(conn)
+            .await
+            .optional()
+            .context("Failed to update webhook subscription")?
+            .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(subscription),
+        message: Some("Updated webhook subscription successfully"),
+    })
+}
+
+/// Delete a webhook subscription along with its delivery log.
+#[utoipa::path(
+    delete,
+    path = "/subscriptions/{id}",
+    tags = ["Webhooks"],
+    params(
+        ("id" = i32, Path, description = "Webhook subscription ID to delete")
+    ),
+    responses(
+        (status = 200, description = "Deleted webhook subscription successfully", body = StdResponse<WebhookSubscriptionEntity, String>)
+    )
+)]
+async fn delete_subscription(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let subscription: WebhookSubscriptionEntity =
+        diesel::delete(webhook_subscriptions::table.find(id))
+            .returning(WebhookSubscriptionEntity::as_returning())
+            .get_result(conn)
+            .await
+            .optional()
+            .context("Failed to delete webhook subscription")?
+            .ok_or(AppError::NotFound)?;
+
+    Ok(StdResponse {
+        data: Some(subscription),
+        message: Some("Deleted webhook subscription successfully"),
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+struct GetSubscriptionDeliveriesQuery {
+    /// Only webhooks with this status, `PENDING`, `SUCCEEDED` or `FAILED`.
+    status: Option<String>,
+}
+
+/// Fetch the most recent webhooks sent to a subscription, newest first.
+#[utoipa::path(
+    get,
+    path = "/subscriptions/{id}/deliveries",
+    tags = ["Webhooks"],
+    params(
+        ("id" = i32, Path, description = "Webhook subscription ID to fetch the log of"),
+        GetSubscriptionDeliveriesQuery
+    ),
+    responses(
+        (status = 200, description = "List webhooks of the subscription", body = StdResponse<Vec<WebhookDeliveryEntity>, String>)
+    )
+)]
+async fn get_subscription_deliveries(
+    Path(id): Path<i32>,
+    Query(query): Query<GetSubscriptionDeliveriesQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let mut deliveries = webhook_deliveries::table
+        .filter(webhook_deliveries::subscription_id.eq(id))
+        .order_by(webhook_deliveries::created_at.desc())
+        .limit(DELIVERY_LOG_LIMIT)
+        .into_boxed();
+    if let Some(status) = query.status {
+        deliveries = deliveries.filter(webhook_deliveries::status.eq(status));
+    }
+    let deliveries: Vec<WebhookDeliveryEntity> = deliveries
+        .get_results(conn)
+        .await
+        .context("Failed to get webhooks")?;
+
+    Ok(StdResponse {
+        data: Some(deliveries),
+        message: Some("Get webhooks successfully"),
+    })
+}
+
+/// Send a test event to a subscription right away, whatever event types it subscribes to.
+#[utoipa::path(
+    post,
+    path = "/subscriptions/{id}/test",
+    tags = ["Webhooks"],
+    params(
+        ("id" = i32, Path, description = "Webhook subscription ID to send a test event to")
+    ),
+    responses(
+        (status = 200, description = "Sent test event", body = StdResponse<WebhookDeliveryEntity, String>)
+    )
+)]
+async fn send_test_event(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let subscription: WebhookSubscriptionEntity = webhook_subscriptions::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get webhook subscription")?
+        .ok_or(AppError::NotFound)?;
+
+    let delivery: WebhookDeliveryEntity = diesel::insert_into(webhook_deliveries::table)
+        .values(CreateWebhookDeliveryEntity {
+            subscription_id: id,
+            outbox_id: None,
+            event_type: TEST_EVENT_TYPE.into(),
+            payload: json!({ "subscription_id": id, "name": subscription.name }).to_string(),
+            next_attempt_at: None,
+        })
+        .returning(WebhookDeliveryEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to create test webhook")?;
+    let delivery = services::webhooks::send(conn, &subscription, &delivery).await?;
+
+    Ok(StdResponse {
+        data: Some(delivery),
+        message: Some("Sent test event"),
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct WebhookDeliveryWithAttempts {
+    webhook: WebhookDeliveryEntity,
+    attempts: Vec<WebhookDeliveryAttemptEntity>,
+}
+
+/// Fetch a webhook with every attempt at sending it.
+#[utoipa::path(
+    get,
+    path = "/deliveries/{id}",
+    tags = ["Webhooks"],
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID to fetch")
+    ),
+    responses(
+        (status = 200, description = "Fetched webhook successfully", body = StdResponse<WebhookDeliveryWithAttempts, String>)
+    )
+)]
+async fn get_webhook_delivery(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    let webhook: WebhookDeliveryEntity = webhook_deliveries::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get webhook")?
+        .ok_or(AppError::NotFound)?;
+    let attempts: Vec<WebhookDeliveryAttemptEntity> = webhook_delivery_attempts::table
+        .filter(webhook_delivery_attempts::webhook_delivery_id.eq(id))
+        .order_by(webhook_delivery_attempts::attempted_at.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get webhook attempts")?;
+
+    Ok(StdResponse {
+        data: Some(WebhookDeliveryWithAttempts { webhook, attempts }),
+        message: Some("Get webhook successfully"),
+    })
+}
+
+/// Re-send a webhook right away, such as one given up on. Its retries start over should
+/// the partner fail again. Webhooks still being retried are left to the dispatcher.
+#[utoipa::path(
+    post,
+    path = "/deliveries/{id}/resend",
+    tags = ["Webhooks"],
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID to re-send")
+    ),
+    responses(
+        (status = 200, description = "Re-sent webhook", body = StdResponse<WebhookDeliveryEntity, String>)
+    )
+)]
+async fn resend_webhook_delivery(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = &mut state
+        .db_pool
+        .get()
+        .await
+        .context("Failed to obtain a DB connection pool")?;
+
+    // Taken off the schedule while it is sent, so the dispatcher does not send it too.
+    let delivery: WebhookDeliveryEntity = diesel::update(
+        webhook_deliveries::table
+            .find(id)
+            .filter(webhook_deliveries::status.ne("PENDING")),
+    )
+    .set((
+        webhook_deliveries::status.eq("PENDING"),
+        webhook_deliveries::attempts.eq(0),
+        webhook_deliveries::next_attempt_at.eq(None::<chrono::DateTime<chrono::Utc>>),
+    ))
+    .returning(WebhookDeliveryEntity::as_returning())
+    .get_result(conn)
+    .await
+    .optional()
+    .context("Failed to reset webhook")?
+    .ok_or_else(|| AppError::BadRequest("Webhook not found or still being retried".into()))?;
+    let subscription: WebhookSubscriptionEntity = webhook_subscriptions::table
+        .find(delivery.subscription_id)
+        .get_result(conn)
+        .await
+        .context("Failed to get webhook subscription")?;
+    let delivery = services::webhooks::send(conn, &subscription, &delivery).await?;
+
+    Ok(StdResponse {
+        data: Some(delivery),
+        message: Some("Re-sent webhook"),
+    })
+}
@@ -1,5 +1,88 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    calendar_closures (id) {
+        id -> Int4,
+        #[max_length = 64]
+        zone -> Nullable<Varchar>,
+        location_id -> Nullable<Int4>,
+        starts_on -> Date,
+        ends_on -> Date,
+        reason -> Text,
+        #[max_length = 100]
+        created_by -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    carrier_events (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        carrier -> Varchar,
+        #[max_length = 128]
+        event_id -> Varchar,
+        #[max_length = 128]
+        tracking_number -> Varchar,
+        #[max_length = 64]
+        code -> Varchar,
+        description -> Nullable<Text>,
+        occurred_at -> Timestamptz,
+        delivery_id -> Nullable<Uuid>,
+        #[max_length = 16]
+        outcome -> Varchar,
+        note -> Nullable<Text>,
+        #[max_length = 100]
+        reviewed_by -> Nullable<Varchar>,
+        reviewed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    carrier_requests (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        #[max_length = 64]
+        carrier -> Varchar,
+        #[max_length = 16]
+        action -> Varchar,
+        #[max_length = 128]
+        tracking_number -> Nullable<Varchar>,
+        reason -> Text,
+        #[max_length = 16]
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Nullable<Timestamptz>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    couriers (id) {
+        id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 20]
+        phone_number -> Nullable<Varchar>,
+        is_active -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        #[max_length = 32]
+        vehicle_type -> Varchar,
+        #[max_length = 64]
+        zone -> Nullable<Varchar>,
+        max_deliveries -> Nullable<Int4>,
+        capacity_grams -> Nullable<Int4>,
+        latitude -> Nullable<Float8>,
+        longitude -> Nullable<Float8>,
+    }
+}
+
 diesel::table! {
     deliveries (id) {
         id -> Uuid,
@@ -9,6 +92,47 @@ diesel::table! {
         status -> Varchar,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        min_temperature_c -> Nullable<Float8>,
+        max_temperature_c -> Nullable<Float8>,
+        requires_review -> Bool,
+        is_controlled -> Bool,
+        requires_id_verification -> Bool,
+        courier_id -> Nullable<Int4>,
+        handed_over_at -> Nullable<Timestamptz>,
+        latitude -> Nullable<Float8>,
+        longitude -> Nullable<Float8>,
+        slot_start -> Nullable<Timestamptz>,
+        slot_end -> Nullable<Timestamptz>,
+        route_sequence -> Nullable<Int4>,
+        estimated_arrival_at -> Nullable<Timestamptz>,
+        #[max_length = 64]
+        zone -> Nullable<Varchar>,
+        #[max_length = 16]
+        priority -> Varchar,
+        patient_id -> Nullable<Int4>,
+        delivery_instructions -> Nullable<Text>,
+        access_code_encrypted -> Nullable<Bytea>,
+        #[max_length = 16]
+        preferred_contact_method -> Nullable<Varchar>,
+        contactless_consent -> Bool,
+        schedule_id -> Nullable<Uuid>,
+        #[max_length = 32]
+        fulfilment_mode -> Varchar,
+        #[max_length = 128]
+        pickup_point -> Nullable<Varchar>,
+        #[max_length = 64]
+        locker_compartment -> Nullable<Varchar>,
+        #[max_length = 64]
+        pickup_code_hash -> Nullable<Varchar>,
+        pickup_expires_at -> Nullable<Timestamptz>,
+        collected_at -> Nullable<Timestamptz>,
+        origin_location_id -> Nullable<Int4>,
+        #[max_length = 64]
+        carrier -> Nullable<Varchar>,
+        #[max_length = 128]
+        tracking_number -> Nullable<Varchar>,
+        assigned_at -> Nullable<Timestamptz>,
+        pickup_code_attempts -> Int4,
     }
 }
 
@@ -33,6 +157,142 @@ diesel::table! {
         is_default -> Nullable<Bool>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        delivery_instructions -> Nullable<Text>,
+        access_code_encrypted -> Nullable<Bytea>,
+        #[max_length = 16]
+        preferred_contact_method -> Nullable<Varchar>,
+        contactless_consent -> Bool,
+        #[max_length = 100]
+        recipient_name_latin -> Nullable<Varchar>,
+        #[max_length = 255]
+        street_address_latin -> Nullable<Varchar>,
+        #[max_length = 100]
+        city_latin -> Nullable<Varchar>,
+        #[max_length = 100]
+        state_latin -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    delivery_custody_entries (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        sequence -> Int4,
+        #[max_length = 64]
+        handoff_type -> Varchar,
+        #[max_length = 100]
+        from_actor -> Varchar,
+        #[max_length = 100]
+        to_actor -> Varchar,
+        location -> Text,
+        #[max_length = 100]
+        seal_number -> Varchar,
+        handed_over_at -> Timestamptz,
+        #[max_length = 64]
+        previous_hash -> Nullable<Varchar>,
+        #[max_length = 64]
+        entry_hash -> Varchar,
+        #[max_length = 64]
+        signature -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_id_verifications (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        #[max_length = 64]
+        id_type -> Varchar,
+        #[max_length = 64]
+        masked_id_number -> Varchar,
+        #[max_length = 100]
+        presented_name -> Varchar,
+        #[max_length = 100]
+        recipient_name -> Nullable<Varchar>,
+        name_matched -> Bool,
+        #[max_length = 100]
+        verified_by -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_incident_attachments (id) {
+        id -> Uuid,
+        incident_id -> Uuid,
+        #[max_length = 255]
+        file_name -> Varchar,
+        #[max_length = 100]
+        content_type -> Varchar,
+        size_bytes -> Int4,
+        content -> Bytea,
+        #[max_length = 16]
+        uploaded_by_type -> Varchar,
+        #[max_length = 100]
+        uploaded_by -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_incident_comments (id) {
+        id -> Uuid,
+        incident_id -> Uuid,
+        #[max_length = 16]
+        author_type -> Varchar,
+        #[max_length = 100]
+        author -> Varchar,
+        body -> Text,
+        is_internal -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_incidents (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        #[max_length = 64]
+        category -> Varchar,
+        #[max_length = 16]
+        severity -> Varchar,
+        #[max_length = 32]
+        status -> Varchar,
+        description -> Text,
+        #[max_length = 200]
+        dedup_key -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        #[max_length = 16]
+        reporter_type -> Varchar,
+        #[max_length = 100]
+        reported_by -> Nullable<Varchar>,
+        #[max_length = 16]
+        resolution -> Nullable<Varchar>,
+        resolution_note -> Nullable<Text>,
+        #[max_length = 100]
+        resolved_by -> Nullable<Varchar>,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    delivery_legs (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        sequence -> Int4,
+        courier_id -> Nullable<Int4>,
+        origin_location_id -> Nullable<Int4>,
+        destination_location_id -> Nullable<Int4>,
+        #[max_length = 16]
+        status -> Varchar,
+        started_at -> Nullable<Timestamptz>,
+        completed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -48,6 +308,212 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    delivery_orders (delivery_id, order_id) {
+        delivery_id -> Uuid,
+        order_id -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_packages (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        package_number -> Int4,
+        weight_grams -> Int4,
+        length_mm -> Int4,
+        width_mm -> Int4,
+        height_mm -> Int4,
+        is_fragile -> Bool,
+        is_refrigerated -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        labelled_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    delivery_ratings (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        patient_id -> Int4,
+        courier_id -> Nullable<Int4>,
+        #[max_length = 64]
+        zone -> Nullable<Varchar>,
+        rating -> Int4,
+        tags -> Jsonb,
+        feedback -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_scans (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        package_id -> Nullable<Uuid>,
+        #[max_length = 64]
+        scan_type -> Varchar,
+        location -> Text,
+        latitude -> Nullable<Float8>,
+        longitude -> Nullable<Float8>,
+        #[max_length = 100]
+        scanned_by -> Varchar,
+        scanned_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_schedule_runs (id) {
+        id -> Uuid,
+        schedule_id -> Uuid,
+        run_on -> Date,
+        #[max_length = 16]
+        status -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_schedules (id) {
+        id -> Uuid,
+        patient_id -> Int4,
+        delivery_address_id -> Nullable<Int4>,
+        source_order_id -> Int4,
+        #[max_length = 255]
+        recurrence -> Varchar,
+        #[max_length = 16]
+        status -> Varchar,
+        starts_on -> Date,
+        ends_on -> Nullable<Date>,
+        next_run_on -> Nullable<Date>,
+        reminded_for -> Nullable<Date>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_sweeper_actions (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        #[max_length = 16]
+        action -> Varchar,
+        #[max_length = 64]
+        delivery_status -> Varchar,
+        last_activity_at -> Timestamptz,
+        detail -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_temperature_excursions (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        reading_id -> Uuid,
+        temperature_c -> Float8,
+        min_temperature_c -> Nullable<Float8>,
+        max_temperature_c -> Nullable<Float8>,
+        #[max_length = 100]
+        reviewed_by -> Nullable<Varchar>,
+        review_note -> Nullable<Text>,
+        reviewed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    delivery_temperature_readings (id) {
+        id -> Uuid,
+        delivery_id -> Uuid,
+        temperature_c -> Float8,
+        #[max_length = 64]
+        source -> Varchar,
+        recorded_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    dispatch_manifest_deliveries (manifest_id, delivery_id) {
+        manifest_id -> Uuid,
+        delivery_id -> Uuid,
+        package_count -> Int4,
+    }
+}
+
+diesel::table! {
+    dispatch_manifests (id) {
+        id -> Uuid,
+        courier_id -> Nullable<Int4>,
+        window_start -> Timestamptz,
+        window_end -> Timestamptz,
+        #[max_length = 64]
+        status -> Varchar,
+        delivery_count -> Int4,
+        package_count -> Int4,
+        csv -> Text,
+        pdf -> Bytea,
+        #[max_length = 100]
+        generated_by -> Varchar,
+        #[max_length = 100]
+        handed_over_by -> Nullable<Varchar>,
+        #[max_length = 100]
+        received_by -> Nullable<Varchar>,
+        confirmed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        origin_location_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    location_opening_hours (id) {
+        id -> Int4,
+        location_id -> Int4,
+        weekday -> Int2,
+        opens_at -> Time,
+        closes_at -> Time,
+    }
+}
+
+diesel::table! {
+    locations (id) {
+        id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 16]
+        location_type -> Varchar,
+        address -> Text,
+        latitude -> Nullable<Float8>,
+        longitude -> Nullable<Float8>,
+        #[max_length = 64]
+        timezone -> Varchar,
+        cutoff_time -> Nullable<Time>,
+        is_active -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    locker_compartments (locker_id, compartment) {
+        #[max_length = 128]
+        locker_id -> Varchar,
+        #[max_length = 64]
+        compartment -> Varchar,
+        delivery_id -> Nullable<Uuid>,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     outbox (id) {
         id -> Int4,
@@ -59,11 +525,180 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    package_items (id) {
+        id -> Uuid,
+        package_id -> Uuid,
+        order_item_id -> Int4,
+        quantity -> Int4,
+        description -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    patient_delegations (id) {
+        id -> Uuid,
+        patient_id -> Int4,
+        delegate_id -> Int4,
+        #[max_length = 16]
+        access_level -> Varchar,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    patient_proxy_audit_logs (id) {
+        id -> Uuid,
+        delegation_id -> Uuid,
+        patient_id -> Int4,
+        delegate_id -> Int4,
+        #[max_length = 64]
+        action -> Varchar,
+        #[max_length = 64]
+        resource_type -> Varchar,
+        #[max_length = 64]
+        resource_id -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Uuid,
+        subscription_id -> Int4,
+        outbox_id -> Nullable<Int4>,
+        event_type -> Text,
+        payload -> Text,
+        #[max_length = 16]
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Nullable<Timestamptz>,
+        last_response_status -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        delivered_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_delivery_attempts (id) {
+        id -> Uuid,
+        webhook_delivery_id -> Uuid,
+        attempt -> Int4,
+        response_status -> Nullable<Int4>,
+        error -> Nullable<Text>,
+        duration_ms -> Int4,
+        attempted_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_outbox_cursor (id) {
+        id -> Int4,
+        last_outbox_id -> Int4,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_outbox_events (outbox_id) {
+        outbox_id -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_subscriptions (id) {
+        id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        url -> Text,
+        event_types -> Jsonb,
+        #[max_length = 128]
+        secret -> Varchar,
+        is_active -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::joinable!(calendar_closures -> locations (location_id));
+diesel::joinable!(carrier_events -> deliveries (delivery_id));
+diesel::joinable!(carrier_requests -> deliveries (delivery_id));
+diesel::joinable!(deliveries -> couriers (courier_id));
+diesel::joinable!(deliveries -> delivery_schedules (schedule_id));
+diesel::joinable!(deliveries -> locations (origin_location_id));
+diesel::joinable!(delivery_custody_entries -> deliveries (delivery_id));
+diesel::joinable!(delivery_id_verifications -> deliveries (delivery_id));
+diesel::joinable!(delivery_incident_attachments -> delivery_incidents (incident_id));
+diesel::joinable!(delivery_incident_comments -> delivery_incidents (incident_id));
+diesel::joinable!(delivery_incidents -> deliveries (delivery_id));
+diesel::joinable!(delivery_legs -> couriers (courier_id));
+diesel::joinable!(delivery_legs -> deliveries (delivery_id));
 diesel::joinable!(delivery_logs -> deliveries (delivery_id));
+diesel::joinable!(delivery_orders -> deliveries (delivery_id));
+diesel::joinable!(delivery_packages -> deliveries (delivery_id));
+diesel::joinable!(delivery_ratings -> couriers (courier_id));
+diesel::joinable!(delivery_ratings -> deliveries (delivery_id));
+diesel::joinable!(delivery_scans -> deliveries (delivery_id));
+diesel::joinable!(delivery_scans -> delivery_packages (package_id));
+diesel::joinable!(delivery_schedule_runs -> delivery_schedules (schedule_id));
+diesel::joinable!(delivery_schedules -> delivery_addresses (delivery_address_id));
+diesel::joinable!(delivery_sweeper_actions -> deliveries (delivery_id));
+diesel::joinable!(delivery_temperature_excursions -> deliveries (delivery_id));
+diesel::joinable!(delivery_temperature_excursions -> delivery_temperature_readings (reading_id));
+diesel::joinable!(delivery_temperature_readings -> deliveries (delivery_id));
+diesel::joinable!(dispatch_manifest_deliveries -> deliveries (delivery_id));
+diesel::joinable!(dispatch_manifest_deliveries -> dispatch_manifests (manifest_id));
+diesel::joinable!(dispatch_manifests -> couriers (courier_id));
+diesel::joinable!(dispatch_manifests -> locations (origin_location_id));
+diesel::joinable!(locker_compartments -> deliveries (delivery_id));
+diesel::joinable!(location_opening_hours -> locations (location_id));
+diesel::joinable!(package_items -> delivery_packages (package_id));
+diesel::joinable!(patient_proxy_audit_logs -> patient_delegations (delegation_id));
+diesel::joinable!(webhook_deliveries -> webhook_subscriptions (subscription_id));
+diesel::joinable!(webhook_delivery_attempts -> webhook_deliveries (webhook_delivery_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    calendar_closures,
+    carrier_events,
+    carrier_requests,
+    couriers,
     deliveries,
     delivery_addresses,
+    delivery_custody_entries,
+    delivery_id_verifications,
+    delivery_incident_attachments,
+    delivery_incident_comments,
+    delivery_incidents,
+    delivery_legs,
     delivery_logs,
+    delivery_orders,
+    delivery_packages,
+    delivery_ratings,
+    delivery_scans,
+    delivery_schedule_runs,
+    delivery_schedules,
+    delivery_sweeper_actions,
+    delivery_temperature_excursions,
+    delivery_temperature_readings,
+    dispatch_manifest_deliveries,
+    dispatch_manifests,
+    location_opening_hours,
+    locations,
+    locker_compartments,
     outbox,
+    package_items,
+    patient_delegations,
+    patient_proxy_audit_logs,
+    webhook_deliveries,
+    webhook_delivery_attempts,
+    webhook_outbox_cursor,
+    webhook_outbox_events,
+    webhook_subscriptions,
 );
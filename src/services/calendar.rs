@@ -0,0 +1,353 @@
+//! Working days, for SLA deadlines, delivery slots and arrival estimates.
+//!
+//! A day is not worked when it is a Thai public holiday from the bundled list, one of the
+//! configured rest days, or covered by a closure of the whole country, of a zone or of an
+//! origin location. Days are counted in the calendar's time zone, and time spent on days
+//! that are not worked does not count towards deadlines.
+
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{
+    config,
+    models::{CalendarClosureEntity, DeliveryEntity},
+    schema::calendar_closures,
+};
+
+/// How far ahead to look for a working day before treating every day as one.
+const HORIZON_DAYS: usize = 366;
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+static HOLIDAYS: OnceLock<Vec<Holiday>> = OnceLock::new();
+
+/// Thai public holidays from `data/holidays/th.csv`, earliest first.
+pub fn holidays() -> &'static [Holiday] {
+    HOLIDAYS.get_or_init(|| {
+        let mut holidays: Vec<Holiday> = include_str!("../../data/holidays/th.csv")
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#') && *line != "date,name")
+            .map(|line| {
+                let (date, name) = line
+                    .split_once(',')
+                    .expect("bundled holidays are `date,name` lines");
+                Holiday {
+                    date: date.parse().expect("bundled holidays have ISO dates"),
+                    name: name.into(),
+                }
+            })
+            .collect();
+        holidays.sort_by_key(|holiday| holiday.date);
+        holidays
+    })
+}
+
+/// This year and next, as of `today`, when no holidays are bundled for them.
+pub fn years_without_holidays(today: NaiveDate) -> Vec<i32> {
+    [today.year(), today.year() + 1]
+        .into_iter()
+        .filter(|year| {
+            !holidays()
+                .iter()
+                .any(|holiday| holiday.date.year() == *year)
+        })
+        .collect()
+}
+
+/// Logs an error for this year and next when they have no bundled holidays, as their
+/// holidays would otherwise silently count as working days.
+pub fn check_bundled_holidays() -> anyhow::Result<()> {
+    let today = Utc::now()
+        .with_timezone(&config::calendar_timezone()?)
+        .date_naive();
+    for year in years_without_holidays(today) {
+        error!(
+            year,
+            "No public holidays are bundled for {year}; add them to data/holidays/th.csv"
+        );
+    }
+    Ok(())
+}
+
+/// What a working day is decided for: closures of `zone` and of `location_id` apply on
+/// top of the nationwide ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scope<'a> {
+    pub zone: Option<&'a str>,
+    pub location_id: Option<i32>,
+}
+
+impl<'a> Scope<'a> {
+    /// The zone a delivery is in and the origin it leaves from.
+    pub fn of(delivery: &'a DeliveryEntity) -> Self {
+        Scope {
+            zone: delivery.zone.as_deref(),
+            location_id: delivery.origin_location_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    timezone: Tz,
+    rest_days: Vec<Weekday>,
+    closures: Vec<CalendarClosureEntity>,
+}
+
+impl Calendar {
+    pub fn new(
+        timezone: Tz,
+        rest_days: Vec<Weekday>,
+        closures: Vec<CalendarClosureEntity>,
+    ) -> Self {
+        Calendar {
+            timezone,
+            rest_days,
+            closures,
+        }
+    }
+
+    /// The configured calendar with the closures deadlines can still run into. Closures
+    /// that ended longer ago than the longest SLA target are left out.
+    pub async fn load(conn: &mut AsyncPgConnection) -> Result<Self, AppError> {
+        let timezone = config::calendar_timezone()?;
+        let now = Utc::now();
+        let oldest = (now - config::sla_targets()?.longest())
+            .with_timezone(&timezone)
+            .date_naive();
+        let closures: Vec<CalendarClosureEntity> = calendar_closures::table
+            .filter(calendar_closures::ends_on.ge(oldest))
+            .get_results(conn)
+            .await
+            .context("Failed to get calendar closures")?;
+        Ok(Calendar::new(
+            timezone,
+            config::non_working_weekdays()?,
+            closures,
+        ))
+    }
+
+    /// Why `date` is not worked in `scope`, or `None` when it is a working day.
+    pub fn closure_reason(&self, date: NaiveDate, scope: Scope) -> Option<&str> {
+        if let Some(holiday) = holidays().iter().find(|holiday| holiday.date == date) {
+            return Some(&holiday.name);
+        }
+        if self.rest_days.contains(&date.weekday()) {
+            return Some("Rest day");
+        }
+        self.closures
+            .iter()
+            .filter(|closure| closure.starts_on <= date && date <= closure.ends_on)
+            .find(|closure| match (&closure.zone, closure.location_id) {
+                (Some(zone), _) => scope.zone == Some(zone.as_str()),
+                (None, Some(location_id)) => scope.location_id == Some(location_id),
+                (None, None) => true,
+            })
+            .map(|closure| closure.reason.as_str())
+    }
+
+    pub fn is_working_day(&self, date: NaiveDate, scope: Scope) -> bool {
+        self.closure_reason(date, scope).is_none()
+    }
+
+    fn start_of_day(&self, date: NaiveDate) -> DateTime<Utc> {
+        let midnight = date.and_time(NaiveTime::MIN);
+        self.timezone
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map(|at| at.with_timezone(&Utc))
+            .unwrap_or_else(|| midnight.and_utc())
+    }
+
+    /// `at` itself on a working day, otherwise the start of the next working day.
+    pub fn next_working_time(&self, at: DateTime<Utc>, scope: Scope) -> DateTime<Utc> {
+        let date = at.with_timezone(&self.timezone).date_naive();
+        if self.is_working_day(date, scope) {
+            return at;
+        }
+        date.iter_days()
+            .skip(1)
+            .take(HORIZON_DAYS)
+            .find(|date| self.is_working_day(*date, scope))
+            .map_or(at, |date| self.start_of_day(date))
+    }
+
+    /// When `duration` of working time has passed since `start`, pausing over days that
+    /// are not worked.
+    pub fn add_working_time(
+        &self,
+        start: DateTime<Utc>,
+        duration: Duration,
+        scope: Scope,
+    ) -> DateTime<Utc> {
+        let mut at = self.next_working_time(start, scope);
+        let mut remaining = duration;
+        for _ in 0..HORIZON_DAYS {
+            let Some(next_date) = at.with_timezone(&self.timezone).date_naive().succ_opt() else {
+                break;
+            };
+            let end_of_day = self.start_of_day(next_date);
+            if at + remaining <= end_of_day {
+                break;
+            }
+            remaining -= end_of_day - at;
+            at = self.next_working_time(end_of_day, scope);
+        }
+        at + remaining
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bangkok calendar resting on weekends, with the given closures.
+    fn calendar(closures: Vec<CalendarClosureEntity>) -> Calendar {
+        Calendar::new(
+            chrono_tz::Asia::Bangkok,
+            vec![Weekday::Sat, Weekday::Sun],
+            closures,
+        )
+    }
+
+    fn closure(
+        zone: Option<&str>,
+        location_id: Option<i32>,
+        starts_on: NaiveDate,
+        ends_on: NaiveDate,
+    ) -> CalendarClosureEntity {
+        CalendarClosureEntity {
+            id: 1,
+            zone: zone.map(Into::into),
+            location_id,
+            starts_on,
+            ends_on,
+            reason: "Flooding".into(),
+            created_by: "ops".into(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+    }
+
+    /// `hour:minute` Bangkok time on the given day of 2026.
+    fn bangkok(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        chrono_tz::Asia::Bangkok
+            .with_ymd_and_hms(2026, month, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn next_working_time_keeps_times_on_working_days() {
+        let at = bangkok(9, 7, 10, 30);
+        assert_eq!(
+            calendar(Vec::new()).next_working_time(at, Scope::default()),
+            at
+        );
+    }
+
+    #[test]
+    fn next_working_time_skips_rest_days_and_holidays() {
+        // Friday 23 October is Chulalongkorn Day, followed by a weekend.
+        assert_eq!(
+            calendar(Vec::new()).next_working_time(bangkok(10, 23, 10, 30), Scope::default()),
+            bangkok(10, 26, 0, 0)
+        );
+    }
+
+    #[test]
+    fn next_working_time_applies_closures_of_the_scope_only() {
+        let calendar = calendar(vec![
+            closure(Some("NORTH"), None, date(9, 7), date(9, 8)),
+            closure(None, Some(7), date(9, 7), date(9, 7)),
+        ]);
+        let at = bangkok(9, 7, 10, 30);
+        let north = Scope {
+            zone: Some("NORTH"),
+            location_id: None,
+        };
+        let location = Scope {
+            zone: Some("SOUTH"),
+            location_id: Some(7),
+        };
+
+        assert_eq!(calendar.next_working_time(at, north), bangkok(9, 9, 0, 0));
+        assert_eq!(
+            calendar.next_working_time(at, location),
+            bangkok(9, 8, 0, 0)
+        );
+        assert_eq!(calendar.next_working_time(at, Scope::default()), at);
+    }
+
+    #[test]
+    fn add_working_time_adds_within_a_working_day() {
+        assert_eq!(
+            calendar(Vec::new()).add_working_time(
+                bangkok(9, 7, 10, 0),
+                Duration::hours(4),
+                Scope::default()
+            ),
+            bangkok(9, 7, 14, 0)
+        );
+    }
+
+    #[test]
+    fn add_working_time_pauses_over_the_weekend() {
+        // Ten hours from Friday 20:00: four on Friday, six from Monday's midnight.
+        assert_eq!(
+            calendar(Vec::new()).add_working_time(
+                bangkok(9, 11, 20, 0),
+                Duration::hours(10),
+                Scope::default()
+            ),
+            bangkok(9, 14, 6, 0)
+        );
+    }
+
+    #[test]
+    fn add_working_time_starts_on_the_next_working_day() {
+        assert_eq!(
+            calendar(Vec::new()).add_working_time(
+                bangkok(9, 12, 9, 0),
+                Duration::hours(1),
+                Scope::default()
+            ),
+            bangkok(9, 14, 1, 0)
+        );
+    }
+
+    #[test]
+    fn add_working_time_pauses_over_closures() {
+        let calendar = calendar(vec![closure(None, None, date(9, 8), date(9, 9))]);
+        assert_eq!(
+            calendar.add_working_time(bangkok(9, 7, 12, 0), Duration::hours(24), Scope::default()),
+            bangkok(9, 10, 12, 0)
+        );
+    }
+
+    #[test]
+    fn years_without_holidays_checks_this_year_and_next() {
+        let on = |year| NaiveDate::from_ymd_opt(year, 12, 1).unwrap();
+        assert!(years_without_holidays(on(2026)).is_empty());
+        assert_eq!(years_without_holidays(on(2027)), vec![2028]);
+        assert_eq!(years_without_holidays(on(2030)), vec![2030, 2031]);
+    }
+}
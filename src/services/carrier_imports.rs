@@ -0,0 +1,319 @@
+//! Tracking updates carriers send as CSV files.
+//!
+//! Every row becomes a carrier event received just like a webhook event, so rows imported
+//! twice are skipped and rows that could not be applied are kept for review. Rows without
+//! an event ID column are identified by their tracking number, status code and time.
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config, csv,
+    schema::carrier_events,
+    services::{
+        carrier_webhooks,
+        carriers::{CarrierAdapter, CarrierEvent},
+    },
+};
+
+/// Rows applied per transaction.
+const BATCH_SIZE: usize = 200;
+
+/// Body limit of the import route, so larger files are refused before being read.
+pub const MAX_FILE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Formats of local times accepted besides RFC 3339, read in `CALENDAR_TIMEZONE`.
+const LOCAL_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+];
+
+/// Header of the CSV column each event field is read from.
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    pub tracking_number: String,
+    pub code: String,
+    pub occurred_at: String,
+    pub description: String,
+    pub event_id: String,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            tracking_number: "tracking_number".into(),
+            code: "code".into(),
+            occurred_at: "occurred_at".into(),
+            description: "description".into(),
+            event_id: "event_id".into(),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// Parses `field=Header` pairs separated by `;`, as in `CARRIER_CSV_COLUMNS_<CARRIER>`.
+    pub fn parse(spec: &str) -> Result<Self, AppError> {
+        let mut mapping = Self::default();
+        for pair in spec
+            .split(';')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (field, header) = pair.split_once('=').ok_or_else(|| {
+                AppError::BadRequest(format!("Column mapping {pair} must be field=Header"))
+            })?;
+            let column = match field.trim() {
+                "tracking_number" => &mut mapping.tracking_number,
+                "code" => &mut mapping.code,
+                "occurred_at" => &mut mapping.occurred_at,
+                "description" => &mut mapping.description,
+                "event_id" => &mut mapping.event_id,
+                field => {
+                    return Err(AppError::BadRequest(format!(
+                        "Unknown field {field}, mappable fields are: tracking_number, code, occurred_at, description, event_id"
+                    )));
+                }
+            };
+            *column = header.trim().into();
+        }
+        Ok(mapping)
+    }
+
+    /// The mapping configured for `carrier`.
+    pub fn of(carrier: &str) -> Result<Self, AppError> {
+        Self::parse(&config::carrier_csv_columns(carrier))
+    }
+
+    fn resolve(&self, header: &[String]) -> Result<Columns, AppError> {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|column| column.trim().eq_ignore_ascii_case(name))
+        };
+        let require = |name: &str| {
+            find(name).ok_or_else(|| AppError::BadRequest(format!("CSV has no {name} column")))
+        };
+        Ok(Columns {
+            tracking_number: require(&self.tracking_number)?,
+            code: require(&self.code)?,
+            occurred_at: require(&self.occurred_at)?,
+            description: find(&self.description),
+            event_id: find(&self.event_id),
+        })
+    }
+}
+
+/// Positions of the mapped columns in a file.
+struct Columns {
+    tracking_number: usize,
+    code: usize,
+    occurred_at: usize,
+    description: Option<usize>,
+    event_id: Option<usize>,
+}
+
+impl Columns {
+    fn event(&self, record: &[String], timezone: Tz) -> Result<CarrierEvent, String> {
+        let field = |column: usize| {
+            record
+                .get(column)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+        let tracking_number = field(self.tracking_number).ok_or("Tracking number is missing")?;
+        let code = field(self.code).ok_or("Status code is missing")?;
+        let occurred_at = field(self.occurred_at).ok_or("Time is missing")?;
+        let occurred_at = parse_time(occurred_at, timezone)
+            .ok_or_else(|| format!("Time {occurred_at} is not in a known format"))?;
+        let event_id = self
+            .event_id
+            .and_then(field)
+            .map(String::from)
+            .unwrap_or_else(|| format!("csv:{tracking_number}:{code}:{}", occurred_at.timestamp()));
+
+        Ok(CarrierEvent {
+            event_id,
+            tracking_number: tracking_number.into(),
+            code: code.into(),
+            description: self.description.and_then(field).map(String::from),
+            occurred_at,
+        })
+    }
+}
+
+fn parse_time(value: &str, timezone: Tz) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    LOCAL_TIME_FORMATS.iter().find_map(|format| {
+        let time = NaiveDateTime::parse_from_str(value, format).ok()?;
+        Some(
+            timezone
+                .from_local_datetime(&time)
+                .earliest()?
+                .with_timezone(&Utc),
+        )
+    })
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ImportedRow {
+    /// Record number in the file, the header being 1.
+    pub row: usize,
+    pub tracking_number: Option<String>,
+    /// Carrier event outcome, `INVALID` for rows that could not be read or `FAILED` for
+    /// rows of a batch that was rolled back.
+    pub outcome: &'static str,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ImportReport {
+    pub carrier: String,
+    pub rows: usize,
+    pub applied: Vec<ImportedRow>,
+    pub skipped: Vec<ImportedRow>,
+    pub failed: Vec<ImportedRow>,
+}
+
+impl ImportReport {
+    fn record(
+        &mut self,
+        row: usize,
+        event: &CarrierEvent,
+        outcome: &'static str,
+        note: Option<String>,
+    ) {
+        let (list, reason) = match outcome {
+            "APPLIED" => (&mut self.applied, None),
+            "LOGGED" => (
+                &mut self.skipped,
+                Some("Status does not move the delivery forward, only logged".into()),
+            ),
+            "DUPLICATE" => (
+                &mut self.skipped,
+                Some("Imported or received before".into()),
+            ),
+            "UNMATCHED" => (
+                &mut self.failed,
+                Some(format!(
+                    "No delivery with this tracking number at {}",
+                    self.carrier
+                )),
+            ),
+            "UNRECOGNIZED" => (
+                &mut self.failed,
+                Some(format!("Unknown status code {}", event.code)),
+            ),
+            _ => (&mut self.failed, note),
+        };
+        list.push(ImportedRow {
+            row,
+            tracking_number: Some(event.tracking_number.clone()),
+            outcome,
+            reason,
+        });
+    }
+}
+
+/// Imports a tracking CSV file of `carrier`, read with `mapping`. Rows are applied in the
+/// order they happened, in batches of their own transaction, and a batch failing on
+/// something other than a refused transition is rolled back and reported as failed.
+/// Shipment cancellations are queued as carrier requests, so a batch rolled back leaves
+/// no shipment cancelled.
+pub async fn import(
+    conn: &mut AsyncPgConnection,
+    carrier: &dyn CarrierAdapter,
+    mapping: &ColumnMapping,
+    file: &str,
+) -> Result<ImportReport, AppError> {
+    let records =
+        csv::read_records(file).map_err(|err| AppError::BadRequest(format!("{err:#}")))?;
+    let Some((header, records)) = records.split_first() else {
+        return Err(AppError::BadRequest("CSV file is empty".into()));
+    };
+    let columns = mapping.resolve(header)?;
+    let timezone = config::calendar_timezone()?;
+
+    let mut report = ImportReport {
+        carrier: carrier.name().into(),
+        rows: records.len(),
+        applied: Vec::new(),
+        skipped: Vec::new(),
+        failed: Vec::new(),
+    };
+    let mut events = Vec::with_capacity(records.len());
+    for (i, record) in records.iter().enumerate() {
+        let row = i + 2;
+        match columns.event(record, timezone) {
+            Ok(event) => events.push((row, event)),
+            Err(reason) => report.failed.push(ImportedRow {
+                row,
+                tracking_number: record
+                    .get(columns.tracking_number)
+                    .filter(|tracking_number| !tracking_number.trim().is_empty())
+                    .cloned(),
+                outcome: "INVALID",
+                reason: Some(reason),
+            }),
+        }
+    }
+    events.sort_by_key(|(_, event)| event.occurred_at);
+
+    for batch in events.chunks(BATCH_SIZE) {
+        let outcomes = conn
+            .transaction(|conn| {
+                Box::pin(async move {
+                    let mut outcomes = Vec::with_capacity(batch.len());
+                    for (_, event) in batch {
+                        let outcome = carrier_webhooks::receive(conn, carrier, event).await?;
+                        let note = if outcome == "REJECTED" {
+                            carrier_events::table
+                                .filter(carrier_events::carrier.eq(carrier.name()))
+                                .filter(carrier_events::event_id.eq(&event.event_id))
+                                .select(carrier_events::note)
+                                .get_result(conn)
+                                .await
+                                .context("Failed to get carrier event")?
+                        } else {
+                            None
+                        };
+                        outcomes.push((outcome, note));
+                    }
+                    Ok::<_, AppError>(outcomes)
+                })
+            })
+            .await;
+
+        match outcomes {
+            Ok(outcomes) => {
+                for ((row, event), (outcome, note)) in batch.iter().zip(outcomes) {
+                    report.record(*row, event, outcome, note);
+                }
+            }
+            Err(err) => {
+                for (row, event) in batch {
+                    report.failed.push(ImportedRow {
+                        row: *row,
+                        tracking_number: Some(event.tracking_number.clone()),
+                        outcome: "FAILED",
+                        reason: Some(format!("Batch rolled back: {err}")),
+                    });
+                }
+            }
+        }
+    }
+
+    for rows in [&mut report.applied, &mut report.skipped, &mut report.failed] {
+        rows.sort_by_key(|row| row.row);
+    }
+    Ok(report)
+}
@@ -0,0 +1,245 @@
+//! Tracking updates carriers push to us.
+//!
+//! Webhooks are signed with an HMAC-SHA256 of their body under one of the carrier's
+//! secrets. Every event is stored once per carrier event ID, so redelivered webhooks are
+//! ignored, and events that could not be applied are kept for review.
+
+use anyhow::Context;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use hmac::{Hmac, Mac};
+use medbook_core::app_error::AppError;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    models::{CreateCarrierEventEntity, CreateDeliveryLogEntity, DeliveryEntity},
+    schema::{carrier_events, deliveries, delivery_logs},
+    services::{
+        carriers::{CarrierAdapter, CarrierEvent},
+        deliveries::{CLOSED_STATUSES, moves_forward, transition},
+    },
+};
+
+/// Outcomes of events someone should look at.
+pub const REVIEW_OUTCOMES: [&str; 3] = ["UNRECOGNIZED", "UNMATCHED", "REJECTED"];
+
+/// Whether `signature`, hex encoded and optionally prefixed with `sha256=`, signs `body`
+/// under any of `secrets`.
+pub fn verify_signature(secrets: &[String], body: &[u8], signature: &str) -> bool {
+    let signature = signature.trim();
+    let Ok(signature) = hex::decode(signature.strip_prefix("sha256=").unwrap_or(signature)) else {
+        return false;
+    };
+    secrets.iter().any(|secret| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body);
+        mac.verify_slice(&signature).is_ok()
+    })
+}
+
+async fn find_delivery(
+    conn: &mut AsyncPgConnection,
+    carrier: &str,
+    tracking_number: &str,
+) -> Result<Option<DeliveryEntity>, AppError> {
+    let delivery = deliveries::table
+        .filter(deliveries::carrier.eq(carrier))
+        .filter(deliveries::tracking_number.eq(tracking_number))
+        .for_update()
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery by tracking number")?;
+    Ok(delivery)
+}
+
+/// Stores `event` unless it was received before, returning the ID of the stored event or
+/// `None` for a duplicate. A concurrent delivery of the same event waits here until the
+/// first one commits, then sees it as a duplicate. The outcome is only settled once the
+/// event was applied, within the same transaction.
+async fn claim(
+    conn: &mut AsyncPgConnection,
+    carrier: &str,
+    event: &CarrierEvent,
+) -> Result<Option<Uuid>, AppError> {
+    let id = diesel::insert_into(carrier_events::table)
+        .values(CreateCarrierEventEntity {
+            carrier: carrier.into(),
+            event_id: event.event_id.clone(),
+            tracking_number: event.tracking_number.clone(),
+            code: event.code.clone(),
+            description: event.description.clone(),
+            occurred_at: event.occurred_at,
+            delivery_id: None,
+            outcome: "RECEIVED".into(),
+            note: None,
+        })
+        .on_conflict((carrier_events::carrier, carrier_events::event_id))
+        .do_nothing()
+        .returning(carrier_events::id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to store carrier event")?;
+    Ok(id)
+}
+
+async fn settle(
+    conn: &mut AsyncPgConnection,
+    id: Uuid,
+    delivery_id: Option<Uuid>,
+    outcome: &'static str,
+    note: Option<String>,
+) -> Result<&'static str, AppError> {
+    diesel::update(carrier_events::table.find(id))
+        .set((
+            carrier_events::delivery_id.eq(delivery_id),
+            carrier_events::outcome.eq(outcome),
+            carrier_events::note.eq(note),
+        ))
+        .execute(conn)
+        .await
+        .context("Failed to store carrier event outcome")?;
+    Ok(outcome)
+}
+
+/// Applies a carrier event to its delivery. Status codes moving the delivery forward, or
+/// calling it off, transition it; other known codes are only logged.
+async fn apply(
+    conn: &mut AsyncPgConnection,
+    carrier: &dyn CarrierAdapter,
+    event: &CarrierEvent,
+    id: Uuid,
+) -> Result<&'static str, AppError> {
+    let Some(delivery) = find_delivery(conn, carrier.name(), &event.tracking_number).await? else {
+        return settle(conn, id, None, "UNMATCHED", None).await;
+    };
+    let Some(status) = carrier.map_status(&event.code) else {
+        return settle(conn, id, Some(delivery.id), "UNRECOGNIZED", None).await;
+    };
+
+    let mut description = format!("Carrier {} reported {}", carrier.name(), event.code);
+    if let Some(carrier_description) = &event.description {
+        description.push_str(&format!(": {carrier_description}"));
+    }
+    let calls_off = ["CANCELLED", "RETURNING"].contains(&status)
+        && status != delivery.status
+        && !CLOSED_STATUSES.contains(&delivery.status.as_str());
+
+    let outcome = if calls_off || moves_forward(&delivery.fulfilment_mode, &delivery.status, status)
+    {
+        transition(conn, delivery.id, status.into(), description).await?;
+        "APPLIED"
+    } else {
+        diesel::insert_into(delivery_logs::table)
+            .values(CreateDeliveryLogEntity {
+                delivery_id: delivery.id,
+                description,
+                status: delivery.status.clone(),
+            })
+            .execute(conn)
+            .await
+            .context("Failed to create delivery log")?;
+        "LOGGED"
+    };
+    settle(conn, id, Some(delivery.id), outcome, None).await
+}
+
+/// Stores and applies one event pushed by `carrier` in a transaction of its own, returning
+/// its outcome, or `DUPLICATE` for events received before. Transitions the delivery refuses, such as a
+/// controlled delivery reported delivered without a custody handoff, are stored as
+/// `REJECTED`.
+pub async fn receive(
+    conn: &mut AsyncPgConnection,
+    carrier: &dyn CarrierAdapter,
+    event: &CarrierEvent,
+) -> Result<&'static str, AppError> {
+    conn.transaction(|conn| {
+        Box::pin(async move {
+            let Some(id) = claim(conn, carrier.name(), event).await? else {
+                return Ok("DUPLICATE");
+            };
+            let applied = conn
+                .transaction(|conn| Box::pin(apply(conn, carrier, event, id)))
+                .await;
+            match applied {
+                Err(AppError::BadRequest(reason)) => {
+                    let delivery =
+                        find_delivery(conn, carrier.name(), &event.tracking_number).await?;
+                    settle(
+                        conn,
+                        id,
+                        delivery.map(|delivery| delivery.id),
+                        "REJECTED",
+                        Some(reason),
+                    )
+                    .await
+                }
+                applied => applied,
+            }
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn secrets() -> Vec<String> {
+        vec!["old-secret".into(), "new-secret".into()]
+    }
+
+    #[test]
+    fn verify_signature_accepts_any_current_secret() {
+        let body = br#"{"events":[]}"#;
+        assert!(verify_signature(
+            &secrets(),
+            body,
+            &sign("old-secret", body)
+        ));
+        assert!(verify_signature(
+            &secrets(),
+            body,
+            &sign("new-secret", body)
+        ));
+    }
+
+    #[test]
+    fn verify_signature_accepts_a_prefix_and_surrounding_whitespace() {
+        let body = br#"{"events":[]}"#;
+        let signature = format!(" sha256={} ", sign("new-secret", body));
+        assert!(verify_signature(&secrets(), body, &signature));
+    }
+
+    #[test]
+    fn verify_signature_rejects_other_secrets_and_bodies() {
+        let body = br#"{"events":[]}"#;
+        assert!(!verify_signature(
+            &secrets(),
+            body,
+            &sign("other-secret", body)
+        ));
+        assert!(!verify_signature(
+            &secrets(),
+            br#"{"events":[{}]}"#,
+            &sign("new-secret", body)
+        ));
+    }
+
+    #[test]
+    fn verify_signature_rejects_malformed_and_missing_signatures() {
+        let body = br#"{"events":[]}"#;
+        assert!(!verify_signature(&secrets(), body, "sha256=not-hex"));
+        assert!(!verify_signature(&secrets(), body, ""));
+        assert!(!verify_signature(&[], body, &sign("new-secret", body)));
+    }
+}
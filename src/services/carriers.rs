@@ -0,0 +1,648 @@
+//! Deliveries outsourced to external courier companies.
+//!
+//! Each carrier is integrated through a [`CarrierAdapter`], and the [`CarrierRegistry`]
+//! decides from `CARRIER_RULES` which deliveries auto-dispatch hands to which carrier.
+//! Outsourced deliveries keep the carrier's name and tracking number, and are no longer
+//! offered to our own couriers.
+//!
+//! Carriers are never called inside a transaction. Bookings and cancellations are queued
+//! as carrier requests in the transaction deciding on them, the carrier is called once it
+//! has committed, and its answer is recorded in a transaction of its own. Requests left
+//! pending are retried by a background job.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, IntoSql, NullableExpressionMethods, QueryDsl,
+    SelectableHelper, sql_types::Bool,
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use futures::future::BoxFuture;
+use medbook_core::app_error::AppError;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    config,
+    models::{
+        CarrierRequestEntity, CreateCarrierRequestEntity, CreateDeliveryLogEntity, DeliveryEntity,
+    },
+    schema::{carrier_requests, deliveries, delivery_logs},
+    services::{deliveries::CLOSED_STATUSES, webhooks},
+};
+
+/// Attempts at cancelling a shipment of a delivery we no longer carry out, before giving
+/// up and leaving it to be cancelled by hand.
+const MAX_CANCEL_ATTEMPTS: i32 = 5;
+const REQUEST_BATCH: i64 = 50;
+/// How long other runs leave a request alone while its carrier is called.
+const CLAIM_SECONDS: i64 = 300;
+
+/// A tracking update as reported by a carrier, in the carrier's own status codes.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct CarrierEvent {
+    /// The carrier's ID of the event, unique per carrier.
+    pub event_id: String,
+    pub tracking_number: String,
+    /// The carrier's status code.
+    pub code: String,
+    pub description: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Integration with an external courier company.
+pub trait CarrierAdapter: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Books a shipment for `delivery`, returning its tracking number.
+    fn create_shipment<'a>(&'a self, delivery: &'a DeliveryEntity)
+    -> BoxFuture<'a, Result<String>>;
+
+    /// Calls off a shipment. Cancelling a shipment twice is not an error.
+    fn cancel<'a>(&'a self, tracking_number: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Tracking history of a shipment, oldest first.
+    fn fetch_tracking<'a>(
+        &'a self,
+        tracking_number: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<CarrierEvent>>>;
+
+    /// Reads the events of a webhook the carrier pushed to us.
+    fn parse_webhook(&self, body: &[u8]) -> Result<Vec<CarrierEvent>>;
+
+    /// Our delivery status for one of the carrier's status codes, `None` when the code is
+    /// not known.
+    fn map_status(&self, code: &str) -> Option<&'static str>;
+}
+
+/// Stages a mock shipment goes through, one every step, with our matching status.
+const MOCK_STAGES: [(&str, &str); 5] = [
+    ("ACCEPTED", "PREPARING"),
+    ("PICKED_UP", "PICKED_UP"),
+    ("IN_TRANSIT", "PICKED_UP"),
+    ("OUT_FOR_DELIVERY", "EN_ROUTE"),
+    ("DELIVERED", "DELIVERED"),
+];
+
+#[derive(Debug, Clone, Copy)]
+struct MockShipment {
+    created_at: DateTime<Utc>,
+    cancelled_at: Option<DateTime<Utc>>,
+}
+
+/// Simulates a carrier for local testing. Shipments move to the next status every `step`
+/// and live in memory only, so they do not survive a restart.
+pub struct MockCarrier {
+    step: Duration,
+    shipments: Mutex<HashMap<String, MockShipment>>,
+}
+
+#[derive(Deserialize)]
+struct MockWebhook {
+    events: Vec<CarrierEvent>,
+}
+
+impl MockCarrier {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step,
+            shipments: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn events_of(
+        &self,
+        tracking_number: &str,
+        shipment: MockShipment,
+        now: DateTime<Utc>,
+    ) -> Vec<CarrierEvent> {
+        let until = shipment.cancelled_at.unwrap_or(now).min(now);
+        let mut events: Vec<CarrierEvent> = MOCK_STAGES
+            .iter()
+            .zip(0..)
+            .map(|((code, _), i)| (code, shipment.created_at + self.step * i))
+            .take_while(|(_, occurred_at)| *occurred_at <= until)
+            .map(|(code, occurred_at)| CarrierEvent {
+                event_id: format!("{tracking_number}-{code}"),
+                tracking_number: tracking_number.into(),
+                code: code.to_string(),
+                description: None,
+                occurred_at,
+            })
+            .collect();
+        if let Some(cancelled_at) = shipment.cancelled_at {
+            events.push(CarrierEvent {
+                event_id: format!("{tracking_number}-CANCELLED"),
+                tracking_number: tracking_number.into(),
+                code: "CANCELLED".into(),
+                description: None,
+                occurred_at: cancelled_at,
+            });
+        }
+        events
+    }
+}
+
+impl CarrierAdapter for MockCarrier {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn create_shipment<'a>(
+        &'a self,
+        delivery: &'a DeliveryEntity,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let tracking_number = format!("MOCK{}", delivery.id.simple()).to_uppercase();
+            let mut shipments = self
+                .shipments
+                .lock()
+                .map_err(|_| anyhow!("Mock carrier state is poisoned"))?;
+            shipments
+                .entry(tracking_number.clone())
+                .or_insert(MockShipment {
+                    created_at: Utc::now(),
+                    cancelled_at: None,
+                });
+            Ok(tracking_number)
+        })
+    }
+
+    fn cancel<'a>(&'a self, tracking_number: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let now = Utc::now();
+            let mut shipments = self
+                .shipments
+                .lock()
+                .map_err(|_| anyhow!("Mock carrier state is poisoned"))?;
+            let shipment = shipments
+                .get_mut(tracking_number)
+                .with_context(|| format!("Unknown shipment {tracking_number}"))?;
+            if shipment.cancelled_at.is_some() {
+                return Ok(());
+            }
+            let delivered_at = shipment.created_at + self.step * (MOCK_STAGES.len() as i32 - 1);
+            if delivered_at <= now {
+                bail!("Shipment {tracking_number} is already delivered");
+            }
+            shipment.cancelled_at = Some(now);
+            Ok(())
+        })
+    }
+
+    fn fetch_tracking<'a>(
+        &'a self,
+        tracking_number: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<CarrierEvent>>> {
+        Box::pin(async move {
+            let shipment = *self
+                .shipments
+                .lock()
+                .map_err(|_| anyhow!("Mock carrier state is poisoned"))?
+                .get(tracking_number)
+                .with_context(|| format!("Unknown shipment {tracking_number}"))?;
+            Ok(self.events_of(tracking_number, shipment, Utc::now()))
+        })
+    }
+
+    fn parse_webhook(&self, body: &[u8]) -> Result<Vec<CarrierEvent>> {
+        let webhook: MockWebhook =
+            serde_json::from_slice(body).context("Mock carrier webhook is malformed")?;
+        Ok(webhook.events)
+    }
+
+    fn map_status(&self, code: &str) -> Option<&'static str> {
+        match code {
+            "CANCELLED" => Some("CANCELLED"),
+            code => MOCK_STAGES
+                .iter()
+                .find(|(stage, _)| *stage == code)
+                .map(|(_, status)| *status),
+        }
+    }
+}
+
+/// What a carrier rule matches deliveries on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleField {
+    Zone,
+    Priority,
+}
+
+/// Sends deliveries whose `field` equals `value` to `carrier`.
+#[derive(Debug, Clone)]
+pub struct CarrierRule {
+    pub field: RuleField,
+    pub value: String,
+    pub carrier: String,
+}
+
+impl CarrierRule {
+    pub fn matches(&self, delivery: &DeliveryEntity) -> bool {
+        match self.field {
+            RuleField::Zone => delivery.zone.as_deref() == Some(self.value.as_str()),
+            RuleField::Priority => delivery.priority == self.value,
+        }
+    }
+}
+
+impl fmt::Display for CarrierRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field {
+            RuleField::Zone => write!(f, "zone {}", self.value),
+            RuleField::Priority => write!(f, "priority {}", self.value),
+        }
+    }
+}
+
+/// The carriers we integrate with, and the rules picking one for a delivery.
+pub struct CarrierRegistry {
+    adapters: HashMap<&'static str, Box<dyn CarrierAdapter>>,
+    rules: Vec<CarrierRule>,
+}
+
+impl CarrierRegistry {
+    pub fn new(adapters: Vec<Box<dyn CarrierAdapter>>) -> Self {
+        Self {
+            adapters: adapters
+                .into_iter()
+                .map(|adapter| (adapter.name(), adapter))
+                .collect(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Parses rules written as `zone:ZONE=CARRIER;priority:PRIORITY=CARRIER`. Every rule
+    /// must name a registered carrier.
+    pub fn with_rules(mut self, spec: &str) -> Result<Self> {
+        for rule in spec
+            .split(';')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let (matcher, carrier) = rule
+                .split_once('=')
+                .with_context(|| format!("Carrier rule {rule} has no carrier"))?;
+            let (field, value) = matcher
+                .split_once(':')
+                .with_context(|| format!("Carrier rule {rule} must match a zone or priority"))?;
+            let field = match field.trim() {
+                "zone" => RuleField::Zone,
+                "priority" => RuleField::Priority,
+                field => bail!("Carrier rules cannot match on {field}"),
+            };
+            let carrier = carrier.trim();
+            if !self.adapters.contains_key(carrier) {
+                bail!("Carrier rule {rule} names unknown carrier {carrier}");
+            }
+            self.rules.push(CarrierRule {
+                field,
+                value: value.trim().into(),
+                carrier: carrier.into(),
+            });
+        }
+        Ok(self)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn CarrierAdapter> {
+        self.adapters.get(name).map(|adapter| adapter.as_ref())
+    }
+
+    /// The carrier the first matching rule sends `delivery` to, along with that rule.
+    pub fn carrier_for(
+        &self,
+        delivery: &DeliveryEntity,
+    ) -> Option<(&dyn CarrierAdapter, &CarrierRule)> {
+        let rule = self.rules.iter().find(|rule| rule.matches(delivery))?;
+        Some((self.get(&rule.carrier)?, rule))
+    }
+}
+
+static REGISTRY: OnceLock<CarrierRegistry> = OnceLock::new();
+
+/// The carrier registry, built from `CARRIER_RULES` on first use.
+pub fn registry() -> Result<&'static CarrierRegistry> {
+    if let Some(registry) = REGISTRY.get() {
+        return Ok(registry);
+    }
+    let registry = CarrierRegistry::new(vec![Box::new(MockCarrier::new(
+        config::mock_carrier_step()?,
+    ))])
+    .with_rules(&config::carrier_rules())
+    .context("CARRIER_RULES is invalid")?;
+    Ok(REGISTRY.get_or_init(|| registry))
+}
+
+/// The adapter of a carrier by name, failing on carriers we do not integrate with.
+pub fn adapter(name: &str) -> Result<&'static dyn CarrierAdapter, AppError> {
+    registry()?
+        .get(name)
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown carrier {name}")))
+}
+
+/// Reserves `delivery` for `carrier`, taking it off our couriers, and queues the booking
+/// of a shipment. The carrier is only called by [`process`] once the transaction has
+/// committed, and `reason` is added to the delivery log when it answers. Must be called
+/// inside a transaction.
+pub async fn request_shipment(
+    conn: &mut AsyncPgConnection,
+    delivery: &DeliveryEntity,
+    carrier: &dyn CarrierAdapter,
+    reason: &str,
+) -> Result<(DeliveryEntity, CarrierRequestEntity), AppError> {
+    let delivery: DeliveryEntity = diesel::update(deliveries::table.find(delivery.id))
+        .set((
+            deliveries::carrier.eq(carrier.name()),
+            deliveries::tracking_number.eq(None::<String>),
+            deliveries::courier_id.eq(None::<i32>),
+            deliveries::assigned_at.eq(None::<DateTime<Utc>>),
+        ))
+        .returning(DeliveryEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to reserve delivery for carrier")?;
+
+    let request = queue(conn, &delivery, carrier.name(), "CREATE", None, reason).await?;
+    Ok((delivery, request))
+}
+
+/// Queues the cancellation of the carrier shipment of `delivery`, if it has one. The
+/// carrier is only called by [`process`] once the transaction has committed, so nothing is
+/// cancelled for a transaction that rolls back. Must be called inside a transaction.
+pub async fn request_cancel(
+    conn: &mut AsyncPgConnection,
+    delivery: &DeliveryEntity,
+    reason: &str,
+) -> Result<Option<CarrierRequestEntity>, AppError> {
+    let (Some(carrier), Some(tracking_number)) = (&delivery.carrier, &delivery.tracking_number)
+    else {
+        return Ok(None);
+    };
+    let request = queue(
+        conn,
+        delivery,
+        carrier,
+        "CANCEL",
+        Some(tracking_number.clone()),
+        reason,
+    )
+    .await?;
+    Ok(Some(request))
+}
+
+async fn queue(
+    conn: &mut AsyncPgConnection,
+    delivery: &DeliveryEntity,
+    carrier: &str,
+    action: &str,
+    tracking_number: Option<String>,
+    reason: &str,
+) -> Result<CarrierRequestEntity, AppError> {
+    let request: CarrierRequestEntity = diesel::insert_into(carrier_requests::table)
+        .values(CreateCarrierRequestEntity {
+            delivery_id: delivery.id,
+            carrier: carrier.into(),
+            action: action.into(),
+            tracking_number,
+            reason: reason.into(),
+        })
+        .returning(CarrierRequestEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to queue carrier request")?;
+    Ok(request)
+}
+
+/// Calls the carriers for the due requests of `delivery_id`, including those queued
+/// while recording the outcome of an earlier one. Returns the requests as they are
+/// afterwards. Must not be called inside a transaction, as carriers may take a while to
+/// answer.
+pub async fn process(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Uuid,
+) -> Result<Vec<CarrierRequestEntity>, AppError> {
+    let mut processed = Vec::new();
+    loop {
+        let due = claim_due(conn, Some(delivery_id)).await?;
+        if due.is_empty() {
+            return Ok(processed);
+        }
+        for request in due {
+            processed.push(call(conn, &request).await?);
+        }
+    }
+}
+
+/// Calls the carriers for due requests of any delivery: those waiting for a retry, and
+/// those left behind when the process calling them stopped. Must not be called inside a
+/// transaction.
+pub async fn process_due(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<CarrierRequestEntity>, AppError> {
+    let mut processed = Vec::new();
+    for request in claim_due(conn, None).await? {
+        processed.push(call(conn, &request).await?);
+    }
+    Ok(processed)
+}
+
+/// Due requests, of `delivery_id` only when given, claimed so that concurrent runs leave
+/// them alone while their carrier is called.
+async fn claim_due(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Option<Uuid>,
+) -> Result<Vec<CarrierRequestEntity>, AppError> {
+    conn.transaction(move |conn| {
+        Box::pin(async move {
+            let now = Utc::now();
+            let due: Vec<CarrierRequestEntity> = carrier_requests::table
+                .filter(carrier_requests::status.eq("PENDING"))
+                .filter(carrier_requests::next_attempt_at.le(now))
+                .filter(
+                    delivery_id
+                        .is_none()
+                        .into_sql::<Bool>()
+                        .or(carrier_requests::delivery_id.nullable().eq(delivery_id)),
+                )
+                .order_by(carrier_requests::created_at.asc())
+                .limit(REQUEST_BATCH)
+                .for_update()
+                .skip_locked()
+                .get_results(conn)
+                .await
+                .context("Failed to get due carrier requests")?;
+            if due.is_empty() {
+                return Ok(due);
+            }
+
+            diesel::update(
+                carrier_requests::table
+                    .filter(carrier_requests::id.eq_any(due.iter().map(|request| request.id))),
+            )
+            .set(carrier_requests::next_attempt_at.eq(now + Duration::seconds(CLAIM_SECONDS)))
+            .execute(conn)
+            .await
+            .context("Failed to claim carrier requests")?;
+            Ok::<_, AppError>(due)
+        })
+    })
+    .await
+}
+
+/// Makes a claimed request of its carrier, then records the outcome.
+async fn call(
+    conn: &mut AsyncPgConnection,
+    request: &CarrierRequestEntity,
+) -> Result<CarrierRequestEntity, AppError> {
+    let outcome: Result<Option<String>> = match registry()?.get(&request.carrier) {
+        None => Err(anyhow!("Unknown carrier {}", request.carrier)),
+        Some(carrier) if request.action == "CREATE" => {
+            let delivery: DeliveryEntity = deliveries::table
+                .find(request.delivery_id)
+                .get_result(conn)
+                .await
+                .context("Failed to get delivery")?;
+            carrier.create_shipment(&delivery).await.map(Some)
+        }
+        Some(carrier) => match &request.tracking_number {
+            Some(tracking_number) => carrier.cancel(tracking_number).await.map(|_| None),
+            None => Err(anyhow!("No shipment to cancel")),
+        },
+    };
+
+    let request = request.clone();
+    conn.transaction(move |conn| Box::pin(record_outcome(conn, request, outcome)))
+        .await
+}
+
+/// Applies the carrier's answer to a request to its delivery.
+async fn record_outcome(
+    conn: &mut AsyncPgConnection,
+    request: CarrierRequestEntity,
+    outcome: Result<Option<String>>,
+) -> Result<CarrierRequestEntity, AppError> {
+    let delivery: DeliveryEntity = deliveries::table
+        .find(request.delivery_id)
+        .for_update()
+        .get_result(conn)
+        .await
+        .context("Failed to get delivery")?;
+    let carrier = request.carrier.as_str();
+    let attempt = request.attempts + 1;
+    // The delivery still waits on this request, rather than having moved on meanwhile.
+    let awaiting = delivery.carrier.as_deref() == Some(carrier)
+        && delivery.tracking_number == request.tracking_number;
+    let is_open = !CLOSED_STATUSES.contains(&delivery.status.as_str());
+
+    let (status, next_attempt_at, error, log) = match (request.action.as_str(), &outcome) {
+        ("CREATE", Ok(tracking_number)) => {
+            let tracking_number = tracking_number.clone().unwrap_or_default();
+            let mut log = None;
+            if awaiting {
+                let delivery: DeliveryEntity = diesel::update(deliveries::table.find(delivery.id))
+                    .set(deliveries::tracking_number.eq(&tracking_number))
+                    .returning(DeliveryEntity::as_returning())
+                    .get_result(conn)
+                    .await
+                    .context("Failed to record carrier shipment")?;
+                log = Some(format!(
+                    "Handed over to carrier {carrier} with tracking number {tracking_number}. {}",
+                    request.reason
+                ));
+                // Cancelled while the carrier was booking it.
+                if delivery.status == "CANCELLED" {
+                    request_cancel(conn, &delivery, "Delivery was cancelled while booking").await?;
+                }
+            }
+            diesel::update(carrier_requests::table.find(request.id))
+                .set(carrier_requests::tracking_number.eq(&tracking_number))
+                .execute(conn)
+                .await
+                .context("Failed to record carrier shipment")?;
+            ("SUCCEEDED", None, None, log)
+        }
+        ("CREATE", Err(err)) => {
+            let mut log = None;
+            // Deliveries the carrier turns down go back to our couriers.
+            if awaiting {
+                diesel::update(deliveries::table.find(delivery.id))
+                    .set(deliveries::carrier.eq(None::<String>))
+                    .execute(conn)
+                    .await
+                    .context("Failed to take delivery back from carrier")?;
+                log = Some(format!(
+                    "Carrier {carrier} could not take the delivery: {err}"
+                ));
+            }
+            ("FAILED", None, Some(err.to_string()), log)
+        }
+        (_, Ok(_)) => {
+            let tracking_number = request.tracking_number.clone().unwrap_or_default();
+            let log = if awaiting && is_open {
+                diesel::update(deliveries::table.find(delivery.id))
+                    .set((
+                        deliveries::carrier.eq(None::<String>),
+                        deliveries::tracking_number.eq(None::<String>),
+                    ))
+                    .execute(conn)
+                    .await
+                    .context("Failed to clear carrier shipment")?;
+                format!(
+                    "Taken back from carrier {carrier}, shipment {tracking_number} cancelled. {}",
+                    request.reason
+                )
+            } else {
+                format!(
+                    "Shipment {tracking_number} cancelled with carrier {carrier}. {}",
+                    request.reason
+                )
+            };
+            ("SUCCEEDED", None, None, Some(log))
+        }
+        (_, Err(err)) => {
+            // An open delivery stays with the carrier, so there is nothing to retry for.
+            if (awaiting && is_open) || attempt >= MAX_CANCEL_ATTEMPTS {
+                let log = format!(
+                    "Carrier {carrier} could not cancel shipment {}: {err}",
+                    request.tracking_number.as_deref().unwrap_or_default()
+                );
+                ("FAILED", None, Some(err.to_string()), Some(log))
+            } else {
+                let retry_at = Utc::now() + webhooks::backoff(attempt);
+                ("PENDING", Some(retry_at), Some(err.to_string()), None)
+            }
+        }
+    };
+
+    if let Some(description) = log {
+        diesel::insert_into(delivery_logs::table)
+            .values(CreateDeliveryLogEntity {
+                delivery_id: delivery.id,
+                description,
+                status: delivery.status.clone(),
+            })
+            .execute(conn)
+            .await
+            .context("Failed to create delivery log")?;
+    }
+
+    let request: CarrierRequestEntity = diesel::update(carrier_requests::table.find(request.id))
+        .set((
+            carrier_requests::status.eq(status),
+            carrier_requests::attempts.eq(attempt),
+            carrier_requests::next_attempt_at.eq(next_attempt_at),
+            carrier_requests::last_error.eq(error),
+        ))
+        .returning(CarrierRequestEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to update carrier request")?;
+    Ok(request)
+}
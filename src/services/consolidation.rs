@@ -0,0 +1,137 @@
+//! Consolidates orders a patient places within minutes of each other into one delivery.
+//!
+//! A new order joins the patient's most recent PREPARING delivery when it was created
+//! within the consolidation window and has the identical address snapshot, origin and
+//! fulfilment mode. The delivery then takes on the stricter handling requirements of both.
+//! Deliveries already assigned to a courier or carrier, on a manifest, or with a package
+//! label printed are left alone, as their handling has been settled and a printed label
+//! counts the packages of the delivery.
+
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use diesel::{
+    ExpressionMethods, OptionalExtension, PgExpressionMethods, QueryDsl, SelectableHelper,
+    dsl::{exists, not},
+    sql_types::Integer,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+
+use crate::{
+    models::{
+        CreateDeliveryEntity, CreateDeliveryLogEntity, CreateDeliveryOrderEntity, DeliveryEntity,
+    },
+    schema::{
+        deliveries, delivery_logs, delivery_orders, delivery_packages, dispatch_manifest_deliveries,
+    },
+    services::deliveries::priority_rank,
+};
+
+/// Key space of the advisory locks serialising consolidation per patient.
+const LOCK_SPACE: i32 = 0x6d62_0100;
+
+/// The stricter of two optional temperature bounds.
+fn stricter(current: Option<f64>, new: Option<f64>, pick: fn(f64, f64) -> f64) -> Option<f64> {
+    match (current, new) {
+        (Some(current), Some(new)) => Some(pick(current, new)),
+        (current, new) => current.or(new),
+    }
+}
+
+/// Attaches the order of `new` to a matching delivery, returning the updated delivery, or
+/// `None` when no delivery qualifies. Must be called inside a transaction.
+pub async fn attach(
+    conn: &mut AsyncPgConnection,
+    new: &CreateDeliveryEntity,
+    window: Duration,
+) -> Result<Option<DeliveryEntity>, AppError> {
+    let (Some(patient_id), Some(delivery_address)) = (new.patient_id, &new.delivery_address) else {
+        return Ok(None);
+    };
+
+    // Orders of a patient arriving together would otherwise each find no delivery and
+    // create one of their own.
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1, $2)")
+        .bind::<Integer, _>(LOCK_SPACE)
+        .bind::<Integer, _>(patient_id)
+        .execute(conn)
+        .await
+        .context("Failed to lock patient deliveries")?;
+
+    let target: Option<DeliveryEntity> = deliveries::table
+        .filter(deliveries::patient_id.eq(patient_id))
+        .filter(deliveries::delivery_address.eq(delivery_address))
+        .filter(deliveries::fulfilment_mode.eq(&new.fulfilment_mode))
+        .filter(deliveries::pickup_point.is_not_distinct_from(&new.pickup_point))
+        .filter(deliveries::origin_location_id.is_not_distinct_from(new.origin_location_id))
+        .filter(deliveries::status.eq("PREPARING"))
+        .filter(deliveries::courier_id.is_null())
+        .filter(deliveries::carrier.is_null())
+        .filter(not(exists(
+            delivery_packages::table
+                .filter(delivery_packages::delivery_id.eq(deliveries::id))
+                .filter(delivery_packages::labelled_at.is_not_null()),
+        )))
+        .filter(not(exists(dispatch_manifest_deliveries::table.filter(
+            dispatch_manifest_deliveries::delivery_id.eq(deliveries::id),
+        ))))
+        .filter(deliveries::created_at.ge(Utc::now() - window))
+        .order_by(deliveries::created_at.desc())
+        .for_update()
+        .first(conn)
+        .await
+        .optional()
+        .context("Failed to find a delivery to consolidate into")?;
+    let Some(target) = target else {
+        return Ok(None);
+    };
+
+    let min_temperature_c = stricter(target.min_temperature_c, new.min_temperature_c, f64::max);
+    let max_temperature_c = stricter(target.max_temperature_c, new.max_temperature_c, f64::min);
+    if let (Some(min), Some(max)) = (min_temperature_c, max_temperature_c)
+        && min > max
+    {
+        // The orders cannot travel in the same temperature range.
+        return Ok(None);
+    }
+    let priority = if priority_rank(&new.priority) > priority_rank(&target.priority) {
+        &new.priority
+    } else {
+        &target.priority
+    };
+
+    let delivery: DeliveryEntity = diesel::update(deliveries::table.find(target.id))
+        .set((
+            deliveries::min_temperature_c.eq(min_temperature_c),
+            deliveries::max_temperature_c.eq(max_temperature_c),
+            deliveries::is_controlled.eq(target.is_controlled || new.is_controlled),
+            deliveries::requires_id_verification
+                .eq(target.requires_id_verification || new.requires_id_verification),
+            deliveries::priority.eq(priority),
+        ))
+        .returning(DeliveryEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to consolidate delivery")?;
+
+    diesel::insert_into(delivery_orders::table)
+        .values(CreateDeliveryOrderEntity {
+            delivery_id: delivery.id,
+            order_id: new.order_id,
+        })
+        .execute(conn)
+        .await
+        .context("Failed to link order to delivery")?;
+
+    diesel::insert_into(delivery_logs::table)
+        .values(CreateDeliveryLogEntity {
+            delivery_id: delivery.id,
+            description: format!("Order {} consolidated into this delivery", new.order_id),
+            status: delivery.status.clone(),
+        })
+        .execute(conn)
+        .await
+        .context("Failed to create delivery log")?;
+
+    Ok(Some(delivery))
+}
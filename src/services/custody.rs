@@ -0,0 +1,356 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::DeliveryCustodyEntryEntity;
+
+pub const PHARMACY_TO_COURIER: &str = "PHARMACY_TO_COURIER";
+pub const COURIER_TO_COURIER: &str = "COURIER_TO_COURIER";
+pub const COURIER_TO_RECIPIENT: &str = "COURIER_TO_RECIPIENT";
+/// Parcels the patient collects at the pharmacy counter never pass through a courier.
+pub const PHARMACY_TO_RECIPIENT: &str = "PHARMACY_TO_RECIPIENT";
+
+pub const HANDOFF_TYPES: [&str; 4] = [
+    PHARMACY_TO_COURIER,
+    COURIER_TO_COURIER,
+    COURIER_TO_RECIPIENT,
+    PHARMACY_TO_RECIPIENT,
+];
+
+/// Whether custody has reached the recipient with `handoff_type`.
+fn reaches_recipient(handoff_type: &str) -> bool {
+    handoff_type == COURIER_TO_RECIPIENT || handoff_type == PHARMACY_TO_RECIPIENT
+}
+
+/// The fields of a custody entry covered by its hash.
+pub struct CustodyHandoff<'a> {
+    pub delivery_id: Uuid,
+    pub sequence: i32,
+    pub handoff_type: &'a str,
+    pub from_actor: &'a str,
+    pub to_actor: &'a str,
+    pub location: &'a str,
+    pub seal_number: &'a str,
+    /// Must already be truncated to microseconds, the precision Postgres stores.
+    pub handed_over_at: DateTime<Utc>,
+}
+
+impl<'a> From<&'a DeliveryCustodyEntryEntity> for CustodyHandoff<'a> {
+    fn from(entry: &'a DeliveryCustodyEntryEntity) -> Self {
+        Self {
+            delivery_id: entry.delivery_id,
+            sequence: entry.sequence,
+            handoff_type: &entry.handoff_type,
+            from_actor: &entry.from_actor,
+            to_actor: &entry.to_actor,
+            location: &entry.location,
+            seal_number: &entry.seal_number,
+            handed_over_at: entry.handed_over_at,
+        }
+    }
+}
+
+/// Hashes a handoff together with the hash of the entry before it, chaining the ledger.
+pub fn entry_hash(previous_hash: Option<&str>, handoff: &CustodyHandoff) -> String {
+    let delivery_id = handoff.delivery_id.to_string();
+    let sequence = handoff.sequence.to_string();
+    let handed_over_at = handoff
+        .handed_over_at
+        .to_rfc3339_opts(SecondsFormat::Micros, true);
+
+    let mut hasher = Sha256::new();
+    for field in [
+        previous_hash.unwrap_or_default(),
+        &delivery_id,
+        &sequence,
+        handoff.handoff_type,
+        handoff.from_actor,
+        handoff.to_actor,
+        handoff.location,
+        handoff.seal_number,
+        &handed_over_at,
+    ] {
+        hasher.update(field.as_bytes());
+        // Unit separator, so that moving characters between fields changes the hash.
+        hasher.update([0x1f]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn mac(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+/// Signs an entry hash with the service's custody key.
+pub fn sign(key: &[u8], entry_hash: &str) -> String {
+    let mut mac = mac(key);
+    mac.update(entry_hash.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn verify_signature(key: &[u8], entry_hash: &str, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = mac(key);
+    mac.update(entry_hash.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Checks whether a handoff may be appended after `entries`, returning the reason if not.
+pub fn check_next_handoff(
+    entries: &[DeliveryCustodyEntryEntity],
+    handoff_type: &str,
+    from_actor: &str,
+) -> Result<(), String> {
+    let Some(last) = entries.last() else {
+        return if handoff_type == PHARMACY_TO_COURIER || handoff_type == PHARMACY_TO_RECIPIENT {
+            Ok(())
+        } else {
+            Err(format!(
+                "The first handoff must be {PHARMACY_TO_COURIER} or {PHARMACY_TO_RECIPIENT}"
+            ))
+        };
+    };
+
+    if reaches_recipient(&last.handoff_type) {
+        return Err("Custody has already been handed to the recipient".into());
+    }
+    if handoff_type == PHARMACY_TO_COURIER || handoff_type == PHARMACY_TO_RECIPIENT {
+        return Err(format!("Only the first handoff can be {handoff_type}"));
+    }
+    if last.to_actor != from_actor {
+        return Err(format!(
+            "Handoff must come from the current custodian, {}",
+            last.to_actor
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct CustodyVerification {
+    /// Every entry's hash, link and signature checks out.
+    pub intact: bool,
+    /// Custody runs unbroken from the pharmacy to the recipient.
+    pub complete: bool,
+    pub problems: Vec<String>,
+}
+
+/// Verifies the hash chain and signatures of a delivery's entries, ordered by sequence,
+/// and whether they form a complete chain of custody.
+pub fn verify(entries: &[DeliveryCustodyEntryEntity], key: &[u8]) -> CustodyVerification {
+    let mut problems = Vec::new();
+    let mut intact = true;
+    let mut complete = true;
+
+    let mut previous: Option<&DeliveryCustodyEntryEntity> = None;
+    for (i, entry) in entries.iter().enumerate() {
+        let expected_sequence = i as i32 + 1;
+        if entry.sequence != expected_sequence {
+            intact = false;
+            problems.push(format!(
+                "Entry {} is out of sequence, expected {expected_sequence}",
+                entry.sequence
+            ));
+        }
+
+        let previous_hash = previous.map(|previous| previous.entry_hash.as_str());
+        if entry.previous_hash.as_deref() != previous_hash {
+            intact = false;
+            problems.push(format!(
+                "Entry {} does not link to the entry before it",
+                entry.sequence
+            ));
+        }
+        if entry_hash(previous_hash, &entry.into()) != entry.entry_hash {
+            intact = false;
+            problems.push(format!("Entry {} has been altered", entry.sequence));
+        }
+        if !verify_signature(key, &entry.entry_hash, &entry.signature) {
+            intact = false;
+            problems.push(format!("Entry {} has an invalid signature", entry.sequence));
+        }
+
+        if let Err(reason) =
+            check_next_handoff(&entries[..i], &entry.handoff_type, &entry.from_actor)
+        {
+            complete = false;
+            problems.push(format!("Entry {}: {reason}", entry.sequence));
+        }
+
+        previous = Some(entry);
+    }
+
+    if previous.is_none_or(|last| !reaches_recipient(&last.handoff_type)) {
+        complete = false;
+        problems.push(format!(
+            "Custody chain does not end with {COURIER_TO_RECIPIENT} or {PHARMACY_TO_RECIPIENT}"
+        ));
+    }
+
+    CustodyVerification {
+        intact,
+        complete,
+        problems,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    const KEY: &[u8] = b"custody-signing-key";
+
+    /// A signed chain of `handoffs`, given as (type, from, to), linked in order.
+    fn chain(handoffs: &[(&str, &str, &str)]) -> Vec<DeliveryCustodyEntryEntity> {
+        let delivery_id = Uuid::nil();
+        let mut entries: Vec<DeliveryCustodyEntryEntity> = Vec::new();
+        for (i, (handoff_type, from_actor, to_actor)) in handoffs.iter().enumerate() {
+            let sequence = i as i32 + 1;
+            let handed_over_at = Utc
+                .with_ymd_and_hms(2026, 10, 18, 9, sequence as u32, 0)
+                .unwrap();
+            let previous_hash = entries.last().map(|entry| entry.entry_hash.clone());
+            let hash = entry_hash(
+                previous_hash.as_deref(),
+                &CustodyHandoff {
+                    delivery_id,
+                    sequence,
+                    handoff_type,
+                    from_actor,
+                    to_actor,
+                    location: "Bangkok",
+                    seal_number: "SEAL-1",
+                    handed_over_at,
+                },
+            );
+            entries.push(DeliveryCustodyEntryEntity {
+                id: Uuid::from_u128(sequence as u128),
+                delivery_id,
+                sequence,
+                handoff_type: handoff_type.to_string(),
+                from_actor: from_actor.to_string(),
+                to_actor: to_actor.to_string(),
+                location: "Bangkok".into(),
+                seal_number: "SEAL-1".into(),
+                handed_over_at,
+                previous_hash,
+                signature: sign(KEY, &hash),
+                entry_hash: hash,
+                created_at: handed_over_at,
+            });
+        }
+        entries
+    }
+
+    fn delivered() -> Vec<DeliveryCustodyEntryEntity> {
+        chain(&[
+            (PHARMACY_TO_COURIER, "pharmacist", "courier-1"),
+            (COURIER_TO_COURIER, "courier-1", "courier-2"),
+            (COURIER_TO_RECIPIENT, "courier-2", "patient"),
+        ])
+    }
+
+    #[test]
+    fn verify_accepts_a_valid_chain() {
+        let verification = verify(&delivered(), KEY);
+        assert!(verification.intact, "{:?}", verification.problems);
+        assert!(verification.complete, "{:?}", verification.problems);
+        assert!(verification.problems.is_empty());
+    }
+
+    #[test]
+    fn verify_accepts_a_pharmacy_counter_collection() {
+        let entries = chain(&[(PHARMACY_TO_RECIPIENT, "pharmacist", "patient")]);
+        let verification = verify(&entries, KEY);
+        assert!(verification.intact && verification.complete);
+    }
+
+    #[test]
+    fn verify_detects_an_altered_entry() {
+        let mut entries = delivered();
+        entries[1].to_actor = "someone-else".into();
+        let verification = verify(&entries, KEY);
+        assert!(!verification.intact);
+        assert!(
+            verification
+                .problems
+                .contains(&"Entry 2 has been altered".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_detects_a_broken_link() {
+        let mut entries = delivered();
+        entries[2].previous_hash = Some(entries[0].entry_hash.clone());
+        let verification = verify(&entries, KEY);
+        assert!(!verification.intact);
+        assert!(
+            verification
+                .problems
+                .contains(&"Entry 3 does not link to the entry before it".to_string())
+        );
+    }
+
+    #[test]
+    fn verify_detects_a_wrong_signature() {
+        let entries = delivered();
+        let verification = verify(&entries, b"another-key");
+        assert!(!verification.intact);
+        assert_eq!(verification.problems.len(), 3);
+
+        let mut entries = delivered();
+        entries[0].signature = sign(KEY, "forged");
+        let verification = verify(&entries, KEY);
+        assert!(!verification.intact);
+        assert_eq!(
+            verification.problems,
+            vec!["Entry 1 has an invalid signature".to_string()]
+        );
+    }
+
+    #[test]
+    fn verify_reports_an_unfinished_chain_as_incomplete() {
+        let entries = chain(&[(PHARMACY_TO_COURIER, "pharmacist", "courier-1")]);
+        let verification = verify(&entries, KEY);
+        assert!(verification.intact);
+        assert!(!verification.complete);
+    }
+
+    #[test]
+    fn verify_reports_handoffs_out_of_sequence_as_incomplete() {
+        let entries = chain(&[
+            (COURIER_TO_COURIER, "courier-1", "courier-2"),
+            (COURIER_TO_RECIPIENT, "courier-2", "patient"),
+        ]);
+        let verification = verify(&entries, KEY);
+        assert!(verification.intact);
+        assert!(!verification.complete);
+    }
+
+    #[test]
+    fn check_next_handoff_requires_a_pharmacy_handoff_first() {
+        assert!(check_next_handoff(&[], PHARMACY_TO_COURIER, "pharmacist").is_ok());
+        assert!(check_next_handoff(&[], PHARMACY_TO_RECIPIENT, "pharmacist").is_ok());
+        assert!(check_next_handoff(&[], COURIER_TO_COURIER, "courier-1").is_err());
+    }
+
+    #[test]
+    fn check_next_handoff_requires_the_current_custodian() {
+        let entries = chain(&[(PHARMACY_TO_COURIER, "pharmacist", "courier-1")]);
+        assert!(check_next_handoff(&entries, COURIER_TO_COURIER, "courier-1").is_ok());
+        assert!(check_next_handoff(&entries, COURIER_TO_COURIER, "courier-2").is_err());
+        assert!(check_next_handoff(&entries, PHARMACY_TO_COURIER, "pharmacist").is_err());
+    }
+
+    #[test]
+    fn check_next_handoff_refuses_handoffs_after_the_recipient() {
+        assert!(check_next_handoff(&delivered(), COURIER_TO_COURIER, "patient").is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Lets a patient grant another user access to their addresses and deliveries, for
+//! example a family member managing their medication.
+//!
+//! `READ` delegates may view, `MANAGE` delegates may also make changes. Everything a
+//! delegate does on a patient's behalf is written to `patient_proxy_audit_logs`, as are
+//! delegations being granted, changed and revoked.
+
+use anyhow::Context;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use uuid::Uuid;
+
+use crate::{
+    models::{CreatePatientProxyAuditLogEntity, PatientDelegationEntity},
+    schema::{patient_delegations, patient_proxy_audit_logs},
+};
+
+pub const ACCESS_LEVELS: [&str; 2] = ["READ", "MANAGE"];
+
+/// Who a patient-facing request acts for.
+#[derive(Debug, Clone, Copy)]
+pub struct Principal {
+    /// Patient whose data is accessed.
+    pub patient_id: i32,
+    /// Authenticated user making the request.
+    pub actor_id: i32,
+    /// Delegation the actor relies on, `None` when acting for themselves.
+    pub delegation_id: Option<Uuid>,
+}
+
+/// Resolves who `actor_id` acts for. Without `on_behalf_of`, or with their own ID, the
+/// actor acts for themselves. Otherwise an active delegation from that patient is
+/// required, granting `MANAGE` when `manage` is set. Patients who have not granted access
+/// are reported as not found.
+pub async fn act_as(
+    conn: &mut AsyncPgConnection,
+    actor_id: i32,
+    on_behalf_of: Option<i32>,
+    manage: bool,
+) -> Result<Principal, AppError> {
+    let Some(patient_id) = on_behalf_of.filter(|patient_id| *patient_id != actor_id) else {
+        return Ok(Principal {
+            patient_id: actor_id,
+            actor_id,
+            delegation_id: None,
+        });
+    };
+
+    let delegation: PatientDelegationEntity = patient_delegations::table
+        .filter(patient_delegations::patient_id.eq(patient_id))
+        .filter(patient_delegations::delegate_id.eq(actor_id))
+        .filter(patient_delegations::revoked_at.is_null())
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delegation")?
+        .ok_or(AppError::NotFound)?;
+    if manage && delegation.access_level != "MANAGE" {
+        return Err(AppError::BadRequest(
+            "Delegation only grants read access".into(),
+        ));
+    }
+
+    Ok(Principal {
+        patient_id,
+        actor_id,
+        delegation_id: Some(delegation.id),
+    })
+}
+
+/// Records an action taken through a delegation. Does nothing when the actor acts for
+/// themselves.
+pub async fn audit(
+    conn: &mut AsyncPgConnection,
+    principal: &Principal,
+    action: &str,
+    resource_type: &str,
+    resource_id: Option<String>,
+) -> Result<(), AppError> {
+    let Some(delegation_id) = principal.delegation_id else {
+        return Ok(());
+    };
+
+    diesel::insert_into(patient_proxy_audit_logs::table)
+        .values(CreatePatientProxyAuditLogEntity {
+            delegation_id,
+            patient_id: principal.patient_id,
+            delegate_id: principal.actor_id,
+            action: action.into(),
+            resource_type: resource_type.into(),
+            resource_id,
+        })
+        .execute(conn)
+        .await
+        .context("Failed to record proxy action")?;
+    Ok(())
+}
+
+/// Records a delegation being granted, changed or revoked, so that the patient sees it
+/// among the actions taken through their delegations.
+pub async fn audit_delegation(
+    conn: &mut AsyncPgConnection,
+    delegation: &PatientDelegationEntity,
+    action: &str,
+) -> Result<(), AppError> {
+    diesel::insert_into(patient_proxy_audit_logs::table)
+        .values(CreatePatientProxyAuditLogEntity {
+            delegation_id: delegation.id,
+            patient_id: delegation.patient_id,
+            delegate_id: delegation.delegate_id,
+            action: action.into(),
+            resource_type: "DELEGATION".into(),
+            resource_id: Some(delegation.id.to_string()),
+        })
+        .execute(conn)
+        .await
+        .context("Failed to record delegation change")?;
+    Ok(())
+}
@@ -0,0 +1,288 @@
+use anyhow::Context;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::{app_error::AppError, outbox};
+use medbook_events::DeliverySuccessEvent;
+use uuid::Uuid;
+
+use crate::{
+    config,
+    events::{DeliveryCancelledEvent, DeliverySuccess, IdVerificationOutcome},
+    models::{
+        CreateDeliveryLogEntity, DeliveryCustodyEntryEntity, DeliveryEntity,
+        DeliveryIdVerificationEntity, DeliveryLogEntity,
+    },
+    schema::{
+        deliveries, delivery_custody_entries, delivery_id_verifications, delivery_logs,
+        delivery_orders,
+    },
+    services::{carriers, custody, legs, pickups},
+};
+
+/// How a delivery reaches the patient.
+pub const FULFILMENT_MODES: [&str; 3] = ["HOME", "PHARMACY_PICKUP", "LOCKER"];
+
+/// Statuses of a home delivery, in the order it moves through them.
+pub const STATUSES: [&str; 4] = ["PREPARING", "PICKED_UP", "EN_ROUTE", "DELIVERED"];
+
+/// Statuses of a parcel collected by the patient at the pharmacy.
+pub const PHARMACY_PICKUP_STATUSES: [&str; 3] = ["PREPARING", "READY_FOR_PICKUP", "COLLECTED"];
+
+/// Statuses of a parcel dropped off at a locker and collected by the patient.
+pub const LOCKER_STATUSES: [&str; 5] = [
+    "PREPARING",
+    "PICKED_UP",
+    "EN_ROUTE",
+    "READY_FOR_PICKUP",
+    "COLLECTED",
+];
+
+/// Statuses of a parcel taken back to the pharmacy after it was not collected.
+pub const RETURN_STATUSES: [&str; 2] = ["RETURNING", "RETURNED"];
+
+/// Statuses after which a delivery no longer changes. Cancelled deliveries stay out of
+/// the status flows so that nothing moves them forward.
+pub const CLOSED_STATUSES: [&str; 4] = ["DELIVERED", "COLLECTED", "RETURNED", "CANCELLED"];
+
+/// The statuses a delivery of `fulfilment_mode` moves through, in order.
+pub fn status_flow(fulfilment_mode: &str) -> &'static [&'static str] {
+    match fulfilment_mode {
+        "PHARMACY_PICKUP" => &PHARMACY_PICKUP_STATUSES,
+        "LOCKER" => &LOCKER_STATUSES,
+        _ => &STATUSES,
+    }
+}
+
+/// Position of `status` in the status flow of `fulfilment_mode`, used to only ever move
+/// deliveries forward automatically.
+pub fn status_rank(fulfilment_mode: &str, status: &str) -> Option<usize> {
+    status_flow(fulfilment_mode)
+        .iter()
+        .position(|candidate| *candidate == status)
+}
+
+/// Whether moving a delivery of `fulfilment_mode` from `from` to `to` is a step forward in
+/// its status flow. Deliveries outside the flow, such as returning ones, never move forward.
+pub fn moves_forward(fulfilment_mode: &str, from: &str, to: &str) -> bool {
+    match (
+        status_rank(fulfilment_mode, from),
+        status_rank(fulfilment_mode, to),
+    ) {
+        (Some(from), Some(to)) => to > from,
+        _ => false,
+    }
+}
+
+/// Delivery priorities, from least to most urgent.
+pub const PRIORITIES: [&str; 3] = ["STANDARD", "EXPRESS", "URGENT"];
+
+/// Position of `priority` in `PRIORITIES`, higher being more urgent.
+pub fn priority_rank(priority: &str) -> Option<usize> {
+    PRIORITIES
+        .iter()
+        .position(|candidate| *candidate == priority)
+}
+
+/// Fetches a delivery belonging to `patient_id`. Deliveries of other patients are
+/// reported as not found.
+pub async fn get_patient_delivery(
+    conn: &mut AsyncPgConnection,
+    patient_id: i32,
+    id: Uuid,
+) -> Result<DeliveryEntity, AppError> {
+    let delivery: DeliveryEntity = deliveries::table
+        .find(id)
+        .filter(deliveries::patient_id.eq(patient_id))
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery")?
+        .ok_or(AppError::NotFound)?;
+    Ok(delivery)
+}
+
+/// Orders carried by a delivery, the one it was created for first.
+pub async fn order_ids(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Uuid,
+) -> Result<Vec<i32>, AppError> {
+    let order_ids: Vec<i32> = delivery_orders::table
+        .filter(delivery_orders::delivery_id.eq(delivery_id))
+        .order_by((
+            delivery_orders::created_at.asc(),
+            delivery_orders::order_id.asc(),
+        ))
+        .select(delivery_orders::order_id)
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery orders")?;
+    Ok(order_ids)
+}
+
+/// Moves a delivery to `status`, records the change in `delivery_logs` and publishes
+/// the events tied to that status. The carrier shipment of a cancelled delivery is
+/// cancelled by [`carriers::process`] once the transaction has committed. Must be called
+/// inside a transaction.
+pub async fn transition(
+    conn: &mut AsyncPgConnection,
+    id: Uuid,
+    status: String,
+    description: String,
+) -> Result<(DeliveryEntity, DeliveryLogEntity), AppError> {
+    let current: DeliveryEntity = deliveries::table
+        .find(id)
+        .for_update()
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery")?
+        .ok_or(AppError::NotFound)?;
+
+    if CLOSED_STATUSES.contains(&current.status.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Delivery is {} and no longer changes",
+            current.status
+        )));
+    }
+
+    if RETURN_STATUSES.contains(&status.as_str()) {
+        // Only parcels left uncollected at their pickup point are sent back.
+        if !["READY_FOR_PICKUP", "RETURNING"].contains(&current.status.as_str())
+            || status == current.status
+        {
+            return Err(AppError::BadRequest(format!(
+                "Delivery is {} and cannot be {status}",
+                current.status
+            )));
+        }
+    } else if status != "CANCELLED"
+        && !status_flow(&current.fulfilment_mode).contains(&status.as_str())
+    {
+        return Err(AppError::BadRequest(format!(
+            "{} deliveries cannot be {status}",
+            current.fulfilment_mode
+        )));
+    }
+
+    // Parcels reach the patient either by being delivered or by being collected.
+    let handed_over = status == "DELIVERED" || status == "COLLECTED";
+
+    if handed_over && current.requires_review {
+        return Err(AppError::BadRequest(
+            "Delivery has a temperature excursion awaiting pharmacist review".into(),
+        ));
+    }
+
+    if handed_over && current.is_controlled {
+        let entries: Vec<DeliveryCustodyEntryEntity> = delivery_custody_entries::table
+            .filter(delivery_custody_entries::delivery_id.eq(id))
+            .order_by(delivery_custody_entries::sequence.asc())
+            .get_results(conn)
+            .await
+            .context("Failed to get custody entries")?;
+
+        let key = config::custody_signing_key()?;
+        let verification = custody::verify(&entries, key.as_bytes());
+        if !(verification.intact && verification.complete) {
+            return Err(AppError::BadRequest(format!(
+                "Custody chain is not complete: {}",
+                verification.problems.join("; ")
+            )));
+        }
+    }
+
+    let id_verification = if handed_over && current.requires_id_verification {
+        let verification: DeliveryIdVerificationEntity = delivery_id_verifications::table
+            .filter(delivery_id_verifications::delivery_id.eq(id))
+            .order_by(delivery_id_verifications::created_at.desc())
+            .first(conn)
+            .await
+            .optional()
+            .context("Failed to get ID verification")?
+            .ok_or_else(|| {
+                AppError::BadRequest("Recipient ID must be verified before delivery".into())
+            })?;
+
+        if !verification.name_matched {
+            return Err(AppError::BadRequest(
+                "Recipient ID does not match the recipient name".into(),
+            ));
+        }
+
+        Some(IdVerificationOutcome {
+            id_type: verification.id_type,
+            masked_id_number: verification.masked_id_number,
+            name_matched: verification.name_matched,
+            verified_by: verification.verified_by,
+            verified_at: verification.created_at,
+        })
+    } else {
+        None
+    };
+
+    let delivery: DeliveryEntity = diesel::update(deliveries::table.find(id))
+        .set(deliveries::status.eq(status.clone()))
+        .returning(DeliveryEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to update delivery status")?;
+
+    match delivery.status.as_str() {
+        "DELIVERED" | "READY_FOR_PICKUP" => legs::close(conn, id, "COMPLETED").await?,
+        "RETURNING" | "CANCELLED" => legs::close(conn, id, "CANCELLED").await?,
+        _ => {}
+    }
+    if delivery.status == "CANCELLED" {
+        carriers::request_cancel(conn, &delivery, &description).await?;
+    }
+
+    let delivery = match delivery.status.as_str() {
+        "READY_FOR_PICKUP" => pickups::make_ready(conn, delivery).await?,
+        _ if current.status == "READY_FOR_PICKUP" => pickups::close(conn, delivery).await?,
+        _ => delivery,
+    };
+
+    let delivery_log = diesel::insert_into(delivery_logs::table)
+        .values(CreateDeliveryLogEntity {
+            delivery_id: delivery.id,
+            description: description.clone(),
+            status,
+        })
+        .returning(DeliveryLogEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to create delivery log")?;
+
+    if handed_over {
+        for order_id in order_ids(conn, delivery.id).await? {
+            outbox::publish(
+                conn,
+                "orders.delivery_success".into(),
+                DeliverySuccess {
+                    event: DeliverySuccessEvent { order_id },
+                    id_verification: id_verification.clone(),
+                },
+            )
+            .await
+            .context("Failed to send outbox")?;
+        }
+    }
+
+    if delivery.status.as_str() == "CANCELLED" {
+        for order_id in order_ids(conn, delivery.id).await? {
+            outbox::publish(
+                conn,
+                "delivery.cancelled".into(),
+                DeliveryCancelledEvent {
+                    delivery_id: delivery.id,
+                    order_id,
+                    reason: description.clone(),
+                },
+            )
+            .await
+            .context("Failed to send outbox")?;
+        }
+    }
+
+    Ok((delivery, delivery_log))
+}
@@ -0,0 +1,479 @@
+//! Automatic assignment of PREPARING deliveries to couriers.
+//!
+//! Every run pairs unassigned deliveries, most urgent first, with the courier a
+//! [`ScoringStrategy`] scores best, taking the load already given to each courier
+//! into account. Deliveries whose origin is closed, or that were ordered after its cutoff,
+//! wait for the next run. Deliveries routed through hubs are left to the couriers of their
+//! legs. Deliveries a carrier rule matches are handed to that external carrier instead.
+//! Each decision carries a human readable reason.
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use anyhow::Context;
+use chrono::Utc;
+use diesel::{
+    ExpressionMethods, QueryDsl,
+    dsl::{exists, not},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use serde::Serialize;
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    models::{CourierEntity, CreateDeliveryLogEntity, DeliveryEntity, DeliveryPackageEntity},
+    schema::{
+        carrier_requests, couriers, deliveries, delivery_legs, delivery_logs, delivery_packages,
+    },
+    services::{
+        carriers,
+        deliveries::{CLOSED_STATUSES, priority_rank},
+        locations,
+        packages::ManifestSummary,
+        route_planner::Coordinates,
+    },
+};
+
+pub const VEHICLE_TYPES: [&str; 4] = ["BICYCLE", "MOTORCYCLE", "CAR", "VAN"];
+
+/// Vehicles able to keep refrigerated packages cold.
+const REFRIGERATED_VEHICLE_TYPES: [&str; 2] = ["CAR", "VAN"];
+
+pub struct DispatchDelivery {
+    pub delivery: DeliveryEntity,
+    pub summary: ManifestSummary,
+}
+
+impl DispatchDelivery {
+    fn location(&self) -> Option<Coordinates> {
+        Some(Coordinates {
+            latitude: self.delivery.latitude?,
+            longitude: self.delivery.longitude?,
+        })
+    }
+}
+
+/// A courier along with the deliveries they already carry.
+pub struct DispatchCourier {
+    pub courier: CourierEntity,
+    pub assigned_deliveries: i32,
+    pub assigned_weight_grams: i64,
+}
+
+impl DispatchCourier {
+    /// Distance from the courier's last known position to the delivery address.
+    pub fn distance_km(&self, delivery: &DispatchDelivery) -> Option<f64> {
+        let courier = Coordinates {
+            latitude: self.courier.latitude?,
+            longitude: self.courier.longitude?,
+        };
+        Some(courier.distance_km(&delivery.location()?))
+    }
+
+    /// Share of the courier's capacity in use once `delivery` is added, from 0 to 1.
+    /// Couriers without limits count as empty.
+    pub fn utilization_with(&self, delivery: &DispatchDelivery) -> f64 {
+        let by_count = self
+            .courier
+            .max_deliveries
+            .map(|max| f64::from(self.assigned_deliveries + 1) / f64::from(max.max(1)));
+        let by_weight = self.courier.capacity_grams.map(|capacity| {
+            (self.assigned_weight_grams + delivery.summary.billable_weight_grams) as f64
+                / f64::from(capacity.max(1))
+        });
+        by_count.into_iter().chain(by_weight).fold(0.0, f64::max)
+    }
+}
+
+/// Rejects couriers that cannot take `delivery` at all: wrong zone, unsuitable vehicle
+/// or no capacity left.
+pub fn check_constraints(
+    delivery: &DispatchDelivery,
+    courier: &DispatchCourier,
+) -> Result<(), String> {
+    if let (Some(zone), Some(courier_zone)) = (&delivery.delivery.zone, &courier.courier.zone)
+        && zone != courier_zone
+    {
+        return Err(format!("serves zone {courier_zone}, not {zone}"));
+    }
+    if delivery.summary.requires_refrigeration
+        && !REFRIGERATED_VEHICLE_TYPES.contains(&courier.courier.vehicle_type.as_str())
+    {
+        return Err(format!(
+            "{} cannot carry refrigerated packages",
+            courier.courier.vehicle_type
+        ));
+    }
+    if courier
+        .courier
+        .max_deliveries
+        .is_some_and(|max| courier.assigned_deliveries >= max)
+    {
+        return Err(format!(
+            "already carries {} deliveries",
+            courier.assigned_deliveries
+        ));
+    }
+    if courier.courier.capacity_grams.is_some_and(|capacity| {
+        courier.assigned_weight_grams + delivery.summary.billable_weight_grams > i64::from(capacity)
+    }) {
+        return Err(format!(
+            "has {} g of capacity left, needs {} g",
+            i64::from(courier.courier.capacity_grams.unwrap_or_default())
+                - courier.assigned_weight_grams,
+            delivery.summary.billable_weight_grams
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `courier`'s vehicle could carry packages totalling `summary` if it carried
+/// nothing else.
+pub fn fits_vehicle(courier: &CourierEntity, summary: &ManifestSummary) -> bool {
+    (!summary.requires_refrigeration
+        || REFRIGERATED_VEHICLE_TYPES.contains(&courier.vehicle_type.as_str()))
+        && courier
+            .capacity_grams
+            .is_none_or(|capacity| summary.billable_weight_grams <= i64::from(capacity))
+}
+
+/// Decides how suitable a courier is for a delivery.
+pub trait ScoringStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Scores `courier` for `delivery`, higher being better. `Err` explains why the
+    /// courier cannot take the delivery.
+    fn score(&self, delivery: &DispatchDelivery, courier: &DispatchCourier) -> Result<f64, String>;
+}
+
+/// Weighs distance against how loaded the courier already is, spreading work across
+/// couriers that are roughly as close.
+pub struct BalancedStrategy;
+
+impl ScoringStrategy for BalancedStrategy {
+    fn name(&self) -> &'static str {
+        "balanced"
+    }
+
+    fn score(&self, delivery: &DispatchDelivery, courier: &DispatchCourier) -> Result<f64, String> {
+        check_constraints(delivery, courier)?;
+        // Unknown positions score as if the courier were 10 km away.
+        let distance_km = courier.distance_km(delivery).unwrap_or(10.0);
+        Ok(100.0 - 2.0 * distance_km - 40.0 * courier.utilization_with(delivery))
+    }
+}
+
+/// Picks the closest courier regardless of load.
+pub struct NearestStrategy;
+
+impl ScoringStrategy for NearestStrategy {
+    fn name(&self) -> &'static str {
+        "nearest"
+    }
+
+    fn score(&self, delivery: &DispatchDelivery, courier: &DispatchCourier) -> Result<f64, String> {
+        check_constraints(delivery, courier)?;
+        let distance_km = courier
+            .distance_km(delivery)
+            .ok_or_else(|| "position unknown".to_string())?;
+        Ok(-distance_km)
+    }
+}
+
+pub fn strategy_by_name(name: &str) -> Option<Box<dyn ScoringStrategy>> {
+    match name {
+        "balanced" => Some(Box::new(BalancedStrategy)),
+        "nearest" => Some(Box::new(NearestStrategy)),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct DispatchDecision {
+    pub delivery_id: Uuid,
+    /// `None` when no courier could take the delivery.
+    pub courier_id: Option<i32>,
+    /// External carrier the delivery was handed to instead of a courier.
+    pub carrier: Option<String>,
+    pub score: Option<f64>,
+    pub reason: String,
+}
+
+/// Pairs each delivery with its best scoring courier, updating courier loads as it goes.
+/// Ties go to the courier with the lowest ID.
+pub fn propose(
+    strategy: &dyn ScoringStrategy,
+    deliveries: &[DispatchDelivery],
+    couriers: &mut [DispatchCourier],
+) -> Vec<DispatchDecision> {
+    deliveries
+        .iter()
+        .map(|delivery| {
+            let mut best: Option<(usize, f64)> = None;
+            let mut rejections = Vec::new();
+            for (i, courier) in couriers.iter().enumerate() {
+                match strategy.score(delivery, courier) {
+                    Ok(score) if best.is_none_or(|(_, best_score)| score > best_score) => {
+                        best = Some((i, score))
+                    }
+                    Ok(_) => {}
+                    Err(reason) => rejections.push(format!("{}: {reason}", courier.courier.name)),
+                }
+            }
+
+            let Some((i, score)) = best else {
+                return DispatchDecision {
+                    delivery_id: delivery.delivery.id,
+                    courier_id: None,
+                    carrier: None,
+                    score: None,
+                    reason: if couriers.is_empty() {
+                        "No active couriers".into()
+                    } else {
+                        format!("No eligible courier ({})", rejections.join("; "))
+                    },
+                };
+            };
+
+            let total = couriers.len();
+            let eligible = total - rejections.len();
+            let courier = &mut couriers[i];
+            courier.assigned_deliveries += 1;
+            courier.assigned_weight_grams += delivery.summary.billable_weight_grams;
+
+            let mut reason = format!(
+                "Courier {} scored {score:.1} with the {} strategy, {eligible} of {total} couriers eligible",
+                courier.courier.name,
+                strategy.name()
+            );
+            if !rejections.is_empty() {
+                reason.push_str(&format!(" ({})", rejections.join("; ")));
+            }
+            DispatchDecision {
+                delivery_id: delivery.delivery.id,
+                courier_id: Some(courier.courier.id),
+                carrier: None,
+                score: Some(score),
+                reason,
+            }
+        })
+        .collect()
+}
+
+/// Proposes couriers for all unassigned PREPARING deliveries ready to leave their
+/// origin, except pharmacy pickups and deliveries routed through hubs, and, unless
+/// `dry_run`, assigns them. Deliveries a carrier rule matches are reserved for the carrier
+/// instead, unless they are controlled or the carrier turned them down before, and are
+/// booked by [`carriers::process`] once the transaction has committed. Deliveries locked
+/// by a concurrent run are skipped, and concurrent runs wait for each other's couriers.
+/// Must be called inside a transaction.
+pub async fn run(
+    conn: &mut AsyncPgConnection,
+    strategy: &dyn ScoringStrategy,
+    dry_run: bool,
+) -> Result<Vec<DispatchDecision>, AppError> {
+    let pending_query = deliveries::table
+        .filter(deliveries::status.eq("PREPARING"))
+        .filter(deliveries::fulfilment_mode.ne("PHARMACY_PICKUP"))
+        .filter(deliveries::courier_id.is_null())
+        .filter(deliveries::carrier.is_null())
+        // Deliveries routed through hubs get the couriers their legs name.
+        .filter(not(exists(
+            delivery_legs::table.filter(delivery_legs::delivery_id.eq(deliveries::id)),
+        )))
+        .order_by((deliveries::created_at.asc(), deliveries::id.asc()));
+    // Dry runs only report, so they must not hide deliveries from a concurrent real run.
+    let mut pending: Vec<DeliveryEntity> = if dry_run {
+        pending_query.get_results(conn).await
+    } else {
+        pending_query
+            .for_update()
+            .skip_locked()
+            .get_results(conn)
+            .await
+    }
+    .context("Failed to get deliveries awaiting dispatch")?;
+
+    // Deliveries wait until their origin is open and past the order's ship time, so
+    // orders placed after the cutoff are not dispatched before the next opening day.
+    // Deliveries from an inactive origin wait until it is reactivated.
+    let origin_ids: Vec<i32> = pending
+        .iter()
+        .filter_map(|delivery| delivery.origin_location_id)
+        .collect();
+    if !origin_ids.is_empty() {
+        let now = Utc::now();
+        let origins = locations::schedules(conn, &origin_ids).await?;
+        pending.retain(|delivery| {
+            let Some(origin_id) = delivery.origin_location_id else {
+                return true;
+            };
+            let Some(origin) = origins.get(&origin_id) else {
+                return false;
+            };
+            origin.is_open(now)
+                && origin
+                    .ships_at(delivery.created_at)
+                    .is_some_and(|ships_at| ships_at <= now)
+        });
+    }
+
+    // Deliveries a carrier turned down, or controlled ones, which must stay in our custody,
+    // go to our couriers.
+    let turned_down: Vec<Uuid> = carrier_requests::table
+        .filter(carrier_requests::delivery_id.eq_any(pending.iter().map(|delivery| delivery.id)))
+        .filter(carrier_requests::action.eq("CREATE"))
+        .filter(carrier_requests::status.eq("FAILED"))
+        .select(carrier_requests::delivery_id)
+        .get_results(conn)
+        .await
+        .context("Failed to get turned down carrier requests")?;
+    let registry = carriers::registry()?;
+    let mut decisions = Vec::new();
+    let mut for_couriers = Vec::with_capacity(pending.len());
+    for delivery in pending {
+        let carrier = registry
+            .carrier_for(&delivery)
+            .filter(|_| !delivery.is_controlled && !turned_down.contains(&delivery.id));
+        let Some((carrier, rule)) = carrier else {
+            for_couriers.push(delivery);
+            continue;
+        };
+        let reason = format!(
+            "Outsourced to carrier {} by the {rule} rule",
+            carrier.name()
+        );
+        info!(
+            delivery_id = %delivery.id,
+            carrier = carrier.name(),
+            dry_run,
+            "Dispatch decision: {}",
+            reason
+        );
+        if !dry_run {
+            carriers::request_shipment(conn, &delivery, carrier, &reason).await?;
+        }
+        decisions.push(DispatchDecision {
+            delivery_id: delivery.id,
+            courier_id: None,
+            carrier: Some(carrier.name().into()),
+            score: None,
+            reason,
+        });
+    }
+    let mut pending = for_couriers;
+    if pending.is_empty() {
+        return Ok(decisions);
+    }
+    // Within a priority, the oldest delivery has the earliest deadline.
+    pending.sort_by_key(|delivery| Reverse(priority_rank(&delivery.priority)));
+
+    // Locking the couriers makes concurrent runs take turns, so each sees the loads the
+    // other assigned and cannot push a courier past its limits.
+    let couriers_query = couriers::table
+        .filter(couriers::is_active.eq(true))
+        .order_by(couriers::id.asc());
+    let active_couriers: Vec<CourierEntity> = if dry_run {
+        couriers_query.get_results(conn).await
+    } else {
+        couriers_query.for_update().get_results(conn).await
+    }
+    .context("Failed to get couriers")?;
+
+    let carried: Vec<(Uuid, Option<i32>)> = deliveries::table
+        .filter(deliveries::courier_id.eq_any(active_couriers.iter().map(|courier| courier.id)))
+        .filter(deliveries::status.ne_all(CLOSED_STATUSES))
+        .select((deliveries::id, deliveries::courier_id))
+        .get_results(conn)
+        .await
+        .context("Failed to get courier loads")?;
+
+    let mut packages: HashMap<Uuid, Vec<DeliveryPackageEntity>> = HashMap::new();
+    let package_rows: Vec<DeliveryPackageEntity> = delivery_packages::table
+        .filter(
+            delivery_packages::delivery_id.eq_any(
+                pending
+                    .iter()
+                    .map(|delivery| delivery.id)
+                    .chain(carried.iter().map(|(id, _)| *id))
+                    .collect::<Vec<_>>(),
+            ),
+        )
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery packages")?;
+    for package in package_rows {
+        packages
+            .entry(package.delivery_id)
+            .or_default()
+            .push(package);
+    }
+    let summary_of = |id: &Uuid| ManifestSummary::of(packages.get(id).into_iter().flatten());
+
+    let mut dispatch_couriers: Vec<DispatchCourier> = active_couriers
+        .into_iter()
+        .map(|courier| {
+            let carried_ids = carried
+                .iter()
+                .filter(|(_, courier_id)| *courier_id == Some(courier.id))
+                .map(|(id, _)| id);
+            DispatchCourier {
+                assigned_deliveries: carried_ids.clone().count() as i32,
+                assigned_weight_grams: carried_ids
+                    .map(|id| summary_of(id).billable_weight_grams)
+                    .sum(),
+                courier,
+            }
+        })
+        .collect();
+
+    let dispatch_deliveries: Vec<DispatchDelivery> = pending
+        .into_iter()
+        .map(|delivery| DispatchDelivery {
+            summary: summary_of(&delivery.id),
+            delivery,
+        })
+        .collect();
+
+    let courier_decisions = propose(strategy, &dispatch_deliveries, &mut dispatch_couriers);
+
+    for (delivery, decision) in dispatch_deliveries.iter().zip(&courier_decisions) {
+        info!(
+            delivery_id = %decision.delivery_id,
+            courier_id = ?decision.courier_id,
+            dry_run,
+            "Dispatch decision: {}",
+            decision.reason
+        );
+        let Some(courier_id) = decision.courier_id else {
+            continue;
+        };
+        if dry_run {
+            continue;
+        }
+
+        diesel::update(deliveries::table.find(decision.delivery_id))
+            .set((
+                deliveries::courier_id.eq(courier_id),
+                deliveries::assigned_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .context("Failed to assign courier")?;
+
+        diesel::insert_into(delivery_logs::table)
+            .values(CreateDeliveryLogEntity {
+                delivery_id: decision.delivery_id,
+                description: format!("Automatically dispatched. {}", decision.reason),
+                status: delivery.delivery.status.clone(),
+            })
+            .execute(conn)
+            .await
+            .context("Failed to create delivery log")?;
+    }
+
+    decisions.extend(courier_decisions);
+    Ok(decisions)
+}
@@ -0,0 +1,73 @@
+use medbook_core::app_error::AppError;
+use serde_json::Value;
+
+pub const ID_TYPES: [&str; 3] = ["NATIONAL_ID", "PASSPORT", "DRIVING_LICENSE"];
+
+/// Longest ID number accepted, as stored in `masked_id_number`.
+const MAX_ID_NUMBER_CHARS: usize = 64;
+
+/// Masks an ID number so the full number is never stored. The last four characters stay
+/// readable, but never more than half of them, so short IDs are masked too.
+pub fn mask_id_number(id_number: &str) -> Result<String, AppError> {
+    let chars: Vec<char> = id_number.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.is_empty() {
+        return Err(AppError::BadRequest("id_number is required".into()));
+    }
+    if chars.len() > MAX_ID_NUMBER_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "id_number must be at most {MAX_ID_NUMBER_CHARS} characters"
+        )));
+    }
+    let masked = chars.len() - 4.min(chars.len() / 2);
+    Ok(chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| if i < masked { '*' } else { *c })
+        .collect())
+}
+
+/// The recipient name from a delivery's address snapshot.
+pub fn recipient_name(delivery_address: Option<&Value>) -> Option<String> {
+    delivery_address?
+        .get("recipient_name")?
+        .as_str()
+        .map(str::to_owned)
+}
+
+/// Compares names ignoring case and surrounding or repeated whitespace.
+pub fn names_match(presented_name: &str, recipient_name: &str) -> bool {
+    let normalize = |name: &str| {
+        name.split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let presented_name = normalize(presented_name);
+    !presented_name.is_empty() && presented_name == normalize(recipient_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_id_number_keeps_last_four_of_long_ids() {
+        assert_eq!(
+            mask_id_number("1 2345 67890 12 3").unwrap(),
+            "*********0123"
+        );
+    }
+
+    #[test]
+    fn mask_id_number_masks_short_ids() {
+        assert_eq!(mask_id_number("1234").unwrap(), "**34");
+        assert_eq!(mask_id_number("123").unwrap(), "**3");
+        assert_eq!(mask_id_number("1").unwrap(), "*");
+    }
+
+    #[test]
+    fn mask_id_number_rejects_empty_and_overlong_ids() {
+        assert!(mask_id_number("  ").is_err());
+        assert!(mask_id_number(&"1".repeat(65)).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Delivery incidents, raised by the system or reported by patients, couriers and
+//! staff, and worked through OPEN, INVESTIGATING and RESOLVED.
+
+use anyhow::Context;
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::{app_error::AppError, outbox};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    events::DeliveryIncidentResolvedEvent,
+    models::{
+        CreateDeliveryIncidentAttachmentEntity, CreateDeliveryIncidentCommentEntity,
+        CreateDeliveryIncidentEntity, DeliveryIncidentAttachmentEntity,
+        DeliveryIncidentCommentEntity, DeliveryIncidentEntity,
+    },
+    schema::{delivery_incident_attachments, delivery_incident_comments, delivery_incidents},
+    services::deliveries::order_ids,
+};
+
+/// Categories patients, couriers and staff can report. The system also raises
+/// `SLA_BREACH` and `STUCK` incidents.
+pub const CATEGORIES: [&str; 5] = ["DAMAGED", "MISSING_ITEMS", "WRONG_ITEM", "LATE", "OTHER"];
+pub const SEVERITIES: [&str; 4] = ["LOW", "MEDIUM", "HIGH", "CRITICAL"];
+pub const STATUSES: [&str; 3] = ["OPEN", "INVESTIGATING", "RESOLVED"];
+pub const RESOLUTIONS: [&str; 3] = ["NONE", "RESHIP", "REFUND"];
+pub const ATTACHMENT_CONTENT_TYPES: [&str; 3] = ["image/jpeg", "image/png", "application/pdf"];
+/// Also the body limit of the upload routes, so larger files are refused before being read.
+pub const MAX_ATTACHMENT_BYTES: usize = 5 * 1024 * 1024;
+/// Length of the `file_name` column.
+pub const MAX_FILE_NAME_CHARS: usize = 255;
+
+/// Severity of an incident reported without one. A wrong or missing medication is
+/// more serious than a late one.
+pub fn default_severity(category: &str) -> &'static str {
+    match category {
+        "WRONG_ITEM" | "MISSING_ITEMS" => "HIGH",
+        "LATE" => "LOW",
+        _ => "MEDIUM",
+    }
+}
+
+pub fn check_category(category: &str) -> Result<(), AppError> {
+    if CATEGORIES.contains(&category) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Allowed categories are: {}",
+            CATEGORIES.join(", ")
+        )))
+    }
+}
+
+/// Records an incident. Incidents with a `dedup_key` are only recorded once, later
+/// attempts return `None`.
+pub async fn raise(
+    conn: &mut AsyncPgConnection,
+    incident: CreateDeliveryIncidentEntity,
+) -> Result<Option<DeliveryIncidentEntity>, AppError> {
+    let incident = diesel::insert_into(delivery_incidents::table)
+        .values(incident)
+        .on_conflict(delivery_incidents::dedup_key)
+        .do_nothing()
+        .returning(DeliveryIncidentEntity::as_returning())
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to create delivery incident")?;
+    Ok(incident)
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct IncidentDetails {
+    pub incident: DeliveryIncidentEntity,
+    pub comments: Vec<DeliveryIncidentCommentEntity>,
+    pub attachments: Vec<DeliveryIncidentAttachmentEntity>,
+}
+
+/// Fetches an incident with its comments and attachments, oldest first. Internal
+/// comments are left out unless `include_internal`.
+pub async fn get_details(
+    conn: &mut AsyncPgConnection,
+    incident: DeliveryIncidentEntity,
+    include_internal: bool,
+) -> Result<IncidentDetails, AppError> {
+    let mut comments = delivery_incident_comments::table
+        .filter(delivery_incident_comments::incident_id.eq(incident.id))
+        .order_by(delivery_incident_comments::created_at.asc())
+        .into_boxed();
+    if !include_internal {
+        comments = comments.filter(delivery_incident_comments::is_internal.eq(false));
+    }
+    let comments: Vec<DeliveryIncidentCommentEntity> = comments
+        .get_results(conn)
+        .await
+        .context("Failed to get incident comments")?;
+
+    let attachments: Vec<DeliveryIncidentAttachmentEntity> = delivery_incident_attachments::table
+        .filter(delivery_incident_attachments::incident_id.eq(incident.id))
+        .order_by(delivery_incident_attachments::created_at.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get incident attachments")?;
+
+    Ok(IncidentDetails {
+        incident,
+        comments,
+        attachments,
+    })
+}
+
+pub async fn add_comment(
+    conn: &mut AsyncPgConnection,
+    comment: CreateDeliveryIncidentCommentEntity,
+) -> Result<DeliveryIncidentCommentEntity, AppError> {
+    if comment.body.trim().is_empty() {
+        return Err(AppError::BadRequest("Comment must not be empty".into()));
+    }
+    let comment = diesel::insert_into(delivery_incident_comments::table)
+        .values(comment)
+        .returning(DeliveryIncidentCommentEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to create incident comment")?;
+    Ok(comment)
+}
+
+pub async fn add_attachment(
+    conn: &mut AsyncPgConnection,
+    attachment: CreateDeliveryIncidentAttachmentEntity,
+) -> Result<DeliveryIncidentAttachmentEntity, AppError> {
+    if !ATTACHMENT_CONTENT_TYPES.contains(&attachment.content_type.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Allowed attachment types are: {}",
+            ATTACHMENT_CONTENT_TYPES.join(", ")
+        )));
+    }
+    let file_name_chars = attachment.file_name.trim().chars().count();
+    if file_name_chars == 0 || file_name_chars > MAX_FILE_NAME_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "file_name must be between 1 and {MAX_FILE_NAME_CHARS} characters"
+        )));
+    }
+    if attachment.content.is_empty() || attachment.content.len() > MAX_ATTACHMENT_BYTES {
+        return Err(AppError::BadRequest(format!(
+            "Attachments must be between 1 byte and {} MB",
+            MAX_ATTACHMENT_BYTES / 1024 / 1024
+        )));
+    }
+    let attachment = diesel::insert_into(delivery_incident_attachments::table)
+        .values(attachment)
+        .returning(DeliveryIncidentAttachmentEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to create incident attachment")?;
+    Ok(attachment)
+}
+
+/// Fetches an attachment of `incident_id`, ready to be sent as a download.
+pub async fn get_attachment(
+    conn: &mut AsyncPgConnection,
+    incident_id: Uuid,
+    attachment_id: Uuid,
+) -> Result<Response, AppError> {
+    let attachment: DeliveryIncidentAttachmentEntity = delivery_incident_attachments::table
+        .find(attachment_id)
+        .filter(delivery_incident_attachments::incident_id.eq(incident_id))
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get incident attachment")?
+        .ok_or(AppError::NotFound)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachment.content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    attachment.file_name.replace('"', "")
+                ),
+            ),
+        ],
+        attachment.content,
+    )
+        .into_response())
+}
+
+/// Moves an incident forward through `STATUSES`. Resolving requires a resolution,
+/// which is published for the orders service. Must be called inside a transaction.
+pub async fn update_status(
+    conn: &mut AsyncPgConnection,
+    id: Uuid,
+    status: &str,
+    resolution: Option<String>,
+    resolution_note: Option<String>,
+    changed_by: &str,
+) -> Result<DeliveryIncidentEntity, AppError> {
+    let rank = |status: &str| STATUSES.iter().position(|candidate| *candidate == status);
+    let Some(new_rank) = rank(status) else {
+        return Err(AppError::BadRequest(format!(
+            "Allowed statuses are: {}",
+            STATUSES.join(", ")
+        )));
+    };
+
+    let current: DeliveryIncidentEntity = delivery_incidents::table
+        .find(id)
+        .for_update()
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery incident")?
+        .ok_or(AppError::NotFound)?;
+
+    if rank(&current.status).is_none_or(|current_rank| current_rank >= new_rank) {
+        return Err(AppError::BadRequest(format!(
+            "Incident cannot move from {} to {}",
+            current.status, status
+        )));
+    }
+
+    if status != "RESOLVED" {
+        let incident = diesel::update(delivery_incidents::table.find(id))
+            .set(delivery_incidents::status.eq(status))
+            .returning(DeliveryIncidentEntity::as_returning())
+            .get_result(conn)
+            .await
+            .context("Failed to update delivery incident")?;
+        return Ok(incident);
+    }
+
+    let resolution = resolution
+        .filter(|resolution| RESOLUTIONS.contains(&resolution.as_str()))
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Resolving requires a resolution of: {}",
+                RESOLUTIONS.join(", ")
+            ))
+        })?;
+
+    let incident: DeliveryIncidentEntity = diesel::update(delivery_incidents::table.find(id))
+        .set((
+            delivery_incidents::status.eq(status),
+            delivery_incidents::resolution.eq(&resolution),
+            delivery_incidents::resolution_note.eq(&resolution_note),
+            delivery_incidents::resolved_by.eq(changed_by),
+            delivery_incidents::resolved_at.eq(Utc::now()),
+        ))
+        .returning(DeliveryIncidentEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to resolve delivery incident")?;
+
+    // Every order on a consolidated delivery is reshipped or refunded.
+    for order_id in order_ids(conn, incident.delivery_id).await? {
+        outbox::publish(
+            conn,
+            "orders.delivery_incident_resolved".into(),
+            DeliveryIncidentResolvedEvent {
+                incident_id: incident.id,
+                delivery_id: incident.delivery_id,
+                order_id,
+                category: incident.category.clone(),
+                resolution: resolution.clone(),
+                resolution_note: resolution_note.clone(),
+                resolved_at: incident.resolved_at.unwrap_or_else(Utc::now),
+            },
+        )
+        .await
+        .context("Failed to create outbox")?;
+    }
+
+    Ok(incident)
+}
@@ -0,0 +1,175 @@
+//! Deliveries routed through hubs, as an ordered chain of legs.
+//!
+//! Each leg has its own courier and runs from one location to the next, the last one
+//! ending at the recipient. Scans start and complete legs, and the delivery's status and
+//! courier follow the leg in progress, so automatic dispatch leaves deliveries with legs
+//! alone. Deliveries without legs are a single trip and are moved by their scans directly.
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use uuid::Uuid;
+
+use crate::{
+    models::DeliveryLegEntity,
+    schema::{deliveries, delivery_legs},
+};
+
+pub const LEG_STATUSES: [&str; 4] = ["PENDING", "IN_TRANSIT", "COMPLETED", "CANCELLED"];
+
+/// Legs of a delivery, in order.
+pub async fn get_legs(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Uuid,
+) -> Result<Vec<DeliveryLegEntity>, AppError> {
+    let legs: Vec<DeliveryLegEntity> = delivery_legs::table
+        .filter(delivery_legs::delivery_id.eq(delivery_id))
+        .order_by(delivery_legs::sequence.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery legs")?;
+    Ok(legs)
+}
+
+/// The delivery status implied by its legs: EN_ROUTE once the last leg has started,
+/// PICKED_UP while it travels between hubs, and `None` before the first leg starts.
+pub fn derived_status(legs: &[DeliveryLegEntity]) -> Option<&'static str> {
+    let last = legs.last()?;
+    if last.status != "PENDING" {
+        Some("EN_ROUTE")
+    } else if legs.iter().any(|leg| leg.status != "PENDING") {
+        Some("PICKED_UP")
+    } else {
+        None
+    }
+}
+
+async fn start(
+    conn: &mut AsyncPgConnection,
+    leg: &DeliveryLegEntity,
+    at: DateTime<Utc>,
+) -> Result<DeliveryLegEntity, AppError> {
+    let leg: DeliveryLegEntity = diesel::update(delivery_legs::table.find(leg.id))
+        .set((
+            delivery_legs::status.eq("IN_TRANSIT"),
+            delivery_legs::started_at.eq(at),
+        ))
+        .returning(DeliveryLegEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to start delivery leg")?;
+
+    // The courier of the leg in progress is the one carrying the delivery.
+    if let Some(courier_id) = leg.courier_id {
+        diesel::update(deliveries::table.find(leg.delivery_id))
+            .set((
+                deliveries::courier_id.eq(courier_id),
+                deliveries::assigned_at.eq(at),
+            ))
+            .execute(conn)
+            .await
+            .context("Failed to hand delivery over to the leg's courier")?;
+    }
+    Ok(leg)
+}
+
+async fn complete(
+    conn: &mut AsyncPgConnection,
+    leg: &DeliveryLegEntity,
+    at: DateTime<Utc>,
+) -> Result<DeliveryLegEntity, AppError> {
+    let leg: DeliveryLegEntity = diesel::update(delivery_legs::table.find(leg.id))
+        .set((
+            delivery_legs::status.eq("COMPLETED"),
+            delivery_legs::completed_at.eq(at),
+        ))
+        .returning(DeliveryLegEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to complete delivery leg")?;
+    Ok(leg)
+}
+
+/// Moves the legs of `delivery_id` along for a scan: PICKUP starts the first leg,
+/// HUB_INBOUND completes the leg arriving at a hub, HUB_OUTBOUND starts the next leg
+/// and OUT_FOR_DELIVERY starts the last one. Returns the legs as they are afterwards,
+/// empty for deliveries without legs. Must be called inside a transaction.
+pub async fn apply_scan(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Uuid,
+    scan_type: &str,
+    at: DateTime<Utc>,
+) -> Result<Vec<DeliveryLegEntity>, AppError> {
+    let mut legs: Vec<DeliveryLegEntity> = delivery_legs::table
+        .filter(delivery_legs::delivery_id.eq(delivery_id))
+        .order_by(delivery_legs::sequence.asc())
+        .for_update()
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery legs")?;
+    let Some(last) = legs.len().checked_sub(1) else {
+        return Ok(legs);
+    };
+
+    let in_transit = legs.iter().position(|leg| leg.status == "IN_TRANSIT");
+    match scan_type {
+        "PICKUP" if legs[0].status == "PENDING" => {
+            legs[0] = start(conn, &legs[0], at).await?;
+        }
+        "HUB_INBOUND" | "HUB_OUTBOUND" | "OUT_FOR_DELIVERY" => {
+            // A missed inbound scan is implied by the departure that follows it.
+            if let Some(i) = in_transit
+                && legs[i].destination_location_id.is_some()
+            {
+                legs[i] = complete(conn, &legs[i], at).await?;
+            }
+            let departing = match scan_type {
+                "HUB_OUTBOUND" => legs.iter().position(|leg| leg.status == "PENDING"),
+                "OUT_FOR_DELIVERY" => Some(last),
+                _ => None,
+            };
+            if let Some(i) = departing
+                && i > 0
+                && legs[i].status == "PENDING"
+                && legs[i - 1].status == "COMPLETED"
+            {
+                legs[i] = start(conn, &legs[i], at).await?;
+            }
+        }
+        _ => {}
+    }
+    Ok(legs)
+}
+
+/// Closes the legs still open when a delivery reaches the recipient or is called off:
+/// `COMPLETED` when it arrived, `CANCELLED` otherwise. Must be called inside a transaction.
+pub async fn close(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Uuid,
+    status: &str,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    let open = delivery_legs::table
+        .filter(delivery_legs::delivery_id.eq(delivery_id))
+        .filter(delivery_legs::status.eq_any(["PENDING", "IN_TRANSIT"]));
+
+    if status == "COMPLETED" {
+        diesel::update(open)
+            .set((
+                delivery_legs::status.eq(status),
+                delivery_legs::completed_at.eq(now),
+            ))
+            .execute(conn)
+            .await
+            .context("Failed to complete delivery legs")?;
+    } else {
+        diesel::update(open)
+            .set(delivery_legs::status.eq(status))
+            .execute(conn)
+            .await
+            .context("Failed to cancel delivery legs")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,317 @@
+//! Origins deliveries leave from, with their opening hours and order cutoffs.
+//!
+//! Opening hours and cutoffs are in the location's own time zone. An order placed before
+//! the cutoff on an opening day ships that day; anything later ships when the location
+//! next opens. Locations stay closed on holidays and on days they are closed for.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    models::{LocationEntity, LocationOpeningHoursEntity},
+    schema::{location_opening_hours, locations},
+    services::calendar::{Calendar, Scope},
+};
+
+pub const LOCATION_TYPES: [&str; 3] = ["PHARMACY", "HUB", "LAB"];
+
+/// How far ahead to look for the next opening before giving up on a location.
+const HORIZON_DAYS: i64 = 14;
+
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// When a location is open and takes orders for same-day dispatch.
+#[derive(Debug, Clone)]
+pub struct OpeningSchedule {
+    location_id: i32,
+    timezone: Tz,
+    cutoff_time: Option<NaiveTime>,
+    hours: Vec<LocationOpeningHoursEntity>,
+    calendar: Calendar,
+}
+
+impl OpeningSchedule {
+    pub fn of(
+        location: &LocationEntity,
+        hours: Vec<LocationOpeningHoursEntity>,
+        calendar: Calendar,
+    ) -> Result<Self, AppError> {
+        Ok(OpeningSchedule {
+            location_id: location.id,
+            timezone: parse_timezone(&location.timezone)?,
+            cutoff_time: location.cutoff_time,
+            hours,
+            calendar,
+        })
+    }
+
+    pub fn scope(&self) -> Scope<'static> {
+        Scope {
+            zone: None,
+            location_id: Some(self.location_id),
+        }
+    }
+
+    /// Opening windows on the local `date`, earliest first. None on days not worked.
+    fn windows_on(&self, date: NaiveDate) -> Vec<Slot> {
+        if !self.calendar.is_working_day(date, self.scope()) {
+            return Vec::new();
+        }
+        let weekday = date.weekday().number_from_monday() as i16;
+        let mut windows: Vec<Slot> = self
+            .hours
+            .iter()
+            .filter(|hours| hours.weekday == weekday)
+            .filter_map(|hours| {
+                // Times skipped by a DST change have no instant, so the window is dropped.
+                let at = |time| {
+                    self.timezone
+                        .from_local_datetime(&date.and_time(time))
+                        .earliest()
+                        .map(|at| at.with_timezone(&Utc))
+                };
+                Some(Slot {
+                    start: at(hours.opens_at)?,
+                    end: at(hours.closes_at)?,
+                })
+            })
+            .collect();
+        windows.sort_by_key(|window| window.start);
+        windows
+    }
+
+    pub fn is_open(&self, at: DateTime<Utc>) -> bool {
+        let date = at.with_timezone(&self.timezone).date_naive();
+        self.windows_on(date)
+            .iter()
+            .any(|window| window.start <= at && at < window.end)
+    }
+
+    /// Earliest time an order placed at `ordered_at` can leave the location, or `None`
+    /// when it does not open within the next two weeks.
+    pub fn ships_at(&self, ordered_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = ordered_at.with_timezone(&self.timezone);
+        let mut date = local.date_naive();
+        if self
+            .cutoff_time
+            .is_some_and(|cutoff| local.time() >= cutoff)
+        {
+            date = date.succ_opt()?;
+        }
+        (0..HORIZON_DAYS)
+            .filter_map(|offset| date.checked_add_signed(Duration::days(offset)))
+            .flat_map(|date| self.windows_on(date))
+            .find(|window| window.end > ordered_at)
+            .map(|window| window.start.max(ordered_at))
+    }
+
+    /// Delivery slots of `slot_length` within opening hours, for `days` opening days from
+    /// when an order placed at `ordered_at` ships.
+    pub fn slots(
+        &self,
+        ordered_at: DateTime<Utc>,
+        days: usize,
+        slot_length: Duration,
+    ) -> Vec<Slot> {
+        let Some(ships_at) = self.ships_at(ordered_at) else {
+            return Vec::new();
+        };
+        let first_date = ships_at.with_timezone(&self.timezone).date_naive();
+
+        (0..HORIZON_DAYS)
+            .filter_map(|offset| first_date.checked_add_signed(Duration::days(offset)))
+            .map(|date| self.windows_on(date))
+            .filter(|windows| !windows.is_empty())
+            .take(days)
+            .flatten()
+            .flat_map(|window| {
+                let mut slots = Vec::new();
+                let mut start = window.start;
+                while start + slot_length <= window.end {
+                    slots.push(Slot {
+                        start,
+                        end: start + slot_length,
+                    });
+                    start += slot_length;
+                }
+                slots
+            })
+            .filter(|slot| slot.start >= ships_at)
+            .collect()
+    }
+}
+
+pub fn parse_timezone(timezone: &str) -> Result<Tz, AppError> {
+    timezone
+        .parse()
+        .map_err(|_| AppError::BadRequest(format!("Unknown time zone {timezone}")))
+}
+
+/// Fetches a location with its opening schedule.
+pub async fn get_location(
+    conn: &mut AsyncPgConnection,
+    id: i32,
+) -> Result<(LocationEntity, Vec<LocationOpeningHoursEntity>), AppError> {
+    let location: LocationEntity = locations::table
+        .find(id)
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get location")?
+        .ok_or(AppError::NotFound)?;
+
+    let hours: Vec<LocationOpeningHoursEntity> = location_opening_hours::table
+        .filter(location_opening_hours::location_id.eq(id))
+        .order_by((
+            location_opening_hours::weekday.asc(),
+            location_opening_hours::opens_at.asc(),
+        ))
+        .get_results(conn)
+        .await
+        .context("Failed to get opening hours")?;
+
+    Ok((location, hours))
+}
+
+/// Opening schedules of those of the given locations that are active, keyed by location ID.
+pub async fn schedules(
+    conn: &mut AsyncPgConnection,
+    ids: &[i32],
+) -> Result<HashMap<i32, OpeningSchedule>, AppError> {
+    let calendar = Calendar::load(conn).await?;
+    let origins: Vec<LocationEntity> = locations::table
+        .filter(locations::id.eq_any(ids))
+        .filter(locations::is_active.eq(true))
+        .get_results(conn)
+        .await
+        .context("Failed to get locations")?;
+
+    let mut hours: HashMap<i32, Vec<LocationOpeningHoursEntity>> = HashMap::new();
+    let hour_rows: Vec<LocationOpeningHoursEntity> = location_opening_hours::table
+        .filter(location_opening_hours::location_id.eq_any(ids))
+        .get_results(conn)
+        .await
+        .context("Failed to get opening hours")?;
+    for row in hour_rows {
+        hours.entry(row.location_id).or_default().push(row);
+    }
+
+    origins
+        .iter()
+        .map(|origin| {
+            let schedule = OpeningSchedule::of(
+                origin,
+                hours.remove(&origin.id).unwrap_or_default(),
+                calendar.clone(),
+            )?;
+            Ok((origin.id, schedule))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Weekday;
+
+    use super::*;
+
+    /// Opens 08:00–17:00 Bangkok time on weekdays and takes orders until 14:00.
+    fn schedule() -> OpeningSchedule {
+        let location = LocationEntity {
+            id: 1,
+            name: "Pharmacy".into(),
+            location_type: "PHARMACY".into(),
+            address: "Bangkok".into(),
+            latitude: None,
+            longitude: None,
+            timezone: "Asia/Bangkok".into(),
+            cutoff_time: NaiveTime::from_hms_opt(14, 0, 0),
+            is_active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let hours = (1..=5)
+            .map(|weekday| LocationOpeningHoursEntity {
+                id: weekday.into(),
+                location_id: location.id,
+                weekday,
+                opens_at: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                closes_at: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            })
+            .collect();
+        let calendar = Calendar::new(
+            chrono_tz::Asia::Bangkok,
+            vec![Weekday::Sat, Weekday::Sun],
+            Vec::new(),
+        );
+        OpeningSchedule::of(&location, hours, calendar).unwrap()
+    }
+
+    /// `hour:minute` Bangkok time on the given day of 2026.
+    fn bangkok(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        chrono_tz::Asia::Bangkok
+            .with_ymd_and_hms(2026, month, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn ships_at_waits_for_the_opening() {
+        assert_eq!(
+            schedule().ships_at(bangkok(9, 7, 6, 30)),
+            Some(bangkok(9, 7, 8, 0))
+        );
+    }
+
+    #[test]
+    fn ships_at_ships_immediately_before_the_cutoff() {
+        assert_eq!(
+            schedule().ships_at(bangkok(9, 7, 13, 59)),
+            Some(bangkok(9, 7, 13, 59))
+        );
+    }
+
+    #[test]
+    fn ships_at_moves_orders_after_the_cutoff_to_the_next_opening() {
+        assert_eq!(
+            schedule().ships_at(bangkok(9, 7, 14, 0)),
+            Some(bangkok(9, 8, 8, 0))
+        );
+    }
+
+    #[test]
+    fn ships_at_skips_rest_days() {
+        assert_eq!(
+            schedule().ships_at(bangkok(9, 11, 15, 0)),
+            Some(bangkok(9, 14, 8, 0))
+        );
+    }
+
+    #[test]
+    fn ships_at_skips_holidays() {
+        // Friday 23 October is Chulalongkorn Day.
+        assert_eq!(
+            schedule().ships_at(bangkok(10, 22, 16, 0)),
+            Some(bangkok(10, 26, 8, 0))
+        );
+    }
+
+    #[test]
+    fn ships_at_gives_up_on_locations_that_never_open() {
+        let mut schedule = schedule();
+        schedule.hours.clear();
+        assert_eq!(schedule.ships_at(bangkok(9, 7, 9, 0)), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Parcel locker integration. Lockers are operated by third parties, so compartments are
+//! allocated and released through a [`LockerProvider`]. Providers are only called outside
+//! of transactions, once the status change needing a compartment has been committed.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result, anyhow};
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl, pooled_connection::bb8::Pool};
+use futures::future::BoxFuture;
+use uuid::Uuid;
+
+use crate::schema::locker_compartments;
+
+/// Allocates and releases locker compartments.
+pub trait LockerProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Reserves a free compartment of `locker_id` for `delivery_id`. `None` when the locker
+    /// is full.
+    fn allocate<'a>(
+        &'a self,
+        locker_id: &'a str,
+        delivery_id: Uuid,
+    ) -> BoxFuture<'a, Result<Option<String>>>;
+
+    /// Frees a compartment once the parcel of `delivery_id` is collected or taken back.
+    /// Compartments since given to another delivery are left alone.
+    fn release<'a>(
+        &'a self,
+        locker_id: &'a str,
+        compartment: &'a str,
+        delivery_id: Uuid,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Keeps compartment allocations in memory. Allocations do not survive a restart and are
+/// not shared between replicas, so this is meant for tests and local development.
+pub struct InMemoryLockerProvider {
+    /// Compartments of each locker, with the delivery occupying them.
+    lockers: Mutex<HashMap<String, BTreeMap<String, Option<Uuid>>>>,
+}
+
+impl InMemoryLockerProvider {
+    pub fn new(lockers: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            lockers: Mutex::new(
+                lockers
+                    .into_iter()
+                    .map(|(locker_id, compartments)| {
+                        (
+                            locker_id,
+                            compartments
+                                .into_iter()
+                                .map(|compartment| (compartment, None))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl LockerProvider for InMemoryLockerProvider {
+    fn name(&self) -> &'static str {
+        "in-memory"
+    }
+
+    fn allocate<'a>(
+        &'a self,
+        locker_id: &'a str,
+        delivery_id: Uuid,
+    ) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            let mut lockers = self
+                .lockers
+                .lock()
+                .map_err(|_| anyhow!("Locker state is poisoned"))?;
+            let compartments = lockers
+                .get_mut(locker_id)
+                .with_context(|| format!("Unknown locker {locker_id}"))?;
+
+            // A retried allocation gets the compartment it already holds.
+            if let Some((compartment, _)) = compartments
+                .iter()
+                .find(|(_, occupant)| **occupant == Some(delivery_id))
+            {
+                return Ok(Some(compartment.clone()));
+            }
+            let Some((compartment, occupant)) = compartments
+                .iter_mut()
+                .find(|(_, occupant)| occupant.is_none())
+            else {
+                return Ok(None);
+            };
+            *occupant = Some(delivery_id);
+            Ok(Some(compartment.clone()))
+        })
+    }
+
+    fn release<'a>(
+        &'a self,
+        locker_id: &'a str,
+        compartment: &'a str,
+        delivery_id: Uuid,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut lockers = self
+                .lockers
+                .lock()
+                .map_err(|_| anyhow!("Locker state is poisoned"))?;
+            if let Some(occupant) = lockers
+                .get_mut(locker_id)
+                .and_then(|compartments| compartments.get_mut(compartment))
+                .filter(|occupant| **occupant == Some(delivery_id))
+            {
+                *occupant = None;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Keeps compartment allocations in `locker_compartments`, so they survive restarts and
+/// are shared between replicas. The lockers of `LOCKER_SITES` are added on start.
+pub struct DatabaseLockerProvider {
+    pool: Pool<AsyncPgConnection>,
+}
+
+impl DatabaseLockerProvider {
+    pub async fn new(
+        pool: Pool<AsyncPgConnection>,
+        lockers: HashMap<String, Vec<String>>,
+    ) -> Result<Self> {
+        let conn = &mut pool
+            .get()
+            .await
+            .context("Failed to obtain a DB connection pool")?;
+        let compartments: Vec<_> = lockers
+            .into_iter()
+            .flat_map(|(locker_id, compartments)| {
+                compartments.into_iter().map(move |compartment| {
+                    (
+                        locker_compartments::locker_id.eq(locker_id.clone()),
+                        locker_compartments::compartment.eq(compartment),
+                    )
+                })
+            })
+            .collect();
+        if !compartments.is_empty() {
+            diesel::insert_into(locker_compartments::table)
+                .values(compartments)
+                .on_conflict_do_nothing()
+                .execute(conn)
+                .await
+                .context("Failed to add locker compartments")?;
+        }
+        Ok(Self { pool: pool.clone() })
+    }
+}
+
+impl LockerProvider for DatabaseLockerProvider {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    fn allocate<'a>(
+        &'a self,
+        locker_id: &'a str,
+        delivery_id: Uuid,
+    ) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            let conn = &mut self
+                .pool
+                .get()
+                .await
+                .context("Failed to obtain a DB connection pool")?;
+            conn.transaction(|conn| {
+                Box::pin(async move {
+                    let compartments: Vec<(String, Option<Uuid>)> = locker_compartments::table
+                        .filter(locker_compartments::locker_id.eq(locker_id))
+                        .order_by(locker_compartments::compartment.asc())
+                        .select((
+                            locker_compartments::compartment,
+                            locker_compartments::delivery_id,
+                        ))
+                        .for_update()
+                        .get_results(conn)
+                        .await
+                        .context("Failed to get locker compartments")?;
+                    if compartments.is_empty() {
+                        return Err(anyhow!("Unknown locker {locker_id}"));
+                    }
+
+                    // A retried allocation gets the compartment it already holds.
+                    if let Some((compartment, _)) = compartments
+                        .iter()
+                        .find(|(_, occupant)| *occupant == Some(delivery_id))
+                    {
+                        return Ok(Some(compartment.clone()));
+                    }
+                    let Some((compartment, _)) = compartments
+                        .into_iter()
+                        .find(|(_, occupant)| occupant.is_none())
+                    else {
+                        return Ok(None);
+                    };
+                    diesel::update(locker_compartments::table.find((locker_id, &compartment)))
+                        .set((
+                            locker_compartments::delivery_id.eq(delivery_id),
+                            locker_compartments::updated_at.eq(Utc::now()),
+                        ))
+                        .execute(conn)
+                        .await
+                        .context("Failed to allocate locker compartment")?;
+                    Ok(Some(compartment))
+                })
+            })
+            .await
+        })
+    }
+
+    fn release<'a>(
+        &'a self,
+        locker_id: &'a str,
+        compartment: &'a str,
+        delivery_id: Uuid,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let conn = &mut self
+                .pool
+                .get()
+                .await
+                .context("Failed to obtain a DB connection pool")?;
+            diesel::update(
+                locker_compartments::table
+                    .find((locker_id, compartment))
+                    .filter(locker_compartments::delivery_id.eq(delivery_id)),
+            )
+            .set((
+                locker_compartments::delivery_id.eq(None::<Uuid>),
+                locker_compartments::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .context("Failed to release locker compartment")?;
+            Ok(())
+        })
+    }
+}
+
+/// Parses lockers written as `LOCKER_ID:COMPARTMENT,COMPARTMENT;LOCKER_ID:...`.
+pub fn parse_sites(spec: &str) -> Result<HashMap<String, Vec<String>>> {
+    let mut lockers = HashMap::new();
+    for site in spec
+        .split(';')
+        .map(str::trim)
+        .filter(|site| !site.is_empty())
+    {
+        let (locker_id, compartments) = site
+            .split_once(':')
+            .with_context(|| format!("Locker {site} has no compartments"))?;
+        lockers.insert(
+            locker_id.trim().to_string(),
+            compartments
+                .split(',')
+                .map(|compartment| compartment.trim().to_string())
+                .filter(|compartment| !compartment.is_empty())
+                .collect(),
+        );
+    }
+    Ok(lockers)
+}
+
+/// The locker provider `name`, serving the lockers of `sites`. `None` for unknown providers.
+pub async fn provider_by_name(
+    name: &str,
+    pool: Pool<AsyncPgConnection>,
+    sites: &str,
+) -> Result<Option<Box<dyn LockerProvider>>> {
+    let lockers = parse_sites(sites).context("LOCKER_SITES is invalid")?;
+    Ok(match name {
+        "database" => Some(Box::new(DatabaseLockerProvider::new(pool, lockers).await?)),
+        "in-memory" => Some(Box::new(InMemoryLockerProvider::new(lockers))),
+        _ => None,
+    })
+}
@@ -0,0 +1,23 @@
+pub mod calendar;
+pub mod carrier_imports;
+pub mod carrier_webhooks;
+pub mod carriers;
+pub mod consolidation;
+pub mod custody;
+pub mod delegations;
+pub mod deliveries;
+pub mod dispatch;
+pub mod id_verification;
+pub mod incidents;
+pub mod legs;
+pub mod locations;
+pub mod lockers;
+pub mod packages;
+pub mod pickups;
+pub mod preferences;
+pub mod ratings;
+pub mod route_planner;
+pub mod schedules;
+pub mod sla;
+pub mod sweeper;
+pub mod webhooks;
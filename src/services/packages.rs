@@ -0,0 +1,209 @@
+use anyhow::Context;
+use diesel::{BelongingToDsl, ExpressionMethods, GroupedBy, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    events::PackageManifest,
+    models::{
+        CreateDeliveryPackageEntity, CreatePackageItemEntity, DeliveryPackageEntity,
+        PackageItemEntity,
+    },
+    schema::{delivery_packages, package_items},
+};
+
+/// Cubic millimetres per gram of volumetric weight (the common 5000 cm³/kg divisor).
+const VOLUMETRIC_DIVISOR: i64 = 5_000;
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct PackageWithItems {
+    pub package: DeliveryPackageEntity,
+    pub items: Vec<PackageItemEntity>,
+}
+
+/// Totals of a delivery's packages, used for courier capacity checks during dispatch and
+/// quoting.
+#[derive(Serialize, ToSchema, Debug, Clone, Default)]
+pub struct ManifestSummary {
+    pub package_count: i64,
+    pub total_weight_grams: i64,
+    pub total_volume_cm3: i64,
+    /// Sum over packages of the greater of actual and volumetric weight.
+    pub billable_weight_grams: i64,
+    pub has_fragile: bool,
+    pub requires_refrigeration: bool,
+}
+
+impl ManifestSummary {
+    pub fn of<'a>(packages: impl IntoIterator<Item = &'a DeliveryPackageEntity>) -> Self {
+        packages
+            .into_iter()
+            .fold(Self::default(), |mut summary, package| {
+                let volume_mm3 = i64::from(package.length_mm)
+                    * i64::from(package.width_mm)
+                    * i64::from(package.height_mm);
+                let weight_grams = i64::from(package.weight_grams);
+
+                summary.package_count += 1;
+                summary.total_weight_grams += weight_grams;
+                summary.total_volume_cm3 += volume_mm3 / 1_000;
+                summary.billable_weight_grams += weight_grams.max(volume_mm3 / VOLUMETRIC_DIVISOR);
+                summary.has_fragile |= package.is_fragile;
+                summary.requires_refrigeration |= package.is_refrigerated;
+                summary
+            })
+    }
+}
+
+/// Checks a package manifest has a positive weight, positive dimensions and positive item
+/// quantities.
+pub fn validate_manifest(manifest: &PackageManifest) -> Result<(), &'static str> {
+    if manifest.weight_grams <= 0 {
+        return Err("weight must be positive");
+    }
+    if [manifest.length_mm, manifest.width_mm, manifest.height_mm]
+        .iter()
+        .any(|dimension| *dimension <= 0)
+    {
+        return Err("dimensions must be positive");
+    }
+    if manifest.items.iter().any(|item| item.quantity <= 0) {
+        return Err("item quantities must be positive");
+    }
+    Ok(())
+}
+
+/// Stores the packages and their items from an order request. The manifests must have
+/// passed [`validate_manifest`]. Must be called inside a transaction.
+pub async fn create_packages(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Uuid,
+    manifests: Vec<PackageManifest>,
+) -> Result<Vec<PackageWithItems>, AppError> {
+    // Consolidated deliveries already have packages from earlier orders.
+    let existing: i64 = delivery_packages::table
+        .filter(delivery_packages::delivery_id.eq(delivery_id))
+        .count()
+        .get_result(conn)
+        .await
+        .context("Failed to count delivery packages")?;
+
+    let mut packages = Vec::with_capacity(manifests.len());
+    for (i, manifest) in manifests.into_iter().enumerate() {
+        let package: DeliveryPackageEntity = diesel::insert_into(delivery_packages::table)
+            .values(CreateDeliveryPackageEntity {
+                delivery_id,
+                package_number: existing as i32 + i as i32 + 1,
+                weight_grams: manifest.weight_grams,
+                length_mm: manifest.length_mm,
+                width_mm: manifest.width_mm,
+                height_mm: manifest.height_mm,
+                is_fragile: manifest.is_fragile,
+                is_refrigerated: manifest.is_refrigerated,
+            })
+            .returning(DeliveryPackageEntity::as_returning())
+            .get_result(conn)
+            .await
+            .context("Failed to create delivery package")?;
+
+        if manifest.items.is_empty() {
+            packages.push(PackageWithItems {
+                package,
+                items: Vec::new(),
+            });
+            continue;
+        }
+
+        let items: Vec<PackageItemEntity> = diesel::insert_into(package_items::table)
+            .values(
+                manifest
+                    .items
+                    .into_iter()
+                    .map(|item| CreatePackageItemEntity {
+                        package_id: package.id,
+                        order_item_id: item.order_item_id,
+                        quantity: item.quantity,
+                        description: item.description,
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .returning(PackageItemEntity::as_returning())
+            .get_results(conn)
+            .await
+            .context("Failed to create package items")?;
+
+        packages.push(PackageWithItems { package, items });
+    }
+    Ok(packages)
+}
+
+/// Fetches the packages of a delivery, in package order, with their items.
+pub async fn get_packages(
+    conn: &mut AsyncPgConnection,
+    delivery_id: Uuid,
+) -> Result<Vec<PackageWithItems>, AppError> {
+    let packages: Vec<DeliveryPackageEntity> = delivery_packages::table
+        .filter(delivery_packages::delivery_id.eq(delivery_id))
+        .order_by(delivery_packages::package_number.asc())
+        .get_results(conn)
+        .await
+        .context("Failed to get delivery packages")?;
+
+    let items: Vec<PackageItemEntity> = PackageItemEntity::belonging_to(&packages)
+        .select(PackageItemEntity::as_select())
+        .get_results(conn)
+        .await
+        .context("Failed to get package items")?;
+
+    Ok(items
+        .grouped_by(&packages)
+        .into_iter()
+        .zip(packages)
+        .map(|(items, package)| PackageWithItems { package, items })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::PackageItemManifest;
+
+    fn manifest() -> PackageManifest {
+        PackageManifest {
+            weight_grams: 250,
+            length_mm: 200,
+            width_mm: 100,
+            height_mm: 50,
+            is_fragile: false,
+            is_refrigerated: false,
+            items: vec![PackageItemManifest {
+                order_item_id: 1,
+                quantity: 2,
+                description: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn validate_manifest_accepts_positive_measures() {
+        assert!(validate_manifest(&manifest()).is_ok());
+    }
+
+    #[test]
+    fn validate_manifest_rejects_non_positive_measures() {
+        let mut weightless = manifest();
+        weightless.weight_grams = 0;
+        assert!(validate_manifest(&weightless).is_err());
+
+        let mut flat = manifest();
+        flat.height_mm = -1;
+        assert!(validate_manifest(&flat).is_err());
+
+        let mut empty = manifest();
+        empty.items[0].quantity = 0;
+        assert!(validate_manifest(&empty).is_err());
+    }
+}
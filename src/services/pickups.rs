@@ -0,0 +1,347 @@
+//! Pharmacy pickup and parcel locker fulfilment.
+//!
+//! Once a parcel reaches its pickup point it becomes READY_FOR_PICKUP and the patient is
+//! sent a pickup code that is only stored hashed. Parcels not collected before
+//! `pickup_expires_at` are sent back through RETURNING and RETURNED.
+//!
+//! Locker compartments are allocated and released by [`sync_lockers`] once the status
+//! change has been committed, so a rolled back change never holds or frees a compartment.
+//! Locker parcels get their pickup code when their compartment is allocated, and keep
+//! `locker_compartment` after leaving READY_FOR_PICKUP until it has been released.
+
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use medbook_core::{app_error::AppError, outbox};
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    config,
+    events::{DeliveryPickupExpiredEvent, DeliveryReadyForPickupEvent},
+    models::{DeliveryEntity, DeliveryLogEntity},
+    schema::deliveries,
+    services::{
+        deliveries::{order_ids, transition},
+        lockers::LockerProvider,
+    },
+};
+
+/// Hashes a pickup code. The delivery ID salts the hash, so equal codes of different
+/// deliveries hash differently.
+pub fn hash_code(delivery_id: Uuid, pickup_code: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(delivery_id.as_bytes());
+    hasher.update(pickup_code.trim().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// A random six digit pickup code.
+fn generate_code() -> Result<String, AppError> {
+    let mut bytes = [0u8; 4];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("Failed to generate a pickup code"))?;
+    Ok(format!("{:06}", u32::from_le_bytes(bytes) % 1_000_000))
+}
+
+/// Wrong pickup codes accepted before the code is replaced by a new one.
+const MAX_CODE_ATTEMPTS: i32 = 5;
+
+/// Issues a new pickup code expiring at `expires_at` and sends it to the patient. Must be
+/// called inside a transaction.
+async fn issue_code(
+    conn: &mut AsyncPgConnection,
+    delivery: DeliveryEntity,
+    locker_compartment: Option<String>,
+    expires_at: DateTime<Utc>,
+) -> Result<DeliveryEntity, AppError> {
+    let pickup_code = generate_code()?;
+    let delivery: DeliveryEntity = diesel::update(deliveries::table.find(delivery.id))
+        .set((
+            deliveries::locker_compartment.eq(&locker_compartment),
+            deliveries::pickup_code_hash.eq(hash_code(delivery.id, &pickup_code)),
+            deliveries::pickup_code_attempts.eq(0),
+            deliveries::pickup_expires_at.eq(expires_at),
+        ))
+        .returning(DeliveryEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to issue pickup code")?;
+
+    outbox::publish(
+        conn,
+        "delivery.ready_for_pickup".into(),
+        DeliveryReadyForPickupEvent {
+            delivery_id: delivery.id,
+            order_id: delivery.order_id,
+            patient_id: delivery.patient_id,
+            fulfilment_mode: delivery.fulfilment_mode.clone(),
+            pickup_point: delivery.pickup_point.clone(),
+            locker_compartment,
+            pickup_code,
+            expires_at,
+        },
+    )
+    .await
+    .context("Failed to create outbox")?;
+
+    Ok(delivery)
+}
+
+/// Makes a delivery that just became READY_FOR_PICKUP collectable by issuing its pickup
+/// code. Locker parcels wait for [`sync_lockers`] to allocate their compartment first.
+/// Must be called inside a transaction.
+pub async fn make_ready(
+    conn: &mut AsyncPgConnection,
+    delivery: DeliveryEntity,
+) -> Result<DeliveryEntity, AppError> {
+    match (&*delivery.fulfilment_mode, &delivery.pickup_point) {
+        ("LOCKER", Some(_)) => Ok(delivery),
+        ("LOCKER", None) => Err(AppError::BadRequest("Locker delivery has no locker".into())),
+        _ => {
+            let expires_at = Utc::now() + config::pickup_hold_time()?;
+            issue_code(conn, delivery, None, expires_at).await
+        }
+    }
+}
+
+/// Invalidates the pickup code of a delivery leaving READY_FOR_PICKUP. Its locker
+/// compartment is released by [`sync_lockers`]. Must be called inside a transaction.
+pub async fn close(
+    conn: &mut AsyncPgConnection,
+    delivery: DeliveryEntity,
+) -> Result<DeliveryEntity, AppError> {
+    let collected_at = (delivery.status == "COLLECTED").then(Utc::now);
+    let delivery: DeliveryEntity = diesel::update(deliveries::table.find(delivery.id))
+        .set((
+            deliveries::pickup_code_hash.eq(None::<String>),
+            deliveries::collected_at.eq(collected_at),
+        ))
+        .returning(DeliveryEntity::as_returning())
+        .get_result(conn)
+        .await
+        .context("Failed to close pickup")?;
+    Ok(delivery)
+}
+
+/// Hands a READY_FOR_PICKUP parcel over to whoever presents its pickup code. Wrong codes
+/// are counted, and after `MAX_CODE_ATTEMPTS` of them the patient is sent a new code. Runs
+/// its own transaction, so that refused attempts are still counted.
+pub async fn collect(
+    conn: &mut AsyncPgConnection,
+    id: Uuid,
+    pickup_code: &str,
+    collected_by: &str,
+) -> Result<(DeliveryEntity, DeliveryLogEntity), AppError> {
+    let collection = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery")?
+                    .ok_or(AppError::NotFound)?;
+
+                if delivery.status != "READY_FOR_PICKUP" {
+                    return Err(AppError::BadRequest(format!(
+                        "Delivery is {}, not ready for pickup",
+                        delivery.status
+                    )));
+                }
+                let Some(expires_at) = delivery.pickup_expires_at else {
+                    return Err(AppError::BadRequest(
+                        "Parcel is still being placed in its locker".into(),
+                    ));
+                };
+                if expires_at < Utc::now() {
+                    return Err(AppError::BadRequest("Pickup code has expired".into()));
+                }
+                if delivery.pickup_code_hash.as_deref()
+                    != Some(hash_code(id, pickup_code).as_str())
+                {
+                    let attempts = delivery.pickup_code_attempts + 1;
+                    if attempts >= MAX_CODE_ATTEMPTS {
+                        let locker_compartment = delivery.locker_compartment.clone();
+                        issue_code(conn, delivery, locker_compartment, expires_at).await?;
+                        return Ok(Err(
+                            "Pickup code is incorrect. Too many attempts, a new code has been sent to the patient",
+                        ));
+                    }
+                    diesel::update(deliveries::table.find(id))
+                        .set(deliveries::pickup_code_attempts.eq(attempts))
+                        .execute(conn)
+                        .await
+                        .context("Failed to count pickup code attempt")?;
+                    return Ok(Err("Pickup code is incorrect"));
+                }
+
+                let place = match (&delivery.pickup_point, &delivery.locker_compartment) {
+                    (Some(pickup_point), Some(compartment)) => {
+                        format!("locker {pickup_point} compartment {compartment}")
+                    }
+                    (Some(pickup_point), None) => pickup_point.clone(),
+                    (None, _) => "the pharmacy".into(),
+                };
+                let collected = transition(
+                    conn,
+                    id,
+                    "COLLECTED".into(),
+                    format!("Collected by {collected_by} from {place}"),
+                )
+                .await?;
+                Ok::<_, AppError>(Ok(collected))
+            })
+        })
+        .await?;
+
+    collection.map_err(|reason| AppError::BadRequest(reason.into()))
+}
+
+#[derive(Debug, Default)]
+pub struct LockerSync {
+    pub allocated: usize,
+    /// Parcels waiting for a compartment of a full locker.
+    pub waiting: usize,
+    pub released: usize,
+}
+
+/// Allocates compartments for locker parcels that became READY_FOR_PICKUP, issuing their
+/// pickup codes, and releases the compartments of parcels that have left it. Must not be
+/// called inside a transaction, as `provider` may be a remote service.
+pub async fn sync_lockers(
+    conn: &mut AsyncPgConnection,
+    provider: &dyn LockerProvider,
+) -> Result<LockerSync, AppError> {
+    let mut sync = LockerSync::default();
+
+    let waiting: Vec<(Uuid, Option<String>)> = deliveries::table
+        .filter(deliveries::fulfilment_mode.eq("LOCKER"))
+        .filter(deliveries::status.eq("READY_FOR_PICKUP"))
+        .filter(deliveries::locker_compartment.is_null())
+        .order_by(deliveries::updated_at.asc())
+        .select((deliveries::id, deliveries::pickup_point))
+        .get_results(conn)
+        .await
+        .context("Failed to get parcels waiting for a locker")?;
+    for (id, locker_id) in waiting {
+        let Some(locker_id) = locker_id else {
+            continue;
+        };
+        let Some(compartment) = provider.allocate(&locker_id, id).await? else {
+            warn!(delivery_id = %id, "Locker {locker_id} has no free compartment");
+            sync.waiting += 1;
+            continue;
+        };
+
+        conn.transaction(|conn| {
+            Box::pin(async move {
+                let delivery: DeliveryEntity = deliveries::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await
+                    .context("Failed to get delivery")?;
+                if delivery.locker_compartment.is_some() {
+                    return Ok(());
+                }
+                if delivery.status == "READY_FOR_PICKUP" {
+                    let expires_at = Utc::now() + config::pickup_hold_time()?;
+                    issue_code(conn, delivery, Some(compartment), expires_at).await?;
+                } else {
+                    // The parcel left before getting its compartment, which is released
+                    // below like any other.
+                    diesel::update(deliveries::table.find(id))
+                        .set(deliveries::locker_compartment.eq(compartment))
+                        .execute(conn)
+                        .await
+                        .context("Failed to record locker compartment")?;
+                }
+                Ok::<_, AppError>(())
+            })
+        })
+        .await?;
+        sync.allocated += 1;
+    }
+
+    let held: Vec<(Uuid, Option<String>, Option<String>)> = deliveries::table
+        .filter(deliveries::status.ne("READY_FOR_PICKUP"))
+        .filter(deliveries::locker_compartment.is_not_null())
+        .select((
+            deliveries::id,
+            deliveries::pickup_point,
+            deliveries::locker_compartment,
+        ))
+        .get_results(conn)
+        .await
+        .context("Failed to get locker compartments to release")?;
+    for (id, locker_id, compartment) in held {
+        let (Some(locker_id), Some(compartment)) = (locker_id, compartment) else {
+            continue;
+        };
+        provider.release(&locker_id, &compartment, id).await?;
+        diesel::update(
+            deliveries::table
+                .find(id)
+                .filter(deliveries::locker_compartment.eq(&compartment)),
+        )
+        .set(deliveries::locker_compartment.eq(None::<String>))
+        .execute(conn)
+        .await
+        .context("Failed to clear locker compartment")?;
+        sync.released += 1;
+    }
+
+    Ok(sync)
+}
+
+/// Sends parcels not collected by `now` back to the pharmacy. Must be called inside a
+/// transaction.
+pub async fn expire(
+    conn: &mut AsyncPgConnection,
+    now: DateTime<Utc>,
+) -> Result<Vec<DeliveryEntity>, AppError> {
+    let expired_ids: Vec<Uuid> = deliveries::table
+        .filter(deliveries::status.eq("READY_FOR_PICKUP"))
+        .filter(deliveries::pickup_expires_at.lt(now))
+        .order_by(deliveries::pickup_expires_at.asc())
+        .select(deliveries::id)
+        .for_update()
+        .skip_locked()
+        .get_results(conn)
+        .await
+        .context("Failed to get expired pickups")?;
+
+    let mut expired = Vec::with_capacity(expired_ids.len());
+    for id in expired_ids {
+        let (delivery, _) = transition(
+            conn,
+            id,
+            "RETURNING".into(),
+            "Not collected in time, returning to the pharmacy".into(),
+        )
+        .await?;
+
+        let order_ids = order_ids(conn, delivery.id).await?;
+        outbox::publish(
+            conn,
+            "delivery.pickup_expired".into(),
+            DeliveryPickupExpiredEvent {
+                delivery_id: delivery.id,
+                order_ids,
+                pickup_point: delivery.pickup_point.clone(),
+                expired_at: now,
+            },
+        )
+        .await
+        .context("Failed to create outbox")?;
+        expired.push(delivery);
+    }
+    Ok(expired)
+}
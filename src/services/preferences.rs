@@ -0,0 +1,37 @@
+//! Patient delivery preferences. Access codes are stored encrypted with AES-256-GCM.
+//! Couriers do not authenticate with this service, so it never decrypts them; couriers
+//! are only told that a delivery has one.
+
+use anyhow::{Result, anyhow};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    rand::{SecureRandom, SystemRandom},
+};
+
+pub const CONTACT_METHODS: [&str; 3] = ["CALL", "SMS", "NONE"];
+
+fn cipher(key: &[u8; 32]) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| anyhow!("Invalid access code key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Encrypts `access_code` under a random nonce, returning the nonce followed by the ciphertext.
+pub fn encrypt_access_code(key: &[u8; 32], access_code: &str) -> Result<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("Failed to generate a nonce"))?;
+
+    let mut in_out = access_code.as_bytes().to_vec();
+    cipher(key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut in_out,
+        )
+        .map_err(|_| anyhow!("Failed to encrypt access code"))?;
+
+    let mut encrypted = nonce.to_vec();
+    encrypted.extend(in_out);
+    Ok(encrypted)
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, BoxableExpression, ExpressionMethods, IntoSql, PgJsonbExpressionMethods,
+    QueryDsl, dsl::count_star, pg::Pg, sql_types::Bool,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::app_error::AppError;
+use serde::Serialize;
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::schema::delivery_ratings;
+
+pub const TAGS: [&str; 3] = ["late", "polite", "damaged"];
+
+diesel::define_sql_function! {
+    /// Postgres' `float8`, so that ratings average to a double instead of a numeric.
+    fn float8(x: diesel::sql_types::Int4) -> diesel::sql_types::Double;
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct RatingSummary {
+    /// Courier ID or zone the ratings are grouped by. `None` collects ratings of
+    /// deliveries without a courier or zone.
+    pub key: Option<String>,
+    pub rating_count: usize,
+    pub average_rating: f64,
+    /// Number of ratings of 1 to 5 stars, in that order.
+    pub distribution: [usize; 5],
+    pub tag_counts: BTreeMap<String, usize>,
+}
+
+type Window = Box<dyn BoxableExpression<delivery_ratings::table, Pg, SqlType = Bool>>;
+
+/// Whether a rating was given in `[from, to)`.
+fn given_within(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Window {
+    let mut window: Window = Box::new(true.into_sql::<Bool>());
+    if let Some(from) = from {
+        window = Box::new(window.and(delivery_ratings::created_at.ge(from)));
+    }
+    if let Some(to) = to {
+        window = Box::new(window.and(delivery_ratings::created_at.lt(to)));
+    }
+    window
+}
+
+/// Aggregates the ratings given in `[from, to)` per value of `$key`, in the order of the
+/// key's own type so that courier IDs sort numerically.
+macro_rules! summarize_by {
+    ($conn:expr, $key:expr => $key_type:ty, $from:expr, $to:expr) => {{
+        let ratings = || delivery_ratings::table.filter(given_within($from, $to));
+
+        let groups: Vec<(Option<$key_type>, i64, Option<f64>)> = ratings()
+            .group_by($key)
+            .select((
+                $key,
+                count_star(),
+                diesel::dsl::avg(float8(delivery_ratings::rating)),
+            ))
+            .order_by($key.asc())
+            .get_results($conn)
+            .await
+            .context("Failed to get rating averages")?;
+        let distribution: Vec<(Option<$key_type>, i32, i64)> = ratings()
+            .group_by(($key, delivery_ratings::rating))
+            .select(($key, delivery_ratings::rating, count_star()))
+            .get_results($conn)
+            .await
+            .context("Failed to get rating distribution")?;
+        let mut tag_counts = Vec::with_capacity(TAGS.len());
+        for tag in TAGS {
+            let counts: Vec<(Option<$key_type>, i64)> = ratings()
+                .filter(delivery_ratings::tags.contains(json!([tag])))
+                .group_by($key)
+                .select(($key, count_star()))
+                .get_results($conn)
+                .await
+                .context("Failed to get rating tag counts")?;
+            tag_counts.extend(counts.into_iter().map(|(key, count)| (key, tag, count)));
+        }
+
+        groups
+            .into_iter()
+            .map(|(key, rating_count, average_rating)| {
+                let mut summary = RatingSummary {
+                    key: key.as_ref().map(|key| key.to_string()),
+                    rating_count: rating_count as usize,
+                    average_rating: average_rating.unwrap_or_default(),
+                    distribution: [0; 5],
+                    tag_counts: BTreeMap::new(),
+                };
+                for (_, rating, count) in distribution.iter().filter(|(of, ..)| *of == key) {
+                    if let Some(slot) = summary.distribution.get_mut((rating - 1) as usize) {
+                        *slot = *count as usize;
+                    }
+                }
+                for (_, tag, count) in tag_counts.iter().filter(|(of, ..)| *of == key) {
+                    summary.tag_counts.insert(tag.to_string(), *count as usize);
+                }
+                summary
+            })
+            .collect::<Vec<_>>()
+    }};
+}
+
+/// Aggregates the ratings given in `[from, to)` per courier or per zone, as `group_by`
+/// names it. Ratings without a courier or zone are summarized last.
+pub async fn summarize(
+    conn: &mut AsyncPgConnection,
+    group_by: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<RatingSummary>, AppError> {
+    let summaries = match group_by {
+        "courier" => summarize_by!(conn, delivery_ratings::courier_id => i32, from, to),
+        "zone" => summarize_by!(conn, delivery_ratings::zone => String, from, to),
+        _ => {
+            return Err(AppError::BadRequest(
+                "Ratings can be grouped by: courier, zone".into(),
+            ));
+        }
+    };
+    Ok(summaries)
+}
@@ -0,0 +1,329 @@
+//! Orders a courier's stops: nearest-neighbour construction followed by 2-opt improvement,
+//! both scored against the stops' time windows and priorities.
+//!
+//! Planning is deterministic: the same stops in the same order always produce the same
+//! route. Times are minutes from the start of the route.
+
+/// Cost of a minute of lateness against a time window, in minutes of travel.
+const LATENESS_PENALTY: f64 = 100.0;
+/// Cost of delaying a stop by a minute, per priority level.
+const PRIORITY_WEIGHT: f64 = 0.5;
+/// Improvements smaller than this are ignored, so floating point noise cannot loop 2-opt.
+const EPSILON: f64 = 1e-6;
+/// Upper bound on 2-opt passes over the route.
+const MAX_PASSES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    /// Great-circle distance in kilometres.
+    pub fn distance_km(&self, other: &Coordinates) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6_371.0;
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stop<K> {
+    pub key: K,
+    pub location: Coordinates,
+    /// Earliest and latest minute service may start.
+    pub window: Option<(f64, f64)>,
+    /// Higher is more urgent.
+    pub priority: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlannerSettings {
+    pub start: Coordinates,
+    pub speed_kmh: f64,
+    pub service_minutes: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedStop<K> {
+    pub key: K,
+    pub arrival_minute: f64,
+    pub service_start_minute: f64,
+    pub lateness_minutes: f64,
+    pub distance_km: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route<K> {
+    pub stops: Vec<PlannedStop<K>>,
+    pub total_distance_km: f64,
+    pub total_lateness_minutes: f64,
+    pub finish_minute: f64,
+}
+
+/// Plans the order in which to visit `stops`.
+pub fn plan<K: Clone>(stops: &[Stop<K>], settings: &PlannerSettings) -> Route<K> {
+    let mut order = nearest_neighbour(stops, settings);
+    two_opt(stops, settings, &mut order);
+    simulate(stops, settings, &order)
+}
+
+fn travel_minutes(from: &Coordinates, to: &Coordinates, settings: &PlannerSettings) -> f64 {
+    from.distance_km(to) / settings.speed_kmh * 60.0
+}
+
+/// Builds a route by repeatedly visiting the cheapest next stop, where cost combines
+/// travel, waiting, lateness and priority.
+fn nearest_neighbour<K>(stops: &[Stop<K>], settings: &PlannerSettings) -> Vec<usize> {
+    let mut visited = vec![false; stops.len()];
+    let mut order = Vec::with_capacity(stops.len());
+    let mut current = Progress {
+        position: settings.start,
+        minute: 0.0,
+        cost: 0.0,
+    };
+
+    while order.len() < stops.len() {
+        let mut best: Option<(usize, Progress)> = None;
+        for (i, stop) in stops.iter().enumerate() {
+            if visited[i] {
+                continue;
+            }
+            let next = current.visit(stop, settings);
+            // Strict comparison keeps the earliest stop on ties, for determinism.
+            if best.is_none_or(|(_, best)| next.cost < best.cost - EPSILON) {
+                best = Some((i, next));
+            }
+        }
+
+        let (next, progress) = best.expect("an unvisited stop remains");
+        visited[next] = true;
+        order.push(next);
+        // Construction picks the cheapest next step, not the cheapest route so far.
+        current = Progress {
+            cost: 0.0,
+            ..progress
+        };
+    }
+    order
+}
+
+/// Reverses route segments while doing so lowers the route's cost.
+fn two_opt<K>(stops: &[Stop<K>], settings: &PlannerSettings, order: &mut [usize]) {
+    let mut prefixes = progress(stops, settings, order);
+    let mut best_cost = prefixes[order.len()].cost;
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for i in 0..order.len().saturating_sub(1) {
+            for j in i + 1..order.len() {
+                order[i..=j].reverse();
+                // Stops before `i` are unchanged, so only the rest of the route is re-costed.
+                let candidate = cost_from(stops, settings, &order[i..], prefixes[i]);
+                if candidate < best_cost - EPSILON {
+                    best_cost = candidate;
+                    improved = true;
+                    prefixes = progress(stops, settings, order);
+                } else {
+                    order[i..=j].reverse();
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// When service can start on arriving at `arrival`, and how late that is.
+fn service_start(arrival: f64, window: Option<(f64, f64)>) -> (f64, f64) {
+    match window {
+        Some((earliest, latest)) => {
+            let start = arrival.max(earliest);
+            (start, (start - latest).max(0.0))
+        }
+        None => (arrival, 0.0),
+    }
+}
+
+/// Where the courier is, when they are free, and the route cost accrued so far.
+#[derive(Clone, Copy)]
+struct Progress {
+    position: Coordinates,
+    minute: f64,
+    cost: f64,
+}
+
+impl Progress {
+    fn visit<K>(self, stop: &Stop<K>, settings: &PlannerSettings) -> Progress {
+        let (start, lateness) = service_start(
+            self.minute + travel_minutes(&self.position, &stop.location, settings),
+            stop.window,
+        );
+        Progress {
+            position: stop.location,
+            minute: start + settings.service_minutes,
+            cost: self.cost
+                + (start - self.minute)
+                + LATENESS_PENALTY * lateness
+                + PRIORITY_WEIGHT * f64::from(stop.priority) * start,
+        }
+    }
+}
+
+/// Progress before each stop of `order`, followed by the progress at the end of the route.
+fn progress<K>(stops: &[Stop<K>], settings: &PlannerSettings, order: &[usize]) -> Vec<Progress> {
+    let mut current = Progress {
+        position: settings.start,
+        minute: 0.0,
+        cost: 0.0,
+    };
+    let mut prefixes = Vec::with_capacity(order.len() + 1);
+    prefixes.push(current);
+    for &i in order {
+        current = current.visit(&stops[i], settings);
+        prefixes.push(current);
+    }
+    prefixes
+}
+
+fn cost_from<K>(
+    stops: &[Stop<K>],
+    settings: &PlannerSettings,
+    order: &[usize],
+    from: Progress,
+) -> f64 {
+    order
+        .iter()
+        .fold(from, |current, &i| current.visit(&stops[i], settings))
+        .cost
+}
+
+fn simulate<K: Clone>(stops: &[Stop<K>], settings: &PlannerSettings, order: &[usize]) -> Route<K> {
+    let mut position = settings.start;
+    let mut minute = 0.0;
+    let mut planned = Vec::with_capacity(order.len());
+    for &i in order {
+        let stop = &stops[i];
+        let distance_km = position.distance_km(&stop.location);
+        let arrival = minute + distance_km / settings.speed_kmh * 60.0;
+        let (start, lateness) = service_start(arrival, stop.window);
+        planned.push(PlannedStop {
+            key: stop.key.clone(),
+            arrival_minute: arrival,
+            service_start_minute: start,
+            lateness_minutes: lateness,
+            distance_km,
+        });
+        position = stop.location;
+        minute = start + settings.service_minutes;
+    }
+
+    Route {
+        total_distance_km: planned.iter().map(|stop| stop.distance_km).sum(),
+        total_lateness_minutes: planned.iter().map(|stop| stop.lateness_minutes).sum(),
+        finish_minute: minute,
+        stops: planned,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: Coordinates = Coordinates {
+        latitude: 13.75,
+        longitude: 100.55,
+    };
+
+    fn settings() -> PlannerSettings {
+        PlannerSettings {
+            start: START,
+            speed_kmh: 30.0,
+            service_minutes: 5.0,
+        }
+    }
+
+    fn stop(
+        key: &'static str,
+        latitude_offset: f64,
+        window: Option<(f64, f64)>,
+    ) -> Stop<&'static str> {
+        Stop {
+            key,
+            location: Coordinates {
+                latitude: START.latitude + latitude_offset,
+                longitude: START.longitude,
+            },
+            window,
+            priority: 0,
+        }
+    }
+
+    fn keys(route: &Route<&'static str>) -> Vec<&'static str> {
+        route.stops.iter().map(|stop| stop.key).collect()
+    }
+
+    #[test]
+    fn plan_of_no_stops_is_empty() {
+        let route = plan::<&str>(&[], &settings());
+        assert!(route.stops.is_empty());
+        assert_eq!(route.total_distance_km, 0.0);
+        assert_eq!(route.finish_minute, 0.0);
+    }
+
+    #[test]
+    fn plan_visits_stops_on_a_line_outwards() {
+        let stops = [
+            stop("far", 0.03, None),
+            stop("near", 0.01, None),
+            stop("middle", 0.02, None),
+        ];
+        let route = plan(&stops, &settings());
+        assert_eq!(keys(&route), ["near", "middle", "far"]);
+        assert_eq!(route.total_lateness_minutes, 0.0);
+        let expected_km = START.distance_km(&stops[0].location);
+        assert!((route.total_distance_km - expected_km).abs() < 1e-9);
+    }
+
+    #[test]
+    fn plan_serves_a_closing_window_first() {
+        // About 11 minutes away, the window is only met by going there first.
+        let stops = [
+            stop("near", 0.01, None),
+            stop("booked", -0.05, Some((0.0, 12.0))),
+        ];
+        let route = plan(&stops, &settings());
+        assert_eq!(keys(&route), ["booked", "near"]);
+        assert_eq!(route.total_lateness_minutes, 0.0);
+    }
+
+    #[test]
+    fn plan_waits_for_a_window_to_open() {
+        let stops = [stop("later", 0.01, Some((60.0, 120.0)))];
+        let route = plan(&stops, &settings());
+        let planned = &route.stops[0];
+        assert!(planned.arrival_minute < 60.0);
+        assert_eq!(planned.service_start_minute, 60.0);
+        assert_eq!(route.finish_minute, 65.0);
+    }
+
+    #[test]
+    fn plan_is_deterministic() {
+        let stops: Vec<Stop<usize>> = (0..12)
+            .map(|i| Stop {
+                key: i,
+                location: Coordinates {
+                    latitude: START.latitude + (i % 4) as f64 * 0.01,
+                    longitude: START.longitude - (i / 4) as f64 * 0.013,
+                },
+                window: (i % 3 == 0).then_some((30.0, 90.0)),
+                priority: (i % 5 == 0) as u8 * 2,
+            })
+            .collect();
+        assert_eq!(plan(&stops, &settings()), plan(&stops, &settings()));
+    }
+}
@@ -0,0 +1,502 @@
+//! Recurring deliveries for long-term prescriptions.
+//!
+//! A schedule repeats an order on the dates given by its recurrence, a subset of iCalendar
+//! RRULE: `FREQ=DAILY|WEEKLY|MONTHLY`, `INTERVAL`, `BYDAY` for weekly and `BYMONTHDAY` for
+//! monthly schedules. Occurrences are counted from `starts_on`. Monthly days past the end
+//! of a month fall on its last day.
+
+use std::str::FromStr;
+
+use anyhow::Context;
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::{app_error::AppError, outbox};
+use serde::Serialize;
+use tracing::warn;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    events::{DeliveryScheduleReminderEvent, RecurringOrderRequestedEvent},
+    models::{CreateDeliveryScheduleRunEntity, DeliveryAddressEntity, DeliveryScheduleEntity},
+    schema::{delivery_addresses, delivery_schedule_runs, delivery_schedules},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// Weekdays of weekly schedules. Empty means the weekday of `starts_on`.
+    pub by_day: Vec<Weekday>,
+    /// Day of monthly schedules. `None` means the day of `starts_on`.
+    pub by_month_day: Option<u32>,
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut by_month_day = None;
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Malformed recurrence part {part}"))?;
+            match name {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(format!("Unsupported frequency {value}")),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| format!("Invalid interval {value}"))?
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        by_day.push(match day {
+                            "MO" => Weekday::Mon,
+                            "TU" => Weekday::Tue,
+                            "WE" => Weekday::Wed,
+                            "TH" => Weekday::Thu,
+                            "FR" => Weekday::Fri,
+                            "SA" => Weekday::Sat,
+                            "SU" => Weekday::Sun,
+                            _ => return Err(format!("Invalid weekday {day}")),
+                        });
+                    }
+                }
+                "BYMONTHDAY" => {
+                    by_month_day = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|day| (1..=31).contains(day))
+                            .ok_or_else(|| format!("Invalid month day {value}"))?,
+                    )
+                }
+                _ => return Err(format!("Unsupported recurrence part {name}")),
+            }
+        }
+
+        let frequency = frequency.ok_or("Recurrence must have a FREQ")?;
+        if !by_day.is_empty() && frequency != Frequency::Weekly {
+            return Err("BYDAY is only supported for weekly schedules".into());
+        }
+        if by_month_day.is_some() && frequency != Frequency::Monthly {
+            return Err("BYMONTHDAY is only supported for monthly schedules".into());
+        }
+        by_day.sort_by_key(|day| day.num_days_from_monday());
+        by_day.dedup();
+
+        Ok(Recurrence {
+            frequency,
+            interval,
+            by_day,
+            by_month_day,
+        })
+    }
+}
+
+impl Recurrence {
+    /// First occurrence on or after `from` of a schedule starting on `start`.
+    pub fn next_on_or_after(&self, start: NaiveDate, from: NaiveDate) -> NaiveDate {
+        let from = from.max(start);
+        let interval = i64::from(self.interval);
+        match self.frequency {
+            Frequency::Daily => {
+                let periods = ((from - start).num_days() + interval - 1) / interval;
+                start + Duration::days(periods * interval)
+            }
+            Frequency::Weekly => {
+                let first_week = start.week(Weekday::Mon).first_day();
+                let days: &[Weekday] = if self.by_day.is_empty() {
+                    &[start.weekday()]
+                } else {
+                    &self.by_day
+                };
+                // Start one period early, as `from` may fall after the period's last day.
+                let mut period = ((from - first_week).num_days() / 7 / interval - 1).max(0);
+                loop {
+                    let week = first_week + Duration::weeks(period * interval);
+                    if let Some(date) = days
+                        .iter()
+                        .map(|day| week + Duration::days(i64::from(day.num_days_from_monday())))
+                        .find(|date| *date >= from)
+                    {
+                        return date;
+                    }
+                    period += 1;
+                }
+            }
+            Frequency::Monthly => {
+                let day = self.by_month_day.unwrap_or(start.day());
+                let months = |date: NaiveDate| date.year() * 12 + date.month0() as i32;
+                let mut period = ((months(from) - months(start)) / self.interval as i32 - 1).max(0);
+                loop {
+                    let month = start.with_day(1).expect("every month has a first day")
+                        + Months::new(period as u32 * self.interval);
+                    let date = (1..=day)
+                        .rev()
+                        .find_map(|day| month.with_day(day))
+                        .expect("every month has a first day");
+                    if date >= from {
+                        return date;
+                    }
+                    period += 1;
+                }
+            }
+        }
+    }
+
+    /// The next `count` occurrences on or after `from`.
+    pub fn upcoming(&self, start: NaiveDate, from: NaiveDate, count: usize) -> Vec<NaiveDate> {
+        let mut dates = Vec::with_capacity(count);
+        let mut from = from;
+        while dates.len() < count {
+            let date = self.next_on_or_after(start, from);
+            dates.push(date);
+            from = date + Duration::days(1);
+        }
+        dates
+    }
+}
+
+/// Parses a recurrence given by a client.
+pub fn parse_recurrence(rule: &str) -> Result<Recurrence, AppError> {
+    rule.parse()
+        .map_err(|err| AppError::BadRequest(format!("Invalid recurrence: {err}")))
+}
+
+/// Fetches a schedule belonging to `patient_id`. Schedules of other patients are reported
+/// as not found.
+pub async fn get_patient_schedule(
+    conn: &mut AsyncPgConnection,
+    patient_id: i32,
+    id: Uuid,
+) -> Result<DeliveryScheduleEntity, AppError> {
+    let schedule: DeliveryScheduleEntity = delivery_schedules::table
+        .find(id)
+        .filter(delivery_schedules::patient_id.eq(patient_id))
+        .get_result(conn)
+        .await
+        .optional()
+        .context("Failed to get delivery schedule")?
+        .ok_or(AppError::NotFound)?;
+    Ok(schedule)
+}
+
+/// Moves `schedule` to its first run on or after `from`, ending it once past `ends_on`.
+pub async fn advance(
+    conn: &mut AsyncPgConnection,
+    schedule: &DeliveryScheduleEntity,
+    from: NaiveDate,
+) -> Result<DeliveryScheduleEntity, AppError> {
+    let recurrence = parse_recurrence(&schedule.recurrence)?;
+    let next_run_on = Some(recurrence.next_on_or_after(schedule.starts_on, from))
+        .filter(|next| schedule.ends_on.is_none_or(|ends_on| *next <= ends_on));
+    let status = match next_run_on {
+        Some(_) => schedule.status.as_str(),
+        None => "ENDED",
+    };
+
+    let schedule: DeliveryScheduleEntity =
+        diesel::update(delivery_schedules::table.find(schedule.id))
+            .set((
+                delivery_schedules::next_run_on.eq(next_run_on),
+                delivery_schedules::status.eq(status),
+            ))
+            .get_result(conn)
+            .await
+            .context("Failed to advance delivery schedule")?;
+    Ok(schedule)
+}
+
+/// Records a run of `schedule`. Returns `false` when the run was already recorded.
+async fn record_run(
+    conn: &mut AsyncPgConnection,
+    schedule: &DeliveryScheduleEntity,
+    run_on: NaiveDate,
+    status: &str,
+) -> Result<bool, AppError> {
+    let inserted = diesel::insert_into(delivery_schedule_runs::table)
+        .values(CreateDeliveryScheduleRunEntity {
+            schedule_id: schedule.id,
+            run_on,
+            status: status.into(),
+        })
+        .on_conflict((
+            delivery_schedule_runs::schedule_id,
+            delivery_schedule_runs::run_on,
+        ))
+        .do_nothing()
+        .execute(conn)
+        .await
+        .context("Failed to record delivery schedule run")?;
+    Ok(inserted > 0)
+}
+
+/// Skips the next run of `schedule`. Must be called inside a transaction.
+pub async fn skip(
+    conn: &mut AsyncPgConnection,
+    schedule: &DeliveryScheduleEntity,
+) -> Result<DeliveryScheduleEntity, AppError> {
+    let Some(run_on) = schedule.next_run_on.filter(|_| schedule.status != "ENDED") else {
+        return Err(AppError::BadRequest("Delivery schedule has ended".into()));
+    };
+    record_run(conn, schedule, run_on, "SKIPPED").await?;
+    advance(conn, schedule, run_on + Duration::days(1)).await
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct ScheduleAction {
+    pub schedule_id: Uuid,
+    pub run_on: NaiveDate,
+    /// `REMINDED`, `REQUESTED`, `MISSED` for runs whose day had passed, or `PAUSED` when
+    /// the schedule has no address left.
+    pub action: String,
+}
+
+/// Sends reminders and requests orders for active schedules whose runs are close enough
+/// to `today`. A run's reminder goes out `reminder_time` ahead, or together with its order
+/// request when that is due first. Runs before `today`, left over from an outage, are
+/// recorded as missed instead. Must be called inside a transaction.
+pub async fn run_due(
+    conn: &mut AsyncPgConnection,
+    today: NaiveDate,
+    lead_time: Duration,
+    reminder_time: Duration,
+) -> Result<Vec<ScheduleAction>, AppError> {
+    let horizon = today + lead_time.max(reminder_time);
+    let due: Vec<DeliveryScheduleEntity> = delivery_schedules::table
+        .filter(delivery_schedules::status.eq("ACTIVE"))
+        .filter(delivery_schedules::next_run_on.le(horizon))
+        .order_by(delivery_schedules::next_run_on.asc())
+        .for_update()
+        .skip_locked()
+        .get_results(conn)
+        .await
+        .context("Failed to get due delivery schedules")?;
+
+    let mut actions = Vec::new();
+    for mut schedule in due {
+        // A schedule may be several runs behind after an outage.
+        while let Some(run_on) = schedule.next_run_on.filter(|_| schedule.status == "ACTIVE") {
+            // Ordering for a day that has passed would ship medication the patient has
+            // likely got by other means, so missed runs are only recorded.
+            if run_on < today {
+                warn!(
+                    schedule_id = %schedule.id,
+                    %run_on,
+                    "Delivery schedule run was missed, skipping it"
+                );
+                if record_run(conn, &schedule, run_on, "MISSED").await? {
+                    actions.push(ScheduleAction {
+                        schedule_id: schedule.id,
+                        run_on,
+                        action: "MISSED".into(),
+                    });
+                }
+                schedule = advance(conn, &schedule, run_on + Duration::days(1)).await?;
+                continue;
+            }
+            let request_due = run_on - lead_time <= today;
+            let remind_due = reminder_time > Duration::zero()
+                && (run_on - reminder_time <= today || request_due)
+                && schedule.reminded_for != Some(run_on);
+            if remind_due {
+                outbox::publish(
+                    conn,
+                    "delivery.schedule_reminder".into(),
+                    DeliveryScheduleReminderEvent {
+                        schedule_id: schedule.id,
+                        patient_id: schedule.patient_id,
+                        source_order_id: schedule.source_order_id,
+                        run_on,
+                    },
+                )
+                .await
+                .context("Failed to create outbox")?;
+
+                schedule = diesel::update(delivery_schedules::table.find(schedule.id))
+                    .set(delivery_schedules::reminded_for.eq(run_on))
+                    .get_result(conn)
+                    .await
+                    .context("Failed to update delivery schedule")?;
+                actions.push(ScheduleAction {
+                    schedule_id: schedule.id,
+                    run_on,
+                    action: "REMINDED".into(),
+                });
+            }
+            if !request_due {
+                break;
+            }
+
+            let address: Option<DeliveryAddressEntity> = match schedule.delivery_address_id {
+                Some(address_id) => delivery_addresses::table
+                    .find(address_id)
+                    .get_result(conn)
+                    .await
+                    .optional()
+                    .context("Failed to get delivery address")?,
+                None => None,
+            };
+            let Some(address) = address else {
+                warn!(
+                    schedule_id = %schedule.id,
+                    "Delivery schedule has no address, pausing it"
+                );
+                diesel::update(delivery_schedules::table.find(schedule.id))
+                    .set(delivery_schedules::status.eq("PAUSED"))
+                    .execute(conn)
+                    .await
+                    .context("Failed to pause delivery schedule")?;
+                actions.push(ScheduleAction {
+                    schedule_id: schedule.id,
+                    run_on,
+                    action: "PAUSED".into(),
+                });
+                break;
+            };
+
+            if record_run(conn, &schedule, run_on, "REQUESTED").await? {
+                outbox::publish(
+                    conn,
+                    "orders.recurring_order_requested".into(),
+                    RecurringOrderRequestedEvent {
+                        schedule_id: schedule.id,
+                        patient_id: schedule.patient_id,
+                        source_order_id: schedule.source_order_id,
+                        run_on,
+                        delivery_address: serde_json::to_value(&address)
+                            .context("Failed to serialize delivery address")?,
+                    },
+                )
+                .await
+                .context("Failed to create outbox")?;
+                actions.push(ScheduleAction {
+                    schedule_id: schedule.id,
+                    run_on,
+                    action: "REQUESTED".into(),
+                });
+            }
+            schedule = advance(conn, &schedule, run_on + Duration::days(1)).await?;
+        }
+    }
+
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn rule(rule: &str) -> Recurrence {
+        rule.parse().unwrap()
+    }
+
+    #[test]
+    fn next_on_or_after_starts_on_the_start_date() {
+        let recurrence = rule("FREQ=DAILY;INTERVAL=3");
+        let start = date(2026, 10, 1);
+        assert_eq!(recurrence.next_on_or_after(start, date(2026, 9, 1)), start);
+        assert_eq!(recurrence.next_on_or_after(start, start), start);
+    }
+
+    #[test]
+    fn next_on_or_after_counts_daily_intervals_from_the_start() {
+        let recurrence = rule("FREQ=DAILY;INTERVAL=3");
+        let start = date(2026, 10, 1);
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 10, 2)),
+            date(2026, 10, 4)
+        );
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 10, 4)),
+            date(2026, 10, 4)
+        );
+    }
+
+    #[test]
+    fn next_on_or_after_picks_the_next_listed_weekday() {
+        // 2026-10-05 is a Monday.
+        let recurrence = rule("FREQ=WEEKLY;BYDAY=MO,TH");
+        let start = date(2026, 10, 5);
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 10, 6)),
+            date(2026, 10, 8)
+        );
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 10, 9)),
+            date(2026, 10, 12)
+        );
+    }
+
+    #[test]
+    fn next_on_or_after_skips_weeks_between_intervals() {
+        let recurrence = rule("FREQ=WEEKLY;INTERVAL=2");
+        let start = date(2026, 10, 5);
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 10, 6)),
+            date(2026, 10, 19)
+        );
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 10, 20)),
+            date(2026, 11, 2)
+        );
+    }
+
+    #[test]
+    fn next_on_or_after_falls_on_the_last_day_of_short_months() {
+        let recurrence = rule("FREQ=MONTHLY;BYMONTHDAY=31");
+        let start = date(2026, 1, 31);
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 2, 1)),
+            date(2026, 2, 28)
+        );
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 3, 1)),
+            date(2026, 3, 31)
+        );
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 4, 1)),
+            date(2026, 4, 30)
+        );
+    }
+
+    #[test]
+    fn next_on_or_after_skips_months_between_intervals() {
+        let recurrence = rule("FREQ=MONTHLY;INTERVAL=3");
+        let start = date(2026, 1, 15);
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 1, 16)),
+            date(2026, 4, 15)
+        );
+        assert_eq!(
+            recurrence.next_on_or_after(start, date(2026, 11, 1)),
+            date(2027, 1, 15)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Service level deadlines derived from a delivery's priority. Deadlines are counted in
+//! working time, so they pause over holidays and closures.
+
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::SlaTargets,
+    models::DeliveryEntity,
+    services::{
+        calendar::{Calendar, Scope},
+        deliveries,
+    },
+};
+
+#[derive(Serialize, ToSchema, Debug, Clone, Copy)]
+pub struct SlaDeadlines {
+    /// When the delivery should have been picked up by a courier.
+    pub pickup_by: DateTime<Utc>,
+    /// When the delivery should have reached the recipient.
+    pub deliver_by: DateTime<Utc>,
+}
+
+impl SlaDeadlines {
+    pub fn of(delivery: &DeliveryEntity, targets: &SlaTargets, calendar: &Calendar) -> Self {
+        let target = targets.of(&delivery.priority);
+        let scope = Scope::of(delivery);
+        SlaDeadlines {
+            pickup_by: calendar.add_working_time(delivery.created_at, target.pickup, scope),
+            deliver_by: calendar.add_working_time(delivery.created_at, target.delivery, scope),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct DeliveryWithSla {
+    #[serde(flatten)]
+    pub delivery: DeliveryEntity,
+    pub sla: SlaDeadlines,
+}
+
+impl DeliveryWithSla {
+    pub fn of(delivery: DeliveryEntity, targets: &SlaTargets, calendar: &Calendar) -> Self {
+        DeliveryWithSla {
+            sla: SlaDeadlines::of(&delivery, targets, calendar),
+            delivery,
+        }
+    }
+}
+
+/// A deadline a delivery has missed.
+#[derive(Debug, Clone, Copy)]
+pub struct SlaBreach {
+    /// `PICKUP_OVERDUE`, `DELIVERY_OVERDUE` or `ETA_PASSED`.
+    pub kind: &'static str,
+    pub severity: &'static str,
+    pub deadline: DateTime<Utc>,
+}
+
+/// Deadlines `delivery` has missed as of `now`. EN_ROUTE deliveries also breach once
+/// they run `eta_grace` past their estimated arrival. Parcels waiting for the patient to
+/// collect them, or on their way back, are not held to the delivery deadline.
+pub fn breaches(
+    delivery: &DeliveryEntity,
+    deadlines: &SlaDeadlines,
+    now: DateTime<Utc>,
+    eta_grace: Duration,
+) -> Vec<SlaBreach> {
+    let severity = |default| {
+        if delivery.priority == "URGENT" {
+            "CRITICAL"
+        } else {
+            default
+        }
+    };
+    let mut breaches = Vec::new();
+    // Pharmacy pickups are never picked up by a courier.
+    if delivery.status == "PREPARING"
+        && delivery.fulfilment_mode != "PHARMACY_PICKUP"
+        && now > deadlines.pickup_by
+    {
+        breaches.push(SlaBreach {
+            kind: "PICKUP_OVERDUE",
+            severity: severity("MEDIUM"),
+            deadline: deadlines.pickup_by,
+        });
+    }
+    if !["DELIVERED", "READY_FOR_PICKUP", "RETURNING"].contains(&delivery.status.as_str())
+        && now > deadlines.deliver_by
+    {
+        breaches.push(SlaBreach {
+            kind: "DELIVERY_OVERDUE",
+            severity: severity("HIGH"),
+            deadline: deadlines.deliver_by,
+        });
+    }
+    if delivery.status == "EN_ROUTE"
+        && let Some(eta) = delivery.estimated_arrival_at
+        && now > eta + eta_grace
+    {
+        breaches.push(SlaBreach {
+            kind: "ETA_PASSED",
+            severity: severity("MEDIUM"),
+            deadline: eta,
+        });
+    }
+    breaches
+}
+
+/// Orders deliveries most urgent first, then by delivery deadline.
+pub fn sort_by_urgency(deliveries: &mut [DeliveryWithSla]) {
+    deliveries.sort_by_key(|entry| {
+        (
+            Reverse(deliveries::priority_rank(&entry.delivery.priority)),
+            entry.sla.deliver_by,
+        )
+    });
+}
@@ -0,0 +1,253 @@
+//! Finds open deliveries without recent `delivery_logs` activity and acts on them
+//! according to the configured policy.
+//!
+//! Cancelling and requeueing only make sense before a courier has the parcel, so
+//! deliveries past PREPARING are always escalated instead.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, QueryDsl, dsl::exists};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use medbook_core::{app_error::AppError, outbox};
+use medbook_events::DeliveryCreatedEvent;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    events::DeliveryStuckEvent,
+    models::{
+        CreateDeliveryIncidentEntity, CreateDeliveryLogEntity, CreateDeliverySweeperActionEntity,
+        DeliveryEntity,
+    },
+    schema::{deliveries, delivery_incidents, delivery_logs, delivery_sweeper_actions},
+    services::{
+        deliveries::{CLOSED_STATUSES, order_ids, transition},
+        incidents,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepPolicy {
+    Escalate,
+    Cancel,
+    Requeue,
+}
+
+impl SweepPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "escalate" => Some(SweepPolicy::Escalate),
+            "cancel" => Some(SweepPolicy::Cancel),
+            "requeue" => Some(SweepPolicy::Requeue),
+            _ => None,
+        }
+    }
+
+    /// Action taken on a stuck delivery in `status`.
+    fn action_for(self, status: &str) -> &'static str {
+        match self {
+            SweepPolicy::Cancel if status == "PREPARING" => "CANCEL",
+            SweepPolicy::Requeue if status == "PREPARING" => "REQUEUE",
+            _ => "ESCALATE",
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct SweepAction {
+    pub delivery_id: Uuid,
+    pub order_id: i32,
+    pub delivery_status: String,
+    pub last_activity_at: DateTime<Utc>,
+    /// `ESCALATE`, `CANCEL`, `REQUEUE`, or `NONE` when the delivery was already escalated.
+    pub action: String,
+    pub detail: String,
+}
+
+/// Acts on deliveries with no activity for `stuck_after`. With `dry_run`, only reports
+/// what would be done. Must be called inside a transaction.
+pub async fn sweep(
+    conn: &mut AsyncPgConnection,
+    policy: SweepPolicy,
+    stuck_after: Duration,
+    dry_run: bool,
+) -> Result<Vec<SweepAction>, AppError> {
+    let now = Utc::now();
+    let cutoff = now - stuck_after;
+
+    // Deliveries created after the cutoff cannot be stuck yet. Parcels waiting to be
+    // collected are handled by the pickup expiry instead.
+    let candidates_query = deliveries::table
+        .filter(deliveries::status.ne_all(CLOSED_STATUSES))
+        .filter(deliveries::status.ne("READY_FOR_PICKUP"))
+        .filter(deliveries::created_at.lt(cutoff))
+        .order_by(deliveries::created_at.asc());
+    // Dry runs only report, so they must not hide deliveries from a concurrent real sweep.
+    let candidates: Vec<DeliveryEntity> = if dry_run {
+        candidates_query.get_results(conn).await
+    } else {
+        candidates_query
+            .for_update()
+            .skip_locked()
+            .get_results(conn)
+            .await
+    }
+    .context("Failed to get open deliveries")?;
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let last_logs: HashMap<Uuid, DateTime<Utc>> = delivery_logs::table
+        .filter(delivery_logs::delivery_id.eq_any(candidates.iter().map(|delivery| delivery.id)))
+        .group_by(delivery_logs::delivery_id)
+        .select((
+            delivery_logs::delivery_id,
+            diesel::dsl::max(delivery_logs::created_at),
+        ))
+        .get_results::<(Uuid, Option<DateTime<Utc>>)>(conn)
+        .await
+        .context("Failed to get delivery activity")?
+        .into_iter()
+        .filter_map(|(delivery_id, last)| Some((delivery_id, last?)))
+        .collect();
+
+    let mut actions = Vec::new();
+    for delivery in candidates {
+        let last_activity_at = last_logs
+            .get(&delivery.id)
+            .copied()
+            .unwrap_or(delivery.created_at);
+        if last_activity_at >= cutoff {
+            continue;
+        }
+
+        let idle = format!(
+            "No activity for {} minutes while {}",
+            (now - last_activity_at).num_minutes(),
+            delivery.status
+        );
+        let mut action = SweepAction {
+            delivery_id: delivery.id,
+            order_id: delivery.order_id,
+            delivery_status: delivery.status.clone(),
+            last_activity_at,
+            action: policy.action_for(&delivery.status).into(),
+            detail: idle.clone(),
+        };
+        let dedup_key = format!(
+            "stuck:{}:{}",
+            delivery.id,
+            last_activity_at.timestamp_micros()
+        );
+        if dry_run {
+            if action.action == "ESCALATE" {
+                let escalated = diesel::select(exists(
+                    delivery_incidents::table.filter(delivery_incidents::dedup_key.eq(&dedup_key)),
+                ))
+                .get_result::<bool>(conn)
+                .await
+                .context("Failed to check for existing escalation")?;
+                if escalated {
+                    action.action = "NONE".into();
+                    action.detail = format!("{idle}, already escalated");
+                }
+            }
+            actions.push(action);
+            continue;
+        }
+
+        match action.action.as_str() {
+            "CANCEL" => {
+                transition(
+                    conn,
+                    delivery.id,
+                    "CANCELLED".into(),
+                    format!("Cancelled by the stuck delivery sweeper. {idle}"),
+                )
+                .await?;
+            }
+            "REQUEUE" => {
+                for order_id in order_ids(conn, delivery.id).await? {
+                    outbox::publish(
+                        conn,
+                        "orders.delivery_created".into(),
+                        DeliveryCreatedEvent {
+                            order_id,
+                            delivery_id: delivery.id,
+                        },
+                    )
+                    .await
+                    .context("Failed to create outbox")?;
+                }
+
+                diesel::insert_into(delivery_logs::table)
+                    .values(CreateDeliveryLogEntity {
+                        delivery_id: delivery.id,
+                        description: format!(
+                            "Creation event requeued by the stuck delivery sweeper. {idle}"
+                        ),
+                        status: delivery.status.clone(),
+                    })
+                    .execute(conn)
+                    .await
+                    .context("Failed to create delivery log")?;
+            }
+            _ => {
+                // Escalations leave no delivery log, so the key stays the same until
+                // the delivery shows activity again.
+                let incident = incidents::raise(
+                    conn,
+                    CreateDeliveryIncidentEntity {
+                        delivery_id: delivery.id,
+                        category: "STUCK".into(),
+                        severity: "HIGH".into(),
+                        description: idle.clone(),
+                        dedup_key: Some(dedup_key),
+                        reporter_type: "SYSTEM".into(),
+                        reported_by: None,
+                    },
+                )
+                .await?;
+
+                let Some(incident) = incident else {
+                    action.action = "NONE".into();
+                    action.detail = format!("{idle}, already escalated");
+                    actions.push(action);
+                    continue;
+                };
+
+                outbox::publish(
+                    conn,
+                    "delivery.stuck".into(),
+                    DeliveryStuckEvent {
+                        delivery_id: delivery.id,
+                        order_id: delivery.order_id,
+                        incident_id: incident.id,
+                        status: delivery.status.clone(),
+                        last_activity_at,
+                    },
+                )
+                .await
+                .context("Failed to create outbox")?;
+            }
+        }
+
+        diesel::insert_into(delivery_sweeper_actions::table)
+            .values(CreateDeliverySweeperActionEntity {
+                delivery_id: delivery.id,
+                action: action.action.clone(),
+                delivery_status: delivery.status.clone(),
+                last_activity_at,
+                detail: action.detail.clone(),
+            })
+            .execute(conn)
+            .await
+            .context("Failed to record sweeper action")?;
+        actions.push(action);
+    }
+
+    Ok(actions)
+}
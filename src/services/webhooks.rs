@@ -0,0 +1,393 @@
+//! Webhooks pushing outbox events to partners.
+//!
+//! The outbox is read through bookkeeping of our own and never written to, so the message
+//! relay is unaffected. Every event is copied into a webhook delivery for each active
+//! subscription to its type, and webhook deliveries are sent until the partner accepts
+//! them, backing off between attempts, so no event is lost to a partner being down.
+//! Transactions commit outbox events out of ID order, so each event is marked once it has
+//! been fanned out, and the cursor below which every event has been only moves past events
+//! an hour old.
+
+use std::{
+    sync::OnceLock,
+    time::{Duration as StdDuration, Instant},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{
+    ExpressionMethods, QueryDsl, SelectableHelper,
+    dsl::{exists, not},
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use hmac::{Hmac, Mac};
+use medbook_core::app_error::AppError;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    config,
+    models::{
+        CreateWebhookDeliveryAttemptEntity, CreateWebhookDeliveryEntity, WebhookDeliveryEntity,
+        WebhookSubscriptionEntity,
+    },
+    schema::{
+        outbox, webhook_deliveries, webhook_delivery_attempts, webhook_outbox_cursor,
+        webhook_outbox_events, webhook_subscriptions,
+    },
+};
+
+/// Outbox event types partners can subscribe to.
+pub const EVENT_TYPES: [&str; 11] = [
+    "delivery.cancelled",
+    "delivery.pickup_expired",
+    "delivery.ready_for_pickup",
+    "delivery.schedule_reminder",
+    "delivery.sla_breached",
+    "delivery.stuck",
+    "delivery.temperature_excursion",
+    "orders.delivery_created",
+    "orders.delivery_incident_resolved",
+    "orders.delivery_success",
+    "orders.recurring_order_requested",
+];
+
+/// Event type of the test events sent on request.
+pub const TEST_EVENT_TYPE: &str = "webhook.test";
+
+/// How old outbox events must be before the cursor moves past them. No transaction stays
+/// open for that long, so no event can commit below the cursor afterwards.
+const CURSOR_LAG_MINUTES: i64 = 60;
+const FAN_OUT_BATCH: i64 = 500;
+const SEND_BATCH: i64 = 50;
+const TIMEOUT_SECONDS: i64 = 10;
+/// How long other runs leave a webhook alone while it is being sent. Webhooks are sent one
+/// after the other, so this covers a whole batch timing out, with time to record it.
+const CLAIM_SECONDS: i64 = SEND_BATCH * TIMEOUT_SECONDS + 60;
+
+/// Whether `subscription` wants events of `event_type`.
+pub fn subscribes_to(subscription: &WebhookSubscriptionEntity, event_type: &str) -> bool {
+    subscription.event_types.as_array().is_some_and(|types| {
+        types
+            .iter()
+            .any(|candidate| candidate.as_str() == Some(event_type))
+    })
+}
+
+/// Wait before the attempt following `attempts` failed ones: 30 seconds, doubling with
+/// every failure up to 6 hours.
+pub fn backoff(attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    Duration::seconds((30_i64 << doublings).min(6 * 60 * 60))
+}
+
+/// Hex encoded HMAC-SHA256 of `{timestamp}.{body}` under `secret`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Copies outbox events not fanned out yet into webhook deliveries for the subscriptions
+/// wanting them, and marks them fanned out. Returns how many webhooks were queued. Must be
+/// called inside a transaction.
+pub async fn fan_out(conn: &mut AsyncPgConnection, now: DateTime<Utc>) -> Result<usize, AppError> {
+    let cursor: i32 = webhook_outbox_cursor::table
+        .select(webhook_outbox_cursor::last_outbox_id)
+        .for_update()
+        .get_result(conn)
+        .await
+        .context("Failed to get webhook outbox cursor")?;
+    let pending = || {
+        outbox::table
+            .filter(outbox::id.gt(cursor))
+            .filter(not(exists(
+                webhook_outbox_events::table
+                    .filter(webhook_outbox_events::outbox_id.eq(outbox::id)),
+            )))
+    };
+
+    let events: Vec<(i32, String, String)> = pending()
+        .order_by(outbox::id.asc())
+        .limit(FAN_OUT_BATCH)
+        .select((outbox::id, outbox::event_type, outbox::payload))
+        .get_results(conn)
+        .await
+        .context("Failed to get outbox events")?;
+
+    let mut queued = 0;
+    if !events.is_empty() {
+        let subscriptions: Vec<WebhookSubscriptionEntity> = webhook_subscriptions::table
+            .filter(webhook_subscriptions::is_active.eq(true))
+            .get_results(conn)
+            .await
+            .context("Failed to get webhook subscriptions")?;
+        let new_deliveries: Vec<CreateWebhookDeliveryEntity> = events
+            .iter()
+            .flat_map(|(outbox_id, event_type, payload)| {
+                subscriptions
+                    .iter()
+                    .filter(|subscription| subscribes_to(subscription, event_type))
+                    .map(|subscription| CreateWebhookDeliveryEntity {
+                        subscription_id: subscription.id,
+                        outbox_id: Some(*outbox_id),
+                        event_type: event_type.clone(),
+                        payload: payload.clone(),
+                        next_attempt_at: Some(now),
+                    })
+            })
+            .collect();
+
+        if !new_deliveries.is_empty() {
+            queued = diesel::insert_into(webhook_deliveries::table)
+                .values(new_deliveries)
+                .on_conflict((
+                    webhook_deliveries::subscription_id,
+                    webhook_deliveries::outbox_id,
+                ))
+                .do_nothing()
+                .execute(conn)
+                .await
+                .context("Failed to queue webhooks")?;
+        }
+
+        diesel::insert_into(webhook_outbox_events::table)
+            .values(
+                events
+                    .iter()
+                    .map(|(outbox_id, _, _)| webhook_outbox_events::outbox_id.eq(outbox_id))
+                    .collect::<Vec<_>>(),
+            )
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await
+            .context("Failed to mark outbox events fanned out")?;
+    }
+
+    // The cursor moves up to the first event still to fan out, but not past recent events,
+    // as a transaction still open may yet commit an event below them.
+    let first_pending: Option<i32> = pending()
+        .select(diesel::dsl::min(outbox::id))
+        .get_result(conn)
+        .await
+        .context("Failed to get first outbox event to fan out")?;
+    let last_settled: Option<i32> = outbox::table
+        .filter(outbox::id.gt(cursor))
+        .filter(outbox::created_at.le(now - Duration::minutes(CURSOR_LAG_MINUTES)))
+        .select(diesel::dsl::max(outbox::id))
+        .get_result(conn)
+        .await
+        .context("Failed to get settled outbox events")?;
+    let next_cursor = match (first_pending, last_settled) {
+        (Some(first_pending), Some(last_settled)) => last_settled.min(first_pending - 1),
+        (None, Some(last_settled)) => last_settled,
+        (_, None) => cursor,
+    };
+    if next_cursor > cursor {
+        diesel::update(webhook_outbox_cursor::table)
+            .set(webhook_outbox_cursor::last_outbox_id.eq(next_cursor))
+            .execute(conn)
+            .await
+            .context("Failed to move webhook outbox cursor")?;
+        diesel::delete(
+            webhook_outbox_events::table.filter(webhook_outbox_events::outbox_id.le(next_cursor)),
+        )
+        .execute(conn)
+        .await
+        .context("Failed to clear fanned out outbox events")?;
+    }
+
+    Ok(queued)
+}
+
+/// Webhooks of active subscriptions due for an attempt, claimed so that concurrent runs
+/// leave them alone while they are sent. Must be called inside a transaction.
+pub async fn claim_due(
+    conn: &mut AsyncPgConnection,
+    now: DateTime<Utc>,
+) -> Result<Vec<(WebhookDeliveryEntity, WebhookSubscriptionEntity)>, AppError> {
+    let due: Vec<WebhookDeliveryEntity> = webhook_deliveries::table
+        .filter(webhook_deliveries::status.eq("PENDING"))
+        .filter(webhook_deliveries::next_attempt_at.le(now))
+        .filter(
+            webhook_deliveries::subscription_id.eq_any(
+                webhook_subscriptions::table
+                    .filter(webhook_subscriptions::is_active.eq(true))
+                    .select(webhook_subscriptions::id),
+            ),
+        )
+        .order_by(webhook_deliveries::next_attempt_at.asc())
+        .limit(SEND_BATCH)
+        .for_update()
+        .skip_locked()
+        .get_results(conn)
+        .await
+        .context("Failed to get due webhooks")?;
+    if due.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    diesel::update(
+        webhook_deliveries::table
+            .filter(webhook_deliveries::id.eq_any(due.iter().map(|delivery| delivery.id))),
+    )
+    .set(webhook_deliveries::next_attempt_at.eq(now + Duration::seconds(CLAIM_SECONDS)))
+    .execute(conn)
+    .await
+    .context("Failed to claim due webhooks")?;
+
+    let subscriptions: Vec<WebhookSubscriptionEntity> = webhook_subscriptions::table
+        .filter(
+            webhook_subscriptions::id.eq_any(due.iter().map(|delivery| delivery.subscription_id)),
+        )
+        .get_results(conn)
+        .await
+        .context("Failed to get webhook subscriptions")?;
+
+    Ok(due
+        .into_iter()
+        .filter_map(|delivery| {
+            let subscription = subscriptions
+                .iter()
+                .find(|subscription| subscription.id == delivery.subscription_id)?
+                .clone();
+            Some((delivery, subscription))
+        })
+        .collect())
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    id: Uuid,
+    event_type: &'a str,
+    created_at: DateTime<Utc>,
+    data: Value,
+}
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+fn client() -> &'static reqwest::Client {
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(StdDuration::from_secs(TIMEOUT_SECONDS as u64))
+            .build()
+            .expect("HTTP client settings are valid")
+    })
+}
+
+/// Posts a webhook to its subscription and records the attempt. Failed webhooks are
+/// retried after a backoff until `WEBHOOK_MAX_ATTEMPTS` is reached, then marked FAILED.
+/// Must not be called inside a transaction, as the partner may take a while to answer.
+pub async fn send(
+    conn: &mut AsyncPgConnection,
+    subscription: &WebhookSubscriptionEntity,
+    delivery: &WebhookDeliveryEntity,
+) -> Result<WebhookDeliveryEntity, AppError> {
+    let max_attempts = config::webhook_max_attempts()?;
+    let body = serde_json::to_string(&Envelope {
+        id: delivery.id,
+        event_type: &delivery.event_type,
+        created_at: delivery.created_at,
+        data: serde_json::from_str(&delivery.payload)
+            .unwrap_or_else(|_| Value::String(delivery.payload.clone())),
+    })
+    .context("Failed to serialize webhook")?;
+    let timestamp = Utc::now().timestamp();
+
+    let started = Instant::now();
+    let result = client()
+        .post(&subscription.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Id", delivery.id.to_string())
+        .header("X-Webhook-Event", &delivery.event_type)
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header(
+            "X-Webhook-Signature",
+            format!("sha256={}", sign(&subscription.secret, timestamp, &body)),
+        )
+        .body(body)
+        .send()
+        .await;
+    let duration_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+    let (response_status, error) = match result {
+        Ok(response) if response.status().is_success() => {
+            (Some(response.status().as_u16() as i32), None)
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            Some(format!("Partner responded with {}", response.status())),
+        ),
+        Err(err) => (None, Some(err.to_string())),
+    };
+
+    let id = delivery.id;
+    let attempt = delivery.attempts + 1;
+    let delivery = conn
+        .transaction(move |conn| {
+            Box::pin(async move {
+                diesel::insert_into(webhook_delivery_attempts::table)
+                    .values(CreateWebhookDeliveryAttemptEntity {
+                        webhook_delivery_id: id,
+                        attempt,
+                        response_status,
+                        error: error.clone(),
+                        duration_ms,
+                    })
+                    .execute(conn)
+                    .await
+                    .context("Failed to record webhook attempt")?;
+
+                let now = Utc::now();
+                let (status, next_attempt_at, delivered_at) = match &error {
+                    None => ("SUCCEEDED", None, Some(now)),
+                    Some(_) if attempt >= max_attempts => ("FAILED", None, None),
+                    Some(_) => ("PENDING", Some(now + backoff(attempt)), None),
+                };
+                let delivery: WebhookDeliveryEntity =
+                    diesel::update(webhook_deliveries::table.find(id))
+                        .set((
+                            webhook_deliveries::status.eq(status),
+                            webhook_deliveries::attempts.eq(attempt),
+                            webhook_deliveries::next_attempt_at.eq(next_attempt_at),
+                            webhook_deliveries::last_response_status.eq(response_status),
+                            webhook_deliveries::last_error.eq(error),
+                            webhook_deliveries::delivered_at.eq(delivered_at),
+                        ))
+                        .returning(WebhookDeliveryEntity::as_returning())
+                        .get_result(conn)
+                        .await
+                        .context("Failed to update webhook")?;
+                Ok::<_, AppError>(delivery)
+            })
+        })
+        .await?;
+
+    Ok(delivery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_starts_at_thirty_seconds() {
+        assert_eq!(backoff(0), Duration::seconds(30));
+        assert_eq!(backoff(1), Duration::seconds(30));
+    }
+
+    #[test]
+    fn backoff_doubles_with_every_failure() {
+        assert_eq!(backoff(2), Duration::seconds(60));
+        assert_eq!(backoff(3), Duration::seconds(120));
+        assert_eq!(backoff(8), Duration::seconds(3840));
+    }
+
+    #[test]
+    fn backoff_is_capped_at_six_hours() {
+        assert_eq!(backoff(11), Duration::hours(6));
+        assert_eq!(backoff(i32::MAX), Duration::hours(6));
+    }
+}